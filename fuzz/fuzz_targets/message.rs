@@ -0,0 +1,59 @@
+//! Feeds arbitrary bytes to the message decoder, which must neither panic nor allocate more than
+//! a maximum length message's worth of memory at once.
+//!
+//! Valid encoded messages to start from are in `fuzz/seeds/message`.
+
+#![no_main]
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use tokio_util::codec::Decoder;
+use ziggurat::{
+    protocol::message::constants::{HEADER_LEN, MAGIC_TESTNET, MAX_MESSAGE_LEN},
+    tools::synthetic_node::MessageCodec,
+};
+
+/// Records the largest single allocation, so oversized ones can be reported as a failure instead
+/// of aborting the fuzzer.
+struct TrackingAllocator;
+
+static LARGEST_ALLOCATION: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LARGEST_ALLOCATION.fetch_max(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        LARGEST_ALLOCATION.fetch_max(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+fuzz_target!(|data: &[u8]| {
+    let mut codec = MessageCodec::new(MAGIC_TESTNET);
+    let mut src = BytesMut::from(data);
+
+    LARGEST_ALLOCATION.store(0, Ordering::Relaxed);
+
+    // Decode messages until the input is exhausted or rejected, like a connection would.
+    while let Ok(Some(_message)) = codec.decode(&mut src) {}
+
+    let largest = LARGEST_ALLOCATION.load(Ordering::Relaxed);
+    assert!(
+        largest <= HEADER_LEN + MAX_MESSAGE_LEN,
+        "decoding allocated {largest} bytes at once"
+    );
+});
@@ -0,0 +1,86 @@
+//! The hash functions shared by message checksums, block and transaction hashes.
+
+use blake2b_simd::Params;
+use sha2::{Digest, Sha256};
+
+/// Returns the double Sha256 (SHA256d) of `bytes`.
+pub fn sha256d(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(Sha256::digest(bytes)).into()
+}
+
+/// Returns the BLAKE2b-256 of `bytes` with the 16 byte `personal`ization, as used by the V5
+/// transaction digests ([ZIP-244](https://zips.z.cash/zip-0244)).
+pub fn blake2b_256(personal: &[u8; 16], bytes: &[u8]) -> [u8; 32] {
+    let hash = Params::new().hash_length(32).personal(personal).hash(bytes);
+
+    let mut digest = [0; 32];
+    digest.copy_from_slice(hash.as_bytes());
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::FromHex;
+
+    use super::*;
+    use crate::vectors::BLOCK_TESTNET_GENESIS_BYTES;
+
+    /// Returns the bytes of a hash as displayed, i.e. reversed.
+    fn displayed(hex: &str) -> [u8; 32] {
+        let mut bytes = <[u8; 32]>::from_hex(hex).unwrap();
+        bytes.reverse();
+        bytes
+    }
+
+    #[test]
+    #[ignore]
+    fn sha256d_known_vectors() {
+        // The first 4 bytes are the checksum of messages without a body, e.g. `verack`.
+        assert_eq!(
+            sha256d(b""),
+            <[u8; 32]>::from_hex(
+                "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            sha256d(b"hello"),
+            <[u8; 32]>::from_hex(
+                "9595c9df90075148eb06860365df33584b75bff782a510c6cd4883a419833d50"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn blake2b_256_known_vectors() {
+        // Checked against Python's `hashlib.blake2b(bytes, digest_size=32, person=personal)`.
+        assert_eq!(
+            blake2b_256(b"ZTxIdTranspaHash", b""),
+            <[u8; 32]>::from_hex(
+                "c33f2e95705faab35f8d533fa61e95c3b7aaba0776b874a9f74fc12784376a59"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            blake2b_256(b"ZTxIdHeadersHash", b"hello"),
+            <[u8; 32]>::from_hex(
+                "a61d8d4812194835c2bf3b1f0ce2138de3c60aaaf70af50de30e3211a454f7cf"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn sha256d_testnet_genesis_header() {
+        // The version, 3 hashes, time, bits, nonce, and the solution with its 3 byte length.
+        const HEADER_LEN: usize = 4 + 32 * 3 + 4 + 4 + 32 + 3 + 1344;
+
+        assert_eq!(
+            sha256d(&BLOCK_TESTNET_GENESIS_BYTES[..HEADER_LEN]),
+            displayed("05a60a92d99d85997cce3b87616c089f6124d7342af37106edc76126334a2c38")
+        );
+    }
+}
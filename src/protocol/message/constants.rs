@@ -11,11 +11,25 @@ pub const HEADER_LEN: usize = 24;
 /// Maximum message length (2 MiB).
 pub const MAX_MESSAGE_LEN: usize = 2 * 1024 * 1024;
 
+// Per-command limits, below `MAX_MESSAGE_LEN`. Unlike the latter they can't be checked from the
+// header, zcashd enforces them while parsing (or handling) the body.
+
+/// The maximum length of a `Version`'s user agent (zcashd's `MAX_SUBVERSION_LENGTH`).
+pub const MAX_USER_AGENT_LEN: usize = 256;
+/// The maximum number of entries in an `Inv`, `GetData` or `NotFound` (zcashd's `MAX_INV_SZ`).
+pub const MAX_INV_LEN: usize = 50_000;
+/// The maximum number of entries in an `Addr` (zcashd's `MAX_ADDR_TO_SEND`).
+pub const MAX_ADDR_LEN: usize = 1000;
+/// The maximum encoded size of a `Block` (zcashd's `MAX_BLOCK_SIZE`), the only message which can
+/// legitimately come close to `MAX_MESSAGE_LEN`.
+pub const MAX_BLOCK_SIZE: usize = 2_000_000;
+
 /// The current network protocol version number.
 pub const PROTOCOL_VERSION: u32 = 170_160;
 /// The current network version identifier.
 pub const MAGIC_TESTNET: [u8; MAGIC_LEN] = [0xfa, 0x1a, 0xf9, 0xbf];
 pub const MAGIC_MAINNET: [u8; MAGIC_LEN] = [0x24, 0xe9, 0x27, 0x64];
+pub const MAGIC_REGTEST: [u8; MAGIC_LEN] = [0xaa, 0xe8, 0x3f, 0x5f];
 
 /// Version message user agent
 pub const USER_AGENT: &str = "MagicBean:5.4.2";
@@ -49,4 +63,12 @@ pub const REJECT_COMMAND: [u8; COMMAND_LEN] = *b"reject\0\0\0\0\0\0";
 pub const FILTERLOAD_COMMAND: [u8; COMMAND_LEN] = *b"filterload\0\0";
 pub const FILTERADD_COMMAND: [u8; COMMAND_LEN] = *b"filteradd\0\0\0";
 pub const FILTERCLEAR_COMMAND: [u8; COMMAND_LEN] = *b"filterclear\0";
+pub const MERKLEBLOCK_COMMAND: [u8; COMMAND_LEN] = *b"merkleblock\0";
+pub const SENDHEADERS_COMMAND: [u8; COMMAND_LEN] = *b"sendheaders\0";
+pub const FEEFILTER_COMMAND: [u8; COMMAND_LEN] = *b"feefilter\0\0\0";
+pub const SENDCMPCT_COMMAND: [u8; COMMAND_LEN] = *b"sendcmpct\0\0\0";
+pub const CMPCTBLOCK_COMMAND: [u8; COMMAND_LEN] = *b"cmpctblock\0\0";
+pub const GETBLOCKTXN_COMMAND: [u8; COMMAND_LEN] = *b"getblocktxn\0";
+pub const BLOCKTXN_COMMAND: [u8; COMMAND_LEN] = *b"blocktxn\0\0\0\0";
+pub const WTXIDRELAY_COMMAND: [u8; COMMAND_LEN] = *b"wtxidrelay\0\0";
 pub const ALERT_COMMAND: [u8; COMMAND_LEN] = *b"alert\0\0\0\0\0\0\0";
@@ -4,18 +4,93 @@ pub mod constants;
 
 use std::io;
 
-use bytes::{Buf, BufMut, BytesMut};
-use sha2::{Digest, Sha256};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 
 use crate::protocol::{
+    hash::sha256d,
     message::constants::*,
     payload::{
-        block::{Block, Headers, LocatorHashes},
+        block::{
+            Block, BlockTxn, BlockTxnRequest, CompactBlock, Headers, LocatorHashes, MerkleBlock,
+            SendCmpct,
+        },
         codec::Codec,
-        Addr, FilterAdd, FilterLoad, Inv, Nonce, Reject, Tx, Version,
+        Addr, BloomFilter, FeeFilter, FilterAdd, Inv, Nonce, Reject, Tx, Version,
     },
 };
 
+/// The error returned when a message header's magic doesn't match the expected network's magic.
+///
+/// It is wrapped in an [`io::Error`] of kind [`io::ErrorKind::InvalidData`] and can be recovered
+/// with [`WrongNetworkMagic::from_io_error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrongNetworkMagic {
+    /// The magic of the network the reader is configured for.
+    pub expected: [u8; MAGIC_LEN],
+    /// The magic found in the header.
+    pub actual: [u8; MAGIC_LEN],
+}
+
+impl WrongNetworkMagic {
+    /// Returns the [`WrongNetworkMagic`] wrapped in the error, if any.
+    pub fn from_io_error(error: &io::Error) -> Option<&Self> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl std::fmt::Display for WrongNetworkMagic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "wrong network magic: expected {:x?}, got {:x?}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for WrongNetworkMagic {}
+
+impl From<WrongNetworkMagic> for io::Error {
+    fn from(error: WrongNetworkMagic) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// The error returned when a message header announces a body longer than [`MAX_MESSAGE_LEN`].
+///
+/// It is wrapped in an [`io::Error`] of kind [`io::ErrorKind::InvalidData`] and can be recovered
+/// with [`BodyTooLong::from_io_error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyTooLong {
+    /// The body length announced in the header.
+    pub declared: u32,
+}
+
+impl BodyTooLong {
+    /// Returns the [`BodyTooLong`] wrapped in the error, if any.
+    pub fn from_io_error(error: &io::Error) -> Option<&Self> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl std::fmt::Display for BodyTooLong {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "message body of {} bytes exceeds the max message length of {MAX_MESSAGE_LEN}",
+            self.declared
+        )
+    }
+}
+
+impl std::error::Error for BodyTooLong {}
+
+impl From<BodyTooLong> for io::Error {
+    fn from(error: BodyTooLong) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
 /// The header of a network message.
 #[derive(Debug, Default, Clone)]
 pub struct MessageHeader {
@@ -60,15 +135,74 @@ impl Codec for MessageHeader {
 }
 
 impl MessageHeader {
-    /// Returns a `MessageHeader` constructed from the message body.
-    pub fn new(command: [u8; COMMAND_LEN], body: &[u8]) -> Self {
+    /// Returns a `MessageHeader` constructed from the message body for the network identified by
+    /// `magic`.
+    pub fn new(magic: [u8; MAGIC_LEN], command: [u8; COMMAND_LEN], body: &[u8]) -> Self {
         MessageHeader {
-            magic: MAGIC,
+            magic,
             command,
             body_length: body.len() as u32,
             checksum: checksum(body),
         }
     }
+
+    /// Returns a `MessageHeader` with the given fields.
+    ///
+    /// Unlike [`MessageHeader::new`] the length and checksum aren't derived from a body, so they can
+    /// be made not to match the one sent with [`MessageHeader::encode_with_body`].
+    pub fn raw(
+        magic: [u8; MAGIC_LEN],
+        command: [u8; COMMAND_LEN],
+        declared_len: u32,
+        checksum: u32,
+    ) -> Self {
+        MessageHeader {
+            magic,
+            command,
+            body_length: declared_len,
+            checksum,
+        }
+    }
+
+    /// Encodes the header followed by `body`, which is written as is regardless of the header's
+    /// length and checksum.
+    pub fn encode_with_body<B: BufMut>(&self, body: &[u8], buffer: &mut B) -> io::Result<()> {
+        self.encode(buffer)?;
+        buffer.put_slice(body);
+
+        Ok(())
+    }
+
+    /// Decodes a header, failing with [`WrongNetworkMagic`] if it wasn't encoded for the network
+    /// identified by `expected_magic`.
+    pub fn decode_with_magic<B: Buf>(
+        bytes: &mut B,
+        expected_magic: [u8; MAGIC_LEN],
+    ) -> io::Result<Self> {
+        let header = Self::decode(bytes)?;
+
+        if header.magic != expected_magic {
+            return Err(WrongNetworkMagic {
+                expected: expected_magic,
+                actual: header.magic,
+            }
+            .into());
+        }
+
+        Ok(header)
+    }
+
+    /// Checks the announced body length doesn't exceed [`MAX_MESSAGE_LEN`], this should be done
+    /// before buffering the body.
+    pub fn check_body_length(&self) -> Result<(), BodyTooLong> {
+        if self.body_length as usize > MAX_MESSAGE_LEN {
+            return Err(BodyTooLong {
+                declared: self.body_length,
+            });
+        }
+
+        Ok(())
+    }
 }
 
 /// A network message.
@@ -93,95 +227,166 @@ pub enum Message {
     MemPool,
     Tx(Tx),
     Reject(Reject),
-    FilterLoad(FilterLoad),
+    FilterLoad(BloomFilter),
     FilterAdd(FilterAdd),
     FilterClear,
+    MerkleBlock(Box<MerkleBlock>),
+    SendHeaders,
+    FeeFilter(FeeFilter),
+    SendCmpct(SendCmpct),
+    CmpctBlock(Box<CompactBlock>),
+    GetBlockTxn(BlockTxnRequest),
+    BlockTxn(BlockTxn),
+    /// Asks for transactions to be announced by wtxid ([BIP-339]), sent between `Version` and
+    /// `Verack`.
+    ///
+    /// [BIP-339]: https://github.com/bitcoin/bips/blob/master/bip-0339.mediawiki
+    WtxidRelay,
     Alert,
+    /// A message with a command which isn't modelled, kept as is so it can be skipped.
+    ///
+    /// The command is kept as raw bytes, so the message encodes back to exactly what was read.
+    Unknown {
+        command: [u8; COMMAND_LEN],
+        body: Bytes,
+    },
 }
 
 macro_rules! encode_with_header_prefix {
-    ($command:expr, $buffer:expr) => {{
-        let header = MessageHeader::new($command, &[]);
+    ($magic:expr, $command:expr, $buffer:expr) => {{
+        let header = MessageHeader::new($magic, $command, &[]);
         header.encode($buffer)?;
     }};
 
-    ($command:expr, $buffer:expr, $payload:expr) => {{
+    // The body is encoded in place after room left for the header, which is filled in once the
+    // checksum is known, so the body is neither copied nor split off into a new allocation.
+    ($magic:expr, $command:expr, $buffer:expr, $payload:expr) => {{
+        let start = $buffer.len();
+        $buffer.put_bytes(0, HEADER_LEN);
         $payload.encode($buffer)?;
-        let serialized_payload = $buffer.split_to($buffer.len()).freeze();
-        let header = MessageHeader::new($command, &serialized_payload);
-        header.encode($buffer)?;
-        $buffer.put_slice(&serialized_payload);
+        let header = MessageHeader::new($magic, $command, &$buffer[start + HEADER_LEN..]);
+        let mut header_slot = &mut $buffer[start..start + HEADER_LEN];
+        header.encode(&mut header_slot)?;
     }};
 }
 
 impl Message {
-    /// Encodes a message into the supplied buffer and returns its header.
-    pub fn encode(&self, buffer: &mut BytesMut) -> io::Result<()> {
+    /// Encodes a message into the supplied buffer for the network identified by `magic`.
+    pub fn encode(&self, magic: [u8; MAGIC_LEN], buffer: &mut BytesMut) -> io::Result<()> {
         match self {
             Self::Version(version) => {
-                encode_with_header_prefix!(VERSION_COMMAND, buffer, version);
+                encode_with_header_prefix!(magic, VERSION_COMMAND, buffer, version);
             }
             Self::Verack => {
-                encode_with_header_prefix!(VERACK_COMMAND, buffer);
+                encode_with_header_prefix!(magic, VERACK_COMMAND, buffer);
             }
             Self::Ping(nonce) => {
-                encode_with_header_prefix!(PING_COMMAND, buffer, nonce);
+                encode_with_header_prefix!(magic, PING_COMMAND, buffer, nonce);
             }
             Self::Pong(nonce) => {
-                encode_with_header_prefix!(PONG_COMMAND, buffer, nonce);
+                encode_with_header_prefix!(magic, PONG_COMMAND, buffer, nonce);
             }
             Self::GetAddr => {
-                encode_with_header_prefix!(GETADDR_COMMAND, buffer);
+                encode_with_header_prefix!(magic, GETADDR_COMMAND, buffer);
             }
             Self::Addr(addr) => {
-                encode_with_header_prefix!(ADDR_COMMAND, buffer, addr);
+                encode_with_header_prefix!(magic, ADDR_COMMAND, buffer, addr);
             }
             Self::GetHeaders(locator_hashes) => {
-                encode_with_header_prefix!(GETHEADERS_COMMAND, buffer, locator_hashes);
+                encode_with_header_prefix!(magic, GETHEADERS_COMMAND, buffer, locator_hashes);
             }
             Self::Headers(headers) => {
-                encode_with_header_prefix!(HEADERS_COMMAND, buffer, headers);
+                encode_with_header_prefix!(magic, HEADERS_COMMAND, buffer, headers);
             }
             Self::GetBlocks(locator_hashes) => {
-                encode_with_header_prefix!(GETBLOCKS_COMMAND, buffer, locator_hashes);
+                encode_with_header_prefix!(magic, GETBLOCKS_COMMAND, buffer, locator_hashes);
             }
             Self::Block(block) => {
-                encode_with_header_prefix!(BLOCK_COMMAND, buffer, block);
+                encode_with_header_prefix!(magic, BLOCK_COMMAND, buffer, block);
             }
             Self::GetData(inv) => {
-                encode_with_header_prefix!(GETDATA_COMMAND, buffer, inv);
+                encode_with_header_prefix!(magic, GETDATA_COMMAND, buffer, inv);
             }
             Self::Inv(inv) => {
-                encode_with_header_prefix!(INV_COMMAND, buffer, inv);
+                encode_with_header_prefix!(magic, INV_COMMAND, buffer, inv);
             }
             Self::NotFound(inv) => {
-                encode_with_header_prefix!(NOTFOUND_COMMAND, buffer, inv);
+                encode_with_header_prefix!(magic, NOTFOUND_COMMAND, buffer, inv);
             }
             Self::MemPool => {
-                encode_with_header_prefix!(MEMPOOL_COMMAND, buffer);
+                encode_with_header_prefix!(magic, MEMPOOL_COMMAND, buffer);
             }
             Self::Tx(tx) => {
-                encode_with_header_prefix!(TX_COMMAND, buffer, tx);
+                encode_with_header_prefix!(magic, TX_COMMAND, buffer, tx);
             }
             Self::Reject(reject) => {
-                encode_with_header_prefix!(REJECT_COMMAND, buffer, reject);
+                encode_with_header_prefix!(magic, REJECT_COMMAND, buffer, reject);
             }
             Self::FilterLoad(filter_load) => {
-                encode_with_header_prefix!(FILTERLOAD_COMMAND, buffer, filter_load);
+                encode_with_header_prefix!(magic, FILTERLOAD_COMMAND, buffer, filter_load);
             }
             Self::FilterAdd(filter) => {
-                encode_with_header_prefix!(FILTERADD_COMMAND, buffer, filter);
+                encode_with_header_prefix!(magic, FILTERADD_COMMAND, buffer, filter);
             }
             Self::FilterClear => {
-                encode_with_header_prefix!(FILTERCLEAR_COMMAND, buffer);
+                encode_with_header_prefix!(magic, FILTERCLEAR_COMMAND, buffer);
+            }
+            Self::MerkleBlock(merkle_block) => {
+                encode_with_header_prefix!(magic, MERKLEBLOCK_COMMAND, buffer, merkle_block);
+            }
+            Self::SendHeaders => {
+                encode_with_header_prefix!(magic, SENDHEADERS_COMMAND, buffer);
+            }
+            Self::FeeFilter(fee_filter) => {
+                encode_with_header_prefix!(magic, FEEFILTER_COMMAND, buffer, fee_filter);
+            }
+            Self::SendCmpct(send_cmpct) => {
+                encode_with_header_prefix!(magic, SENDCMPCT_COMMAND, buffer, send_cmpct);
+            }
+            Self::CmpctBlock(compact_block) => {
+                encode_with_header_prefix!(magic, CMPCTBLOCK_COMMAND, buffer, compact_block);
+            }
+            Self::GetBlockTxn(request) => {
+                encode_with_header_prefix!(magic, GETBLOCKTXN_COMMAND, buffer, request);
+            }
+            Self::BlockTxn(block_txn) => {
+                encode_with_header_prefix!(magic, BLOCKTXN_COMMAND, buffer, block_txn);
+            }
+            Self::WtxidRelay => {
+                encode_with_header_prefix!(magic, WTXIDRELAY_COMMAND, buffer);
             }
             // Don't send deprecated alert messages.
             Self::Alert => (),
+            Self::Unknown { command, body } => {
+                let header = MessageHeader::new(magic, *command, body);
+                header.encode(buffer)?;
+                buffer.put_slice(body);
+            }
         }
 
         Ok(())
     }
 
+    /// Returns a [`Message::Unknown`] with the `command` padded with `NUL`s.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the command is longer than [`COMMAND_LEN`].
+    pub fn unknown(command: &str, body: impl Into<Bytes>) -> io::Result<Self> {
+        if command.len() > COMMAND_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the command {command:?} is longer than {COMMAND_LEN} bytes"),
+            ));
+        }
+
+        let mut command_bytes = [0u8; COMMAND_LEN];
+        command_bytes[..command.len()].copy_from_slice(command.as_bytes());
+
+        Ok(Self::Unknown {
+            command: command_bytes,
+            body: body.into(),
+        })
+    }
+
     /// Decodes the bytes into a message.
     pub fn decode<B: Buf>(command: [u8; 12], bytes: &mut B) -> io::Result<Self> {
         let message = match command {
@@ -201,16 +406,27 @@ impl Message {
             MEMPOOL_COMMAND => Self::MemPool,
             TX_COMMAND => Self::Tx(Tx::decode(bytes)?),
             REJECT_COMMAND => Self::Reject(Reject::decode(bytes)?),
+            FILTERLOAD_COMMAND => Self::FilterLoad(BloomFilter::decode(bytes)?),
+            FILTERADD_COMMAND => Self::FilterAdd(FilterAdd::decode(bytes)?),
+            FILTERCLEAR_COMMAND => Self::FilterClear,
+            MERKLEBLOCK_COMMAND => Self::MerkleBlock(Box::new(MerkleBlock::decode(bytes)?)),
+            SENDHEADERS_COMMAND => Self::SendHeaders,
+            FEEFILTER_COMMAND => Self::FeeFilter(FeeFilter::decode(bytes)?),
+            SENDCMPCT_COMMAND => Self::SendCmpct(SendCmpct::decode(bytes)?),
+            CMPCTBLOCK_COMMAND => Self::CmpctBlock(Box::new(CompactBlock::decode(bytes)?)),
+            GETBLOCKTXN_COMMAND => Self::GetBlockTxn(BlockTxnRequest::decode(bytes)?),
+            BLOCKTXN_COMMAND => Self::BlockTxn(BlockTxn::decode(bytes)?),
+            WTXIDRELAY_COMMAND => Self::WtxidRelay,
             // Explicitly ignore alert messages since they are deprecated.
             ALERT_COMMAND => {
                 bytes.advance(bytes.remaining());
                 Self::Alert
             }
-            cmd => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    format!("Unknown command string: {cmd:?}"),
-                ))
+            // Keep the raw body, so the reader can skip the message and carry on.
+            command => {
+                let body = bytes.copy_to_bytes(bytes.remaining());
+
+                Self::Unknown { command, body }
             }
         };
 
@@ -240,17 +456,29 @@ impl std::fmt::Display for Message {
             Message::FilterLoad(_) => f.write_str("FilterLoad"),
             Message::FilterAdd(_) => f.write_str("FilterAdd"),
             Message::FilterClear => f.write_str("FilterClear"),
+            Message::MerkleBlock(_) => f.write_str("MerkleBlock"),
+            Message::SendHeaders => f.write_str("SendHeaders"),
+            Message::FeeFilter(fee_filter) => {
+                f.write_fmt(format_args!("FeeFilter({})", fee_filter.feerate))
+            }
+            Message::SendCmpct(send_cmpct) => f.write_fmt(format_args!(
+                "SendCmpct({}, {})",
+                send_cmpct.announce, send_cmpct.version
+            )),
+            Message::CmpctBlock(_) => f.write_str("CmpctBlock"),
+            Message::GetBlockTxn(_) => f.write_str("GetBlockTxn"),
+            Message::BlockTxn(_) => f.write_str("BlockTxn"),
+            Message::WtxidRelay => f.write_str("WtxidRelay"),
             Message::Alert => f.write_str("Alert"),
+            Message::Unknown { command, .. } => f.write_fmt(format_args!(
+                "Unknown({})",
+                String::from_utf8_lossy(command).trim_end_matches('\0')
+            )),
         }
     }
 }
 
+/// Returns the first 4 bytes of the body's double Sha256.
 fn checksum(bytes: &[u8]) -> u32 {
-    let sha2 = Sha256::digest(bytes);
-    let sha2d = Sha256::digest(sha2);
-
-    let mut checksum = [0u8; 4];
-    checksum[0..4].copy_from_slice(&sha2d[0..4]);
-
-    u32::from_le_bytes(checksum)
+    u32::from_le_bytes(sha256d(bytes)[..4].try_into().unwrap())
 }
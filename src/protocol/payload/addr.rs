@@ -45,12 +45,15 @@ impl Codec for Addr {
 }
 
 /// A network address.
+///
+/// Only the original `Addr` encoding is supported, `AddrV2` (BIP 155) entries carry the same
+/// `time` and `services` fields but aren't implemented by the codec.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NetworkAddr {
-    /// The last time this address was seen.
+    /// The last time this address was seen, with second precision on the wire.
     /// Note: Present only when version is >= 31402
     pub last_seen: Option<OffsetDateTime>,
-    /// The services supported by this address.
+    /// The services supported by this address, see [`NODE_NETWORK`](super::version::NODE_NETWORK).
     pub services: u64,
     /// The socket address.
     pub addr: SocketAddr,
@@ -68,6 +71,23 @@ impl NetworkAddr {
         }
     }
 
+    /// Sets the services advertised for this address.
+    pub fn with_services(mut self, services: u64) -> Self {
+        self.services = services;
+        self
+    }
+
+    /// Sets the time this address was last seen.
+    pub fn with_last_seen(mut self, last_seen: OffsetDateTime) -> Self {
+        self.last_seen = Some(last_seen);
+        self
+    }
+
+    /// Returns the time this address was last seen as a UNIX timestamp, as it's encoded.
+    pub fn last_seen_timestamp(&self) -> Option<i64> {
+        self.last_seen.map(|last_seen| last_seen.unix_timestamp())
+    }
+
     pub fn encode_without_timestamp<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.put_u64_le(self.services);
 
@@ -93,7 +113,9 @@ impl NetworkAddr {
         bytes.copy_to_slice(&mut octets);
         let v6_addr = Ipv6Addr::from(octets);
 
-        let ip_addr = match v6_addr.to_ipv4() {
+        // Only IPv4-mapped addresses are IPv4, `to_ipv4` would also convert the deprecated
+        // IPv4-compatible ones, e.g. read `::1` as `0.0.0.1`.
+        let ip_addr = match v6_addr.to_ipv4_mapped() {
             Some(v4_addr) => V4(v4_addr),
             None => V6(v6_addr),
         };
@@ -109,6 +131,9 @@ impl NetworkAddr {
 }
 
 impl Codec for NetworkAddr {
+    // The timestamp, services, IP and port.
+    const MIN_ENCODED_LEN: usize = 4 + 8 + 16 + 2;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         let timestamp: u32 = self
             .last_seen
@@ -133,3 +158,69 @@ impl Codec for NetworkAddr {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::protocol::payload::tests::huge_count_followed_by;
+
+    #[test]
+    #[ignore]
+    fn ipv4_is_encoded_as_mapped() {
+        let addr = NetworkAddr::new(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8233));
+
+        let mut bytes = Vec::new();
+        addr.encode_without_timestamp(&mut bytes).unwrap();
+        assert_eq!(
+            &bytes[8..24],
+            &Ipv4Addr::LOCALHOST.to_ipv6_mapped().octets()
+        );
+
+        let decoded = NetworkAddr::decode_without_timestamp(&mut &bytes[..]).unwrap();
+        assert_eq!(decoded.addr, addr.addr);
+    }
+
+    #[test]
+    #[ignore]
+    fn ipv6_loopback_is_not_decoded_as_ipv4() {
+        let addr = NetworkAddr::new(SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 8233));
+
+        let mut bytes = Vec::new();
+        addr.encode_without_timestamp(&mut bytes).unwrap();
+        assert_eq!(&bytes[8..24], &Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(&bytes[24..], &8233u16.to_be_bytes());
+
+        let decoded = NetworkAddr::decode_without_timestamp(&mut &bytes[..]).unwrap();
+        assert_eq!(decoded.addr, addr.addr);
+    }
+
+    #[test]
+    #[ignore]
+    fn time_and_services_round_trip() {
+        let last_seen = OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap();
+        let addr = NetworkAddr::new(SocketAddr::new(Ipv4Addr::new(1, 2, 3, 4).into(), 8233))
+            .with_services(0b101)
+            .with_last_seen(last_seen);
+
+        let mut bytes = Vec::new();
+        addr.encode(&mut bytes).unwrap();
+        let decoded = NetworkAddr::decode(&mut &bytes[..]).unwrap();
+
+        assert_eq!(decoded.services, 0b101);
+        assert_eq!(decoded.last_seen_timestamp(), Some(1_600_000_000));
+        assert_eq!(decoded, addr);
+    }
+
+    #[test]
+    #[ignore]
+    fn huge_address_count_is_rejected() {
+        // The count is followed by a single entry.
+        let bytes = huge_count_followed_by(30);
+
+        let error = Addr::decode(&mut &bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("don't fit"), "{error}");
+    }
+}
@@ -2,11 +2,15 @@
 
 use std::{convert::TryInto, io};
 
-use bytes::{Buf, BufMut};
+use bytes::{Buf, BufMut, Bytes};
 use sha2::Digest;
 
-use crate::protocol::payload::{
-    codec::Codec, inv::InvHash, read_n_bytes, Hash, ProtocolVersion, Tx, VarInt,
+use crate::protocol::{
+    hash::sha256d,
+    message::constants::{MAGIC_LEN, MAGIC_REGTEST, MAGIC_TESTNET},
+    payload::{
+        bounded_count, codec::Codec, inv::InvHash, read_n_bytes, Hash, ProtocolVersion, Tx, VarInt,
+    },
 };
 
 /// The locator hash object, used to communicate chain state.
@@ -37,6 +41,47 @@ impl LocatorHashes {
     pub fn empty() -> Self {
         Self::new(Vec::new(), Hash::zeroed())
     }
+
+    /// Returns a `LocatorHashes` instance describing the chain of `known_hashes`, ordered from
+    /// genesis to tip, the way zcashd's `GetLocator` does.
+    ///
+    /// The hashes are selected from the tip back, densely at first and then with doubling gaps,
+    /// always ending with the genesis hash.
+    pub fn build(known_hashes: &[Hash]) -> Self {
+        let mut block_locator_hashes = Vec::new();
+        let mut step = 1;
+        let mut height = match known_hashes.len().checked_sub(1) {
+            Some(tip) => tip,
+            None => return Self::empty(),
+        };
+
+        loop {
+            block_locator_hashes.push(known_hashes[height]);
+            if height == 0 {
+                break;
+            }
+
+            height = height.saturating_sub(step);
+            if block_locator_hashes.len() > 10 {
+                step *= 2;
+            }
+        }
+
+        Self::new(block_locator_hashes, Hash::zeroed())
+    }
+
+    /// Returns a `LocatorHashes` instance requesting the headers following the last of `headers`,
+    /// or `None` if there are no headers to continue from.
+    ///
+    /// This is used to page through a chain longer than [`MAX_HEADERS_RESULTS`].
+    pub fn from_last_header(headers: &Headers) -> Option<Self> {
+        let last = headers.headers.last()?;
+        let hash = last
+            .double_sha256()
+            .expect("encoding a header into a buffer can't fail");
+
+        Some(Self::new(vec![hash], Hash::zeroed()))
+    }
 }
 
 impl Codec for LocatorHashes {
@@ -76,6 +121,18 @@ impl Block {
         self.header.double_sha256()
     }
 
+    /// Returns the block's hash, see [`Header::hash`].
+    pub fn hash(&self) -> Hash {
+        self.header.hash()
+    }
+
+    /// Verifies the block's proof of work on the network identified by `magic`, see
+    /// [`Header::verify_pow`].
+    #[cfg(feature = "pow")]
+    pub fn verify_pow(&self, magic: [u8; MAGIC_LEN]) -> bool {
+        self.header.verify_pow(magic)
+    }
+
     /// Creates the testnet genesis block.
     pub fn testnet_genesis() -> Self {
         let mut cursor = std::io::Cursor::new(&crate::vectors::BLOCK_TESTNET_GENESIS_BYTES[..]);
@@ -178,6 +235,9 @@ impl Codec for Block {
     }
 }
 
+/// The maximum number of headers a node returns in reply to a single `getheaders` request.
+pub const MAX_HEADERS_RESULTS: usize = 2000;
+
 /// A list of block headers.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Headers {
@@ -196,8 +256,57 @@ impl Headers {
             headers: Vec::new(),
         }
     }
+
+    /// Returns the hash of each header, in order.
+    pub fn hashes(&self) -> Vec<Hash> {
+        self.headers.iter().map(Header::hash).collect()
+    }
+
+    /// Checks the headers form a chain, i.e. each header's `prev_block` is the hash of the header
+    /// preceding it.
+    ///
+    /// The first header isn't checked, as its parent isn't part of the list.
+    pub fn validate_continuity(&self) -> Result<(), ContinuityError> {
+        for (index, pair) in self.headers.windows(2).enumerate() {
+            let expected = pair[0].hash();
+
+            if pair[1].prev_block != expected {
+                return Err(ContinuityError {
+                    index: index + 1,
+                    expected,
+                    actual: pair[1].prev_block,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The error returned by [`Headers::validate_continuity`] when a header doesn't extend the one
+/// preceding it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContinuityError {
+    /// The index of the first header which doesn't extend its predecessor.
+    pub index: usize,
+    /// The hash of the preceding header.
+    pub expected: Hash,
+    /// The `prev_block` of the header at `index`.
+    pub actual: Hash,
 }
 
+impl std::fmt::Display for ContinuityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "header {} doesn't extend its predecessor: expected prev_block {:?}, got {:?}",
+            self.index, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ContinuityError {}
+
 impl Codec for Headers {
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         self.headers.encode(buffer)
@@ -209,6 +318,532 @@ impl Codec for Headers {
     }
 }
 
+/// A filtered block, sent in reply to a `getdata` for a [`InvHash::FilteredBlock`] once a bloom
+/// filter has been loaded, see [BIP 37](https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MerkleBlock {
+    /// The block's header.
+    pub header: Header,
+    /// The number of transactions in the block, including the unmatched ones.
+    pub total_txs: u32,
+    /// The hashes of the partial merkle tree, in depth-first order.
+    pub hashes: Vec<Hash>,
+    /// The flag bits of the partial merkle tree, packed per 8 in a byte (least significant first).
+    pub flags: Vec<u8>,
+}
+
+impl MerkleBlock {
+    /// Recomputes the merkle root from the partial merkle tree, returning it along with the hashes
+    /// of the matched transactions.
+    ///
+    /// Fails if the partial merkle tree is malformed, e.g. if not all hashes or flag bits are
+    /// consumed by the traversal.
+    pub fn compute_merkle_root(&self) -> io::Result<(Hash, Vec<Hash>)> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let total_txs = self.total_txs as usize;
+        if total_txs == 0 {
+            return Err(invalid("MerkleBlock has no transactions"));
+        }
+        if self.hashes.len() > total_txs {
+            return Err(invalid("MerkleBlock has more hashes than transactions"));
+        }
+        if self.flags.len() * 8 < self.hashes.len() {
+            return Err(invalid("MerkleBlock has fewer flag bits than hashes"));
+        }
+
+        let mut tree = PartialMerkleTree {
+            total_txs,
+            hashes: &self.hashes,
+            flags: &self.flags,
+            hashes_used: 0,
+            bits_used: 0,
+            matches: Vec::new(),
+        };
+
+        let mut height = 0;
+        while tree.width(height) > 1 {
+            height += 1;
+        }
+
+        let root = tree.traverse(height, 0)?;
+
+        // All the flag bits (up to the byte boundary) and hashes must have been used.
+        if tree.bits_used.div_ceil(8) != self.flags.len() {
+            return Err(invalid("MerkleBlock has unused flag bits"));
+        }
+        if tree.hashes_used != self.hashes.len() {
+            return Err(invalid("MerkleBlock has unused hashes"));
+        }
+
+        Ok((root, tree.matches))
+    }
+
+    /// Verifies the partial merkle tree matches the merkle root in the header, returning the hashes
+    /// of the matched transactions.
+    pub fn verify(&self) -> io::Result<Vec<Hash>> {
+        let (root, matches) = self.compute_merkle_root()?;
+
+        if root != self.header.merkle_root {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "MerkleBlock root {:?} doesn't match the header's {:?}",
+                    root, self.header.merkle_root
+                ),
+            ));
+        }
+
+        Ok(matches)
+    }
+}
+
+/// The state of a depth-first traversal of a partial merkle tree, see
+/// [BIP 37](https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki#partial-merkle-branch-format).
+struct PartialMerkleTree<'a> {
+    total_txs: usize,
+    hashes: &'a [Hash],
+    flags: &'a [u8],
+    hashes_used: usize,
+    bits_used: usize,
+    matches: Vec<Hash>,
+}
+
+impl PartialMerkleTree<'_> {
+    /// The number of nodes at the given height, the leaves being at height `0`.
+    fn width(&self, height: u32) -> usize {
+        (self.total_txs + (1 << height) - 1) >> height
+    }
+
+    fn traverse(&mut self, height: u32, pos: usize) -> io::Result<Hash> {
+        let overflow = || io::Error::new(io::ErrorKind::InvalidData, "MerkleBlock tree overflow");
+
+        let flag = self
+            .flags
+            .get(self.bits_used / 8)
+            .map(|byte| (byte >> (self.bits_used % 8)) & 1 == 1)
+            .ok_or_else(overflow)?;
+        self.bits_used += 1;
+
+        if height == 0 || !flag {
+            // The hash is given, either for a leaf or for a subtree without any matches.
+            let hash = *self.hashes.get(self.hashes_used).ok_or_else(overflow)?;
+            self.hashes_used += 1;
+
+            if height == 0 && flag {
+                self.matches.push(hash);
+            }
+
+            return Ok(hash);
+        }
+
+        let left = self.traverse(height - 1, pos * 2)?;
+        let right = if pos * 2 + 1 < self.width(height - 1) {
+            let right = self.traverse(height - 1, pos * 2 + 1)?;
+            // Identical siblings allow forging a tree with duplicated transactions (CVE-2012-2459).
+            if right == left {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "MerkleBlock has identical sibling hashes",
+                ));
+            }
+            right
+        } else {
+            left
+        };
+
+        Ok(merkle_parent(&left, &right))
+    }
+}
+
+/// Returns the double Sha256 hash of the concatenated child hashes.
+fn merkle_parent(left: &Hash, right: &Hash) -> Hash {
+    let mut buffer = [0u8; 64];
+    buffer[..32].copy_from_slice(&left.0);
+    buffer[32..].copy_from_slice(&right.0);
+
+    Hash::new(sha256d(&buffer))
+}
+
+impl Codec for MerkleBlock {
+    fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
+        self.header.encode_without_tx_count(buffer)?;
+        buffer.put_u32_le(self.total_txs);
+        self.hashes.encode(buffer)?;
+        VarInt(self.flags.len()).encode(buffer)?;
+        buffer.put_slice(&self.flags);
+
+        Ok(())
+    }
+
+    fn decode<B: Buf>(bytes: &mut B) -> io::Result<Self> {
+        let header = Header::decode_without_tx_count(bytes)?;
+        let total_txs = u32::from_le_bytes(read_n_bytes(bytes)?);
+        let hashes = Vec::decode(bytes)?;
+
+        let flags_len = bounded_count(*VarInt::decode(bytes)?, 1, bytes.remaining())?;
+        let mut flags = vec![0u8; flags_len];
+        bytes.copy_to_slice(&mut flags);
+
+        Ok(Self {
+            header,
+            total_txs,
+            hashes,
+            flags,
+        })
+    }
+}
+
+/// Negotiates compact block relay, see [BIP 152](https://github.com/bitcoin/bips/blob/master/bip-0152.mediawiki).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SendCmpct {
+    /// Requests new blocks to be announced with a [`CompactBlock`] (high-bandwidth mode) instead
+    /// of an `inv` or `headers`.
+    pub announce: bool,
+    /// The compact block protocol version.
+    pub version: u64,
+}
+
+impl SendCmpct {
+    /// Returns a `SendCmpct` for version 1, optionally requesting high-bandwidth mode.
+    pub fn new(announce: bool) -> Self {
+        Self {
+            announce,
+            version: 1,
+        }
+    }
+}
+
+impl Codec for SendCmpct {
+    fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
+        buffer.put_u8(self.announce as u8);
+        buffer.put_u64_le(self.version);
+
+        Ok(())
+    }
+
+    fn decode<B: Buf>(bytes: &mut B) -> io::Result<Self> {
+        let announce = match u8::from_le_bytes(read_n_bytes(bytes)?) {
+            0 => false,
+            1 => true,
+            flag => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("SendCmpct announce flag is {flag}, expected 0 or 1"),
+                ))
+            }
+        };
+        let version = u64::from_le_bytes(read_n_bytes(bytes)?);
+
+        Ok(Self { announce, version })
+    }
+}
+
+/// A transaction sent in full as part of a [`CompactBlock`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct PrefilledTx {
+    /// The index of the transaction in the block (absolute, the differential encoding is handled
+    /// by [`CompactBlock`]).
+    pub index: usize,
+    /// The transaction.
+    pub tx: Tx,
+}
+
+/// The length of a short transaction ID in bytes.
+pub const SHORT_ID_LEN: usize = 6;
+
+/// A block relayed with short transaction IDs, sent in a `cmpctblock` message, see
+/// [BIP 152](https://github.com/bitcoin/bips/blob/master/bip-0152.mediawiki).
+#[derive(Debug, PartialEq, Clone)]
+pub struct CompactBlock {
+    /// The block's header.
+    pub header: Header,
+    /// The nonce used to derive the short ID key.
+    pub nonce: u64,
+    /// The short IDs of the transactions which aren't prefilled, see [`CompactBlock::short_id`].
+    pub short_ids: Vec<u64>,
+    /// The transactions sent in full, usually at least the coinbase.
+    pub prefilled_txs: Vec<PrefilledTx>,
+    /// The SipHash key derived from the header and nonce, computed on decode.
+    short_id_key: (u64, u64),
+}
+
+impl CompactBlock {
+    /// Returns a new `CompactBlock`, deriving the short IDs of the transactions which aren't
+    /// prefilled.
+    pub fn new(block: &Block, nonce: u64, prefilled: &[usize]) -> io::Result<Self> {
+        let short_id_key = short_id_key(&block.header, nonce)?;
+
+        let mut short_ids = Vec::new();
+        let mut prefilled_txs = Vec::new();
+        for (index, tx) in block.txs.iter().enumerate() {
+            if prefilled.contains(&index) {
+                prefilled_txs.push(PrefilledTx {
+                    index,
+                    tx: tx.clone(),
+                });
+            } else {
+                short_ids.push(short_id(short_id_key, &tx.double_sha256()?));
+            }
+        }
+
+        Ok(Self {
+            header: block.header.clone(),
+            nonce,
+            short_ids,
+            prefilled_txs,
+            short_id_key,
+        })
+    }
+
+    /// Returns the short ID of the transaction with the given hash in this block.
+    pub fn short_id(&self, tx_hash: &Hash) -> u64 {
+        short_id(self.short_id_key, tx_hash)
+    }
+
+    /// Indicates if the block's short IDs contain the transaction with the given hash.
+    pub fn contains(&self, tx_hash: &Hash) -> bool {
+        self.short_ids.contains(&self.short_id(tx_hash))
+    }
+
+    /// Returns the total number of transactions in the block.
+    pub fn tx_count(&self) -> usize {
+        self.short_ids.len() + self.prefilled_txs.len()
+    }
+}
+
+impl Codec for CompactBlock {
+    fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
+        self.header.encode_without_tx_count(buffer)?;
+        buffer.put_u64_le(self.nonce);
+
+        VarInt(self.short_ids.len()).encode(buffer)?;
+        for short_id in &self.short_ids {
+            buffer.put_slice(&short_id.to_le_bytes()[..SHORT_ID_LEN]);
+        }
+
+        VarInt(self.prefilled_txs.len()).encode(buffer)?;
+        let mut next_index = 0;
+        for prefilled in &self.prefilled_txs {
+            encode_differential_index(prefilled.index, &mut next_index, buffer)?;
+            prefilled.tx.encode(buffer)?;
+        }
+
+        Ok(())
+    }
+
+    fn decode<B: Buf>(bytes: &mut B) -> io::Result<Self> {
+        let header = Header::decode_without_tx_count(bytes)?;
+        let nonce = u64::from_le_bytes(read_n_bytes(bytes)?);
+
+        let short_ids_len =
+            bounded_count(*VarInt::decode(bytes)?, SHORT_ID_LEN, bytes.remaining())?;
+        let short_ids = (0..short_ids_len)
+            .map(|_| {
+                let mut short_id = [0u8; 8];
+                bytes.copy_to_slice(&mut short_id[..SHORT_ID_LEN]);
+                u64::from_le_bytes(short_id)
+            })
+            .collect();
+
+        // An index takes at least a byte, the transaction more.
+        let prefilled_len = bounded_count(
+            *VarInt::decode(bytes)?,
+            1 + Tx::MIN_ENCODED_LEN,
+            bytes.remaining(),
+        )?;
+        let mut prefilled_txs = Vec::with_capacity(prefilled_len);
+        let mut next_index = 0;
+        for _ in 0..prefilled_len {
+            let index = decode_differential_index(bytes, &mut next_index)?;
+            let tx = Tx::decode(bytes)?;
+            prefilled_txs.push(PrefilledTx { index, tx });
+        }
+
+        let short_id_key = short_id_key(&header, nonce)?;
+
+        Ok(Self {
+            header,
+            nonce,
+            short_ids,
+            prefilled_txs,
+            short_id_key,
+        })
+    }
+}
+
+/// Requests the transactions missing from a [`CompactBlock`], sent in a `getblocktxn` message.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BlockTxnRequest {
+    /// The hash of the block the transactions belong to.
+    pub block_hash: Hash,
+    /// The indexes of the requested transactions in the block, in ascending order.
+    pub indexes: Vec<usize>,
+}
+
+impl BlockTxnRequest {
+    /// Returns a new `BlockTxnRequest` for the transactions at `indexes` in the block.
+    pub fn new(block_hash: Hash, indexes: Vec<usize>) -> Self {
+        Self {
+            block_hash,
+            indexes,
+        }
+    }
+}
+
+impl Codec for BlockTxnRequest {
+    fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
+        self.block_hash.encode(buffer)?;
+
+        VarInt(self.indexes.len()).encode(buffer)?;
+        let mut next_index = 0;
+        for index in &self.indexes {
+            encode_differential_index(*index, &mut next_index, buffer)?;
+        }
+
+        Ok(())
+    }
+
+    fn decode<B: Buf>(bytes: &mut B) -> io::Result<Self> {
+        let block_hash = Hash::decode(bytes)?;
+
+        // Each index takes at least a byte.
+        let len = bounded_count(*VarInt::decode(bytes)?, 1, bytes.remaining())?;
+        let mut indexes = Vec::with_capacity(len);
+        let mut next_index = 0;
+        for _ in 0..len {
+            indexes.push(decode_differential_index(bytes, &mut next_index)?);
+        }
+
+        Ok(Self {
+            block_hash,
+            indexes,
+        })
+    }
+}
+
+/// The transactions requested with a [`BlockTxnRequest`], sent in a `blocktxn` message.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlockTxn {
+    /// The hash of the block the transactions belong to.
+    pub block_hash: Hash,
+    /// The requested transactions, in the order of the request's indexes.
+    pub txs: Vec<Tx>,
+}
+
+impl Codec for BlockTxn {
+    fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
+        self.block_hash.encode(buffer)?;
+        self.txs.encode(buffer)
+    }
+
+    fn decode<B: Buf>(bytes: &mut B) -> io::Result<Self> {
+        let block_hash = Hash::decode(bytes)?;
+        let txs = Vec::decode(bytes)?;
+
+        Ok(Self { block_hash, txs })
+    }
+}
+
+/// Encodes a transaction index as the difference to the index following the previous one.
+///
+/// `next_index` starts at `0` and is advanced past `index`, indexes must be strictly ascending.
+fn encode_differential_index<B: BufMut>(
+    index: usize,
+    next_index: &mut usize,
+    buffer: &mut B,
+) -> io::Result<()> {
+    let diff = index.checked_sub(*next_index).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "transaction indexes must be strictly ascending",
+        )
+    })?;
+    VarInt(diff).encode(buffer)?;
+    *next_index = index + 1;
+
+    Ok(())
+}
+
+/// Decodes a transaction index encoded with [`encode_differential_index`].
+///
+/// Indexes beyond `u16::MAX` are rejected, as no block can hold that many transactions.
+fn decode_differential_index<B: Buf>(bytes: &mut B, next_index: &mut usize) -> io::Result<usize> {
+    let index = next_index
+        .checked_add(*VarInt::decode(bytes)?)
+        .filter(|index| *index <= u16::MAX as usize)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "transaction index overflow"))?;
+    *next_index = index + 1;
+
+    Ok(index)
+}
+
+/// Derives the SipHash key used for short IDs from the single Sha256 of the header and nonce.
+fn short_id_key(header: &Header, nonce: u64) -> io::Result<(u64, u64)> {
+    let mut buffer = Vec::new();
+    header.encode_without_tx_count(&mut buffer)?;
+    buffer.put_u64_le(nonce);
+
+    let hash = sha2::Sha256::digest(buffer);
+    let k0 = u64::from_le_bytes(hash[0..8].try_into().unwrap());
+    let k1 = u64::from_le_bytes(hash[8..16].try_into().unwrap());
+
+    Ok((k0, k1))
+}
+
+/// Returns the short ID for a transaction hash: the lower 6 bytes of its SipHash-2-4.
+fn short_id((k0, k1): (u64, u64), tx_hash: &Hash) -> u64 {
+    siphash_2_4(k0, k1, &tx_hash.0) & 0xffff_ffff_ffff
+}
+
+/// A minimal SipHash-2-4 implementation, see the [paper](https://www.aumasson.jp/siphash/siphash.pdf).
+fn siphash_2_4(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+
+    let round = |v: &mut [u64; 4]| {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    };
+
+    let compress = |v: &mut [u64; 4], m: u64| {
+        v[3] ^= m;
+        round(v);
+        round(v);
+        v[0] ^= m;
+    };
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        compress(&mut v, u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+
+    // The remaining bytes are padded with the message length in the last byte.
+    let mut last = [0u8; 8];
+    last[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    last[7] = data.len() as u8;
+    compress(&mut v, u64::from_le_bytes(last));
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
 /// A block header, see the [Zcash protocol
 /// spec](https://zips.z.cash/protocol/protocol.pdf#blockheader) for details.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -228,13 +863,16 @@ pub struct Header {
     /// The nonce used in the version messages, `Nonce(u64)`, is NOT the same as the nonce the
     /// block was generated with as it uses a `u32`.
     pub nonce: [u8; 32],
-    /// The size of the Equihash solution in bytes (always `1344`).
+    /// The size of the Equihash solution in bytes, see [`equihash_solution_size`].
     pub solution_size: VarInt,
     /// The Equihash solution.
-    pub solution: [u8; 1344],
+    pub solution: Bytes,
 }
 
 impl Codec for Header {
+    // The fixed size fields, the solution and transaction count come on top.
+    const MIN_ENCODED_LEN: usize = 4 + 32 + 32 + 32 + 4 + 4 + 32;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         self.encode_without_tx_count(buffer)?;
         // Encode tx_count=0
@@ -260,6 +898,59 @@ impl Codec for Header {
     }
 }
 
+/// Returns the Equihash `(n, k)` parameters of the network identified by `magic`: (200, 9), except
+/// on regtest which uses (48, 5).
+pub fn equihash_parameters(magic: [u8; MAGIC_LEN]) -> (u32, u32) {
+    match magic {
+        MAGIC_REGTEST => (48, 5),
+        _ => (200, 9),
+    }
+}
+
+/// Returns the size in bytes of the Equihash solution in block headers on the network identified by
+/// `magic`, i.e. `1344` except on regtest where it is `36`.
+pub fn equihash_solution_size(magic: [u8; MAGIC_LEN]) -> usize {
+    let (n, k) = equihash_parameters(magic);
+    // 2^k indices of n / (k + 1) + 1 bits each.
+    ((1 << k) * (n / (k + 1) + 1) / 8) as usize
+}
+
+/// Returns `true` if `size` is the Equihash solution size of a known network, see
+/// [`equihash_solution_size`].
+///
+/// Headers don't say which network they belong to, but the solution's length prefix tells which
+/// parameters they were mined with.
+fn is_known_solution_size(size: usize) -> bool {
+    [MAGIC_TESTNET, MAGIC_REGTEST]
+        .into_iter()
+        .any(|magic| equihash_solution_size(magic) == size)
+}
+
+/// Expands the compact `bits` representation of a target into a 256-bit little-endian number.
+///
+/// Returns `None` for negative, zero or overflowing targets, which no hash can meet.
+#[cfg(feature = "pow")]
+fn compact_to_target(bits: u32) -> Option<[u8; 32]> {
+    let exponent = (bits >> 24) as usize;
+    let mantissa = bits & 0x007f_ffff;
+    if bits & 0x0080_0000 != 0 || mantissa == 0 {
+        return None;
+    }
+
+    let mut target = [0u8; 32];
+    if exponent <= 3 {
+        target[..4].copy_from_slice(&(mantissa >> (8 * (3 - exponent))).to_le_bytes());
+    } else {
+        for (i, byte) in mantissa.to_le_bytes()[..3].iter().enumerate() {
+            if *byte != 0 {
+                *target.get_mut(exponent - 3 + i)? = *byte;
+            }
+        }
+    }
+
+    Some(target)
+}
+
 impl Header {
     /// Calculates the double Sha256 hash for this header.
     pub fn double_sha256(&self) -> std::io::Result<Hash> {
@@ -267,17 +958,42 @@ impl Header {
 
         self.encode_without_tx_count(&mut buffer)?;
 
-        let hash_bytes_1 = sha2::Sha256::digest(buffer);
-        let hash_bytes_2 = sha2::Sha256::digest(hash_bytes_1);
+        Ok(Hash::new(sha256d(&buffer)))
+    }
+
+    /// Returns the header's hash: the double Sha256 of its encoding, solution included (1487
+    /// bytes on mainnet and testnet), without the transaction count.
+    pub fn hash(&self) -> Hash {
+        self.double_sha256()
+            .expect("encoding a header into a buffer can't fail")
+    }
+
+    /// Verifies the header's Equihash solution with the parameters of the network identified by
+    /// `magic` and checks its hash meets the target encoded in `bits`.
+    ///
+    /// This is expensive and only available with the `pow` feature.
+    #[cfg(feature = "pow")]
+    pub fn verify_pow(&self, magic: [u8; MAGIC_LEN]) -> bool {
+        let (n, k) = equihash_parameters(magic);
+
+        let mut input = Vec::new();
+        if self.encode_equihash_input(&mut input).is_err()
+            || equihash::is_valid_solution(n, k, &input, &self.nonce, &self.solution).is_err()
+        {
+            return false;
+        }
 
-        let hash = Hash::new(hash_bytes_2.try_into().unwrap());
+        let Some(target) = compact_to_target(self.bits) else {
+            return false;
+        };
+        let hash = self.hash();
 
-        Ok(hash)
+        // Both are little-endian, so compare from the most significant byte.
+        hash.0.iter().rev().le(target.iter().rev())
     }
 
-    /// Encodes [Header] without the VarInt `tx_count=0`. This is useful for [Block] encoding which requires
-    /// `tx_count=N`, as well as Hash calculation as it excludes `tx_count`.
-    fn encode_without_tx_count<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
+    /// Encodes the fields preceding the nonce, which make up the Equihash input.
+    fn encode_equihash_input<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         self.version.encode(buffer)?;
         self.prev_block.encode(buffer)?;
         self.merkle_root.encode(buffer)?;
@@ -285,6 +1001,14 @@ impl Header {
 
         buffer.put_u32_le(self.timestamp);
         buffer.put_u32_le(self.bits);
+
+        Ok(())
+    }
+
+    /// Encodes [Header] without the VarInt `tx_count=0`. This is useful for [Block] encoding which requires
+    /// `tx_count=N`, as well as Hash calculation as it excludes `tx_count`.
+    fn encode_without_tx_count<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
+        self.encode_equihash_input(buffer)?;
         buffer.put_slice(&self.nonce);
 
         self.solution_size.encode(buffer)?;
@@ -309,7 +1033,21 @@ impl Header {
         let nonce = read_n_bytes(bytes)?;
 
         let solution_size = VarInt::decode(bytes)?;
-        let solution = read_n_bytes(bytes)?;
+        if !is_known_solution_size(*solution_size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Header.solution_size = {}, expected {} or {}",
+                    *solution_size,
+                    equihash_solution_size(MAGIC_TESTNET),
+                    equihash_solution_size(MAGIC_REGTEST),
+                ),
+            ));
+        }
+        if bytes.remaining() < *solution_size {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let solution = bytes.copy_to_bytes(*solution_size);
 
         Ok(Self {
             version,
@@ -332,7 +1070,7 @@ mod tests {
     use hex::FromHex;
 
     use super::*;
-    use crate::vectors::*;
+    use crate::{protocol::payload::tests::huge_count_followed_by, vectors::*};
 
     #[test]
     #[ignore]
@@ -637,6 +1375,129 @@ mod tests {
         assert_eq!(block_bytes, buffer);
     }
 
+    #[test]
+    #[ignore]
+    fn txids_commit_to_the_merkle_root() {
+        // Coinbase only V1 transactions, V4 ones after Sapling, and V5 ones after NU5.
+        for bytes in [
+            &BLOCK_TESTNET_GENESIS_BYTES[..],
+            &BLOCK_TESTNET_0_903_800_BYTES[..],
+            &BLOCK_TESTNET_1_599_200_BYTES[..],
+            &BLOCK_TESTNET_1_599_201_BYTES[..],
+        ] {
+            let block = Block::decode(&mut Cursor::new(bytes)).unwrap();
+
+            let mut level = block
+                .txs
+                .iter()
+                .map(|tx| tx.txid().unwrap())
+                .collect::<Vec<_>>();
+            while level.len() > 1 {
+                level = level
+                    .chunks(2)
+                    .map(|pair| merkle_parent(&pair[0], pair.last().unwrap()))
+                    .collect();
+            }
+
+            assert_eq!(level[0], block.header.merkle_root);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn v4_wtxid_has_placeholder_auth_digest() {
+        let block = Block::decode(&mut Cursor::new(&BLOCK_TESTNET_0_903_800_BYTES[..])).unwrap();
+        let tx = block.txs.iter().find(|tx| matches!(tx, Tx::V4(_))).unwrap();
+
+        // The coinbase's txid, as displayed reversed by block explorers:
+        // d96ba8c0e9aa658904195206a36c075e5306a0fa347f9712fb1a6249bd770b0d.
+        let mut encoded = Vec::new();
+        tx.wtxid().unwrap().encode(&mut encoded).unwrap();
+        assert_eq!(
+            encoded,
+            <Vec<u8>>::from_hex(concat!(
+                "0d0b77bd49621afb12977f34faa006535e076ca3065219048965aae9c0a86bd9",
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            ))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn v5_wtxid_known_answers() {
+        // The first two NU5 blocks only hold their V5 coinbase, whose txid is the merkle root
+        // found in the headers. The authorizing data digests aren't committed to on their own
+        // (the block commitment also covers the chain history), they were computed from the
+        // ZIP-244 spec with Python's `hashlib.blake2b` instead.
+        for (bytes, wtxid) in [
+            (
+                &BLOCK_TESTNET_1_599_200_BYTES[..],
+                concat!(
+                    "8039c40bd3ab96700f2242b1200608d4a1c74264bc71b3f956a2339b4606957b",
+                    "f28d65342ad1df6bb1c21797e158102aa54381fcabf26b62e7a4ba78322bdef4",
+                ),
+            ),
+            (
+                &BLOCK_TESTNET_1_599_201_BYTES[..],
+                concat!(
+                    "f6c3f2ef2b6f542ad77967e75314f45c509033eb887b09828ff9137c2b73ef47",
+                    "75e5fd3387db867dc06750294eb71b005f76416d87e3d54c0bdbde78d07210a2",
+                ),
+            ),
+        ] {
+            let block = Block::decode(&mut Cursor::new(bytes)).unwrap();
+            let tx = match &block.txs[..] {
+                [tx @ Tx::V5(_)] => tx,
+                txs => panic!("expected a single V5 transaction, got {txs:?}"),
+            };
+
+            let mut encoded = Vec::new();
+            tx.wtxid().unwrap().encode(&mut encoded).unwrap();
+            assert_eq!(encoded, <Vec<u8>>::from_hex(wtxid).unwrap());
+            assert_eq!(tx.txid().unwrap(), block.header.merkle_root);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn block_hash_is_the_next_prev_block() {
+        let chains: [[&[u8]; 3]; 2] = [
+            [
+                &BLOCK_TESTNET_GENESIS_BYTES,
+                &BLOCK_TESTNET_0_000_001_BYTES,
+                &BLOCK_TESTNET_0_000_002_BYTES,
+            ],
+            [
+                &BLOCK_TESTNET_1_599_199_BYTES,
+                &BLOCK_TESTNET_1_599_200_BYTES,
+                &BLOCK_TESTNET_1_599_201_BYTES,
+            ],
+        ];
+
+        for chain in chains {
+            let blocks = chain
+                .map(|bytes| Block::decode(&mut Cursor::new(bytes)).unwrap())
+                .to_vec();
+
+            for pair in blocks.windows(2) {
+                assert_eq!(pair[1].header.prev_block, pair[0].hash());
+            }
+
+            let headers = Headers::new(blocks.iter().map(|block| block.header.clone()).collect());
+            let hashes = blocks.iter().map(Block::hash).collect::<Vec<_>>();
+            assert_eq!(headers.hashes(), hashes);
+
+            // The hash covers the whole header, solution included.
+            let mut buffer = Vec::new();
+            blocks[0]
+                .header
+                .encode_without_tx_count(&mut buffer)
+                .unwrap();
+            assert_eq!(buffer.len(), 1487);
+        }
+    }
+
     #[test]
     #[ignore]
     fn testnet_genesis_block_hash() {
@@ -652,4 +1513,333 @@ mod tests {
 
         assert_eq!(expected, hash);
     }
+
+    /// Builds a merkle block for three transactions with the second one matched, see
+    /// [`MerkleBlock::compute_merkle_root`].
+    fn crafted_merkle_block() -> MerkleBlock {
+        let leaves = [Hash::new([1; 32]), Hash::new([2; 32]), Hash::new([3; 32])];
+        let left = merkle_parent(&leaves[0], &leaves[1]);
+        let right = merkle_parent(&leaves[2], &leaves[2]);
+
+        let mut header = Block::testnet_genesis().header;
+        header.merkle_root = merkle_parent(&left, &right);
+
+        MerkleBlock {
+            header,
+            total_txs: 3,
+            hashes: vec![leaves[0], leaves[1], right],
+            // Traversal order: root (1), left (1), leaf 0 (0), leaf 1 (1), right (0).
+            flags: vec![0b0000_1011],
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn merkle_block_verifies() {
+        let merkle_block = crafted_merkle_block();
+
+        assert_eq!(merkle_block.verify().unwrap(), vec![Hash::new([2; 32])]);
+    }
+
+    #[test]
+    #[ignore]
+    fn merkle_block_round_trip() {
+        let merkle_block = crafted_merkle_block();
+
+        let mut buffer = Vec::new();
+        merkle_block.encode(&mut buffer).unwrap();
+
+        let decoded = MerkleBlock::decode(&mut Cursor::new(&buffer[..])).unwrap();
+        assert_eq!(decoded, merkle_block);
+    }
+
+    #[test]
+    #[ignore]
+    fn merkle_block_with_tampered_flags_fails() {
+        let mut merkle_block = crafted_merkle_block();
+        // Claim the left subtree has no matches, which shifts the hashes around.
+        merkle_block.flags = vec![0b0000_1001];
+
+        assert!(merkle_block.verify().is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn merkle_block_with_wrong_root_fails() {
+        let mut merkle_block = crafted_merkle_block();
+        merkle_block.header.merkle_root = Hash::zeroed();
+
+        assert!(merkle_block.compute_merkle_root().is_ok());
+        assert!(merkle_block.verify().is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn siphash_test_vectors() {
+        // From the reference implementation, key 00..0f.
+        let k0 = u64::from_le_bytes([0, 1, 2, 3, 4, 5, 6, 7]);
+        let k1 = u64::from_le_bytes([8, 9, 10, 11, 12, 13, 14, 15]);
+        let message = (0..32).collect::<Vec<u8>>();
+
+        assert_eq!(siphash_2_4(k0, k1, &[]), 0x726f_db47_dd0e_0e31);
+        assert_eq!(siphash_2_4(k0, k1, &message[..15]), 0xa129_ca61_49be_45e5);
+        assert_eq!(siphash_2_4(k0, k1, &message), 0x7127_512f_72f2_7cce);
+    }
+
+    #[test]
+    #[ignore]
+    fn compact_block_round_trip() {
+        let block = Block::testnet_1();
+        let compact_block = CompactBlock::new(&block, 0x1234_5678_9abc_def0, &[]).unwrap();
+
+        let mut buffer = Vec::new();
+        compact_block.encode(&mut buffer).unwrap();
+        let decoded = CompactBlock::decode(&mut Cursor::new(&buffer[..])).unwrap();
+
+        assert_eq!(decoded, compact_block);
+        assert_eq!(decoded.tx_count(), block.txs.len());
+        for tx in &block.txs {
+            assert!(decoded.contains(&tx.double_sha256().unwrap()));
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn compact_block_prefilled_round_trip() {
+        let block = Block::testnet_1();
+        let compact_block = CompactBlock::new(&block, 7, &[0]).unwrap();
+        assert!(compact_block.short_ids.is_empty());
+
+        let mut buffer = Vec::new();
+        compact_block.encode(&mut buffer).unwrap();
+        let decoded = CompactBlock::decode(&mut Cursor::new(&buffer[..])).unwrap();
+
+        assert_eq!(decoded, compact_block);
+        assert_eq!(decoded.prefilled_txs[0].tx, block.txs[0]);
+    }
+
+    #[test]
+    #[ignore]
+    fn differential_indexes_round_trip() {
+        let request = BlockTxnRequest::new(Hash([1; 32]), vec![0, 1, 5, 6, 300]);
+
+        let mut buffer = Vec::new();
+        request.encode(&mut buffer).unwrap();
+
+        // Hash, count, then 0, 0, 3, 0 and 293 (a 3-byte VarInt).
+        assert_eq!(buffer[32..], [5, 0, 0, 3, 0, 0xfd, 0x25, 0x01]);
+        assert_eq!(
+            BlockTxnRequest::decode(&mut Cursor::new(&buffer[..])).unwrap(),
+            request
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn differential_indexes_must_ascend() {
+        let mut buffer = Vec::new();
+
+        let repeated = BlockTxnRequest::new(Hash([0; 32]), vec![2, 2]);
+        assert!(repeated.encode(&mut buffer).is_err());
+
+        let descending = BlockTxnRequest::new(Hash([0; 32]), vec![2, 1]);
+        assert!(descending.encode(&mut buffer).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn differential_indexes_overflow_is_rejected() {
+        let mut buffer = Hash([0; 32]).0.to_vec();
+        buffer.push(2);
+        buffer.extend_from_slice(&[0xfd, 0xff, 0xff]);
+        buffer.push(0);
+
+        // The first index is 65535, the second would be 65536.
+        assert!(BlockTxnRequest::decode(&mut Cursor::new(&buffer[..])).is_err());
+        buffer[33] = 1;
+        assert_eq!(
+            BlockTxnRequest::decode(&mut Cursor::new(&buffer[..36]))
+                .unwrap()
+                .indexes,
+            vec![u16::MAX as usize]
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn block_txn_round_trip() {
+        let block = Block::testnet_1();
+        let block_txn = BlockTxn {
+            block_hash: block.double_sha256().unwrap(),
+            txs: block.txs,
+        };
+
+        let mut buffer = Vec::new();
+        block_txn.encode(&mut buffer).unwrap();
+
+        assert_eq!(
+            BlockTxn::decode(&mut Cursor::new(&buffer[..])).unwrap(),
+            block_txn
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn headers_continuity() {
+        let headers = Block::initial_testnet_blocks()
+            .into_iter()
+            .map(|block| block.header)
+            .collect::<Vec<_>>();
+
+        assert!(Headers::new(headers.clone()).validate_continuity().is_ok());
+        assert!(Headers::empty().validate_continuity().is_ok());
+
+        // A gap is reported at the first header after it.
+        let mut gapped = headers.clone();
+        gapped.remove(3);
+        let error = Headers::new(gapped).validate_continuity().unwrap_err();
+        assert_eq!(error.index, 3);
+        assert_eq!(error.actual, headers[3].double_sha256().unwrap());
+
+        // Swapping two headers breaks the chain at the first of them.
+        let mut reordered = headers;
+        reordered.swap(5, 6);
+        let error = Headers::new(reordered).validate_continuity().unwrap_err();
+        assert_eq!(error.index, 5);
+    }
+
+    #[test]
+    #[ignore]
+    fn locator_build() {
+        let known_hashes = (0..=100).map(|i| Hash::new([i; 32])).collect::<Vec<_>>();
+        let selected = |locator: LocatorHashes| {
+            locator
+                .block_locator_hashes
+                .iter()
+                .map(|hash| hash.0[0])
+                .collect::<Vec<_>>()
+        };
+
+        // Dense from the tip, then the gaps double until genesis is reached.
+        let locator = LocatorHashes::build(&known_hashes);
+        assert_eq!(locator.hash_stop, Hash::zeroed());
+        assert_eq!(
+            selected(locator),
+            vec![100, 99, 98, 97, 96, 95, 94, 93, 92, 91, 90, 89, 87, 83, 75, 59, 27, 0]
+        );
+
+        // A short chain is selected in its entirety.
+        assert_eq!(
+            selected(LocatorHashes::build(&known_hashes[..5])),
+            vec![4, 3, 2, 1, 0]
+        );
+
+        assert!(LocatorHashes::build(&[]).block_locator_hashes.is_empty());
+    }
+
+    #[test]
+    #[ignore]
+    fn locator_from_last_header() {
+        let headers = Block::initial_testnet_blocks()
+            .into_iter()
+            .map(|block| block.header)
+            .collect::<Vec<_>>();
+        let last_hash = headers.last().unwrap().double_sha256().unwrap();
+
+        let locator = LocatorHashes::from_last_header(&Headers::new(headers)).unwrap();
+        assert_eq!(locator.block_locator_hashes, vec![last_hash]);
+        assert_eq!(locator.hash_stop, Hash::zeroed());
+
+        assert!(LocatorHashes::from_last_header(&Headers::empty()).is_none());
+    }
+
+    #[test]
+    #[ignore]
+    fn header_with_wrong_solution_size_is_rejected() {
+        let header = Block::testnet_1().header;
+        let mut buffer = Vec::new();
+        header.encode(&mut buffer).unwrap();
+        assert_eq!(
+            Header::decode(&mut Cursor::new(&buffer[..])).unwrap(),
+            header
+        );
+
+        // The solution size directly follows the 140 bytes of fixed size fields.
+        let solution_size_offset = 140;
+        assert_eq!(buffer[solution_size_offset..][..3], [0xfd, 0x40, 0x05]);
+
+        // A truncated solution, with its size adjusted to match.
+        let mut truncated = buffer.clone();
+        truncated[solution_size_offset + 1] = 0x3f;
+        truncated.remove(solution_size_offset + 3);
+        assert!(Header::decode(&mut Cursor::new(&truncated[..])).is_err());
+
+        // A regtest sized solution is read as such, whichever network is being tested.
+        let mut regtest = buffer[..solution_size_offset].to_vec();
+        regtest.push(36);
+        regtest.extend_from_slice(&[0xab; 36]);
+        regtest.push(0);
+        let decoded = Header::decode(&mut Cursor::new(&regtest[..])).unwrap();
+        assert_eq!(decoded.solution, [0xab; 36][..]);
+
+        // A solution which is shorter than its size claims.
+        buffer.truncate(solution_size_offset + 3 + 1000);
+        assert!(Header::decode(&mut Cursor::new(&buffer[..])).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn equihash_solution_sizes() {
+        assert_eq!(equihash_solution_size(MAGIC_REGTEST), 36);
+        assert_eq!(equihash_solution_size(MAGIC_TESTNET), 1344);
+    }
+
+    #[test]
+    #[ignore]
+    #[cfg(feature = "pow")]
+    fn compact_target_expansion() {
+        let target = compact_to_target(0x1d00_ffff).unwrap();
+        assert_eq!(target[26..29], [0xff, 0xff, 0x00]);
+        assert!(target[..26]
+            .iter()
+            .chain(&target[29..])
+            .all(|byte| *byte == 0));
+
+        assert_eq!(
+            compact_to_target(0x0312_3456).unwrap()[..3],
+            [0x56, 0x34, 0x12]
+        );
+        assert_eq!(compact_to_target(0x0212_3456).unwrap()[..2], [0x34, 0x12]);
+        // Negative, zero and overflowing targets.
+        assert!(compact_to_target(0x0480_0000).is_none());
+        assert!(compact_to_target(0x1d00_0000).is_none());
+        assert!(compact_to_target(0x2101_0000).is_none());
+    }
+
+    #[test]
+    #[ignore]
+    #[cfg(feature = "pow")]
+    fn testnet_blocks_pow() {
+        for block in Block::initial_testnet_blocks() {
+            assert!(block.verify_pow(MAGIC_TESTNET));
+        }
+
+        // Regtest parameters don't fit a testnet solution.
+        assert!(!Block::testnet_1().verify_pow(MAGIC_REGTEST));
+
+        let mut block = Block::testnet_1();
+        block.header.nonce[0] ^= 1;
+        assert!(!block.verify_pow(MAGIC_TESTNET));
+    }
+
+    #[test]
+    #[ignore]
+    fn huge_header_count_is_rejected() {
+        // The count is followed by a single header's fixed fields.
+        let bytes = huge_count_followed_by(140);
+
+        let error = Headers::decode(&mut &bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("don't fit"), "{error}");
+    }
 }
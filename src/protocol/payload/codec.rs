@@ -4,10 +4,14 @@ use std::io;
 
 use bytes::{Buf, BufMut};
 
-use super::VarInt;
+use super::{bounded_count, VarInt};
 
 /// A trait for unifying encoding and decoding.
 pub trait Codec {
+    /// The minimum number of bytes an encoded value takes, used to check the length declared for
+    /// a vector of values against the bytes left before allocating it.
+    const MIN_ENCODED_LEN: usize = 1;
+
     /// Encodes the payload into the supplied buffer.
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()>;
 
@@ -31,7 +35,17 @@ impl<T: Codec> Codec for Vec<T> {
     where
         Self: Sized,
     {
-        let length = *VarInt::decode(bytes)?;
-        (0..length).map(|_| T::decode(bytes)).collect()
+        let length = bounded_count(
+            *VarInt::decode(bytes)?,
+            T::MIN_ENCODED_LEN,
+            bytes.remaining(),
+        )?;
+
+        let mut elements = Vec::with_capacity(length);
+        for _ in 0..length {
+            elements.push(T::decode(bytes)?);
+        }
+
+        Ok(elements)
     }
 }
@@ -0,0 +1,55 @@
+//! Fee filter payload types, see [BIP 133](https://github.com/bitcoin/bips/blob/master/bip-0133.mediawiki).
+
+use std::io;
+
+use bytes::{Buf, BufMut};
+
+use crate::protocol::payload::{codec::Codec, read_n_bytes};
+
+/// A fee filter payload, the minimum fee rate of the transactions the sender wants relayed.
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub struct FeeFilter {
+    /// The fee rate, in zatoshis per 1000 bytes.
+    pub feerate: u64,
+}
+
+impl FeeFilter {
+    /// Returns a new `FeeFilter` with the given fee rate.
+    pub fn new(feerate: u64) -> Self {
+        Self { feerate }
+    }
+}
+
+impl Codec for FeeFilter {
+    fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
+        buffer.put_u64_le(self.feerate);
+
+        Ok(())
+    }
+
+    fn decode<B: Buf>(bytes: &mut B) -> io::Result<Self> {
+        let feerate = u64::from_le_bytes(read_n_bytes(bytes)?);
+
+        Ok(Self { feerate })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn fee_filter_round_trip() {
+        let original = FeeFilter::new(1000);
+
+        let mut buffer = Vec::new();
+        original.encode(&mut buffer).unwrap();
+        assert_eq!(buffer, 1000u64.to_le_bytes());
+
+        let decoded = FeeFilter::decode(&mut Cursor::new(&buffer[..])).unwrap();
+        assert_eq!(decoded, original);
+    }
+}
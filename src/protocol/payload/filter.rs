@@ -1,10 +1,17 @@
 //! Bloom filtering types, see [BIP 37](https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki).
 
-use std::io::{self, Cursor, ErrorKind, Read};
+use std::io::{self, ErrorKind};
 
 use bytes::{Buf, BufMut};
 
-use crate::protocol::payload::{codec::Codec, read_n_bytes};
+use crate::protocol::payload::{bounded_count, codec::Codec, read_n_bytes, VarInt};
+
+/// The maximum size of a filter in bytes.
+pub const MAX_BLOOM_FILTER_SIZE: usize = 36_000;
+/// The maximum number of hash functions a filter can use.
+pub const MAX_HASH_FUNCS: u32 = 50;
+/// The maximum size of a data element added to a filter in bytes.
+pub const MAX_FILTER_ADD_SIZE: usize = 520;
 
 /// A modification to an existing filter.
 #[derive(Debug, PartialEq, Eq, Default, Clone)]
@@ -13,50 +20,75 @@ pub struct FilterAdd {
     pub data: Vec<u8>,
 }
 
-/// A new filter on the connection.
+/// A new filter on the connection, sent in a `filterload` message.
 #[derive(Debug, PartialEq, Eq, Default, Clone)]
-pub struct FilterLoad {
+pub struct BloomFilter {
     /// The filter itself.
     pub filter: Vec<u8>,
-    /// The number of hash functions to use in this filter.
+    /// The number of hash functions to use in this filter (`nHashFuncs`).
     pub hash_fn_count: u32,
-    /// A random value to add to the hash function's seed.
+    /// A random value to add to the hash function's seed (`nTweak`).
     pub tweak: u32,
-    /// Flags that control how matched items are added to the filter.
+    /// Flags that control how matched items are added to the filter (`nFlags`).
     pub flags: u8,
 }
 
+impl BloomFilter {
+    /// Returns a filter which matches everything, useful to have the node send back all the
+    /// transactions of a filtered block.
+    pub fn match_all() -> Self {
+        Self {
+            filter: vec![0xff; 1],
+            hash_fn_count: 1,
+            tweak: 0,
+            flags: 0,
+        }
+    }
+}
+
+/// Encodes `data` as a byte vector prefixed with its length.
+fn encode_var_bytes<B: BufMut>(data: &[u8], buffer: &mut B) -> io::Result<()> {
+    VarInt::new(data.len()).encode(buffer)?;
+    buffer.put_slice(data);
+
+    Ok(())
+}
+
+/// Decodes a byte vector prefixed with its length, failing if it's longer than `max_len`.
+fn decode_var_bytes<B: Buf>(bytes: &mut B, max_len: usize, name: &str) -> io::Result<Vec<u8>> {
+    let len = *VarInt::decode(bytes)?;
+
+    if len > max_len {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Maximum {name} length is {max_len}, but got {len}"),
+        ));
+    }
+
+    let mut data = vec![0u8; bounded_count(len, 1, bytes.remaining())?];
+    bytes.copy_to_slice(&mut data);
+
+    Ok(data)
+}
+
 impl Codec for FilterAdd {
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
-        buffer.put_slice(&self.data);
-
-        Ok(())
+        encode_var_bytes(&self.data, buffer)
     }
 
     fn decode<B: Buf>(bytes: &mut B) -> io::Result<Self>
     where
         Self: Sized,
     {
-        let mut data = Vec::new();
-        bytes.reader().read_to_end(&mut data)?;
-
-        if data.len() > 520 {
-            return Err(io::Error::new(
-                ErrorKind::InvalidData,
-                format!(
-                    "Maximum FilterAdd data length is 520, but got {}",
-                    data.len()
-                ),
-            ));
-        }
+        let data = decode_var_bytes(bytes, MAX_FILTER_ADD_SIZE, "FilterAdd data")?;
 
         Ok(Self { data })
     }
 }
 
-impl Codec for FilterLoad {
+impl Codec for BloomFilter {
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
-        buffer.put_slice(&self.filter);
+        encode_var_bytes(&self.filter, buffer)?;
         buffer.put_u32_le(self.hash_fn_count);
         buffer.put_u32_le(self.tweak);
         buffer.put_u8(self.flags);
@@ -68,38 +100,18 @@ impl Codec for FilterLoad {
     where
         Self: Sized,
     {
-        // Have to read to end in order to get size of filter.
-        // (we only know the final 9 bytes are reserved for the other fields)
-        let mut buffer = Vec::new();
-        let bytes_read = bytes.reader().read_to_end(&mut buffer)?;
+        let filter = decode_var_bytes(bytes, MAX_BLOOM_FILTER_SIZE, "filter")?;
 
-        const NON_FILTER_BYTES: usize = 4 + 4 + 1;
-        if bytes_read < NON_FILTER_BYTES {
-            return Err(io::Error::new(
-                ErrorKind::InvalidData,
-                format!(
-                    "Minimum FilterLoad bytes required is {NON_FILTER_BYTES} but only got {bytes_read}"
-                ),
-            ));
-        }
-        let filter_bytes = bytes_read - NON_FILTER_BYTES;
-        // maximum filter size is 36k bytes
-        const MAX_FILTER_BYTES: usize = 36_000;
-        if filter_bytes > MAX_FILTER_BYTES {
+        let hash_fn_count = u32::from_le_bytes(read_n_bytes(bytes)?);
+        if hash_fn_count > MAX_HASH_FUNCS {
             return Err(io::Error::new(
                 ErrorKind::InvalidData,
-                format!("Maximum filter bytes is {MAX_FILTER_BYTES} but got {filter_bytes}"),
+                format!("Maximum hash function count is {MAX_HASH_FUNCS}, but got {hash_fn_count}"),
             ));
         }
 
-        let mut cursor = Cursor::new(&buffer[..]);
-
-        let mut filter = vec![0; filter_bytes];
-        cursor.read_exact(&mut filter)?;
-
-        let hash_fn_count = u32::from_le_bytes(read_n_bytes(&mut cursor)?);
-        let tweak = u32::from_le_bytes(read_n_bytes(&mut cursor)?);
-        let flags = u8::from_le_bytes(read_n_bytes(&mut cursor)?);
+        let tweak = u32::from_le_bytes(read_n_bytes(bytes)?);
+        let flags = u8::from_le_bytes(read_n_bytes(bytes)?);
 
         Ok(Self {
             filter,
@@ -112,25 +124,81 @@ impl Codec for FilterLoad {
 
 #[cfg(test)]
 mod tests {
+    use std::io::Cursor;
+
     use super::*;
 
     #[test]
     #[ignore]
     fn filter_load_roundtrip() {
-        let original = FilterLoad::default();
+        let original = BloomFilter {
+            filter: vec![0xab; 32],
+            hash_fn_count: 11,
+            tweak: 0xdead_beef,
+            flags: 1,
+        };
 
         let mut buffer = Vec::new();
         original.encode(&mut buffer).unwrap();
 
         let mut cursor = Cursor::new(&buffer[..]);
-        let decoded = FilterLoad::decode(&mut cursor).unwrap();
+        let decoded = BloomFilter::decode(&mut cursor).unwrap();
         assert_eq!(decoded, original);
+        assert!(!cursor.has_remaining());
+    }
+
+    #[test]
+    #[ignore]
+    fn filter_load_wire_layout() {
+        let filter = BloomFilter {
+            filter: vec![0x01, 0x02],
+            hash_fn_count: 3,
+            tweak: 4,
+            flags: 5,
+        };
+
+        let mut buffer = Vec::new();
+        filter.encode(&mut buffer).unwrap();
+
+        assert_eq!(buffer, [2, 1, 2, 3, 0, 0, 0, 4, 0, 0, 0, 5]);
+    }
+
+    #[test]
+    #[ignore]
+    fn filter_load_rejects_oversized_filter() {
+        let filter = BloomFilter {
+            filter: vec![0; MAX_BLOOM_FILTER_SIZE + 1],
+            ..Default::default()
+        };
+
+        let mut buffer = Vec::new();
+        filter.encode(&mut buffer).unwrap();
+
+        let mut cursor = Cursor::new(&buffer[..]);
+        assert!(BloomFilter::decode(&mut cursor).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn filter_load_rejects_too_many_hash_functions() {
+        let filter = BloomFilter {
+            hash_fn_count: MAX_HASH_FUNCS + 1,
+            ..Default::default()
+        };
+
+        let mut buffer = Vec::new();
+        filter.encode(&mut buffer).unwrap();
+
+        let mut cursor = Cursor::new(&buffer[..]);
+        assert!(BloomFilter::decode(&mut cursor).is_err());
     }
 
     #[test]
     #[ignore]
     fn filter_add_roundtrip() {
-        let original = FilterAdd::default();
+        let original = FilterAdd {
+            data: vec![7; MAX_FILTER_ADD_SIZE],
+        };
 
         let mut buffer = Vec::new();
         original.encode(&mut buffer).unwrap();
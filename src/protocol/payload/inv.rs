@@ -70,6 +70,9 @@ impl InvHash {
 }
 
 impl Codec for InvHash {
+    // An `Error` entry is only the type.
+    const MIN_ENCODED_LEN: usize = 4;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.put_u32_le(self.code());
 
@@ -135,3 +138,20 @@ impl Codec for WtxId {
         Ok(Self { id, auth_digest })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::payload::tests::huge_count_followed_by;
+
+    #[test]
+    #[ignore]
+    fn huge_inventory_count_is_rejected() {
+        // The count is followed by a single entry.
+        let bytes = huge_count_followed_by(36);
+
+        let error = Inv::decode(&mut &bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("don't fit"), "{error}");
+    }
+}
@@ -30,12 +30,22 @@ use crate::protocol::message::constants::{MAX_MESSAGE_LEN, PROTOCOL_VERSION};
 pub mod codec;
 
 pub mod filter;
-pub use filter::{FilterAdd, FilterLoad};
+pub use filter::{BloomFilter, FilterAdd};
+
+pub mod fee_filter;
+pub use fee_filter::FeeFilter;
 
 /// A `u64`-backed nonce.
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub struct Nonce(u64);
 
+impl Nonce {
+    /// Creates a `Nonce` with the given value.
+    pub fn new(nonce: u64) -> Self {
+        Self(nonce)
+    }
+}
+
 impl Default for Nonce {
     fn default() -> Self {
         Self(thread_rng().gen())
@@ -173,11 +183,7 @@ impl VarStr {
             ));
         }
 
-        if bytes.remaining() < str_len.0 {
-            return Err(io::ErrorKind::InvalidData.into());
-        }
-
-        let mut buffer = vec![0u8; str_len.0];
+        let mut buffer = vec![0u8; bounded_count(*str_len, 1, bytes.remaining())?];
         bytes.copy_to_slice(&mut buffer);
 
         Ok(VarStr(String::from_utf8(buffer).map_err(|err| {
@@ -203,6 +209,8 @@ impl Hash {
 }
 
 impl Codec for Hash {
+    const MIN_ENCODED_LEN: usize = 32;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.put_slice(&self.0);
 
@@ -233,6 +241,18 @@ pub fn read_n_bytes<const N: usize, B: Buf>(bytes: &mut B) -> io::Result<[u8; N]
     Ok(buffer)
 }
 
+/// Checks that `declared` elements, each encoded in at least `min_elem_size` bytes, fit in the
+/// `remaining` bytes, so a length prefix can't cause an allocation larger than the message.
+pub fn bounded_count(declared: usize, min_elem_size: usize, remaining: usize) -> io::Result<usize> {
+    match declared.checked_mul(min_elem_size) {
+        Some(required) if required <= remaining => Ok(declared),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{declared} elements of {min_elem_size}+ bytes don't fit in {remaining} bytes"),
+        )),
+    }
+}
+
 /// Reads a timestamp encoded as 8 bytes.
 pub fn read_timestamp<B: Buf>(bytes: &mut B) -> io::Result<OffsetDateTime> {
     let timestamp_i64 = i64::from_le_bytes(read_n_bytes(bytes)?);
@@ -246,3 +266,30 @@ pub fn read_short_timestamp<B: Buf>(bytes: &mut B) -> io::Result<OffsetDateTime>
     OffsetDateTime::from_unix_timestamp(timestamp_u32.into())
         .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Bad UTC timestamp"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::message::constants::MAX_MESSAGE_LEN;
+
+    /// Returns a vector length prefix claiming more entries than a maximum-length message can
+    /// hold, followed by `len` zeroed bytes (e.g. enough for a single entry).
+    pub(super) fn huge_count_followed_by(len: usize) -> Vec<u8> {
+        let mut bytes = vec![0xfe];
+        bytes.extend_from_slice(&(MAX_MESSAGE_LEN as u32).to_le_bytes());
+        bytes.resize(bytes.len() + len, 0);
+
+        bytes
+    }
+
+    #[test]
+    #[ignore]
+    fn bounded_count_checks_remaining_bytes() {
+        assert_eq!(bounded_count(3, 4, 12).unwrap(), 3);
+        assert_eq!(bounded_count(0, 36, 0).unwrap(), 0);
+
+        assert!(bounded_count(4, 4, 12).is_err());
+        // The required number of bytes overflows.
+        assert!(bounded_count(usize::MAX, 2, usize::MAX).is_err());
+    }
+}
@@ -1,11 +1,18 @@
 //! Transaction-related types.
 
-use std::{convert::TryInto, io};
+use std::io;
 
-use bytes::{Buf, BufMut};
-use sha2::Digest;
+use bytes::{Buf, BufMut, Bytes};
 
-use crate::protocol::payload::{codec::Codec, inv::InvHash, read_n_bytes, Hash, VarInt};
+use crate::protocol::{
+    hash::{blake2b_256, sha256d},
+    payload::{
+        bounded_count,
+        codec::Codec,
+        inv::{InvHash, WtxId},
+        read_n_bytes, Hash, VarInt,
+    },
+};
 
 /// A Zcash transaction ([spec](https://zips.z.cash/protocol/canopy.pdf#txnencodingandconsensus)).
 ///
@@ -26,21 +33,71 @@ impl Tx {
 
         self.encode(&mut buffer)?;
 
-        let hash_bytes_1 = sha2::Sha256::digest(buffer);
-        let hash_bytes_2 = sha2::Sha256::digest(hash_bytes_1);
+        Ok(Hash::new(sha256d(&buffer)))
+    }
+
+    /// Returns the transaction ID, the double Sha256 of the transaction for versions 1-4.
+    ///
+    /// The ID of a V5 transaction is a BLAKE2b digest of its effects, leaving out the
+    /// authorizing data ([ZIP-244]).
+    ///
+    /// [ZIP-244]: https://zips.z.cash/zip-0244
+    pub fn txid(&self) -> io::Result<Hash> {
+        match self {
+            Tx::V5(tx) => tx.txid(),
+            _ => self.double_sha256(),
+        }
+    }
 
-        let hash = Hash::new(hash_bytes_2.try_into().unwrap());
+    /// Returns the witnessed transaction ID: the [`txid`](Self::txid) followed by the
+    /// authorizing data digest, which is 32 `0xff` bytes for versions 1-4 ([ZIP-244]).
+    ///
+    /// [ZIP-244]: https://zips.z.cash/zip-0244
+    pub fn wtxid(&self) -> io::Result<WtxId> {
+        let auth_digest = match self {
+            Tx::V5(tx) => tx.auth_digest()?,
+            _ => Hash::new([0xff; 32]),
+        };
 
-        Ok(hash)
+        Ok(WtxId {
+            id: self.txid()?,
+            auth_digest,
+        })
     }
 
     /// Convenience function which creates the [`InvHash`] for this `Tx`.
     pub fn inv_hash(&self) -> InvHash {
         InvHash::Tx(self.double_sha256().unwrap())
     }
+
+    /// Creates a V1 transaction spending output `prev_out_index` of the transaction with hash
+    /// `prev_out_hash` into a single output worth `value` zatoshis.
+    ///
+    /// The scripts are left empty, so the transaction is well-formed but doesn't pass script
+    /// validation.
+    pub fn spending(prev_out_hash: Hash, prev_out_index: u32, value: i64) -> Self {
+        Tx::V1(TxV1 {
+            tx_in: vec![TxIn {
+                prev_out_hash,
+                prev_out_index,
+                script_len: VarInt(0),
+                script: Vec::new(),
+                sequence: u32::MAX,
+            }],
+            tx_out: vec![TxOut {
+                value,
+                pk_script_len: VarInt(0),
+                pk_script: Vec::new(),
+            }],
+            lock_time: 0,
+        })
+    }
 }
 
 impl Codec for Tx {
+    // The header, with the version.
+    const MIN_ENCODED_LEN: usize = 4;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         match self {
             Tx::V1(tx) => {
@@ -179,8 +236,12 @@ impl Codec for TxV2 {
         let tx_out = Vec::<TxOut>::decode(bytes)?;
         let lock_time = u32::from_le_bytes(read_n_bytes(bytes)?);
 
-        let join_split_count = *VarInt::decode(bytes)?;
-        let mut join_split = Vec::new();
+        let join_split_count = bounded_count(
+            *VarInt::decode(bytes)?,
+            JoinSplit::MIN_ENCODED_LEN,
+            bytes.remaining(),
+        )?;
+        let mut join_split = Vec::with_capacity(join_split_count);
 
         for _ in 0..join_split_count {
             let description = JoinSplit::decode_bctv14(bytes)?;
@@ -266,8 +327,12 @@ impl Codec for TxV3 {
         let lock_time = u32::from_le_bytes(read_n_bytes(bytes)?);
         let expiry_height = u32::from_le_bytes(read_n_bytes(bytes)?);
 
-        let join_split_count = *VarInt::decode(bytes)?;
-        let mut join_split = Vec::new();
+        let join_split_count = bounded_count(
+            *VarInt::decode(bytes)?,
+            JoinSplit::MIN_ENCODED_LEN,
+            bytes.remaining(),
+        )?;
+        let mut join_split = Vec::with_capacity(join_split_count);
 
         for _ in 0..join_split_count {
             let description = JoinSplit::decode_bctv14(bytes)?;
@@ -375,15 +440,19 @@ impl Codec for TxV4 {
         let spends_sapling = Vec::<SpendDescriptionV4>::decode(bytes)?;
         let outputs_sapling = Vec::<OutputDescriptionV4>::decode(bytes)?;
 
-        let join_split_count = VarInt::decode(bytes)?;
-        let mut join_split = Vec::new();
+        let join_split_count = bounded_count(
+            *VarInt::decode(bytes)?,
+            JoinSplit::MIN_ENCODED_LEN,
+            bytes.remaining(),
+        )?;
+        let mut join_split = Vec::with_capacity(join_split_count);
 
-        for _ in 0..*join_split_count {
+        for _ in 0..join_split_count {
             let description = JoinSplit::decode_groth16(bytes)?;
             join_split.push(description);
         }
 
-        let (join_split_pub_key, join_split_sig) = if *join_split_count > 0 {
+        let (join_split_pub_key, join_split_sig) = if join_split_count > 0 {
             if bytes.remaining() < 96 {
                 return Err(io::ErrorKind::InvalidData.into());
             }
@@ -449,11 +518,224 @@ pub struct TxV5 {
     value_balance_orchard: Option<i64>,
     anchor_orchard: Option<[u8; 32]>,
 
-    proofs_orchard: Option<Vec<u8>>,
+    proofs_orchard: Option<Bytes>,
     auth_sigs_orchard: Option<Vec<[u8; 64]>>,
     binding_sig_orchard: Option<[u8; 64]>,
 }
 
+// The note ciphertexts are hashed in three parts by ZIP-244: the compact note (as sent to light
+// clients), the memo, and the rest.
+const ENC_COMPACT_END: usize = 52;
+const ENC_MEMO_END: usize = ENC_COMPACT_END + 512;
+
+impl TxV5 {
+    /// The header, with the version and overwintered flag.
+    const HEADER: u32 = 5 | 1 << 31;
+
+    /// Returns the ZIP-244 transaction ID, which commits to the header and the transparent,
+    /// Sapling and Orchard effects.
+    fn txid(&self) -> io::Result<Hash> {
+        let mut digests = Vec::with_capacity(4 * 32);
+        digests.put_slice(&self.header_digest());
+        digests.put_slice(&self.transparent_digest()?);
+        digests.put_slice(&self.sapling_digest());
+        digests.put_slice(&self.orchard_digest());
+
+        Ok(Hash::new(blake2b_256(
+            &self.personal(b"ZcashTxHash_"),
+            &digests,
+        )))
+    }
+
+    /// Returns the ZIP-244 authorizing data digest, which commits to the scripts, proofs and
+    /// signatures.
+    fn auth_digest(&self) -> io::Result<Hash> {
+        let mut script_sigs = Vec::new();
+        for input in &self.tx_in {
+            input.script_len.encode(&mut script_sigs)?;
+            script_sigs.put_slice(&input.script);
+        }
+
+        let sapling = if self.spends_sapling.len() + self.outputs_sapling.len() > 0 {
+            let mut auth = Vec::new();
+            for proof in &self.spend_proofs_sapling {
+                auth.put_slice(proof);
+            }
+            for auth_sig in &self.spend_auth_sigs_sapling {
+                auth.put_slice(auth_sig);
+            }
+            for proof in &self.output_proofs_sapling {
+                auth.put_slice(proof);
+            }
+            // Must be present.
+            auth.put_slice(&self.binding_sig_sapling.unwrap());
+
+            blake2b_256(b"ZTxAuthSapliHash", &auth)
+        } else {
+            blake2b_256(b"ZTxAuthSapliHash", &[])
+        };
+
+        let orchard = if !self.actions_orchard.is_empty() {
+            // The proofs are hashed without their length, the fields must be present.
+            let mut auth = self.proofs_orchard.as_ref().unwrap().to_vec();
+            for auth_sig in self.auth_sigs_orchard.as_ref().unwrap() {
+                auth.put_slice(auth_sig);
+            }
+            auth.put_slice(&self.binding_sig_orchard.unwrap());
+
+            blake2b_256(b"ZTxAuthOrchaHash", &auth)
+        } else {
+            blake2b_256(b"ZTxAuthOrchaHash", &[])
+        };
+
+        let mut digests = Vec::with_capacity(3 * 32);
+        digests.put_slice(&blake2b_256(b"ZTxAuthTransHash", &script_sigs));
+        digests.put_slice(&sapling);
+        digests.put_slice(&orchard);
+
+        Ok(Hash::new(blake2b_256(
+            &self.personal(b"ZTxAuthHash_"),
+            &digests,
+        )))
+    }
+
+    /// Completes a personalization with the consensus branch, so digests differ across network
+    /// upgrades.
+    fn personal(&self, prefix: &[u8; 12]) -> [u8; 16] {
+        let mut personal = [0; 16];
+        personal[..12].copy_from_slice(prefix);
+        personal[12..].copy_from_slice(&self.consensus_branch.to_le_bytes());
+        personal
+    }
+
+    fn header_digest(&self) -> [u8; 32] {
+        let mut header = Vec::with_capacity(5 * 4);
+        header.put_u32_le(Self::HEADER);
+        header.put_u32_le(self.group_id);
+        header.put_u32_le(self.consensus_branch);
+        header.put_u32_le(self.lock_time);
+        header.put_u32_le(self.expiry_height);
+
+        blake2b_256(b"ZTxIdHeadersHash", &header)
+    }
+
+    fn transparent_digest(&self) -> io::Result<[u8; 32]> {
+        if self.tx_in.is_empty() && self.tx_out.is_empty() {
+            return Ok(blake2b_256(b"ZTxIdTranspaHash", &[]));
+        }
+
+        let mut prevouts = Vec::new();
+        let mut sequences = Vec::new();
+        for input in &self.tx_in {
+            input.prev_out_hash.encode(&mut prevouts)?;
+            prevouts.put_u32_le(input.prev_out_index);
+            sequences.put_u32_le(input.sequence);
+        }
+
+        let mut outputs = Vec::new();
+        self.tx_out
+            .iter()
+            .try_for_each(|output| output.encode(&mut outputs))?;
+
+        let mut digests = Vec::with_capacity(3 * 32);
+        digests.put_slice(&blake2b_256(b"ZTxIdPrevoutHash", &prevouts));
+        digests.put_slice(&blake2b_256(b"ZTxIdSequencHash", &sequences));
+        digests.put_slice(&blake2b_256(b"ZTxIdOutputsHash", &outputs));
+
+        Ok(blake2b_256(b"ZTxIdTranspaHash", &digests))
+    }
+
+    fn sapling_digest(&self) -> [u8; 32] {
+        if self.spends_sapling.is_empty() && self.outputs_sapling.is_empty() {
+            return blake2b_256(b"ZTxIdSaplingHash", &[]);
+        }
+
+        let spends = if !self.spends_sapling.is_empty() {
+            // Must be present, it's shared by the spends.
+            let anchor = self.anchor_sapling.unwrap();
+
+            let mut compact = Vec::new();
+            let mut noncompact = Vec::new();
+            for spend in &self.spends_sapling {
+                compact.put_slice(&spend.nullifier);
+                noncompact.put_slice(&spend.cv);
+                noncompact.put_slice(&anchor);
+                noncompact.put_slice(&spend.rk);
+            }
+
+            let mut digests = Vec::with_capacity(2 * 32);
+            digests.put_slice(&blake2b_256(b"ZTxIdSSpendCHash", &compact));
+            digests.put_slice(&blake2b_256(b"ZTxIdSSpendNHash", &noncompact));
+            blake2b_256(b"ZTxIdSSpendsHash", &digests)
+        } else {
+            blake2b_256(b"ZTxIdSSpendsHash", &[])
+        };
+
+        let outputs = if !self.outputs_sapling.is_empty() {
+            let mut compact = Vec::new();
+            let mut memos = Vec::new();
+            let mut noncompact = Vec::new();
+            for output in &self.outputs_sapling {
+                compact.put_slice(&output.cmu);
+                compact.put_slice(&output.ephemeral_key);
+                compact.put_slice(&output.enc_ciphertext[..ENC_COMPACT_END]);
+                memos.put_slice(&output.enc_ciphertext[ENC_COMPACT_END..ENC_MEMO_END]);
+                noncompact.put_slice(&output.cv);
+                noncompact.put_slice(&output.enc_ciphertext[ENC_MEMO_END..]);
+                noncompact.put_slice(&output.out_ciphertext);
+            }
+
+            let mut digests = Vec::with_capacity(3 * 32);
+            digests.put_slice(&blake2b_256(b"ZTxIdSOutC__Hash", &compact));
+            digests.put_slice(&blake2b_256(b"ZTxIdSOutM__Hash", &memos));
+            digests.put_slice(&blake2b_256(b"ZTxIdSOutN__Hash", &noncompact));
+            blake2b_256(b"ZTxIdSOutputHash", &digests)
+        } else {
+            blake2b_256(b"ZTxIdSOutputHash", &[])
+        };
+
+        let mut digests = Vec::with_capacity(2 * 32 + 8);
+        digests.put_slice(&spends);
+        digests.put_slice(&outputs);
+        // Must be present.
+        digests.put_i64_le(self.value_balance_sapling.unwrap());
+
+        blake2b_256(b"ZTxIdSaplingHash", &digests)
+    }
+
+    fn orchard_digest(&self) -> [u8; 32] {
+        if self.actions_orchard.is_empty() {
+            return blake2b_256(b"ZTxIdOrchardHash", &[]);
+        }
+
+        let mut compact = Vec::new();
+        let mut memos = Vec::new();
+        let mut noncompact = Vec::new();
+        for action in &self.actions_orchard {
+            compact.put_slice(&action.nullifier);
+            compact.put_slice(&action.cmx);
+            compact.put_slice(&action.ephemeral_key);
+            compact.put_slice(&action.enc_ciphertext[..ENC_COMPACT_END]);
+            memos.put_slice(&action.enc_ciphertext[ENC_COMPACT_END..ENC_MEMO_END]);
+            noncompact.put_slice(&action.cv);
+            noncompact.put_slice(&action.rk);
+            noncompact.put_slice(&action.enc_ciphertext[ENC_MEMO_END..]);
+            noncompact.put_slice(&action.out_ciphertext);
+        }
+
+        let mut digests = Vec::with_capacity(3 * 32 + 1 + 8 + 32);
+        digests.put_slice(&blake2b_256(b"ZTxIdOrcActCHash", &compact));
+        digests.put_slice(&blake2b_256(b"ZTxIdOrcActMHash", &memos));
+        digests.put_slice(&blake2b_256(b"ZTxIdOrcActNHash", &noncompact));
+        // Must be present.
+        digests.put_u8(self.flags_orchard.unwrap());
+        digests.put_i64_le(self.value_balance_orchard.unwrap());
+        digests.put_slice(&self.anchor_orchard.unwrap());
+
+        blake2b_256(b"ZTxIdOrchardHash", &digests)
+    }
+}
+
 impl Codec for TxV5 {
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.put_u32_le(self.group_id);
@@ -597,16 +879,8 @@ impl Codec for TxV5 {
             let anchor_orchard = read_n_bytes(bytes)?;
 
             // Decode the orchard proofs.
-            let n_proofs_orchard = VarInt::decode(bytes)?;
-
-            if bytes.remaining() < *n_proofs_orchard {
-                return Err(io::ErrorKind::InvalidData.into());
-            }
-
-            let mut proofs_orchard = Vec::new();
-            for _ in 0..*n_proofs_orchard {
-                proofs_orchard.push(bytes.get_u8());
-            }
+            let n_proofs_orchard = bounded_count(*VarInt::decode(bytes)?, 1, bytes.remaining())?;
+            let proofs_orchard = bytes.copy_to_bytes(n_proofs_orchard);
 
             // Decode orchard auth sigs.
             let mut auth_sigs_orchard = Vec::new();
@@ -668,6 +942,9 @@ struct TxIn {
 }
 
 impl Codec for TxIn {
+    // The previous output, an empty script and the sequence.
+    const MIN_ENCODED_LEN: usize = 32 + 4 + 1 + 4;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         self.prev_out_hash.encode(buffer)?;
         buffer.put_u32_le(self.prev_out_index);
@@ -685,12 +962,7 @@ impl Codec for TxIn {
         let prev_out_index = u32::from_le_bytes(read_n_bytes(bytes)?);
 
         let script_len = VarInt::decode(bytes)?;
-
-        if bytes.remaining() < script_len.0 {
-            return Err(io::ErrorKind::InvalidData.into());
-        }
-
-        let mut script = vec![0u8; script_len.0];
+        let mut script = vec![0u8; bounded_count(*script_len, 1, bytes.remaining())?];
         bytes.copy_to_slice(&mut script);
 
         let sequence = u32::from_le_bytes(read_n_bytes(bytes)?);
@@ -713,6 +985,9 @@ struct TxOut {
 }
 
 impl Codec for TxOut {
+    // The value and an empty script.
+    const MIN_ENCODED_LEN: usize = 8 + 1;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.put_i64_le(self.value);
         self.pk_script_len.encode(buffer)?;
@@ -724,12 +999,7 @@ impl Codec for TxOut {
     fn decode<B: Buf>(bytes: &mut B) -> io::Result<Self> {
         let value = i64::from_le_bytes(read_n_bytes(bytes)?);
         let pk_script_len = VarInt::decode(bytes)?;
-
-        if bytes.remaining() < pk_script_len.0 {
-            return Err(io::ErrorKind::InvalidData.into());
-        }
-
-        let mut pk_script = vec![0u8; pk_script_len.0];
+        let mut pk_script = vec![0u8; bounded_count(*pk_script_len, 1, bytes.remaining())?];
         bytes.copy_to_slice(&mut pk_script);
 
         Ok(Self {
@@ -760,6 +1030,9 @@ struct JoinSplit {
 }
 
 impl JoinSplit {
+    /// The size of a description with a Groth16 proof, the smaller kind.
+    const MIN_ENCODED_LEN: usize = 8 + 8 + 32 + 64 + 64 + 32 + 32 + 64 + 192 + 1202;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.put_u64_le(self.pub_old);
         buffer.put_u64_le(self.pub_new);
@@ -865,6 +1138,8 @@ struct SpendDescriptionV4 {
 }
 
 impl Codec for SpendDescriptionV4 {
+    const MIN_ENCODED_LEN: usize = 32 * 4 + 192 + 64;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.put_slice(&self.cv);
         buffer.put_slice(&self.anchor);
@@ -903,6 +1178,8 @@ struct SpendDescriptionV5 {
 }
 
 impl Codec for SpendDescriptionV5 {
+    const MIN_ENCODED_LEN: usize = 32 * 3;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.put_slice(&self.cv);
         buffer.put_slice(&self.nullifier);
@@ -931,6 +1208,8 @@ struct OutputDescriptionV4 {
 }
 
 impl Codec for OutputDescriptionV4 {
+    const MIN_ENCODED_LEN: usize = 32 * 3 + 580 + 80 + 192;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.put_slice(&self.cv);
         buffer.put_slice(&self.cmu);
@@ -971,6 +1250,8 @@ struct OutputDescriptionV5 {
 }
 
 impl Codec for OutputDescriptionV5 {
+    const MIN_ENCODED_LEN: usize = 32 * 3 + 580 + 80;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.put_slice(&self.cv);
         buffer.put_slice(&self.cmu);
@@ -1010,6 +1291,8 @@ struct ActionDescription {
 }
 
 impl Codec for ActionDescription {
+    const MIN_ENCODED_LEN: usize = 32 * 5 + 580 + 80;
+
     fn encode<B: BufMut>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.put_slice(&self.cv);
         buffer.put_slice(&self.nullifier);
@@ -1043,11 +1326,278 @@ impl Codec for ActionDescription {
     }
 }
 
+#[cfg(test)]
+pub(crate) mod strategies {
+    //! [`proptest`] strategies for well-formed V1-V4 transactions, they live here as the
+    //! transaction fields are private. V5 is left out, as its encoding isn't stable yet.
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+
+    /// Generates `N` arbitrary bytes.
+    pub(crate) fn bytes<const N: usize>() -> impl Strategy<Value = [u8; N]> {
+        vec(any::<u8>(), N).prop_map(|bytes| bytes.try_into().unwrap())
+    }
+
+    /// Generates an arbitrary [`Hash`].
+    pub(crate) fn hash() -> impl Strategy<Value = Hash> {
+        bytes::<32>().prop_map(Hash::new)
+    }
+
+    /// Generates a transaction of any of the supported versions.
+    pub(crate) fn tx() -> impl Strategy<Value = Tx> {
+        prop_oneof![
+            tx_v1().prop_map(Tx::V1),
+            tx_v2().prop_map(Tx::V2),
+            tx_v3().prop_map(Tx::V3),
+            tx_v4().prop_map(Tx::V4),
+        ]
+    }
+
+    fn tx_v1() -> impl Strategy<Value = TxV1> {
+        (vec(tx_in(), 0..4), vec(tx_out(), 0..4), any::<u32>()).prop_map(
+            |(tx_in, tx_out, lock_time)| TxV1 {
+                tx_in,
+                tx_out,
+                lock_time,
+            },
+        )
+    }
+
+    fn tx_v2() -> impl Strategy<Value = TxV2> {
+        (
+            vec(tx_in(), 0..4),
+            vec(tx_out(), 0..4),
+            any::<u32>(),
+            join_splits(false),
+        )
+            .prop_map(
+                |(tx_in, tx_out, lock_time, (join_split, join_split_pub_key, join_split_sig))| {
+                    TxV2 {
+                        tx_in,
+                        tx_out,
+                        lock_time,
+                        join_split,
+                        join_split_pub_key,
+                        join_split_sig,
+                    }
+                },
+            )
+    }
+
+    fn tx_v3() -> impl Strategy<Value = TxV3> {
+        (
+            any::<u32>(),
+            vec(tx_in(), 0..4),
+            vec(tx_out(), 0..4),
+            any::<u32>(),
+            any::<u32>(),
+            join_splits(false),
+        )
+            .prop_map(
+                |(
+                    group_id,
+                    tx_in,
+                    tx_out,
+                    lock_time,
+                    expiry_height,
+                    (join_split, join_split_pub_key, join_split_sig),
+                )| TxV3 {
+                    group_id,
+                    tx_in,
+                    tx_out,
+                    lock_time,
+                    expiry_height,
+                    join_split,
+                    join_split_pub_key,
+                    join_split_sig,
+                },
+            )
+    }
+
+    fn tx_v4() -> impl Strategy<Value = TxV4> {
+        (
+            any::<u32>(),
+            vec(tx_in(), 0..4),
+            vec(tx_out(), 0..4),
+            any::<u32>(),
+            any::<u32>(),
+            any::<i64>(),
+            vec(spend_description_v4(), 0..3),
+            vec(output_description_v4(), 0..3),
+            join_splits(true),
+            bytes::<64>(),
+        )
+            .prop_map(
+                |(
+                    group_id,
+                    tx_in,
+                    tx_out,
+                    lock_time,
+                    expiry_height,
+                    value_balance_sapling,
+                    spends_sapling,
+                    outputs_sapling,
+                    (join_split, join_split_pub_key, join_split_sig),
+                    binding_sig,
+                )| {
+                    // The binding signature is only encoded alongside sapling descriptions.
+                    let has_sapling = !spends_sapling.is_empty() || !outputs_sapling.is_empty();
+
+                    TxV4 {
+                        group_id,
+                        tx_in,
+                        tx_out,
+                        lock_time,
+                        expiry_height,
+                        value_balance_sapling,
+                        spends_sapling,
+                        outputs_sapling,
+                        join_split,
+                        join_split_pub_key,
+                        join_split_sig,
+                        binding_sig_sapling: has_sapling.then_some(binding_sig),
+                    }
+                },
+            )
+    }
+
+    fn tx_in() -> impl Strategy<Value = TxIn> {
+        (hash(), any::<u32>(), vec(any::<u8>(), 0..64), any::<u32>()).prop_map(
+            |(prev_out_hash, prev_out_index, script, sequence)| TxIn {
+                prev_out_hash,
+                prev_out_index,
+                script_len: VarInt(script.len()),
+                script,
+                sequence,
+            },
+        )
+    }
+
+    fn tx_out() -> impl Strategy<Value = TxOut> {
+        (any::<i64>(), vec(any::<u8>(), 0..64)).prop_map(|(value, pk_script)| TxOut {
+            value,
+            pk_script_len: VarInt(pk_script.len()),
+            pk_script,
+        })
+    }
+
+    /// Generates the join split descriptions along with their key and signature, which are only
+    /// present if there is at least one description.
+    #[allow(clippy::type_complexity)]
+    fn join_splits(
+        groth16: bool,
+    ) -> impl Strategy<Value = (Vec<JoinSplit>, Option<[u8; 32]>, Option<[u8; 64]>)> {
+        (vec(join_split(groth16), 0..3), bytes::<32>(), bytes::<64>()).prop_map(
+            |(join_split, pub_key, sig)| {
+                let present = !join_split.is_empty();
+                (
+                    join_split,
+                    present.then_some(pub_key),
+                    present.then_some(sig),
+                )
+            },
+        )
+    }
+
+    fn join_split(groth16: bool) -> impl Strategy<Value = JoinSplit> {
+        let zkproof = if groth16 {
+            bytes::<192>().prop_map(Zkproof::Groth16).boxed()
+        } else {
+            bytes::<296>().prop_map(Zkproof::BCTV14).boxed()
+        };
+
+        (
+            any::<u64>(),
+            any::<u64>(),
+            bytes::<32>(),
+            bytes::<64>(),
+            bytes::<64>(),
+            bytes::<32>(),
+            bytes::<32>(),
+            bytes::<64>(),
+            zkproof,
+            bytes::<1202>(),
+        )
+            .prop_map(
+                |(
+                    pub_old,
+                    pub_new,
+                    anchor,
+                    nullifiers,
+                    commitments,
+                    ephemeral_key,
+                    random_seed,
+                    vmacs,
+                    zkproof,
+                    enc_ciphertexts,
+                )| JoinSplit {
+                    pub_old,
+                    pub_new,
+                    anchor,
+                    nullifiers,
+                    commitments,
+                    ephemeral_key,
+                    random_seed,
+                    vmacs,
+                    zkproof,
+                    enc_ciphertexts,
+                },
+            )
+    }
+
+    fn spend_description_v4() -> impl Strategy<Value = SpendDescriptionV4> {
+        (
+            bytes::<32>(),
+            bytes::<32>(),
+            bytes::<32>(),
+            bytes::<32>(),
+            bytes::<192>(),
+            bytes::<64>(),
+        )
+            .prop_map(|(cv, anchor, nullifier, rk, zkproof, spend_auth_sig)| {
+                SpendDescriptionV4 {
+                    cv,
+                    anchor,
+                    nullifier,
+                    rk,
+                    zkproof,
+                    spend_auth_sig,
+                }
+            })
+    }
+
+    fn output_description_v4() -> impl Strategy<Value = OutputDescriptionV4> {
+        (
+            bytes::<32>(),
+            bytes::<32>(),
+            bytes::<32>(),
+            bytes::<580>(),
+            bytes::<80>(),
+            bytes::<192>(),
+        )
+            .prop_map(
+                |(cv, cmu, ephemeral_key, enc_ciphertext, out_ciphertext, zkproof)| {
+                    OutputDescriptionV4 {
+                        cv,
+                        cmu,
+                        ephemeral_key,
+                        enc_ciphertext,
+                        out_ciphertext,
+                        zkproof,
+                    }
+                },
+            )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use io::Cursor;
 
     use super::*;
+    use crate::protocol::payload::tests::huge_count_followed_by;
 
     #[test]
     #[ignore]
@@ -1064,6 +1614,20 @@ mod tests {
         assert_eq!(tx_v1, Tx::decode(&mut Cursor::new(&bytes)).unwrap());
     }
 
+    #[test]
+    #[ignore]
+    fn spending_transaction_round_trip() {
+        let tx = Tx::spending(Hash::new([7; 32]), 3, 1000);
+
+        let mut bytes = Vec::new();
+        tx.encode(&mut bytes).unwrap();
+
+        // The outpoint follows the version and the input count.
+        assert_eq!(&bytes[5..37], &[7; 32]);
+        assert_eq!(&bytes[37..41], &3u32.to_le_bytes());
+        assert_eq!(tx, Tx::decode(&mut Cursor::new(&bytes)).unwrap());
+    }
+
     #[test]
     #[ignore]
     fn empty_transaction_v2_round_trip() {
@@ -1158,4 +1722,35 @@ mod tests {
 
         assert_eq!(tx_v5, Tx::decode(&mut Cursor::new(&bytes)).unwrap());
     }
+
+    /// Returns a V1 transaction's version followed by `counts` and a vector length claiming more
+    /// entries than a maximum-length message can hold, but only a few bytes of them.
+    fn v1_with_huge_count(counts: &[u8]) -> Vec<u8> {
+        let mut bytes = 1u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(counts);
+        bytes.extend(huge_count_followed_by(64));
+
+        bytes
+    }
+
+    #[test]
+    #[ignore]
+    fn huge_input_count_is_rejected() {
+        let bytes = v1_with_huge_count(&[]);
+
+        let error = Tx::decode(&mut &bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("don't fit"), "{error}");
+    }
+
+    #[test]
+    #[ignore]
+    fn huge_output_count_is_rejected() {
+        // No inputs.
+        let bytes = v1_with_huge_count(&[0]);
+
+        let error = Tx::decode(&mut &bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("don't fit"), "{error}");
+    }
 }
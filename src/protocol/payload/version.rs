@@ -13,6 +13,14 @@ use crate::protocol::{
     },
 };
 
+/// The service bit of a node serving the full block chain.
+pub const NODE_NETWORK: u64 = 1;
+/// The service bit of a node supporting bloom filters, see [BIP 111](https://github.com/bitcoin/bips/blob/master/bip-0111.mediawiki).
+pub const NODE_BLOOM: u64 = 1 << 2;
+
+/// The first protocol version with the `relay` field, see [BIP 37](https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki).
+pub const RELAY_VERSION: u32 = 70_001;
+
 /// A version payload.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Version {
@@ -33,6 +41,8 @@ pub struct Version {
     /// The start last block received by the sender.
     pub start_height: i32,
     /// Specifies if the receiver should relay transactions.
+    ///
+    /// Only encoded from [`RELAY_VERSION`], older versions always have transactions relayed.
     pub relay: bool,
 }
 
@@ -42,16 +52,16 @@ impl Version {
     pub fn new(addr_recv: SocketAddr, addr_from: SocketAddr) -> Self {
         Self {
             version: ProtocolVersion::current(),
-            services: 1,
+            services: NODE_NETWORK,
             timestamp: OffsetDateTime::now_utc(),
             addr_recv: NetworkAddr {
                 last_seen: None,
-                services: 1,
+                services: NODE_NETWORK,
                 addr: addr_recv,
             },
             addr_from: NetworkAddr {
                 last_seen: None,
-                services: 1,
+                services: NODE_NETWORK,
                 addr: addr_from,
             },
             nonce: Nonce::default(),
@@ -67,6 +77,20 @@ impl Version {
         self.version = ProtocolVersion(version);
         self
     }
+
+    /// Sets the nonce, which the receiver compares to the ones it sent to detect connections to
+    /// itself. A random one is used by default.
+    pub fn with_nonce(mut self, nonce: Nonce) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    /// Sets the services advertised by the sender.
+    pub fn with_services(mut self, services: u64) -> Self {
+        self.services = services;
+        self.addr_from.services = services;
+        self
+    }
 }
 
 impl Codec for Version {
@@ -81,7 +105,9 @@ impl Codec for Version {
         self.nonce.encode(buffer)?;
         self.user_agent.encode(buffer)?;
         buffer.put_i32_le(self.start_height);
-        buffer.put_u8(self.relay as u8);
+        if self.version.0 >= RELAY_VERSION {
+            buffer.put_u8(self.relay as u8);
+        }
 
         Ok(())
     }
@@ -98,7 +124,11 @@ impl Codec for Version {
         let user_agent = VarStr::decode(bytes)?;
 
         let start_height = i32::from_le_bytes(read_n_bytes(bytes)?);
-        let relay = u8::from_le_bytes(read_n_bytes(bytes)?) != 0;
+        let relay = if version.0 >= RELAY_VERSION {
+            u8::from_le_bytes(read_n_bytes(bytes)?) != 0
+        } else {
+            true
+        };
 
         Ok(Self {
             version,
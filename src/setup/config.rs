@@ -4,18 +4,24 @@ use std::{
     fmt::Write,
     fs, io,
     io::{Error, ErrorKind},
-    net::{IpAddr, Ipv4Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use serde::{Deserialize, Serialize};
 
-use crate::setup::node::Action;
+use crate::{
+    protocol::message::constants::{MAGIC_LEN, MAGIC_REGTEST, MAGIC_TESTNET},
+    setup::node::{Action, Readiness},
+    tools::LONG_TIMEOUT,
+};
 
 // The names of the files the node configurations will be written to.
 const ZEBRA_CONFIG: &str = "zebra.toml";
 const ZCASHD_CONFIG: &str = "zcash.conf";
 const ZCASHD_CACHE: &str = "testnet3";
+const ZCASHD_REGTEST_CACHE: &str = "regtest";
 
 // Ziggurat's configuration directory and file. Caches are written to this directory.
 const CONFIG: &str = ".ziggurat";
@@ -24,13 +30,177 @@ const CONFIG_FILE: &str = "config.toml";
 const DEFAULT_PORT: u16 = 8080;
 
 /// Convenience struct for reading Ziggurat's configuration file.
+///
+/// The node to run is picked by `kind`, from its `[zcashd]` or `[zebra]` table if present and the
+/// top-level `path` and `start_command` otherwise.
 #[derive(Deserialize)]
 struct ConfigFile {
     kind: NodeKind,
+    path: Option<PathBuf>,
+    start_command: Option<String>,
+    zcashd: Option<NodeCommand>,
+    zebra: Option<NodeCommand>,
+    start_timeout_secs: Option<u64>,
+    address_family: Option<AddressFamily>,
+    rpc: Option<RpcConfig>,
+    performance: Option<PerformanceConfig>,
+    resistance: Option<ResistanceConfig>,
+}
+
+/// Where and how to start a node of a given kind, read from the `[zcashd]` and `[zebra]` tables in
+/// `config.toml`.
+#[derive(Deserialize)]
+struct NodeCommand {
     path: PathBuf,
     start_command: String,
 }
 
+/// Load and SLA settings for the performance tests, read from the optional `[performance]` table
+/// in `config.toml`. Missing fields fall back to their defaults.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PerformanceConfig {
+    /// The number of concurrent connections to open.
+    pub connections: u16,
+    /// The maximum time (in ms) the node may take to accept all connections.
+    pub max_accept_time_ms: u64,
+    /// The maximum median handshake latency (in ms).
+    pub max_p50_handshake_ms: u64,
+    /// The maximum 95th percentile handshake latency (in ms).
+    pub max_p95_handshake_ms: u64,
+    /// The number of Pings sent per second when flooding the node.
+    pub flood_ping_rate: u32,
+    /// How long (in seconds) to flood the node for.
+    pub flood_duration_secs: u64,
+    /// The maximum 99th percentile Pong latency (in ms) while flooding.
+    pub max_p99_pong_ms: u64,
+    /// The number of transactions sent when measuring their propagation.
+    pub relay_txs: u16,
+    /// The maximum 95th percentile latency (in ms) between sending a transaction to the node and
+    /// another peer receiving its `Inv`, which includes the node's randomized trickle delay.
+    pub max_p95_tx_relay_ms: u64,
+}
+
+impl Default for PerformanceConfig {
+    fn default() -> Self {
+        Self {
+            connections: 100,
+            max_accept_time_ms: 5_000,
+            max_p50_handshake_ms: 100,
+            max_p95_handshake_ms: 500,
+            flood_ping_rate: 1_000,
+            flood_duration_secs: 10,
+            max_p99_pong_ms: 100,
+            relay_txs: 50,
+            max_p95_tx_relay_ms: 15_000,
+        }
+    }
+}
+
+/// Settings for the resistance tests, read from the optional `[resistance]` table in
+/// `config.toml`. Missing fields fall back to their defaults.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ResistanceConfig {
+    /// The delay (in ms) between the single bytes written by a slow-loris peer.
+    pub slow_loris_byte_delay_ms: u64,
+    /// The time (in seconds) the node has to either complete a slow-loris handshake or disconnect
+    /// the peer.
+    pub slow_loris_timeout_secs: u64,
+    /// The time (in seconds) the node has to disconnect a peer which never completes the
+    /// handshake.
+    pub handshake_timeout_secs: u64,
+    /// The time (in seconds) the node has to disconnect a peer which stops writing in the middle
+    /// of a message.
+    pub partial_message_timeout_secs: u64,
+}
+
+impl Default for ResistanceConfig {
+    fn default() -> Self {
+        Self {
+            slow_loris_byte_delay_ms: 10,
+            slow_loris_timeout_secs: 60,
+            handshake_timeout_secs: 60,
+            partial_message_timeout_secs: 120,
+        }
+    }
+}
+
+/// The node's JSON-RPC settings, read from the optional `[rpc]` table in `config.toml`.
+#[derive(Deserialize, Debug, Clone)]
+pub(super) struct RpcConfig {
+    /// The port the node's RPC server listens on (bound to localhost).
+    pub(super) port: u16,
+    /// The RPC username (ignored by zebra, which doesn't support authentication).
+    pub(super) user: Option<String>,
+    /// The RPC password (ignored by zebra, which doesn't support authentication).
+    pub(super) password: Option<String>,
+}
+
+impl RpcConfig {
+    /// Returns the local address of the node's RPC server.
+    pub(super) fn addr(&self) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), self.port)
+    }
+}
+
+/// The network the node is configured to run on.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    /// The public test network.
+    #[default]
+    Testnet,
+    /// A local regression test network, blocks can be generated on demand over RPC.
+    Regtest,
+}
+
+impl Network {
+    /// Returns the magic bytes identifying the network in message headers.
+    pub fn magic(&self) -> [u8; MAGIC_LEN] {
+        match self {
+            Network::Testnet => MAGIC_TESTNET,
+            Network::Regtest => MAGIC_REGTEST,
+        }
+    }
+
+    /// Returns a protocol version floor on the network: peers claiming a lower version are
+    /// refused by both nodes.
+    ///
+    /// The actual minimum may be higher, both nodes raise it with each network upgrade active at
+    /// their tip. The floors predate every upgrade the nodes support:
+    /// - testnet: 170_012, the Canopy version, older peers get an obsolete `Reject` or are
+    ///   disconnected (ZG-CONFORMANCE-008);
+    /// - regtest: 170_002, zcashd's `MIN_PEER_PROTO_VERSION`, no upgrade is active on a fresh
+    ///   chain.
+    pub fn min_peer_version(&self) -> u32 {
+        match self {
+            Network::Testnet => 170_012,
+            Network::Regtest => 170_002,
+        }
+    }
+}
+
+/// The address family the node listens on, and connects to its initial peers with.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all(deserialize = "lowercase"))]
+pub enum AddressFamily {
+    /// The node listens on `127.0.0.1`.
+    #[default]
+    Ipv4,
+    /// The node listens on `::1`.
+    Ipv6,
+}
+
+impl AddressFamily {
+    /// Returns the loopback address of the family.
+    pub fn loopback(&self) -> IpAddr {
+        match self {
+            AddressFamily::Ipv4 => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            AddressFamily::Ipv6 => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        }
+    }
+}
+
 /// Node configuration abstracted by a [`Node`] instance.
 ///
 /// The information contained in this struct will be written to a config file read by the node at
@@ -48,8 +218,21 @@ pub(super) struct NodeConfig {
     pub(super) max_peers: usize,
     /// Setting this option to true will enable node logging to stdout.
     pub(super) log_to_stdout: bool,
+    /// Setting this option to true will capture the node's stdout and stderr.
+    pub(super) log_capture: bool,
+    /// A file the captured output is also written to.
+    pub(super) log_capture_file: Option<PathBuf>,
     /// Defines the initial action to take once the node has started.
     pub(super) initial_action: Action,
+    /// An existing (pre-synced) chain data directory to start the node with.
+    pub(super) data_dir: Option<PathBuf>,
+    /// The network the node runs on.
+    pub(super) network: Network,
+    /// How long to wait for the node to become reachable on start, overrides the value from
+    /// `config.toml`.
+    pub(super) start_timeout: Option<Duration>,
+    /// The check deciding the node is ready to answer queries.
+    pub(super) readiness: Readiness,
 }
 
 impl NodeConfig {
@@ -66,15 +249,21 @@ impl NodeConfig {
             initial_peers: HashSet::new(),
             max_peers: 50,
             log_to_stdout: false,
+            log_capture: false,
+            log_capture_file: None,
             initial_action: Action::None,
+            data_dir: None,
+            network: Network::default(),
+            start_timeout: None,
+            readiness: Readiness::default(),
         })
     }
 }
 
 /// Describes the node kind, currently supports the two known variants.
-#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all(deserialize = "lowercase"))]
-pub(super) enum NodeKind {
+pub enum NodeKind {
     Zebra,
     Zcashd,
 }
@@ -88,10 +277,53 @@ impl NodeKind {
         }
     }
 
-    pub(super) fn cache_path(&self, wrapping_dir: &Path) -> Option<PathBuf> {
+    /// Indicates if the node negotiates wtxid relay with `wtxidrelay` ([BIP-339]).
+    ///
+    /// Neither does, they ignore the message: Zcash has no such negotiation, [ZIP-239] announces
+    /// V5 transactions by wtxid (`MSG_WTX`) to every peer from protocol version 170014, and
+    /// older transactions by txid.
+    ///
+    /// [BIP-339]: https://github.com/bitcoin/bips/blob/master/bip-0339.mediawiki
+    /// [ZIP-239]: https://zips.z.cash/zip-0239
+    pub fn supports_wtxid_relay(&self) -> bool {
         match self {
-            NodeKind::Zebra => None,
-            NodeKind::Zcashd => Some(wrapping_dir.join(ZCASHD_CACHE)),
+            NodeKind::Zebra => false,
+            NodeKind::Zcashd => false,
+        }
+    }
+
+    /// Returns how long the node waits before pinging a peer which completed the handshake, and
+    /// between two pings.
+    ///
+    /// - zcashd: 2 minutes (`PING_INTERVAL`), it only pings peers which don't have a ping in
+    ///   flight;
+    /// - zebra: 59 seconds (`HEARTBEAT_INTERVAL`).
+    pub fn ping_interval(&self) -> Duration {
+        match self {
+            NodeKind::Zebra => Duration::from_secs(59),
+            NodeKind::Zcashd => Duration::from_secs(2 * 60),
+        }
+    }
+
+    /// Returns how long the node waits for a `Pong` before disconnecting the peer.
+    ///
+    /// - zcashd: 20 minutes (`TIMEOUT_INTERVAL`), which is also how long a peer can stay silent;
+    /// - zebra: 20 seconds (`REQUEST_TIMEOUT`), a heartbeat failure closes the connection.
+    ///
+    /// An unresponsive peer is disconnected within `ping_interval() + ping_timeout()` of the
+    /// handshake (ZG-CONFORMANCE-028).
+    pub fn ping_timeout(&self) -> Duration {
+        match self {
+            NodeKind::Zebra => Duration::from_secs(20),
+            NodeKind::Zcashd => Duration::from_secs(20 * 60),
+        }
+    }
+
+    pub(super) fn cache_path(&self, wrapping_dir: &Path, network: Network) -> Option<PathBuf> {
+        match (self, network) {
+            (NodeKind::Zebra, _) => None,
+            (NodeKind::Zcashd, Network::Testnet) => Some(wrapping_dir.join(ZCASHD_CACHE)),
+            (NodeKind::Zcashd, Network::Regtest) => Some(wrapping_dir.join(ZCASHD_REGTEST_CACHE)),
         }
     }
 }
@@ -107,26 +339,56 @@ pub(super) struct NodeMetaData {
     pub(super) start_command: OsString,
     /// The args to run with the start command.
     pub(super) start_args: Vec<OsString>,
+    /// How long to wait for the node to become reachable on start.
+    pub(super) start_timeout: Duration,
+    /// The address family the node listens on by default.
+    pub(super) address_family: AddressFamily,
+    /// The RPC settings, if the node's RPC server should be enabled.
+    pub(super) rpc: Option<RpcConfig>,
+    /// The load and SLA settings used by the performance tests.
+    pub(super) performance: PerformanceConfig,
+    /// The settings used by the resistance tests.
+    pub(super) resistance: ResistanceConfig,
 }
 
 impl NodeMetaData {
-    pub(super) fn new(config_path: PathBuf) -> io::Result<Self> {
+    /// Reads the metadata of the node `kind`, or of the `kind` set in `config.toml` if `None`.
+    pub(super) fn new(config_path: PathBuf, kind: Option<NodeKind>) -> io::Result<Self> {
         // Read Ziggurat's configuration file.
         let path = config_path.join(CONFIG_FILE);
         let config_string = fs::read_to_string(path)?;
         let config_file: ConfigFile =
             toml::from_str(&config_string).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let kind = kind.unwrap_or(config_file.kind);
 
         let args_from = |command: &str| -> Vec<OsString> {
             command.split_whitespace().map(OsString::from).collect()
         };
 
-        let mut start_args = args_from(&config_file.start_command);
+        let (table, command) = match kind {
+            NodeKind::Zcashd => ("zcashd", config_file.zcashd),
+            NodeKind::Zebra => ("zebra", config_file.zebra),
+        };
+        let (path, start_command) = match (command, config_file.path, config_file.start_command) {
+            (Some(command), ..) => (command.path, command.start_command),
+            (None, Some(path), Some(start_command)) => (path, start_command),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("config.toml has neither a [{table}] table nor a top-level path and start_command"),
+                ))
+            }
+        };
+
+        let mut start_args = args_from(&start_command);
+        if start_args.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "start_command is empty"));
+        }
         let start_command = start_args.remove(0);
 
         // Insert the node's config file path into start args.
-        let config_file_path = config_file.kind.config_filepath(&config_path);
-        match config_file.kind {
+        let config_file_path = kind.config_filepath(&config_path);
+        match kind {
             NodeKind::Zebra => {
                 // Zebra's final arg must be `start`, so we insert the actual args before it.
                 let n_args = start_args.len();
@@ -140,15 +402,23 @@ impl NodeMetaData {
                 start_args.insert(n_args, config_file_path.into_os_string());
             }
             NodeKind::Zcashd => {
-                start_args.push(format!("-datadir={}", config_path.to_str().unwrap()).into());
+                // The data directory is only known at start time, see `Node::start`.
+                start_args.push(format!("-conf={}", config_file_path.to_str().unwrap()).into());
             }
         }
 
         Ok(Self {
-            kind: config_file.kind,
-            path: config_file.path,
+            kind,
+            path,
             start_command,
             start_args,
+            start_timeout: config_file
+                .start_timeout_secs
+                .map_or(LONG_TIMEOUT, Duration::from_secs),
+            address_family: config_file.address_family.unwrap_or_default(),
+            rpc: config_file.rpc,
+            performance: config_file.performance.unwrap_or_default(),
+            resistance: config_file.resistance.unwrap_or_default(),
         })
     }
 }
@@ -159,11 +429,15 @@ pub(super) struct ZebraConfigFile {
     network: NetworkConfig,
     state: StateConfig,
     tracing: TracingConfig,
+    rpc: Option<ZebraRpcConfig>,
 }
 
 impl ZebraConfigFile {
     /// Generate the toml configuration as a string.
-    pub(super) fn generate(config: &NodeConfig) -> Result<String, toml::ser::Error> {
+    pub(super) fn generate(
+        config: &NodeConfig,
+        rpc: Option<&RpcConfig>,
+    ) -> Result<String, toml::ser::Error> {
         // Create the structs to prepare for encoding.
         let initial_testnet_peers: HashSet<String> = config
             .initial_peers
@@ -177,15 +451,21 @@ impl ZebraConfigFile {
                 listen_addr: config.local_addr,
                 initial_testnet_peers,
                 peerset_initial_target_size: config.max_peers,
-                network: String::from("Testnet"),
+                network: format!("{:?}", config.network),
             },
             state: StateConfig {
-                cache_dir: None,
-                ephemeral: true,
+                cache_dir: config
+                    .data_dir
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().into_owned()),
+                ephemeral: config.data_dir.is_none(),
             },
             tracing: TracingConfig {
                 filter: Some("zebra_network=trace,zebrad=trace".to_string()),
             },
+            rpc: rpc.map(|rpc| ZebraRpcConfig {
+                listen_addr: rpc.addr(),
+            }),
         };
 
         // Write the toml to a string.
@@ -212,13 +492,22 @@ struct TracingConfig {
     filter: Option<String>,
 }
 
+#[derive(Serialize)]
+struct ZebraRpcConfig {
+    listen_addr: SocketAddr,
+}
+
 /// Convenience struct for writing a zcashd compatible configuration file.
 pub(super) struct ZcashdConfigFile;
 
 impl ZcashdConfigFile {
-    pub(super) fn generate(config: &NodeConfig) -> String {
+    pub(super) fn generate(config: &NodeConfig, rpc: Option<&RpcConfig>) -> String {
+        let network = match config.network {
+            Network::Testnet => "testnet=1",
+            Network::Regtest => "regtest=1",
+        };
         let mut contents = format!(
-            "testnet=1\nwhitebind={}\nmaxconnections={}\n",
+            "{network}\nwhitebind={}\nmaxconnections={}\n",
             config.local_addr, config.max_peers
         );
 
@@ -230,6 +519,22 @@ impl ZcashdConfigFile {
             }
         }
 
+        if let Some(rpc) = rpc {
+            let _ = writeln!(
+                contents,
+                "server=1\nrpcbind=127.0.0.1\nrpcallowip=127.0.0.1\nrpcport={}",
+                rpc.port
+            );
+            // `getnewaddress` is deprecated, but still the simplest way to get a wallet address.
+            contents.push_str("allowdeprecated=getnewaddress\n");
+            if let Some(user) = &rpc.user {
+                let _ = writeln!(contents, "rpcuser={user}");
+            }
+            if let Some(password) = &rpc.password {
+                let _ = writeln!(contents, "rpcpassword={password}");
+            }
+        }
+
         contents
     }
 }
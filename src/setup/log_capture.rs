@@ -0,0 +1,107 @@
+//! Captures the output of a node's process, so it can be inspected once a test fails.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::Path,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use parking_lot::Mutex;
+
+/// The maximum number of lines kept in memory, older lines are dropped first.
+const MAX_LINES: usize = 100_000;
+
+/// The lines read from a node's stdout and stderr, interleaved in the order they were read.
+#[derive(Default)]
+pub(super) struct LogCapture {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    file: Option<Arc<Mutex<File>>>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl LogCapture {
+    /// Creates a new capture, which also writes the lines to the file at `path` if set.
+    pub(super) fn new(path: Option<&Path>) -> io::Result<Self> {
+        let file = path
+            .map(File::create)
+            .transpose()?
+            .map(|file| Arc::new(Mutex::new(file)));
+
+        Ok(Self {
+            file,
+            ..Default::default()
+        })
+    }
+
+    /// Spawns a thread reading `output` until it's closed, i.e. until the process exits. Lines are
+    /// also echoed to stdout if `echo` is set.
+    pub(super) fn read_from<R: Read + Send + 'static>(&mut self, output: R, echo: bool) {
+        let lines = self.lines.clone();
+        let file = self.file.clone();
+
+        self.readers.push(thread::spawn(move || {
+            // Non UTF-8 output ends the capture for this stream, like a closed pipe would.
+            for line in BufReader::new(output).lines().map_while(Result::ok) {
+                if echo {
+                    println!("{line}");
+                }
+
+                if let Some(file) = &file {
+                    // A failed write only affects the file, the line is still kept in memory.
+                    let _ = writeln!(file.lock(), "{line}");
+                }
+
+                let mut lines = lines.lock();
+                if lines.len() == MAX_LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+            }
+        }));
+    }
+
+    /// Waits for the readers to reach the end of their streams, the process must have exited.
+    pub(super) fn join(&mut self) {
+        for reader in self.readers.drain(..) {
+            let _ = reader.join();
+        }
+    }
+
+    /// Returns all the captured lines.
+    pub(super) fn lines(&self) -> Vec<String> {
+        self.lines.lock().iter().cloned().collect()
+    }
+
+    /// Returns the last `n` captured lines, joined with newlines.
+    pub(super) fn tail(&self, n: usize) -> String {
+        let lines = self.lines.lock();
+        let skip = lines.len().saturating_sub(n);
+
+        lines
+            .iter()
+            .skip(skip)
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn capture_keeps_lines_in_order() {
+        let mut logs = LogCapture::new(None).unwrap();
+        logs.read_from(&b"one\ntwo\nthree\n"[..], false);
+        logs.join();
+
+        assert_eq!(logs.lines(), vec!["one", "two", "three"]);
+        assert_eq!(logs.tail(2), "two\nthree");
+        assert_eq!(logs.tail(5), "one\ntwo\nthree");
+    }
+}
@@ -1,4 +1,8 @@
 //! Utilities for setting up and tearing down node instances (`zcashd` or `zebra`).
 
 mod config;
+mod log_capture;
 pub mod node;
+pub mod rpc;
+
+pub use config::{AddressFamily, Network, NodeKind, PerformanceConfig, ResistanceConfig};
@@ -3,6 +3,7 @@
 use std::{
     fs, io,
     net::SocketAddr,
+    path::PathBuf,
     process::{Child, Command, ExitStatus, Stdio},
     time::Duration,
 };
@@ -10,17 +11,26 @@ use std::{
 use tracing::error;
 
 use crate::{
-    protocol::payload::{
-        block::{Block, Headers},
-        Hash, Inv,
+    protocol::{
+        message::constants::MAGIC_LEN,
+        payload::{
+            block::{Block, Headers},
+            Hash, Inv,
+        },
+    },
+    setup::{
+        config::{
+            AddressFamily, Network, NodeConfig, NodeKind, NodeMetaData, PerformanceConfig,
+            ResistanceConfig, ZcashdConfigFile, ZebraConfigFile,
+        },
+        log_capture::LogCapture,
+        rpc::RpcClient,
     },
-    setup::config::{NodeConfig, NodeKind, NodeMetaData, ZcashdConfigFile, ZebraConfigFile},
     tools::{
         message_filter::{Filter, MessageFilter},
         synthetic_node::SyntheticNode,
         LONG_TIMEOUT,
     },
-    wait_until,
 };
 
 /// Actions to prepare node state on start.
@@ -43,6 +53,29 @@ pub enum Action {
     ),
 }
 
+/// How [`Node::wait_until_ready`] decides the node is ready to answer queries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Readiness {
+    /// The RPC server reports the chain height, see [`RpcClient::get_block_count`].
+    #[default]
+    BlockCount,
+    /// The RPC server answers `getinfo`, which zcashd and zebra both implement.
+    Info,
+    /// The node reports a chain of at least this height.
+    Height(u32),
+}
+
+impl Readiness {
+    /// Runs the check once, returning `false` if the node answered but isn't ready yet.
+    async fn check(&self, rpc: &RpcClient) -> io::Result<bool> {
+        match self {
+            Readiness::BlockCount => rpc.get_block_count().await.map(|_| true),
+            Readiness::Info => rpc.get_info().await.map(|_| true),
+            Readiness::Height(min) => rpc.get_block_count().await.map(|height| height >= *min),
+        }
+    }
+}
+
 /// Represents an instance of a node, its configuration and setup/teardown intricacies.
 pub struct Node {
     /// Configuration definable in tests and written to the node's configuration file on start.
@@ -52,6 +85,8 @@ pub struct Node {
     meta: NodeMetaData,
     /// Process of the running node.
     process: Option<Child>,
+    /// The output captured from the last run of the node, if capture is enabled.
+    logs: Option<LogCapture>,
 }
 
 impl Node {
@@ -65,15 +100,33 @@ impl Node {
     /// [`max_peers`]: method@Node::max_peers
     /// [`log_to_stdout`]: method@Node::log_to_stdout
     pub fn new() -> io::Result<Self> {
+        Self::new_with_kind(None)
+    }
+
+    /// Creates a new [`Node`] instance of the given `kind`, regardless of the `kind` set in
+    /// `config.toml`, e.g. to run zcashd and zebra side by side.
+    ///
+    /// The kind should be configured in its own `[zcashd]` or `[zebra]` table, the top-level
+    /// `path` and `start_command` are used otherwise.
+    pub fn with_kind(kind: NodeKind) -> io::Result<Self> {
+        Self::new_with_kind(Some(kind))
+    }
+
+    fn new_with_kind(kind: Option<NodeKind>) -> io::Result<Self> {
         // Config (to be written to node configuration file).
         let config = NodeConfig::new()?;
-        let meta = NodeMetaData::new(config.path.clone())?;
+        let meta = NodeMetaData::new(config.path.clone(), kind)?;
+        let address_family = meta.address_family;
 
-        Ok(Self {
+        let mut node = Self {
             config,
             meta,
             process: None,
-        })
+            logs: None,
+        };
+        node.address_family(address_family);
+
+        Ok(node)
     }
 
     /// Returns the (external) address of the node.
@@ -81,6 +134,55 @@ impl Node {
         self.config.local_addr
     }
 
+    /// Returns the kind of the node under test, as set in `config.toml`.
+    pub fn kind(&self) -> NodeKind {
+        self.meta.kind
+    }
+
+    /// Returns the network the node is configured for, see [`network`](method@Node::network).
+    pub fn configured_network(&self) -> Network {
+        self.config.network
+    }
+
+    /// Returns the magic of the network the node is configured for, which synthetic nodes need to
+    /// talk to it (see [`SyntheticNodeBuilder::with_network_magic`]).
+    ///
+    /// [`SyntheticNodeBuilder::with_network_magic`]: crate::tools::synthetic_node::SyntheticNodeBuilder::with_network_magic
+    pub fn magic(&self) -> [u8; MAGIC_LEN] {
+        self.config.network.magic()
+    }
+
+    /// Returns `true` if the node under test is zebra, for tests which tolerate its known
+    /// deviations.
+    pub fn is_zebra(&self) -> bool {
+        self.meta.kind == NodeKind::Zebra
+    }
+
+    /// Returns an [`RpcClient`] for the node.
+    ///
+    /// Errors if the `[rpc]` table is missing from `config.toml`, in which case the node's RPC
+    /// server isn't enabled.
+    pub fn rpc(&self) -> io::Result<RpcClient> {
+        let rpc_config = self.meta.rpc.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "RPC isn't configured, add an [rpc] table to config.toml",
+            )
+        })?;
+
+        Ok(RpcClient::new(rpc_config, self.meta.kind))
+    }
+
+    /// Returns the load and SLA settings for the performance tests.
+    pub fn performance_config(&self) -> &PerformanceConfig {
+        &self.meta.performance
+    }
+
+    /// Returns the settings for the resistance tests.
+    pub fn resistance_config(&self) -> &ResistanceConfig {
+        &self.meta.resistance
+    }
+
     /// Sets the initial peers (ports only) for the node.
     ///
     /// The ip used to construct the addresses can be optionally set in the configuration file and
@@ -91,6 +193,27 @@ impl Node {
         self
     }
 
+    /// Sets the address family the node listens on, overriding the `address_family` set in
+    /// `config.toml` (IPv4 by default). The port is left unchanged.
+    pub fn address_family(&mut self, family: AddressFamily) -> &mut Self {
+        self.config.local_addr.set_ip(family.loopback());
+        self
+    }
+
+    /// Sets the port the node listens on, defaults to 8080. Nodes running at the same time need
+    /// distinct ports.
+    pub fn listening_port(&mut self, port: u16) -> &mut Self {
+        self.config.local_addr.set_port(port);
+        self
+    }
+
+    /// Starts the node without its RPC server, even if the `[rpc]` table is set in
+    /// `config.toml`. Nodes running at the same time would otherwise compete for the RPC port.
+    pub fn disable_rpc(&mut self) -> &mut Self {
+        self.meta.rpc = None;
+        self
+    }
+
     /// Sets the maximum connection value for the node.
     pub fn max_peers(&mut self, max_peers: usize) -> &mut Self {
         self.config.max_peers = max_peers;
@@ -103,6 +226,39 @@ impl Node {
         self
     }
 
+    /// Captures the node's stdout and stderr, which can be read with [`logs`](method@Node::logs)
+    /// and [`log_tail`](method@Node::log_tail), including after the node has been stopped.
+    ///
+    /// The output is still echoed if [`log_to_stdout`](method@Node::log_to_stdout) is set.
+    pub fn enable_log_capture(&mut self) -> &mut Self {
+        self.config.log_capture = true;
+        self
+    }
+
+    /// Also writes the captured output to the file at `path`, enabling capture if needed.
+    pub fn log_capture_file(&mut self, path: PathBuf) -> &mut Self {
+        self.config.log_capture = true;
+        self.config.log_capture_file = Some(path);
+        self
+    }
+
+    /// Returns the output captured from the node's last run, empty if capture isn't enabled.
+    pub fn logs(&self) -> Vec<String> {
+        self.logs
+            .as_ref()
+            .map(LogCapture::lines)
+            .unwrap_or_default()
+    }
+
+    /// Returns the last `n` lines of the node's captured output, e.g. to attach them to a failure
+    /// message.
+    pub fn log_tail(&self, n: usize) -> String {
+        self.logs
+            .as_ref()
+            .map(|logs| logs.tail(n))
+            .unwrap_or_default()
+    }
+
     /// Sets the initial action to undertake once the node has started. See [`Action`] for more
     /// information on what the actions pertain.
     pub fn initial_action(&mut self, action: Action) -> &mut Self {
@@ -110,6 +266,40 @@ impl Node {
         self
     }
 
+    /// Sets the network the node runs on, defaults to [`Network::Testnet`].
+    ///
+    /// Synthetic nodes talking to it need the matching magic, see [`magic`](method@Node::magic).
+    pub fn network(&mut self, network: Network) -> &mut Self {
+        self.config.network = network;
+        self
+    }
+
+    /// Sets the check used by [`wait_until_ready`](method@Node::wait_until_ready), defaults to
+    /// [`Readiness::BlockCount`].
+    pub fn readiness(&mut self, readiness: Readiness) -> &mut Self {
+        self.config.readiness = readiness;
+        self
+    }
+
+    /// Sets how long [`start`](method@Node::start) waits for the node to become reachable before
+    /// failing with [`io::ErrorKind::TimedOut`].
+    ///
+    /// Defaults to `start_timeout_secs` from `config.toml`, or [`LONG_TIMEOUT`] if it isn't set.
+    pub fn start_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.config.start_timeout = Some(timeout);
+        self
+    }
+
+    /// Starts the node against an existing chain data directory instead of an empty cache.
+    ///
+    /// This allows the node to serve real blocks and headers. The directory is left untouched on
+    /// cleanup and [`start`](method@Node::start) will wait until the node reports a nonzero chain
+    /// height over RPC (see [`rpc`](method@Node::rpc)) before returning.
+    pub fn data_dir(&mut self, path: PathBuf) -> &mut Self {
+        self.config.data_dir = Some(path);
+        self
+    }
+
     /// Starts the node instance.
     ///
     /// This function will write the appropriate configuration file and run the start command
@@ -118,6 +308,15 @@ impl Node {
         // cleanup any previous runs (node.stop won't always be reached e.g. test panics, or SIGINT)
         self.cleanup()?;
 
+        if let Some(data_dir) = &self.config.data_dir {
+            if !data_dir.is_dir() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("data directory {} doesn't exist", data_dir.display()),
+                ));
+            }
+        }
+
         // Setup the listener if there is some initial action required
         let synthetic_node = match self.config.initial_action {
             Action::None => None,
@@ -144,39 +343,129 @@ impl Node {
         // Generate config files for Zebra or Zcashd node.
         self.generate_config_file()?;
 
-        let (stdout, stderr) = match self.config.log_to_stdout {
-            true => {
-                // Also append `-printtoconsole` argument if logging to stdout is enabled.
-                self.meta.start_args.push("-printtoconsole".into());
-                (Stdio::inherit(), Stdio::inherit())
-            }
-            false => (Stdio::null(), Stdio::null()),
+        let (stdout, stderr) = match (self.config.log_capture, self.config.log_to_stdout) {
+            (true, _) => (Stdio::piped(), Stdio::piped()),
+            (false, true) => (Stdio::inherit(), Stdio::inherit()),
+            (false, false) => (Stdio::null(), Stdio::null()),
         };
 
-        let process = Command::new(&self.meta.start_command)
+        // The flags differ per node kind, zebra takes everything else from its config file.
+        let mut start_args = self.meta.start_args.clone();
+        if self.meta.kind == NodeKind::Zcashd {
+            // Zcashd only logs to its debug.log file unless told otherwise.
+            let print_to_console = std::ffi::OsString::from("-printtoconsole");
+            if (self.config.log_capture || self.config.log_to_stdout)
+                && !start_args.contains(&print_to_console)
+            {
+                start_args.push(print_to_console);
+            }
+
+            let data_dir = self.config.data_dir.as_ref().unwrap_or(&self.config.path);
+            start_args.push(format!("-datadir={}", data_dir.display()).into());
+        }
+
+        let mut process = Command::new(&self.meta.start_command)
             .current_dir(&self.meta.path)
-            .args(&self.meta.start_args)
+            .args(&start_args)
             .stdin(Stdio::null())
             .stdout(stdout)
             .stderr(stderr)
             .spawn()
             .expect("node failed to start");
 
+        self.logs = None;
+        if self.config.log_capture {
+            let mut logs = LogCapture::new(self.config.log_capture_file.as_deref())?;
+            let echo = self.config.log_to_stdout;
+            if let Some(stdout) = process.stdout.take() {
+                logs.read_from(stdout, echo);
+            }
+            if let Some(stderr) = process.stderr.take() {
+                logs.read_from(stderr, echo);
+            }
+            self.logs = Some(logs);
+        }
+
         self.process = Some(process);
 
         if let Some(synthetic_node) = synthetic_node {
             self.perform_initial_action(synthetic_node).await?;
         }
 
+        if self.config.data_dir.is_some() {
+            // The chain is loaded from the data directory, so it can't be empty.
+            self.poll_readiness(Readiness::Height(1))
+                .await
+                .map_err(|e| {
+                    io::Error::new(e.kind(), format!("{e}, is the data directory synced?"))
+                })?;
+        }
+
         Ok(())
     }
 
+    /// Returns how long to wait for the node to become reachable on start.
+    fn start_timeout_duration(&self) -> Duration {
+        self.config.start_timeout.unwrap_or(self.meta.start_timeout)
+    }
+
+    /// Polls the node's RPC server until it passes the configured [`Readiness`] check.
+    ///
+    /// A node accepting connections may still be loading its chain, tests querying blocks should
+    /// wait for it to be ready first. Errors with [`io::ErrorKind::TimedOut`] if the check doesn't
+    /// pass within the start timeout (see [`start_timeout`](method@Node::start_timeout)), and
+    /// requires the `[rpc]` table in `config.toml`.
+    pub async fn wait_until_ready(&self) -> io::Result<()> {
+        self.poll_readiness(self.config.readiness).await
+    }
+
+    async fn poll_readiness(&self, readiness: Readiness) -> io::Result<()> {
+        const SLEEP: Duration = Duration::from_millis(500);
+
+        let rpc = self.rpc()?;
+        let timeout = self.start_timeout_duration();
+        let now = std::time::Instant::now();
+        loop {
+            // The RPC server might not be up yet, so errors are retried until the timeout.
+            let last = match readiness.check(&rpc).await {
+                Ok(true) => return Ok(()),
+                Ok(false) => "the check didn't pass".to_string(),
+                Err(e) => e.to_string(),
+            };
+
+            if now.elapsed() >= timeout {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("the node wasn't ready ({readiness:?}) within {timeout:?}: {last}"),
+                ));
+            }
+
+            tokio::time::sleep(SLEEP).await;
+        }
+    }
+
     async fn perform_initial_action(&self, mut synthetic_node: SyntheticNode) -> io::Result<()> {
+        // The node connects to the synthetic node once it's up, so the first connection is
+        // bounded by the start timeout rather than the usual message timeouts.
+        let start_timeout = self.start_timeout_duration();
+        let timed_out = |_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "the node didn't connect within {start_timeout:?}, consider raising \
+                     `start_timeout_secs` in config.toml"
+                ),
+            )
+        };
+
         match self.config.initial_action {
             Action::None => {}
             Action::WaitForConnection => {
                 // The synthetic node will accept the connection and handshake by itself.
-                wait_until!(LONG_TIMEOUT, synthetic_node.num_connected() == 1);
+                synthetic_node
+                    .wait_for_connection_timeout(start_timeout)
+                    .await
+                    .map_err(timed_out)?;
             }
             Action::SeedWithTestnetBlocks(_) if self.meta.kind == NodeKind::Zebra => {
                 unimplemented!("zebra doesn't support block seeding");
@@ -193,6 +482,10 @@ impl Node {
                     .collect::<Vec<_>>();
 
                 // respond to GetHeaders(Block[0])
+                synthetic_node
+                    .wait_for_connection_timeout(start_timeout)
+                    .await
+                    .map_err(timed_out)?;
                 let source = match synthetic_node.recv_message_timeout(LONG_TIMEOUT).await? {
                     (source, Message::GetHeaders(locations)) => {
                         // The request should be from the genesis hash onwards,
@@ -272,6 +565,11 @@ impl Node {
                 Some(exit_code) => Some(format!("crashed with {exit_code}")),
             };
 
+            // The process has exited, so its output streams are closed.
+            if let Some(logs) = self.logs.as_mut() {
+                logs.join();
+            }
+
             self.cleanup()?;
 
             if let Some(crash_msg) = crashed {
@@ -316,9 +614,9 @@ impl Node {
     fn generate_config_file(&self) -> io::Result<()> {
         let config_file_path = self.meta.kind.config_filepath(&self.config.path);
         let content = match self.meta.kind {
-            NodeKind::Zebra => ZebraConfigFile::generate(&self.config)
+            NodeKind::Zebra => ZebraConfigFile::generate(&self.config, self.meta.rpc.as_ref())
                 .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
-            NodeKind::Zcashd => ZcashdConfigFile::generate(&self.config),
+            NodeKind::Zcashd => ZcashdConfigFile::generate(&self.config, self.meta.rpc.as_ref()),
         };
 
         fs::write(config_file_path, content)
@@ -339,8 +637,17 @@ impl Node {
     }
 
     fn cleanup_cache(&self) -> io::Result<()> {
+        // A pre-synced data directory must never be removed.
+        if self.config.data_dir.is_some() {
+            return Ok(());
+        }
+
         // Zebra doesn't currently use a cache as it's configured in ephemeral mode.
-        if let Some(path) = self.meta.kind.cache_path(&self.config.path) {
+        if let Some(path) = self
+            .meta
+            .kind
+            .cache_path(&self.config.path, self.config.network)
+        {
             if let Err(e) = fs::remove_dir_all(path) {
                 // Directory may not exist, so we let that error through
                 if e.kind() != std::io::ErrorKind::NotFound {
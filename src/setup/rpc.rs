@@ -0,0 +1,326 @@
+//! A minimal JSON-RPC client, used to verify node state which can't be observed over the wire.
+
+use std::{
+    convert::TryInto,
+    fmt::Write,
+    io::{self, Error, ErrorKind},
+    net::SocketAddr,
+};
+
+use base64::Engine;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::{
+    protocol::payload::Hash,
+    setup::config::{NodeKind, RpcConfig},
+};
+
+/// The number of blocks a coinbase output must be buried under before a regtest wallet can spend
+/// it.
+pub const COINBASE_MATURITY: u32 = 100;
+
+/// RPC methods unsupported by zebra.
+const ZEBRA_UNSUPPORTED: [&str; 8] = [
+    "generate",
+    "getblockhash",
+    "getpeerinfo",
+    "getnewaddress",
+    "sendtoaddress",
+    "listunspent",
+    "createrawtransaction",
+    "signrawtransaction",
+];
+
+/// A subset of the information returned by `getpeerinfo` for a single peer.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PeerInfo {
+    /// The node's id for the peer.
+    pub id: u64,
+    /// The address of the peer, in `<ip>:<port>` form.
+    pub addr: String,
+    /// Indicates if the peer initiated the connection.
+    pub inbound: bool,
+    /// The protocol version advertised by the peer.
+    pub version: u32,
+    /// The user agent advertised by the peer.
+    pub subver: String,
+    /// The start height advertised by the peer.
+    pub startingheight: i32,
+}
+
+/// A subset of the information returned by `listunspent` for a single transparent output.
+#[derive(Deserialize, Debug, Clone)]
+pub struct UnspentOutput {
+    /// The hash of the transaction containing the output, in its RPC representation.
+    pub txid: String,
+    /// The index of the output in the transaction.
+    pub vout: u32,
+    /// The value of the output in ZEC.
+    pub amount: f64,
+    /// Indicates if the output is spendable by the node's wallet.
+    pub spendable: bool,
+}
+
+#[derive(Deserialize)]
+struct SignedTransaction {
+    hex: String,
+    complete: bool,
+}
+
+#[derive(Deserialize)]
+struct BlockchainInfo {
+    blocks: u32,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// A JSON-RPC client for the node under test.
+///
+/// Calls the node doesn't support (zebra only implements a subset of the zcashd RPCs) fail with
+/// [`ErrorKind::Unsupported`] without being sent.
+pub struct RpcClient {
+    addr: SocketAddr,
+    auth: Option<String>,
+    kind: NodeKind,
+}
+
+impl RpcClient {
+    pub(super) fn new(config: &RpcConfig, kind: NodeKind) -> Self {
+        let auth = config.user.as_ref().map(|user| {
+            let credentials = format!("{user}:{}", config.password.as_deref().unwrap_or_default());
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        });
+
+        Self {
+            addr: config.addr(),
+            auth,
+            kind,
+        }
+    }
+
+    /// Returns the address of the node's RPC server.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the height of the node's best chain.
+    pub async fn get_block_count(&self) -> io::Result<u32> {
+        match self.kind {
+            // Zebra doesn't implement `getblockcount`, but reports the height in `getblockchaininfo`.
+            NodeKind::Zebra => {
+                let info: BlockchainInfo = self.call("getblockchaininfo", json!([])).await?;
+                Ok(info.blocks)
+            }
+            NodeKind::Zcashd => self.call("getblockcount", json!([])).await,
+        }
+    }
+
+    /// Returns the node's general information.
+    ///
+    /// The fields differ between zcashd and zebra, so the result is left untyped.
+    pub async fn get_info(&self) -> io::Result<Value> {
+        self.call("getinfo", json!([])).await
+    }
+
+    /// Returns the hash of the block at `height` in the node's best chain.
+    pub async fn get_block_hash(&self, height: u32) -> io::Result<Hash> {
+        let hash: String = self.call("getblockhash", json!([height])).await?;
+        hash_from_rpc_hex(&hash)
+    }
+
+    /// Mines `n` blocks immediately and returns their hashes (regtest only).
+    pub async fn generate(&self, n: u32) -> io::Result<Vec<Hash>> {
+        let hashes: Vec<String> = self.call("generate", json!([n])).await?;
+        hashes.iter().map(|hash| hash_from_rpc_hex(hash)).collect()
+    }
+
+    /// Returns the node's connected peers.
+    pub async fn get_peer_info(&self) -> io::Result<Vec<PeerInfo>> {
+        self.call("getpeerinfo", json!([])).await
+    }
+
+    /// Returns the hashes of the transactions in the node's mempool.
+    pub async fn get_raw_mempool(&self) -> io::Result<Vec<Hash>> {
+        let hashes: Vec<String> = self.call("getrawmempool", json!([])).await?;
+        hashes.iter().map(|hash| hash_from_rpc_hex(hash)).collect()
+    }
+
+    /// Returns a new transparent address from the node's wallet.
+    pub async fn get_new_address(&self) -> io::Result<String> {
+        self.call("getnewaddress", json!([])).await
+    }
+
+    /// Sends `amount` ZEC from the node's wallet to `address` and returns the transaction's hash.
+    ///
+    /// On regtest the wallet only has funds once a coinbase has matured, i.e. after mining at least
+    /// [`COINBASE_MATURITY`] + 1 blocks.
+    pub async fn send_to_address(&self, address: &str, amount: f64) -> io::Result<Hash> {
+        let hash: String = self.call("sendtoaddress", json!([address, amount])).await?;
+        hash_from_rpc_hex(&hash)
+    }
+
+    /// Returns the transparent outputs the node's wallet can spend.
+    pub async fn list_unspent(&self) -> io::Result<Vec<UnspentOutput>> {
+        self.call("listunspent", json!([])).await
+    }
+
+    /// Creates a transaction spending `input` to `address`, without signing or broadcasting it.
+    ///
+    /// The difference between the input's value and `amount` is left as the fee.
+    pub async fn create_raw_transaction(
+        &self,
+        input: &UnspentOutput,
+        address: &str,
+        amount: f64,
+    ) -> io::Result<String> {
+        self.create_raw_transaction_with_outputs(input, &[(address.to_owned(), amount)])
+            .await
+    }
+
+    /// Creates a transaction spending `input` to each of the `(address, amount)` outputs, without
+    /// signing or broadcasting it. The addresses must be distinct.
+    ///
+    /// The difference between the input's value and the outputs' is left as the fee.
+    pub async fn create_raw_transaction_with_outputs(
+        &self,
+        input: &UnspentOutput,
+        outputs: &[(String, f64)],
+    ) -> io::Result<String> {
+        let outputs = outputs
+            .iter()
+            .map(|(address, amount)| (address.clone(), json!(amount)))
+            .collect::<serde_json::Map<_, _>>();
+
+        self.call(
+            "createrawtransaction",
+            json!([[{ "txid": input.txid, "vout": input.vout }], outputs]),
+        )
+        .await
+    }
+
+    /// Signs a transaction created with [`RpcClient::create_raw_transaction`] using the node's
+    /// wallet and returns its serialization, without broadcasting it.
+    pub async fn sign_raw_transaction(&self, hex: &str) -> io::Result<Vec<u8>> {
+        let signed: SignedTransaction = self.call("signrawtransaction", json!([hex])).await?;
+        if !signed.complete {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "signrawtransaction returned an incomplete transaction",
+            ));
+        }
+
+        hex::decode(signed.hex).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Indicates if the node supports the RPC `method`.
+    pub fn supports(&self, method: &str) -> bool {
+        match self.kind {
+            NodeKind::Zebra => !ZEBRA_UNSUPPORTED.contains(&method),
+            NodeKind::Zcashd => true,
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> io::Result<T> {
+        if !self.supports(method) {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{:?} doesn't support the {method} RPC", self.kind),
+            ));
+        }
+
+        let body = json!({
+            "jsonrpc": "1.0",
+            "id": "ziggurat",
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+
+        let mut request = format!(
+            "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.addr,
+            body.len()
+        );
+        if let Some(auth) = &self.auth {
+            let _ = write!(request, "Authorization: Basic {auth}\r\n");
+        }
+        request.push_str("\r\n");
+        request.push_str(&body);
+
+        let mut stream = TcpStream::connect(self.addr).await?;
+        stream.write_all(request.as_bytes()).await?;
+
+        // The connection is closed by the server once the response has been written.
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+
+        // Errors are also returned as a JSON body (with a non-200 status), so the status line is
+        // skipped along with the rest of the HTTP headers.
+        let body_start = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "malformed HTTP response"))?
+            + 4;
+
+        let response: RpcResponse<T> = serde_json::from_slice(&response[body_start..])
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(Error::new(
+                ErrorKind::Other,
+                format!("{method} failed with {}: {}", error.code, error.message),
+            )),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{method} returned neither a result nor an error"),
+            )),
+        }
+    }
+}
+
+/// Converts a hash from its RPC representation (byte-reversed hex) into a [`Hash`].
+fn hash_from_rpc_hex(hex: &str) -> io::Result<Hash> {
+    let mut bytes = hex::decode(hex).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    bytes.reverse();
+
+    let bytes: [u8; 32] = bytes.try_into().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid hash length: {hex}"),
+        )
+    })?;
+
+    Ok(Hash::new(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::payload::block::Block;
+
+    #[test]
+    #[ignore]
+    fn hash_from_rpc_hex_is_byte_reversed() {
+        let hash =
+            hash_from_rpc_hex("05a60a92d99d85997cce3b87616c089f6124d7342af37106edc76126334a2c38")
+                .unwrap();
+
+        assert_eq!(hash, Block::testnet_genesis().double_sha256().unwrap());
+    }
+}
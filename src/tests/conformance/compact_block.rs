@@ -0,0 +1,167 @@
+//! Contains test cases which cover ZG-CONFORMANCE-021 and ZG-CONFORMANCE-022.
+//!
+//! The node announces new blocks with a `CmpctBlock` once high-bandwidth compact block relay has
+//! been negotiated with `SendCmpct`, and serves the block's transactions with `BlockTxn`.
+//!
+//! Note: this test requires the `[rpc]` table to be present in `config.toml`.
+//!
+//! Neither zcashd nor zebra implement BIP-152, so these tests are only built with the `bip152`
+//! feature.
+
+use crate::{
+    protocol::{
+        message::Message,
+        payload::{
+            block::{BlockTxnRequest, CompactBlock, SendCmpct},
+            Inv, InvHash,
+        },
+    },
+    setup::{
+        node::{Action, Node},
+        rpc::COINBASE_MATURITY,
+        Network,
+    },
+    tools::{synthetic_node::SyntheticNode, LONG_TIMEOUT},
+};
+
+/// The number of wallet transactions mined alongside the coinbase in the `GetBlockTxn` test.
+const MEMPOOL_TXS: usize = 3;
+
+/// Negotiates high-bandwidth compact block relay, mines a block on the regtest node and returns
+/// its compact announcement.
+async fn generate_compact_block(node: &Node, synthetic_node: &mut SyntheticNode) -> CompactBlock {
+    synthetic_node
+        .unicast(node.addr(), Message::SendCmpct(SendCmpct::new(true)))
+        .unwrap();
+
+    let hashes = node.rpc().unwrap().generate(1).await.unwrap();
+    let hash = hashes[0];
+
+    // The node may send other messages (e.g. its own SendCmpct) before announcing the block.
+    loop {
+        match synthetic_node.recv_message_timeout(LONG_TIMEOUT).await {
+            Ok((_, Message::CmpctBlock(compact_block)))
+                if compact_block.header.double_sha256().unwrap() == hash =>
+            {
+                break *compact_block
+            }
+            Ok((_, Message::Inv(inv))) if inv.inventory.contains(&InvHash::Block(hash)) => {
+                panic!("the generated block was announced with an Inv instead of a CmpctBlock")
+            }
+            Ok((_, Message::Headers(_))) => {
+                panic!("the generated block was announced with Headers instead of a CmpctBlock")
+            }
+            Ok(_) => continue,
+            Err(e) => panic!("the generated block wasn't announced: {e}"),
+        }
+    }
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c021_CMPCT_BLOCK_announcement_after_high_bandwidth_SEND_CMPCT() {
+    // zcashd: unsupported (BIP-152 was never implemented, `sendcmpct` is ignored)
+    // zebra:  unsupported (no compact blocks)
+
+    let mut node = Node::new().unwrap();
+    node.network(Network::Regtest)
+        .initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    let compact_block = generate_compact_block(&node, &mut synthetic_node).await;
+
+    // The coinbase can't be in the receiver's mempool, so it must be prefilled.
+    assert_eq!(compact_block.prefilled_txs[0].index, 0);
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c022_GET_BLOCK_TXN_returns_requested_transactions() {
+    // zcashd: unsupported (BIP-152 was never implemented, no compact block is announced)
+    // zebra:  unsupported (no compact blocks)
+
+    let mut node = Node::new().unwrap();
+    node.network(Network::Regtest)
+        .initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    // Seed the mempool with wallet transactions, so the block holds more than its coinbase.
+    let rpc = node.rpc().unwrap();
+    rpc.generate(COINBASE_MATURITY + 1).await.unwrap();
+    let address = rpc.get_new_address().await.unwrap();
+    for _ in 0..MEMPOOL_TXS {
+        rpc.send_to_address(&address, 1.0).await.unwrap();
+    }
+
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    let compact_block = generate_compact_block(&node, &mut synthetic_node).await;
+    let block_hash = compact_block.header.double_sha256().unwrap();
+    assert_eq!(compact_block.tx_count(), MEMPOOL_TXS + 1);
+
+    // A sparse selection, which skips the prefilled coinbase.
+    let indexes = vec![1, 3];
+    synthetic_node
+        .unicast(
+            node.addr(),
+            Message::GetBlockTxn(BlockTxnRequest::new(block_hash, indexes.clone())),
+        )
+        .unwrap();
+
+    let block_txn = loop {
+        match synthetic_node.recv_message_timeout(LONG_TIMEOUT).await {
+            Ok((_, Message::BlockTxn(block_txn))) => break block_txn,
+            Ok(_) => continue,
+            Err(e) => panic!("no BlockTxn was received: {e}"),
+        }
+    };
+
+    // The full block tells which transactions are at the requested indexes.
+    synthetic_node
+        .unicast(
+            node.addr(),
+            Message::GetData(Inv::new(vec![InvHash::Block(block_hash)])),
+        )
+        .unwrap();
+    let block = loop {
+        match synthetic_node.recv_message_timeout(LONG_TIMEOUT).await {
+            Ok((_, Message::Block(block))) => break block,
+            Ok(_) => continue,
+            Err(e) => panic!("no Block was received: {e}"),
+        }
+    };
+
+    // Exactly the requested transactions, in the requested order.
+    let expected = indexes
+        .iter()
+        .map(|&i| block.txs[i].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(block_txn.block_hash, block_hash);
+    assert_eq!(block_txn.txs, expected);
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
@@ -0,0 +1,49 @@
+//! Contains test cases which cover ZG-CONFORMANCE-020.
+//!
+//! The node advertises its minimum relay fee rate with a `FeeFilter` after the handshake.
+
+use crate::{
+    protocol::message::Message,
+    setup::node::{Action, Node},
+    tools::{
+        message_filter::{Filter, MessageFilter},
+        synthetic_node::SyntheticNode,
+        LONG_TIMEOUT,
+    },
+};
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c020_FEE_FILTER_after_handshake() {
+    // zebra:  fail (doesn't send feefilter)
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    // Let the fee filter through to the inbound queue, the auto-responder would drop it.
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_message_filter(
+            MessageFilter::with_all_auto_reply().with_feefilter_filter(Filter::Disabled),
+        )
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    // The node may send other messages (e.g. GetAddr) before the fee filter.
+    let fee_filter = loop {
+        match synthetic_node.recv_message_timeout(LONG_TIMEOUT).await {
+            Ok((_, Message::FeeFilter(fee_filter))) => break fee_filter,
+            Ok(_) => continue,
+            Err(e) => panic!("no FeeFilter was received: {e}"),
+        }
+    };
+
+    assert_ne!(fee_filter.feerate, 0);
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
@@ -1,5 +1,8 @@
 use crate::{
-    setup::node::{Action, Node},
+    setup::{
+        node::{Action, Node},
+        AddressFamily,
+    },
     tools::{synthetic_node::SyntheticNode, LONG_TIMEOUT},
     wait_until,
 };
@@ -59,3 +62,35 @@ async fn c002_handshake_when_node_initiates_connection() {
     synthetic_node.shut_down().await;
     node.stop().unwrap();
 }
+
+#[tokio::test]
+async fn c001_t2_handshake_over_ipv6() {
+    // ZG-CONFORMANCE-001, over IPv6 loopback (`::1`).
+    //
+    // zcashd: pass
+    // zebra:  pass
+
+    let mut node = Node::new().unwrap();
+    node.address_family(AddressFamily::Ipv6)
+        .initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+    assert!(node.addr().is_ipv6());
+
+    let synthetic_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_address_family(AddressFamily::Ipv6)
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+    assert!(synthetic_node.is_connected(node.addr()));
+
+    // The node's version must carry our 16-byte address, not an IPv4-mapped one.
+    let version = synthetic_node.handshake_info(&node.addr()).unwrap();
+    assert_eq!(version.addr_recv.addr.ip(), AddressFamily::Ipv6.loopback());
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
@@ -0,0 +1,64 @@
+//! Contains test cases which cover ZG-CONFORMANCE-023.
+//!
+//! The node doesn't re-handshake when a peer sends a second `Version` after the handshake.
+
+use crate::{
+    protocol::{message::Message, payload::Version},
+    setup::node::{Action, Node},
+    tools::{
+        synthetic_node::{PingPongError, SyntheticNode},
+        LONG_TIMEOUT,
+    },
+};
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c023_VERSION_after_handshake_is_ignored_or_disconnected() {
+    // zcashd: pass (replies with a `duplicate` Reject and keeps the connection)
+    // zebra:  pass (treats it as a protocol violation and disconnects)
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    synthetic_node
+        .unicast(
+            node.addr(),
+            Message::Version(Version::new(node.addr(), synthetic_node.listening_addr())),
+        )
+        .unwrap();
+
+    // Use Ping-Pong to check the node has processed the Version, a Reject doesn't count as a
+    // reply to it but a Version or Verack would mean the node started a new handshake. Retrying
+    // after a Reject means the previous Pong arrives with a stale nonce, which is skipped too.
+    loop {
+        match synthetic_node
+            .ping_pong_timeout(node.addr(), LONG_TIMEOUT)
+            .await
+        {
+            Ok(_) | Err(PingPongError::ConnectionAborted) => break,
+            Err(PingPongError::Unexpected(message))
+                if matches!(*message, Message::Reject(_) | Message::Pong(_)) =>
+            {
+                continue
+            }
+            Err(PingPongError::Unexpected(message)) => {
+                panic!("the node replied to the second Version with {message}")
+            }
+            Err(err) => panic!("the node neither ignored the Version nor disconnected: {err}"),
+        }
+    }
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
@@ -13,7 +13,7 @@ use crate::{
         },
     },
     setup::node::{Action, Node},
-    tools::{synthetic_node::SyntheticNode, LONG_TIMEOUT, RECV_TIMEOUT},
+    tools::{synthetic_node::SyntheticNode, LONG_TIMEOUT},
 };
 
 mod when_node_receives_connection {
@@ -282,8 +282,9 @@ mod when_node_initiates_connection {
         // Wait for the node to establish the connection.
         // This will result in a connection in which the Version's have
         // already been exchanged.
-        let node_addr =
-            tokio::time::timeout(LONG_TIMEOUT, synthetic_node.wait_for_connection()).await?;
+        let node_addr = synthetic_node
+            .wait_for_connection_timeout(LONG_TIMEOUT)
+            .await?;
 
         // Send a non-verack message.
         // We expect the node to not disconnect before completing the handshake.
@@ -293,7 +294,7 @@ mod when_node_initiates_connection {
         synthetic_node.unicast(node_addr, Message::Verack)?;
 
         // Read Verack.
-        match synthetic_node.recv_message_timeout(RECV_TIMEOUT).await {
+        match synthetic_node.next_message().await {
             Ok((_, Message::Verack)) => Ok(()),
             Ok((_, unexpected)) => Err(io::Error::new(
                 io::ErrorKind::Other,
@@ -5,6 +5,7 @@
 use std::io;
 
 use crate::{
+    message_matches,
     protocol::{
         message::Message,
         payload::{
@@ -13,7 +14,7 @@ use crate::{
         },
     },
     setup::node::{Action, Node},
-    tools::{synthetic_node::SyntheticNode, LONG_TIMEOUT, RECV_TIMEOUT},
+    tools::{conversation::Conversation, synthetic_node::SyntheticNode, LONG_TIMEOUT},
 };
 
 mod when_node_receives_connection {
@@ -144,46 +145,17 @@ mod when_node_receives_connection {
         let mut synthetic_node = SyntheticNode::builder().build().await?;
         synthetic_node.connect(node.addr()).await?;
 
-        // Send a non-version message.
-        synthetic_node.unicast(node.addr(), message)?;
-
-        // Expect the node to ignore the previous message, verify by completing the handshake.
-        // Send Version.
-        synthetic_node.unicast(
-            node.addr(),
-            Message::Version(Version::new(synthetic_node.listening_addr(), node.addr())),
-        )?;
-
-        // Read Version.
-        match synthetic_node.recv_message_timeout(RECV_TIMEOUT).await {
-            Ok((_, Message::Version(..))) => Ok(()),
-            Ok((_, unexpected)) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Message was not ignored. Instead of Version received {unexpected}"),
-            )),
-            Err(_timeout) if !synthetic_node.is_connected(node.addr()) => Err(io::Error::new(
-                io::ErrorKind::ConnectionAborted,
-                "Connection terminated",
-            )),
-            Err(err) => Err(err),
-        }?;
-
-        // Send Verack.
-        synthetic_node.unicast(node.addr(), Message::Verack)?;
-
-        // Read Verack.
-        match synthetic_node.recv_message_timeout(RECV_TIMEOUT).await {
-            Ok((_, Message::Verack)) => Ok(()),
-            Ok((_, unexpected)) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Message was not ignored. Instead of Verack received {unexpected}"),
-            )),
-            Err(_timeout) if !synthetic_node.is_connected(node.addr()) => Err(io::Error::new(
-                io::ErrorKind::ConnectionAborted,
-                "Connection terminated",
-            )),
-            Err(err) => Err(err),
-        }?;
+        // Send a non-version message, expect the node to ignore it, verify by completing the
+        // handshake.
+        let version = Version::new(synthetic_node.listening_addr(), node.addr());
+        Conversation::new(&mut synthetic_node, node.addr())
+            .send(message)
+            .send(Message::Version(version))
+            .expect(message_matches!(Message::Version(..)))
+            .send(Message::Verack)
+            .expect(message_matches!(Message::Verack))
+            .run()
+            .await?;
 
         // Gracefully shut down the nodes.
         synthetic_node.shut_down().await;
@@ -321,49 +293,21 @@ mod when_node_initiates_connection {
             .await?;
 
         // Wait for the node to establish the connection.
-        let node_addr =
-            tokio::time::timeout(LONG_TIMEOUT, synthetic_node.wait_for_connection()).await?;
-
-        // Send a non-version message.
-        synthetic_node.unicast(node_addr, message)?;
-
-        // Expect the node to ignore the previous message, verify by completing the handshake.
-        // Send Version.
-        synthetic_node.unicast(
-            node_addr,
-            Message::Version(Version::new(synthetic_node.listening_addr(), node_addr)),
-        )?;
-
-        // Read Version.
-        match synthetic_node.recv_message_timeout(RECV_TIMEOUT).await {
-            Ok((_, Message::Version(..))) => Ok(()),
-            Ok((_, unexpected)) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Message was not ignored. Instead of Version received {unexpected}"),
-            )),
-            Err(_timeout) if !synthetic_node.is_connected(node.addr()) => Err(io::Error::new(
-                io::ErrorKind::ConnectionAborted,
-                "Connection terminated",
-            )),
-            Err(err) => Err(err),
-        }?;
-
-        // Send Verack.
-        synthetic_node.unicast(node_addr, Message::Verack)?;
-
-        // Read Verack.
-        match synthetic_node.recv_message_timeout(RECV_TIMEOUT).await {
-            Ok((_, Message::Verack)) => Ok(()),
-            Ok((_, unexpected)) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Message was not ignored. Instead of Verack received {unexpected}"),
-            )),
-            Err(_timeout) if !synthetic_node.is_connected(node.addr()) => Err(io::Error::new(
-                io::ErrorKind::ConnectionAborted,
-                "Connection terminated",
-            )),
-            Err(err) => Err(err),
-        }?;
+        let node_addr = synthetic_node
+            .wait_for_connection_timeout(LONG_TIMEOUT)
+            .await?;
+
+        // Send a non-version message, expect the node to ignore it, verify by completing the
+        // handshake.
+        let version = Version::new(synthetic_node.listening_addr(), node_addr);
+        Conversation::new(&mut synthetic_node, node_addr)
+            .send(message)
+            .send(Message::Version(version))
+            .expect(message_matches!(Message::Version(..)))
+            .send(Message::Verack)
+            .expect(message_matches!(Message::Verack))
+            .run()
+            .await?;
 
         // Gracefully shut down the nodes.
         synthetic_node.shut_down().await;
@@ -1,4 +1,6 @@
 mod complete_handshake;
+mod duplicate_version;
 mod ignore_message_inplace_of_verack;
 mod ignore_message_inplace_of_version;
 mod reject_version;
+mod verack_before_version;
@@ -2,11 +2,11 @@ use assert_matches::assert_matches;
 
 use crate::{
     protocol::{
-        message::Message,
+        message::{constants::PROTOCOL_VERSION, Message},
         payload::{reject::CCode, Version},
     },
     setup::node::{Action, Node},
-    tools::{synthetic_node::SyntheticNode, LONG_TIMEOUT},
+    tools::{synthetic_node::SyntheticNode, LONG_TIMEOUT, RECV_TIMEOUT},
     wait_until,
 };
 
@@ -52,6 +52,50 @@ async fn c007_PING_reusing_nonce() {
     node.stop().unwrap();
 }
 
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c007_VERSION_inbound_reusing_outbound_nonce() {
+    // ZG-CONFORMANCE-007
+    //
+    // The node rejects inbound connections reusing the nonce of one of its pending outbound
+    // connections, i.e. connections to itself.
+    //
+    // zcashd: pass
+    // zebra:  pass
+
+    // Receives the node's version, but leaves the handshake pending.
+    let mut outbound_peer = SyntheticNode::builder().build().await.unwrap();
+
+    let mut node = Node::new().unwrap();
+    node.initial_peers(vec![outbound_peer.listening_addr()])
+        .start()
+        .await
+        .unwrap();
+
+    let (_, version) = outbound_peer
+        .recv_message_timeout(LONG_TIMEOUT)
+        .await
+        .unwrap();
+    let nonce = assert_matches!(version, Message::Version(version) => version.nonce);
+
+    // Connect to the node, reflecting its nonce as if it had connected to itself.
+    let reflecting_peer = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_version_nonce(nonce)
+        .build()
+        .await
+        .unwrap();
+
+    // The node may refuse the handshake, or drop the connection right after it.
+    if reflecting_peer.connect(node.addr()).await.is_ok() {
+        wait_until!(LONG_TIMEOUT, reflecting_peer.num_connected() == 0);
+    }
+
+    outbound_peer.shut_down().await;
+    reflecting_peer.shut_down().await;
+    node.stop().unwrap();
+}
+
 #[tokio::test]
 #[allow(non_snake_case)]
 async fn c008_VERSION_with_obsolete_number() {
@@ -107,3 +151,96 @@ async fn c008_VERSION_with_obsolete_number() {
     // Gracefully shut down the node.
     node.stop().unwrap();
 }
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c008_VERSION_older_or_newer_number_negotiates_lowest() {
+    // ZG-CONFORMANCE-008
+    //
+    // The node uses the lower of both versions with peers it accepts: it answers a newer peer
+    // with its own version. Versions below its minimum are covered by
+    // `c008_VERSION_below_minimum_is_refused`.
+    //
+    // zcashd: pass
+    // zebra:  pass
+
+    // Ours and a future version.
+    let versions = [PROTOCOL_VERSION, PROTOCOL_VERSION + 100];
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    for version in versions {
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .with_protocol_version(version)
+            .build()
+            .await
+            .unwrap();
+
+        synthetic_node.connect(node.addr()).await.unwrap();
+        let node_version = synthetic_node
+            .handshake_info(&node.addr())
+            .unwrap()
+            .version
+            .0;
+        assert_eq!(
+            synthetic_node.negotiated_version(node.addr()),
+            Some(version.min(node_version))
+        );
+
+        // The connection is usable with the negotiated version.
+        synthetic_node
+            .ping_pong_timeout(node.addr(), RECV_TIMEOUT)
+            .await
+            .unwrap();
+
+        synthetic_node.shut_down().await;
+    }
+
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c008_VERSION_below_minimum_is_refused() {
+    // ZG-CONFORMANCE-008
+    //
+    // The node refuses peers claiming a version below its minimum, see
+    // `Network::min_peer_version`, down to pre-Zcash (Bitcoin) protocol versions.
+    //
+    // zcashd: pass (sends an obsolete `Reject` and disconnects)
+    // zebra:  pass (disconnects)
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    // Just below the floor, and a Bitcoin version.
+    let floor = node.configured_network().min_peer_version();
+    for version in [floor - 1, 70_002] {
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .with_protocol_version(version)
+            .build()
+            .await
+            .unwrap();
+
+        // The version is rejected during the handshake, or the node drops the connection right
+        // after it.
+        if synthetic_node.connect(node.addr()).await.is_ok() {
+            wait_until!(LONG_TIMEOUT, synthetic_node.num_connected() == 0);
+        }
+
+        synthetic_node.shut_down().await;
+    }
+
+    node.stop().unwrap();
+}
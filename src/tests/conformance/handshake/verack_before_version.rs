@@ -0,0 +1,133 @@
+//! Contains test cases which cover ZG-CONFORMANCE-024.
+//!
+//! The node doesn't advance the handshake when it receives a `Verack` before any `Version`: it
+//! either ignores it and still completes the handshake afterwards, or disconnects.
+
+use std::{io, net::SocketAddr};
+
+use crate::{
+    protocol::{message::Message, payload::Version},
+    setup::node::{Action, Node},
+    tools::{synthetic_node::SyntheticNode, LONG_TIMEOUT},
+};
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c024_t1_VERACK_before_VERSION_when_node_receives_connection() {
+    // zcashd: pass (ignored)
+    // zebra:  pass (ignored)
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    // Connect to the node, don't handshake.
+    let mut synthetic_node = SyntheticNode::builder().build().await.unwrap();
+    synthetic_node.connect(node_addr).await.unwrap();
+
+    synthetic_node.unicast(node_addr, Message::Verack).unwrap();
+
+    // The early Verack mustn't count as the handshake being done, so the node still expects the
+    // usual Version -> Version -> Verack -> Verack sequence.
+    run_test_case(&mut synthetic_node, node_addr, true)
+        .await
+        .unwrap();
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c024_t2_VERACK_before_VERSION_when_node_initiates_connection() {
+    // zcashd: pass (ignored)
+    // zebra:  pass (ignored)
+
+    let mut synthetic_node = SyntheticNode::builder().build().await.unwrap();
+
+    let mut node = Node::new().unwrap();
+    node.initial_peers(vec![synthetic_node.listening_addr()])
+        .start()
+        .await
+        .unwrap();
+
+    let node_addr = synthetic_node
+        .wait_for_connection_timeout(LONG_TIMEOUT)
+        .await
+        .unwrap();
+
+    // The node opens with its Version, answer it with a Verack instead of our own Version.
+    expect_message(&mut synthetic_node, node_addr, "Version", |message| {
+        matches!(message, Message::Version(..))
+    })
+    .await
+    .unwrap();
+    synthetic_node.unicast(node_addr, Message::Verack).unwrap();
+
+    // The node already sent its Version, so it should only answer ours with a Verack.
+    run_test_case(&mut synthetic_node, node_addr, false)
+        .await
+        .unwrap();
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+/// Sends our [`Message::Version`] after the early [`Message::Verack`] and checks the node either
+/// completes the handshake or has disconnected, with `expect_version` indicating if the node's
+/// Version is still to come.
+async fn run_test_case(
+    synthetic_node: &mut SyntheticNode,
+    node_addr: SocketAddr,
+    expect_version: bool,
+) -> io::Result<()> {
+    if !synthetic_node.is_connected(node_addr) {
+        return Ok(());
+    }
+
+    synthetic_node.unicast(
+        node_addr,
+        Message::Version(Version::new(node_addr, synthetic_node.listening_addr())),
+    )?;
+
+    if expect_version {
+        let connected = expect_message(synthetic_node, node_addr, "Version", |message| {
+            matches!(message, Message::Version(..))
+        })
+        .await?;
+        if !connected {
+            return Ok(());
+        }
+
+        synthetic_node.unicast(node_addr, Message::Verack)?;
+    }
+
+    expect_message(synthetic_node, node_addr, "Verack", |message| {
+        matches!(message, Message::Verack)
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Reads the next message, which must match `is_expected`. Returns `false` if the node
+/// disconnected instead.
+async fn expect_message(
+    synthetic_node: &mut SyntheticNode,
+    node_addr: SocketAddr,
+    expected: &str,
+    is_expected: impl Fn(&Message) -> bool,
+) -> io::Result<bool> {
+    match synthetic_node.next_message().await {
+        Ok((_, message)) if is_expected(&message) => Ok(true),
+        Ok((_, unexpected)) => Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("The handshake got out of order. Instead of {expected} received {unexpected}"),
+        )),
+        Err(_timeout) if !synthetic_node.is_connected(node_addr) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
@@ -15,7 +15,7 @@ use std::io;
 use crate::{
     protocol::{
         message::Message,
-        payload::{block::Block, reject::CCode, FilterAdd, FilterLoad, Inv, Version},
+        payload::{block::Block, reject::CCode, BloomFilter, FilterAdd, Inv, Version},
     },
     setup::node::{Action, Node},
     tools::{
@@ -93,7 +93,7 @@ async fn c009_t5_BLOOM_FILTER_ADD() {
 async fn c009_t6_BLOOM_FILTER_LOAD() {
     // zcashd: fail (ccode: Malformed)
     // zebra:  fail (ignored)
-    run_test_case(Message::FilterLoad(FilterLoad::default()), CCode::Obsolete)
+    run_test_case(Message::FilterLoad(BloomFilter::default()), CCode::Obsolete)
         .await
         .unwrap();
 }
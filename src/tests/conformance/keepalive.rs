@@ -0,0 +1,101 @@
+//! Contains test cases which cover ZG-CONFORMANCE-028.
+//!
+//! The node pings idle peers, and disconnects those which don't answer. The waits are bounded by
+//! the node's ping interval and timeout, see `NodeKind::ping_interval` and
+//! `NodeKind::ping_timeout`: these tests take minutes against zebra, and over 20 minutes against
+//! zcashd.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::{
+    protocol::message::Message,
+    setup::node::{Action, Node},
+    tools::{synthetic_node::SyntheticNode, LONG_TIMEOUT, RECV_TIMEOUT},
+};
+
+/// How often the connection is checked while waiting.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c028_t1_PING_unanswered_disconnects() {
+    // zcashd: pass
+    // zebra:  pass
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+    let kind = node.kind();
+
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .without_auto_pong()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    // The node's Pings are received but never answered.
+    let deadline = Instant::now() + kind.ping_interval() + kind.ping_timeout() + LONG_TIMEOUT;
+    let mut pings = 0;
+    while synthetic_node.is_connected(node.addr()) && Instant::now() < deadline {
+        if let Ok((_, Message::Ping(_))) = synthetic_node.recv_message_timeout(POLL_INTERVAL).await
+        {
+            pings += 1;
+        }
+    }
+
+    assert!(
+        !synthetic_node.is_connected(node.addr()),
+        "{kind:?} kept an unresponsive peer connected"
+    );
+    assert!(pings > 0, "{kind:?} never pinged the idle peer");
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c028_t2_PING_answered_keeps_connection_alive() {
+    // zcashd: pass
+    // zebra:  pass
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+    let kind = node.kind();
+
+    // The node's Pings are answered by the auto-responder.
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    // Outlast the window in which an unresponsive peer is disconnected, by another ping cycle.
+    let deadline = Instant::now() + kind.ping_interval() * 2 + kind.ping_timeout() + LONG_TIMEOUT;
+    while Instant::now() < deadline {
+        assert!(
+            synthetic_node.is_connected(node.addr()),
+            "{kind:?} disconnected a peer answering its pings"
+        );
+        synthetic_node.discard_messages();
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    synthetic_node
+        .ping_pong_timeout(node.addr(), RECV_TIMEOUT)
+        .await
+        .unwrap();
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
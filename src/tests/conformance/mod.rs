@@ -1,5 +1,11 @@
+#[cfg(feature = "bip152")]
+mod compact_block;
+mod fee_filter;
 mod handshake;
 mod invalid_message;
+mod keepalive;
 mod peering;
 mod query;
+mod reconnection;
+mod tx_relay;
 mod unsolicited_response;
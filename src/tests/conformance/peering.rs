@@ -1,17 +1,29 @@
-use std::net::SocketAddr;
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
 
 use assert_matches::assert_matches;
+use time::OffsetDateTime;
+use tokio::time::{timeout, Instant};
 
 use crate::{
     protocol::{
         message::Message,
-        payload::{addr::NetworkAddr, Addr},
+        payload::{
+            addr::NetworkAddr,
+            version::{NODE_BLOOM, NODE_NETWORK},
+            Addr,
+        },
+    },
+    setup::{
+        node::{Action, Node},
+        NodeKind,
     },
-    setup::node::{Action, Node},
     tools::{
         message_filter::{Filter, MessageFilter},
         synthetic_node::SyntheticNode,
-        LONG_TIMEOUT,
+        LONG_TIMEOUT, RECV_TIMEOUT,
     },
     wait_until,
 };
@@ -182,3 +194,257 @@ async fn c014_correctly_lists_peers() {
 
     node.stop().unwrap();
 }
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn c026_t1_GETADDR_is_answered_a_limited_number_of_times() {
+    // ZG-CONFORMANCE-026
+    //
+    // The node answers a limited number of `GetAddr` messages per connection, instead of dumping
+    // its address book for each of them (which a peer could use for amplification).
+    //
+    // The observed limits, see `getaddr_limit`:
+    //
+    //  zcashd: 0, `GetAddr` from an inbound peer is logged as an unknown command (see c014).
+    //  zebra:  1, later requests on the same connection are ignored.
+    //
+    // zcashd: pass
+    // zebra:  pass
+
+    /// The number of addresses the node is seeded with.
+    const SEEDED: usize = 50;
+    /// The number of `GetAddr` messages sent.
+    const REQUESTS: usize = 10;
+    /// How long responses are collected for, responses may be delayed by the node.
+    const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    // Seed the node's address book, so it has addresses to answer with. zcashd doesn't relay an
+    // `Addr` with more than 10 entries, so the querier won't receive them unsolicited.
+    let seeded = (0..SEEDED as u8)
+        .map(|i| SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, i + 1)), 8233))
+        .collect::<Vec<_>>();
+    let mut seeder = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    seeder.connect(node.addr()).await.unwrap();
+    seeder
+        .unicast(
+            node.addr(),
+            Message::Addr(Addr::new(
+                seeded.iter().map(|&addr| NetworkAddr::new(addr)).collect(),
+            )),
+        )
+        .unwrap();
+    seeder
+        .ping_pong_timeout(node.addr(), RECV_TIMEOUT)
+        .await
+        .unwrap();
+
+    let mut querier = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    querier.connect(node.addr()).await.unwrap();
+
+    for _ in 0..REQUESTS {
+        querier.unicast(node.addr(), Message::GetAddr).unwrap();
+    }
+
+    // Only count the responses, the node may also advertise its own address.
+    let mut responses = 0;
+    let deadline = Instant::now() + RESPONSE_TIMEOUT;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match timeout(remaining, querier.recv_message()).await {
+            Ok((_, Message::Addr(addr))) => {
+                if addr.iter().any(|entry| seeded.contains(&entry.addr)) {
+                    responses += 1;
+                }
+            }
+            Ok(_) => continue,
+            Err(_) => break,
+        }
+    }
+
+    let limit = getaddr_limit(node.kind());
+    assert!(
+        responses <= limit,
+        "{:?} answered {responses} of {REQUESTS} `GetAddr` messages, expected at most {limit}",
+        node.kind()
+    );
+
+    seeder.shut_down().await;
+    querier.shut_down().await;
+    node.stop().unwrap();
+}
+
+/// The number of `GetAddr` messages each implementation was observed to answer per connection.
+fn getaddr_limit(kind: NodeKind) -> usize {
+    match kind {
+        NodeKind::Zcashd => 0,
+        NodeKind::Zebra => 1,
+    }
+}
+
+/// How long the observer waits for gossiped addresses, zcashd sends them to each peer on a timer
+/// averaging 30s.
+const RELAY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How far in the future zcashd accepts an address' timestamp.
+const MAX_FUTURE_DRIFT: Duration = Duration::from_secs(10 * 60);
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn c027_t1_ADDR_time_and_services_are_preserved_when_relayed() {
+    // ZG-CONFORMANCE-027
+    //
+    // The node relays a freshly gossiped address to its other peers with the `time` and `services`
+    // fields it was announced with.
+    //
+    // zcashd relays an `Addr` with at most 10 entries, and of those only the routable entries seen
+    // in the last 10 minutes, see `c027_t2` for how the timestamps are clamped.
+    //
+    // zcashd: pass
+    // zebra:  fail (gossiped addresses aren't relayed, only shared in response to `GetAddr`)
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let (mut announcer, mut observer) = connect_gossip_peers(node.addr()).await;
+
+    let last_seen = OffsetDateTime::now_utc() - Duration::from_secs(60);
+    let services = NODE_NETWORK | NODE_BLOOM;
+    let announced = NetworkAddr::new(SocketAddr::new(Ipv4Addr::new(1, 2, 4, 1).into(), 8233))
+        .with_services(services)
+        .with_last_seen(last_seen);
+    announcer
+        .unicast(
+            node.addr(),
+            Message::Addr(Addr::new(vec![announced.clone()])),
+        )
+        .unwrap();
+
+    let relayed = wait_for_relayed(&mut observer, &[announced.addr]).await;
+    let relayed = relayed
+        .first()
+        .expect("the announced address wasn't relayed");
+
+    assert_eq!(relayed.services, services);
+    assert_eq!(
+        relayed.last_seen_timestamp(),
+        Some(last_seen.unix_timestamp())
+    );
+
+    announcer.shut_down().await;
+    observer.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn c027_t2_ADDR_with_future_time_is_not_relayed_as_is() {
+    // ZG-CONFORMANCE-027
+    //
+    // The node doesn't relay an address with a timestamp far in the future unchanged.
+    //
+    // zcashd replaces timestamps more than 10 minutes in the future (or before 1973) with one 5
+    // days in the past. Only addresses seen in the last 10 minutes are relayed, so the clamped
+    // address is kept in the address book but never relayed. Addresses from the address book
+    // (e.g. in response to `GetAddr`) also get a 2 hour penalty applied to their timestamp.
+    //
+    // zebra caps the timestamps of gossiped addresses at the current time, and truncates the
+    // ones it shares to 30 minute intervals.
+    //
+    // zcashd: pass
+    // zebra:  pass (gossiped addresses aren't relayed)
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let (mut announcer, mut observer) = connect_gossip_peers(node.addr()).await;
+
+    // The fresh address marks the point where the batch has been relayed.
+    let now = OffsetDateTime::now_utc();
+    let fresh = NetworkAddr::new(SocketAddr::new(Ipv4Addr::new(1, 2, 4, 2).into(), 8233));
+    let future = NetworkAddr::new(SocketAddr::new(Ipv4Addr::new(1, 2, 4, 3).into(), 8233))
+        .with_last_seen(now + Duration::from_secs(60 * 60));
+    announcer
+        .unicast(
+            node.addr(),
+            Message::Addr(Addr::new(vec![fresh.clone(), future.clone()])),
+        )
+        .unwrap();
+
+    for relayed in wait_for_relayed(&mut observer, &[fresh.addr, future.addr]).await {
+        if relayed.addr == future.addr {
+            let last_seen = relayed.last_seen.unwrap();
+            assert!(
+                last_seen <= now + MAX_FUTURE_DRIFT,
+                "the address was relayed with a timestamp {} in the future",
+                last_seen - now
+            );
+        }
+    }
+
+    announcer.shut_down().await;
+    observer.shut_down().await;
+    node.stop().unwrap();
+}
+
+/// Connects a peer announcing addresses and one observing them being relayed to the node.
+async fn connect_gossip_peers(node_addr: SocketAddr) -> (SyntheticNode, SyntheticNode) {
+    let mut peers = Vec::with_capacity(2);
+    for _ in 0..2 {
+        let peer = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+        peer.connect(node_addr).await.unwrap();
+        peers.push(peer);
+    }
+
+    let observer = peers.pop().unwrap();
+    let announcer = peers.pop().unwrap();
+    (announcer, observer)
+}
+
+/// Collects the entries for `addrs` relayed to the observer, until all of them were seen or the
+/// [`RELAY_TIMEOUT`] expires.
+async fn wait_for_relayed(observer: &mut SyntheticNode, addrs: &[SocketAddr]) -> Vec<NetworkAddr> {
+    let mut relayed = Vec::new();
+    let deadline = Instant::now() + RELAY_TIMEOUT;
+
+    while relayed.len() < addrs.len() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match timeout(remaining, observer.recv_message()).await {
+            Ok((_, Message::Addr(addr))) => relayed.extend(
+                addr.iter()
+                    .filter(|entry| addrs.contains(&entry.addr))
+                    .cloned(),
+            ),
+            Ok(_) => continue,
+            Err(_) => break,
+        }
+    }
+
+    relayed
+}
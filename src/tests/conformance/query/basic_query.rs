@@ -10,6 +10,7 @@
 //! Queries and expected replies:
 //!
 //!     - Ping           -> Pong
+//!     - Ping, Ping     -> Pong, Pong (in any order, with the matching nonces)
 //!     - GetAddr        -> Addr
 //!     - Mempool        -> Inv
 //!     - GetBlocks      -> Inv
@@ -237,6 +238,40 @@ mod node_is_not_seeded_with_blocks {
         assert_matches!(reply, Message::Headers(..));
     }
 
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn c011_t15_PING_outstanding_nonces() {
+        // Both Pings are sent before reading any Pong, a node which only remembers the last Ping
+        // (or a single nonce) fails to answer the first one.
+        //
+        // zcashd: pass
+        // zebra:  pass
+        let mut node = Node::new().unwrap();
+        node.initial_action(Action::WaitForConnection)
+            .start()
+            .await
+            .unwrap();
+
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(node.addr()).await.unwrap();
+
+        let nonces = [Nonce::default(), Nonce::default()];
+        assert_ne!(nonces[0], nonces[1]);
+        synthetic_node
+            .ping_pong_nonces_timeout(node.addr(), &nonces, RECV_TIMEOUT)
+            .await
+            .unwrap();
+
+        // Gracefully shut down the nodes.
+        synthetic_node.shut_down().await;
+        node.stop().unwrap();
+    }
+
     async fn run_test_case(query: Message) -> io::Result<Message> {
         // Spin up a non-seeded node instance.
         let mut node = Node::new().unwrap();
@@ -0,0 +1,142 @@
+//! Contains test cases which cover ZG-CONFORMANCE-019
+//!
+//! The node responds to `GetData` requests for filtered blocks with a `MerkleBlock` once a bloom
+//! filter has been loaded, see [BIP 37](https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki).
+//!
+//! Note: Zebra doesn't support bloom filters and as such cannot run any of these tests successfully.
+//!
+//! Note: Zcashd only serves filtered blocks with `peerbloomfilters=1` set.
+
+use assert_matches::assert_matches;
+
+use crate::{
+    protocol::{
+        message::Message,
+        payload::{
+            version::{NODE_BLOOM, NODE_NETWORK},
+            BloomFilter, Inv, InvHash,
+        },
+    },
+    setup::node::{Action, Node},
+    tests::conformance::query::{run_test_queries, SEED_BLOCKS},
+    tools::{
+        reaction::{classify, Reaction},
+        synthetic_node::SyntheticNode,
+        DISCONNECT_TIMEOUT,
+    },
+};
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c019_t1_GET_DATA_filtered_block() {
+    // zebra:  unsupported (no bloom filters)
+    let block = SEED_BLOCKS[5].clone();
+    let hash = block.double_sha256().unwrap();
+
+    let queries = vec![
+        Message::FilterLoad(BloomFilter::match_all()),
+        Message::GetData(Inv::new(vec![InvHash::FilteredBlock(hash)])),
+    ];
+    let response = run_test_queries(queries).await.unwrap();
+
+    // The merkle block is followed by the matched transactions, which is all of them.
+    let (merkle_block, txs) = response.split_first().expect("no response");
+    assert_matches!(
+        merkle_block,
+        Message::MerkleBlock(merkle_block)
+            if merkle_block.header == block.header
+                && merkle_block.total_txs as usize == block.txs.len()
+    );
+
+    // The proof must be consistent with the header, matching every transaction.
+    if let Message::MerkleBlock(merkle_block) = merkle_block {
+        let matches = merkle_block.verify().unwrap();
+        assert_eq!(matches.len(), block.txs.len());
+    }
+
+    let expected_txs = block.txs.into_iter().map(Message::Tx).collect::<Vec<_>>();
+    assert_eq!(txs, expected_txs);
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c019_t2_GET_DATA_filtered_block_after_filter_clear() {
+    // zebra:  unsupported (no bloom filters)
+    let block = SEED_BLOCKS[5].clone();
+    let hash = block.double_sha256().unwrap();
+
+    // Without a filter the node doesn't serve filtered blocks.
+    let queries = vec![
+        Message::FilterLoad(BloomFilter::match_all()),
+        Message::FilterClear,
+        Message::GetData(Inv::new(vec![InvHash::FilteredBlock(hash)])),
+    ];
+    let response = run_test_queries(queries).await.unwrap();
+
+    assert!(
+        !response
+            .iter()
+            .any(|message| matches!(message, Message::MerkleBlock(..))),
+        "unexpected response: {response:?}"
+    );
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c019_t3_FILTER_LOAD_depends_on_node_services_only() {
+    // Whether a `FilterLoad` is accepted depends on the node advertising `NODE_BLOOM`, not on the
+    // services we advertise: a peer without `NODE_BLOOM` is still a valid bloom filter client.
+    //
+    // zcashd: pass
+    // zebra:  unsupported (no bloom filters)
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let mut reactions = Vec::new();
+    for services in [NODE_NETWORK, NODE_NETWORK | NODE_BLOOM] {
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .with_services(services)
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(node.addr()).await.unwrap();
+
+        let node_services = synthetic_node
+            .handshake_info(&node.addr())
+            .unwrap()
+            .services;
+
+        let reaction = classify(
+            &mut synthetic_node,
+            node.addr(),
+            Message::FilterLoad(BloomFilter::match_all()),
+            DISCONNECT_TIMEOUT,
+        )
+        .await;
+
+        // A node advertising `NODE_BLOOM` must keep serving us after loading the filter.
+        if node_services & NODE_BLOOM != 0 {
+            assert_eq!(
+                reaction,
+                Reaction::Ignored,
+                "the node advertises NODE_BLOOM, but reacted to FilterLoad with {reaction} \
+                 (our services: {services:#x})"
+            );
+        }
+
+        reactions.push(reaction);
+        synthetic_node.shut_down().await;
+    }
+
+    assert_eq!(
+        reactions[0], reactions[1],
+        "the node's reaction to FilterLoad depends on our NODE_BLOOM service bit"
+    );
+
+    node.stop().unwrap();
+}
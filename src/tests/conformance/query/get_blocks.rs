@@ -2,10 +2,11 @@
 //!
 //! The node responds to `GetBlocks` requests with a list of blocks based on the provided range.
 //!
-//! Three broad categories are tested.
+//! Four broad categories are tested.
 //!  1. no-range limit (stop_hash = [0]).
 //!  2. stop_hash == start_hash (i.e. the range should be zero).
 //!  3. ranged queries (stop_hash is valid).
+//!  4. locators of a fork the node doesn't know, from a peer on a stale tip.
 //!
 //! Note: Zebra does not support seeding with chain data and as such cannot run any of these tests successfully.
 //!
@@ -22,7 +23,7 @@ use crate::{
         message::Message,
         payload::{block::LocatorHashes, Hash, Inv},
     },
-    tests::conformance::query::{run_test_query, SEED_BLOCKS},
+    tests::conformance::query::{run_test_query, unknown_hashes, SEED_BLOCKS},
 };
 
 /// Contains a [`Message::GetBlocks`] query.
@@ -342,6 +343,41 @@ mod ranged {
     }
 }
 
+mod fork {
+    //! The locator starts with the hashes of the peer's fork, followed by the sparse, older
+    //! hashes of the chain it shares with the node.
+    use super::*;
+
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn c016_t18_GET_BLOCKS_unknown_fork() {
+        // The node skips the fork's hashes and resumes from the most recent common block.
+        //
+        // zcashd: pass
+        let common = [6, 4, 0];
+        let mut hashes = unknown_hashes(10);
+        hashes.extend(common.map(|i| SEED_BLOCKS[i].double_sha256().unwrap()));
+        let query = GetBlocks::from_hashes(hashes, Hash::zeroed());
+
+        let response = run_test_case(query).await.unwrap();
+        let expected = Response::inv_with_range(common[0] + 1, None);
+        assert_eq!(response, expected);
+    }
+
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn c016_t19_GET_BLOCKS_unknown_fork_from_genesis() {
+        // Not even genesis is shared, the node falls back to its own genesis block.
+        //
+        // zcashd: pass
+        let query = GetBlocks::from_hashes(unknown_hashes(10), Hash::zeroed());
+
+        let response = run_test_case(query).await.unwrap();
+        let expected = Response::inv_with_range(1, None);
+        assert_eq!(response, expected);
+    }
+}
+
 /// A wrapper around [`run_test_query`] which maps its output to [`Response`].
 async fn run_test_case(query: GetBlocks) -> io::Result<Response> {
     let mut reply = run_test_query(query.0).await?;
@@ -5,13 +5,19 @@
 //! Note: Zebra does not support seeding with chain data and as such cannot run any of these tests successfully.
 //!
 //! Note: Zcashd currently ignores requests for non-existent blocks. We expect a [`Message::NotFound`] response.
+//!
+//! Note: [`not_found`] tolerates zebra silently dropping requests for unknown data.
+//!
+//! Note: [`download`] doesn't negotiate compact blocks, the node must reply with a full [`Message::Block`].
 
 use crate::{
     protocol::{
         message::Message,
-        payload::{inv::InvHash, Hash, Inv},
+        payload::{inv::InvHash, Hash, Inv, Nonce},
     },
+    setup::node::{Action, Node},
     tests::conformance::query::{run_test_query, SEED_BLOCKS},
+    tools::synthetic_node::SyntheticNode,
 };
 
 mod single_block {
@@ -175,3 +181,125 @@ mod multiple_blocks {
         assert_eq!(response, expected);
     }
 }
+
+mod not_found {
+    use super::*;
+
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn c018_t10_GET_DATA_random_block_is_NOT_FOUND() {
+        // zcashd: fail (ignores non-existent block)
+        // zebra:  pass (a missing reply is tolerated)
+        let mut node = Node::new().unwrap();
+        node.initial_action(Action::WaitForConnection)
+            .start()
+            .await
+            .unwrap();
+
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(node.addr()).await.unwrap();
+
+        let inv = Inv::new(vec![InvHash::Block(Hash::new(rand::random()))]);
+        synthetic_node
+            .unicast(node.addr(), Message::GetData(inv.clone()))
+            .unwrap();
+
+        // The Pong marks the end of the node's reply to the request, if it sends one at all.
+        let nonce = Nonce::default();
+        synthetic_node
+            .unicast(node.addr(), Message::Ping(nonce))
+            .unwrap();
+
+        let mut not_found = None;
+        loop {
+            match synthetic_node.next_message().await {
+                Ok((_, Message::Pong(rx_nonce))) if rx_nonce == nonce => break,
+                Ok((_, Message::NotFound(rx_inv))) => not_found = Some(rx_inv),
+                Ok(_) => continue,
+                Err(e) => panic!("no Pong was received: {e}"),
+            }
+        }
+
+        match not_found {
+            Some(rx_inv) => assert_eq!(rx_inv, inv),
+            None if node.is_zebra() => (),
+            None => panic!("the node didn't reply with NotFound"),
+        }
+
+        synthetic_node.shut_down().await;
+        node.stop().unwrap();
+    }
+}
+
+mod download {
+    use super::*;
+
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn c018_t12_GET_DATA_block_hashes_to_requested_hash() {
+        // zcashd: pass
+        let mut node = Node::new().unwrap();
+        node.initial_action(Action::SeedWithTestnetBlocks(SEED_BLOCKS.len()))
+            .start()
+            .await
+            .unwrap();
+
+        // No SendCmpct is sent, so the node has no reason to reply with a CmpctBlock.
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(node.addr()).await.unwrap();
+
+        let requested = SEED_BLOCKS[5].double_sha256().unwrap();
+        let query = Message::GetData(Inv::new(vec![InvHash::Block(requested)]));
+        synthetic_node.unicast(node.addr(), query).unwrap();
+
+        let block = loop {
+            match synthetic_node.next_message().await {
+                Ok((_, Message::Block(block))) => break block,
+                Ok((_, Message::CmpctBlock(_))) => {
+                    panic!("the node sent a CmpctBlock without compact mode being negotiated")
+                }
+                Ok(_) => continue,
+                Err(e) => panic!("no Block was received: {e}"),
+            }
+        };
+
+        // The hash is computed from the header we received, not taken from the seed block.
+        assert_eq!(block.header.double_sha256().unwrap(), requested);
+
+        synthetic_node.shut_down().await;
+        node.stop().unwrap();
+    }
+}
+
+#[cfg(feature = "pow")]
+mod pow {
+    use super::*;
+    use crate::protocol::message::constants::MAGIC_TESTNET;
+
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn c018_t11_GET_DATA_blocks_have_valid_pow() {
+        // zcashd: pass
+        let inv_hash = SEED_BLOCKS.iter().map(|block| block.inv_hash()).collect();
+        let query = Message::GetData(Inv::new(inv_hash));
+        let response = run_test_query(query).await.unwrap();
+
+        assert_eq!(response.len(), SEED_BLOCKS.len());
+        for message in response {
+            match message {
+                Message::Block(block) => assert!(block.verify_pow(MAGIC_TESTNET)),
+                message => panic!("expected Block, got {message}"),
+            }
+        }
+    }
+}
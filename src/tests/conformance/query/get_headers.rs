@@ -2,10 +2,11 @@
 //!
 //! The node responds to `GetHeaders` request with a list of block headers based on the provided range.
 //!
-//! Three broad categories are tested.
+//! Four broad categories are tested.
 //!  1. no-range limit (stop_hash = [0]).
 //!  2. stop_hash == start_hash (i.e. the range should be zero).
 //!  3. ranged queries (stop_hash is valid).
+//!  4. multi-hash locators describing a partial or forked chain, as built by a syncing peer.
 //!
 //! Note: Zebra does not support seeding with chain data and as such cannot run any of these tests successfully.
 
@@ -19,7 +20,7 @@ use crate::{
             Hash,
         },
     },
-    tests::conformance::query::{run_test_query, SEED_BLOCKS},
+    tests::conformance::query::{run_test_query, unknown_hashes, SEED_BLOCKS},
 };
 
 /// Contains a [`Message::GetHeaders`] query.
@@ -48,6 +49,20 @@ impl GetHeaders {
             stop_hash,
         )))
     }
+
+    /// Creates a [`GetHeaders`] query with the locator of `chain`, ordered from genesis to tip,
+    /// see [`LocatorHashes::build`].
+    fn from_chain(chain: &[Hash]) -> Self {
+        Self(Message::GetHeaders(LocatorHashes::build(chain)))
+    }
+}
+
+/// Returns the hashes of the [`SEED_BLOCKS`] up to and including `index`.
+fn seed_hashes(index: usize) -> Vec<Hash> {
+    SEED_BLOCKS[..=index]
+        .iter()
+        .map(|block| block.double_sha256().unwrap())
+        .collect()
 }
 
 /// The response of a node to a query.
@@ -340,6 +355,161 @@ mod ranged {
     }
 }
 
+mod continuity {
+    use super::*;
+
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn c017_t18_GET_HEADERS_from_block_0_are_continuous() {
+        // Checks the headers chain up to genesis, independently of the seeded blocks.
+        //
+        // zcashd: pass
+        let genesis_hash = SEED_BLOCKS[0].double_sha256().unwrap();
+        let response = run_test_case(GetHeaders::from_indices(0, None))
+            .await
+            .unwrap();
+
+        let headers = match response {
+            Response::Reply(reply) => match *reply {
+                Message::Headers(headers) => headers,
+                message => panic!("expected Headers, got {message}"),
+            },
+            response => panic!("expected a single Headers reply, got {response:?}"),
+        };
+
+        assert_eq!(headers.headers[0].prev_block, genesis_hash);
+        if let Err(error) = headers.validate_continuity() {
+            panic!("{error}");
+        }
+    }
+}
+
+mod locator {
+    //! The node latches onto the first locator hash on its chain (the best common block), and
+    //! returns the headers following it.
+    use super::*;
+
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn c017_t20_GET_HEADERS_locator_of_partial_chain() {
+        // A peer which has the chain up to `index` gets the rest of it.
+        //
+        // zcashd: pass
+        let index = 6;
+        let query = GetHeaders::from_chain(&seed_hashes(index));
+
+        let response = run_test_case(query).await.unwrap();
+        let expected = Response::headers_with_range(index + 1, None);
+        assert_eq!(response, expected);
+    }
+
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn c017_t21_GET_HEADERS_locator_of_fork() {
+        // A peer on a fork of the chain from `index` gets the headers following the fork point,
+        // its own blocks come first in the locator but are unknown to the node.
+        //
+        // zcashd: pass
+        let index = 4;
+        let mut chain = seed_hashes(index);
+        chain.extend(unknown_hashes(3));
+        let query = GetHeaders::from_chain(&chain);
+
+        let response = run_test_case(query).await.unwrap();
+        let expected = Response::headers_with_range(index + 1, None);
+        assert_eq!(response, expected);
+    }
+
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn c017_t22_GET_HEADERS_locator_off_chain() {
+        // None of the locator hashes are on the node's chain, not even genesis: the node falls
+        // back to its genesis block and returns the headers from block 1.
+        //
+        // zcashd: pass
+        let query = GetHeaders::from_chain(&unknown_hashes(8));
+
+        let response = run_test_case(query).await.unwrap();
+        let expected = Response::headers_with_range(1, None);
+        assert_eq!(response, expected);
+    }
+}
+
+mod pagination {
+    use super::*;
+    use crate::{
+        protocol::payload::block::MAX_HEADERS_RESULTS,
+        setup::node::{Action, Node},
+        tools::synthetic_node::SyntheticNode,
+    };
+
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn c017_t19_GET_HEADERS_pages_are_capped_and_continuous() {
+        // Pages through the chain from genesis, each request continuing from the last header of the
+        // previous reply, until an empty reply marks the tip.
+        //
+        // zcashd: pass
+        let mut node = Node::new().unwrap();
+        node.initial_action(Action::SeedWithTestnetBlocks(SEED_BLOCKS.len()))
+            .start()
+            .await
+            .unwrap();
+
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(node.addr()).await.unwrap();
+
+        let mut previous_hash = SEED_BLOCKS[0].double_sha256().unwrap();
+        let mut locator = LocatorHashes::new(vec![previous_hash], Hash::zeroed());
+        let mut received = 0;
+        // Every seeded block is its own page at worst, the extra request fetches the empty page.
+        for _ in 0..SEED_BLOCKS.len() {
+            synthetic_node
+                .unicast(node.addr(), Message::GetHeaders(locator))
+                .unwrap();
+
+            let headers = loop {
+                match synthetic_node.next_message().await {
+                    Ok((_, Message::Headers(headers))) => break headers,
+                    Ok(_) => continue,
+                    Err(e) => panic!("no Headers were received: {e}"),
+                }
+            };
+
+            assert!(
+                headers.headers.len() <= MAX_HEADERS_RESULTS,
+                "received {} headers in a single reply",
+                headers.headers.len()
+            );
+
+            locator = match LocatorHashes::from_last_header(&headers) {
+                Some(locator) => locator,
+                None => break,
+            };
+
+            // Each page must extend the previous one.
+            assert_eq!(headers.headers[0].prev_block, previous_hash);
+            if let Err(error) = headers.validate_continuity() {
+                panic!("{error}");
+            }
+
+            previous_hash = locator.block_locator_hashes[0];
+            received += headers.headers.len();
+        }
+
+        // The genesis block was used as the locator, so it isn't returned.
+        assert_eq!(received, SEED_BLOCKS.len() - 1);
+
+        synthetic_node.shut_down().await;
+        node.stop().unwrap();
+    }
+}
+
 /// A wrapper around [`run_test_query`] which maps its output to [`Response`].
 async fn run_test_case(query: GetHeaders) -> io::Result<Response> {
     let mut reply = run_test_query(query.0).await?;
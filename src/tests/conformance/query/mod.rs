@@ -3,16 +3,18 @@ use std::io;
 use crate::{
     protocol::{
         message::Message,
-        payload::{block::Block, Nonce},
+        payload::{block::Block, Hash, Nonce},
     },
     setup::node::{Action, Node},
-    tools::{synthetic_node::SyntheticNode, RECV_TIMEOUT},
+    tools::synthetic_node::SyntheticNode,
 };
 
 mod basic_query;
+mod bloom_filter;
 mod get_blocks;
 mod get_data;
 mod get_headers;
+mod node_state;
 
 lazy_static::lazy_static!(
     /// The blocks that the node is seeded with for this test module.
@@ -21,10 +23,21 @@ lazy_static::lazy_static!(
     };
 );
 
+/// Returns `n` hashes of blocks the node doesn't know, e.g. those of a fork.
+fn unknown_hashes(n: u8) -> Vec<Hash> {
+    (0..n).map(|i| Hash::new([0xf0 ^ i; 32])).collect()
+}
+
 /// Starts a node seeded with the initial testnet chain, connects a single
 /// SyntheticNode and sends a query. The node's responses to this query is
 /// then returned.
 async fn run_test_query(query: Message) -> io::Result<Vec<Message>> {
+    run_test_queries(vec![query]).await
+}
+
+/// Like [`run_test_query`], but sends several queries in order. The node's
+/// responses to all of them are returned.
+async fn run_test_queries(queries: Vec<Message>) -> io::Result<Vec<Message>> {
     // Spin up a node instance with knowledge of the initial testnet-chain.
     let mut node = Node::new().unwrap();
     node.initial_action(Action::SeedWithTestnetBlocks(SEED_BLOCKS.len()))
@@ -41,17 +54,20 @@ async fn run_test_query(query: Message) -> io::Result<Vec<Message>> {
     // Connect to the node and initiate handshake.
     synthetic_node.connect(node.addr()).await?;
 
-    // Send the query.
-    synthetic_node.unicast(node.addr(), query)?;
+    // Send the queries.
+    for query in queries {
+        synthetic_node.unicast(node.addr(), query)?;
+    }
 
-    // Send a Ping - once we receive the matching Pong we know our query has been fully processed.
+    // Send a Ping - once we receive the matching Pong we know our queries have been fully
+    // processed.
     let nonce = Nonce::default();
     synthetic_node.unicast(node.addr(), Message::Ping(nonce))?;
 
     // Receive messages until we receive the matching Pong, or we timeout.
     let mut messages = Vec::new();
     loop {
-        match synthetic_node.recv_message_timeout(RECV_TIMEOUT).await? {
+        match synthetic_node.next_message().await? {
             (_, Message::Pong(rx_nonce)) if rx_nonce == nonce => break,
             (_, message) => messages.push(message),
         }
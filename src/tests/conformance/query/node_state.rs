@@ -0,0 +1,282 @@
+//! Contains test cases which cover ZG-CONFORMANCE-029, ZG-CONFORMANCE-030 and
+//! ZG-CONFORMANCE-031.
+//!
+//! They use the node's RPC interface to verify state which can't be observed over the wire.
+//!
+//! Note: these tests require the `[rpc]` table to be present in `config.toml`.
+
+use crate::{
+    protocol::{
+        message::Message,
+        payload::{block::LocatorHashes, BloomFilter, Hash, Inv, InvHash},
+    },
+    setup::{
+        node::{Action, Node, Readiness},
+        rpc::COINBASE_MATURITY,
+        Network,
+    },
+    tests::conformance::query::SEED_BLOCKS,
+    tools::{synthetic_node::SyntheticNode, LONG_TIMEOUT},
+};
+
+#[tokio::test]
+async fn c029_t1_seeded_chain_is_reported_via_rpc() {
+    // zcashd: pass
+    // zebra:  unsupported (no block seeding, no `getblockcount`)
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::SeedWithTestnetBlocks(SEED_BLOCKS.len()))
+        .start()
+        .await
+        .unwrap();
+
+    // The seeded blocks are only connected once the node has processed them.
+    let tip = (SEED_BLOCKS.len() - 1) as u32;
+    node.readiness(Readiness::Height(tip));
+    node.wait_until_ready().await.unwrap();
+
+    let rpc = node.rpc().unwrap();
+
+    // The genesis block is at height 0.
+    let height = rpc.get_block_count().await.unwrap();
+    assert_eq!(height as usize, SEED_BLOCKS.len() - 1);
+
+    for (height, block) in SEED_BLOCKS.iter().enumerate() {
+        let hash = rpc.get_block_hash(height as u32).await.unwrap();
+        assert_eq!(hash, block.hash());
+    }
+
+    // Nothing has been relayed to the node.
+    assert!(rpc.get_raw_mempool().await.unwrap().is_empty());
+
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c029_t2_GET_HEADERS_hashes_match_rpc() {
+    // zcashd: pass
+    // zebra:  unsupported (no block seeding, no `getblockhash`)
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::SeedWithTestnetBlocks(SEED_BLOCKS.len()))
+        .start()
+        .await
+        .unwrap();
+
+    let tip = (SEED_BLOCKS.len() - 1) as u32;
+    node.readiness(Readiness::Height(tip));
+    node.wait_until_ready().await.unwrap();
+
+    let rpc = node.rpc().unwrap();
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    let genesis = rpc.get_block_hash(0).await.unwrap();
+    assert_eq!(genesis, SEED_BLOCKS[0].hash());
+    synthetic_node
+        .unicast(
+            node.addr(),
+            Message::GetHeaders(LocatorHashes::new(vec![genesis], Hash::zeroed())),
+        )
+        .unwrap();
+
+    // The node may send other messages (e.g. GetAddr) before replying.
+    let headers = loop {
+        match synthetic_node.recv_message_timeout(LONG_TIMEOUT).await {
+            Ok((_, Message::Headers(headers))) => break headers,
+            Ok(_) => continue,
+            Err(e) => panic!("no headers in reply to getheaders: {e}"),
+        }
+    };
+
+    let mut expected = Vec::new();
+    for height in 1..=tip {
+        expected.push(rpc.get_block_hash(height).await.unwrap());
+    }
+    assert_eq!(headers.hashes(), expected);
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c029_t3_GET_DATA_block_at_height_matches_seed() {
+    // zcashd: pass
+    // zebra:  unsupported (no block seeding, no `getblockhash`)
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::SeedWithTestnetBlocks(SEED_BLOCKS.len()))
+        .start()
+        .await
+        .unwrap();
+
+    let tip = (SEED_BLOCKS.len() - 1) as u32;
+    node.readiness(Readiness::Height(tip));
+    node.wait_until_ready().await.unwrap();
+
+    let rpc = node.rpc().unwrap();
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    for (height, block) in SEED_BLOCKS.iter().enumerate() {
+        let fetched = synthetic_node
+            .fetch_block_at_height(&rpc, node.addr(), height as u32)
+            .await
+            .unwrap();
+        assert_eq!(&fetched, block);
+    }
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c030_t1_INV_generated_block_is_announced() {
+    // zcashd: pass
+    // zebra:  unsupported (no `generate`)
+
+    let mut node = Node::new().unwrap();
+    node.network(Network::Regtest)
+        .initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    let hashes = node.rpc().unwrap().generate(1).await.unwrap();
+    let expected = Inv::new(vec![InvHash::Block(hashes[0])]);
+
+    // The node may send other messages (e.g. GetAddr) before announcing the block.
+    loop {
+        match synthetic_node.recv_message_timeout(LONG_TIMEOUT).await {
+            Ok((_, Message::Inv(inv))) if inv == expected => break,
+            Ok(_) => continue,
+            Err(e) => panic!("the generated block wasn't announced: {e}"),
+        }
+    }
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c030_t2_HEADERS_generated_block_is_announced_after_SEND_HEADERS() {
+    // zcashd: pass
+    // zebra:  unsupported (no `generate`)
+
+    let mut node = Node::new().unwrap();
+    node.network(Network::Regtest)
+        .initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    // `SendHeaders` is sent during the handshake, before the block is generated.
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_send_headers()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    let hashes = node.rpc().unwrap().generate(1).await.unwrap();
+    let hash = hashes[0];
+
+    // The node may send other messages (e.g. GetAddr) before announcing the block.
+    loop {
+        match synthetic_node.recv_message_timeout(LONG_TIMEOUT).await {
+            Ok((_, Message::Headers(headers)))
+                if headers.headers.iter().any(|header| header.hash() == hash) =>
+            {
+                break
+            }
+            Ok((_, Message::Inv(inv))) if inv.inventory.contains(&InvHash::Block(hash)) => {
+                panic!("the generated block was announced with an Inv instead of Headers")
+            }
+            Ok(_) => continue,
+            Err(e) => panic!("the generated block wasn't announced: {e}"),
+        }
+    }
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c031_t1_MEMPOOL_lists_wallet_transaction() {
+    // zcashd: pass
+    // zebra:  unsupported (no wallet)
+
+    // Note: zcashd only answers `mempool` if the peer has asked for transactions to be relayed,
+    // which our version message doesn't, or has loaded a bloom filter (which also enables
+    // relaying). The reply is then restricted to the transactions matching the filter, so an
+    // all-matching filter is loaded first. Without `peerbloomfilters=1` non-whitelisted peers
+    // are disconnected for sending `mempool`, the synthetic node is whitelisted by `whitebind`.
+
+    let mut node = Node::new().unwrap();
+    node.network(Network::Regtest)
+        .initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let rpc = node.rpc().unwrap();
+    rpc.generate(COINBASE_MATURITY + 1).await.unwrap();
+    let address = rpc.get_new_address().await.unwrap();
+    let hash = rpc.send_to_address(&address, 1.0).await.unwrap();
+    assert!(rpc.get_raw_mempool().await.unwrap().contains(&hash));
+
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    synthetic_node
+        .unicast(node.addr(), Message::FilterLoad(BloomFilter::match_all()))
+        .unwrap();
+    synthetic_node
+        .unicast(node.addr(), Message::MemPool)
+        .unwrap();
+
+    // The node may send other messages (e.g. GetAddr) before replying.
+    loop {
+        match synthetic_node.recv_message_timeout(LONG_TIMEOUT).await {
+            Ok((_, Message::Inv(inv))) if inv.inventory.contains(&InvHash::Tx(hash)) => break,
+            Ok(_) => continue,
+            Err(e) => panic!("the transaction wasn't listed in reply to mempool: {e}"),
+        }
+    }
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
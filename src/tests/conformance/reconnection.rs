@@ -0,0 +1,89 @@
+//! Contains test cases which cover ZG-CONFORMANCE-032.
+//!
+//! A synthetic node reconnects to a node which dropped it by restarting, and then reconnects
+//! repeatedly to the same node.
+
+use std::time::Duration;
+
+use crate::{
+    setup::node::{Action, Node},
+    tools::{
+        synthetic_node::{RetryPolicy, SyntheticNode},
+        RECV_TIMEOUT,
+    },
+};
+
+/// The number of times the synthetic node reconnects once the node is back up.
+const RECONNECTS: usize = 3;
+
+#[tokio::test]
+async fn c032_t1_peer_reconnects_after_node_restart() {
+    // ZG-CONFORMANCE-032
+    //
+    // The synthetic node keeps retrying while the node is down, and re-establishes the connection
+    // once it's back up, within the retry policy's bounds. The restarted node then accepts the
+    // same peer reconnecting repeatedly.
+    //
+    // zcashd: pass
+    // zebra:  pass
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node_addr).await.unwrap();
+
+    // Long enough to cover the node's restart.
+    let policy = RetryPolicy {
+        initial_delay: Duration::from_millis(100),
+        multiplier: 2,
+        max_delay: Duration::from_secs(2),
+        max_retries: 30,
+    };
+
+    node.stop().unwrap();
+
+    // Retrying starts while the node is still down.
+    let (restarted, reconnected) = tokio::join!(
+        node.start(),
+        synthetic_node.connect_with_retry(node_addr, policy)
+    );
+    restarted.unwrap();
+    reconnected.unwrap_or_else(|e| {
+        panic!(
+            "not reconnected after {} retries over {:?}: {e}",
+            policy.max_retries,
+            policy.max_total_delay()
+        )
+    });
+
+    synthetic_node
+        .ping_pong_timeout(node_addr, RECV_TIMEOUT)
+        .await
+        .unwrap();
+
+    // The node accepts the same peer reconnecting right after disconnecting.
+    for _ in 0..RECONNECTS {
+        synthetic_node.disconnect(node_addr).await;
+        synthetic_node
+            .connect_with_retry(node_addr, RetryPolicy::default())
+            .await
+            .unwrap();
+        synthetic_node
+            .ping_pong_timeout(node_addr, RECV_TIMEOUT)
+            .await
+            .unwrap();
+    }
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
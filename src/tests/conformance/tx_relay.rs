@@ -0,0 +1,590 @@
+//! Contains test cases which cover ZG-CONFORMANCE-025.
+//!
+//! The node accepts a valid transaction sent with `Tx` into its mempool and relays it to its other
+//! peers, and rejects an invalid one with a `Reject`. A transaction announced with `Inv` is pulled
+//! with `GetData`.
+//!
+//! Note: these tests require the `[rpc]` table to be present in `config.toml`, the transactions are
+//! created and signed by the node's wallet without being broadcast.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::{
+    protocol::{
+        message::Message,
+        payload::{
+            codec::Codec, reject::CCode, BloomFilter, Hash, Inv, InvHash, Nonce, Reject, Tx,
+        },
+    },
+    setup::{
+        node::{Action, Node},
+        rpc::{UnspentOutput, COINBASE_MATURITY},
+        Network, NodeKind,
+    },
+    tools::{synthetic_node::SyntheticNode, LONG_TIMEOUT},
+};
+
+/// The fee left by the valid transaction, enough to be relayed under ZIP-317.
+const FEE: f64 = 0.0001;
+/// How long duplicate announcements are watched for extra `GetData` requests, well below the 2
+/// minutes after which zcashd asks again for a transaction which wasn't delivered.
+const DEDUP_WINDOW: Duration = Duration::from_secs(10);
+/// How often the connection is checked while waiting for the node's verdict.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// zcashd's default `-txunpaidactionlimit`, transactions not paying the ZIP-317 fee for more
+/// logical actions aren't accepted.
+const UNPAID_ACTION_LIMIT: usize = 50;
+const ZATS_PER_ZEC: f64 = 100_000_000.0;
+
+/// The node's verdict on a submitted transaction.
+#[derive(Debug)]
+enum Outcome {
+    /// The node didn't reject the transaction.
+    Accepted,
+    /// The node replied with this `Reject`.
+    Rejected(Reject),
+}
+
+/// Starts a regtest node whose wallet has matured funds, returning it along with a spendable
+/// output.
+async fn start_funded_node() -> (Node, UnspentOutput) {
+    let (node, mut inputs) = start_node_with_inputs(1).await;
+
+    (node, inputs.remove(0))
+}
+
+/// Starts a regtest node whose wallet has at least `n` matured coinbase outputs, returning it
+/// along with `n` of them.
+async fn start_node_with_inputs(n: usize) -> (Node, Vec<UnspentOutput>) {
+    let mut node = Node::new().unwrap();
+    node.network(Network::Regtest)
+        .initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let rpc = node.rpc().unwrap();
+    rpc.generate(COINBASE_MATURITY + n as u32).await.unwrap();
+    let inputs = rpc
+        .list_unspent()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|output| output.spendable)
+        .take(n)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        inputs.len(),
+        n,
+        "the wallet doesn't have enough spendable outputs"
+    );
+
+    (node, inputs)
+}
+
+/// Has the node's wallet sign a transaction spending `input` to a fresh address, without it being
+/// broadcast.
+async fn create_transaction(node: &Node, input: &UnspentOutput, amount: f64) -> Tx {
+    let signed = sign_transaction(node, input, &[amount]).await;
+
+    Tx::decode(&mut &signed[..]).unwrap()
+}
+
+/// Has the node's wallet sign a transaction spending `input` to an output per amount, each to a
+/// fresh address, and returns its serialization without it being broadcast.
+async fn sign_transaction(node: &Node, input: &UnspentOutput, amounts: &[f64]) -> Vec<u8> {
+    let rpc = node.rpc().unwrap();
+
+    let mut outputs = Vec::with_capacity(amounts.len());
+    for amount in amounts {
+        outputs.push((rpc.get_new_address().await.unwrap(), *amount));
+    }
+
+    let unsigned = rpc
+        .create_raw_transaction_with_outputs(input, &outputs)
+        .await
+        .unwrap();
+
+    rpc.sign_raw_transaction(&unsigned).await.unwrap()
+}
+
+/// A transaction crafted to be rejected for a specific reason.
+struct InvalidTx {
+    name: &'static str,
+    /// Valid transactions to send beforehand.
+    setup: Vec<Tx>,
+    tx: Tx,
+    /// zcashd's `Reject` code and the start of its reason, `None` if it drops the transaction
+    /// without one.
+    zcashd: Option<(CCode, &'static str)>,
+}
+
+impl InvalidTx {
+    /// Returns the `Reject` code and reason expected from a node of the given kind, zebra doesn't
+    /// send `Reject` messages at all.
+    fn expected(&self, kind: NodeKind) -> Option<(CCode, &'static str)> {
+        match kind {
+            NodeKind::Zcashd => self.zcashd,
+            NodeKind::Zebra => None,
+        }
+    }
+}
+
+/// Creates a transaction for each rejection reason covered, each spending a distinct one of the
+/// four `inputs`.
+async fn invalid_transactions(node: &Node, inputs: &[UnspentOutput]) -> Vec<InvalidTx> {
+    let [bad_signature, overspend, unpaid, double_spend] = inputs else {
+        panic!("expected 4 inputs, got {}", inputs.len());
+    };
+
+    // Flips a bit of the signature's R value, which keeps its DER encoding valid. The script
+    // follows the outpoint and its length, and starts with the signature's push opcode, and the
+    // DER sequence and integer headers.
+    let mut signed = sign_transaction(node, bad_signature, &[bad_signature.amount - FEE]).await;
+    let mut outpoint = hex::decode(&bad_signature.txid).unwrap();
+    outpoint.reverse();
+    outpoint.extend_from_slice(&bad_signature.vout.to_le_bytes());
+    let script = signed
+        .windows(outpoint.len())
+        .position(|window| window == outpoint)
+        .expect("the input isn't spent by the transaction")
+        + outpoint.len()
+        + 1;
+    signed[script + 5] ^= 1;
+    let bad_signature = Tx::decode(&mut &signed[..]).unwrap();
+
+    // Leaves no fee for more logical actions than zcashd's default `-txunpaidactionlimit`.
+    let zats = (unpaid.amount * ZATS_PER_ZEC).round() as u64;
+    let outputs = (UNPAID_ACTION_LIMIT + 1) as u64;
+    let amount = (zats / outputs) as f64 / ZATS_PER_ZEC;
+    let signed = sign_transaction(node, unpaid, &vec![amount; outputs as usize]).await;
+    let unpaid = Tx::decode(&mut &signed[..]).unwrap();
+
+    // Both spend the same input to different addresses.
+    let spent = create_transaction(node, double_spend, double_spend.amount - FEE).await;
+    let double_spend = create_transaction(node, double_spend, double_spend.amount - FEE).await;
+
+    vec![
+        InvalidTx {
+            name: "bad signature",
+            setup: vec![],
+            tx: bad_signature,
+            zcashd: Some((CCode::Invalid, "mandatory-script-verify-flag-failed")),
+        },
+        InvalidTx {
+            name: "overspend",
+            setup: vec![],
+            tx: create_transaction(node, overspend, overspend.amount + 1.0).await,
+            zcashd: Some((CCode::Invalid, "bad-txns-in-belowout")),
+        },
+        InvalidTx {
+            name: "unpaid actions",
+            setup: vec![],
+            tx: unpaid,
+            zcashd: Some((CCode::InsufficientFee, "tx unpaid action limit exceeded")),
+        },
+        // zcashd doesn't replace mempool transactions, conflicting ones are dropped silently.
+        InvalidTx {
+            name: "double spend",
+            setup: vec![spent],
+            tx: double_spend,
+            zcashd: None,
+        },
+    ]
+}
+
+/// Announces the transaction with hash `hash` to the node with an `Inv`.
+fn announce(node: &Node, synthetic_node: &SyntheticNode, hash: Hash) {
+    synthetic_node
+        .unicast(node.addr(), Message::Inv(Inv::new(vec![InvHash::Tx(hash)])))
+        .unwrap();
+}
+
+/// Waits up to `timeout` for the node to request data with a `GetData`, returning the requested
+/// items.
+async fn next_get_data(synthetic_node: &mut SyntheticNode, timeout: Duration) -> Option<Inv> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match synthetic_node.recv_message_timeout(remaining).await {
+            Ok((_, Message::GetData(inv))) => break Some(inv),
+            Ok(_) => continue,
+            Err(_) => break None,
+        }
+    }
+}
+
+/// Sends `tx` to the node and returns its verdict.
+///
+/// The node validates a transaction before processing the following message, so a `Reject` is
+/// sent before the reply to a subsequent `Ping`. A peer disconnected in the meantime gets no
+/// reply.
+async fn submit(node: &Node, synthetic_node: &mut SyntheticNode, tx: Tx) -> Outcome {
+    synthetic_node
+        .unicast(node.addr(), Message::Tx(tx))
+        .unwrap();
+
+    let nonce = Nonce::default();
+    synthetic_node
+        .unicast(node.addr(), Message::Ping(nonce))
+        .unwrap();
+
+    let mut outcome = Outcome::Accepted;
+    let deadline = Instant::now() + LONG_TIMEOUT;
+    loop {
+        match synthetic_node.recv_message_timeout(POLL_INTERVAL).await {
+            Ok((_, Message::Pong(rx_nonce))) if rx_nonce == nonce => break outcome,
+            Ok((_, Message::Reject(reject))) if reject.message.0 == "tx" => {
+                outcome = Outcome::Rejected(reject)
+            }
+            Ok(_) => continue,
+            // The node may disconnect a peer right after rejecting its transaction.
+            Err(_) if !synthetic_node.is_connected(node.addr()) => break outcome,
+            Err(_) if Instant::now() >= deadline => panic!("no Pong was received"),
+            Err(_) => continue,
+        }
+    }
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c025_t1_TX_valid_is_accepted_and_relayed() {
+    // zcashd: pass
+    // zebra:  unsupported (no wallet)
+
+    let (mut node, input) = start_funded_node().await;
+    let tx = create_transaction(&node, &input, input.amount - FEE).await;
+    let hash = tx.double_sha256().unwrap();
+
+    let mut sender = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    sender.connect(node.addr()).await.unwrap();
+
+    // Our version message doesn't ask for transactions to be relayed, loading a filter does.
+    let mut observer = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    observer.connect(node.addr()).await.unwrap();
+    observer
+        .unicast(node.addr(), Message::FilterLoad(BloomFilter::match_all()))
+        .unwrap();
+
+    match submit(&node, &mut sender, tx).await {
+        Outcome::Accepted => (),
+        Outcome::Rejected(reject) => panic!("the valid transaction was rejected: {reject:?}"),
+    }
+
+    // The node trickles its announcements, so wait for the relay before checking the mempool.
+    let relayed = loop {
+        match observer.recv_message_timeout(LONG_TIMEOUT).await {
+            Ok((_, Message::Inv(inv))) if inv.inventory.contains(&InvHash::Tx(hash)) => break true,
+            Ok(_) => continue,
+            Err(_) => break false,
+        }
+    };
+    let in_mempool = node
+        .rpc()
+        .unwrap()
+        .get_raw_mempool()
+        .await
+        .unwrap()
+        .contains(&hash);
+
+    assert!(
+        relayed || in_mempool,
+        "the transaction was neither relayed nor added to the mempool"
+    );
+
+    sender.shut_down().await;
+    observer.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c025_t2_TX_overspending_is_rejected() {
+    // zcashd: pass
+    // zebra:  unsupported (no wallet)
+
+    let (mut node, input) = start_funded_node().await;
+    // Signing doesn't check the outputs are covered by the inputs.
+    let tx = create_transaction(&node, &input, input.amount + 1.0).await;
+    let hash = tx.double_sha256().unwrap();
+
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    match submit(&node, &mut synthetic_node, tx).await {
+        Outcome::Rejected(reject) => assert_eq!(reject.ccode, CCode::Invalid),
+        Outcome::Accepted => panic!("the overspending transaction wasn't rejected"),
+    }
+
+    assert!(!node
+        .rpc()
+        .unwrap()
+        .get_raw_mempool()
+        .await
+        .unwrap()
+        .contains(&hash));
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c025_t3_INV_tx_is_requested_with_GET_DATA() {
+    // zcashd: pass
+    // zebra:  unsupported (no wallet)
+
+    let (mut node, input) = start_funded_node().await;
+    let tx = create_transaction(&node, &input, input.amount - FEE).await;
+    let hash = tx.double_sha256().unwrap();
+
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    announce(&node, &synthetic_node, hash);
+
+    let requested = next_get_data(&mut synthetic_node, LONG_TIMEOUT)
+        .await
+        .expect("the announced transaction wasn't requested");
+    assert_eq!(requested.inventory, vec![InvHash::Tx(hash)]);
+
+    // Answering the request completes the relay, the transaction is accepted like one sent
+    // unannounced.
+    match submit(&node, &mut synthetic_node, tx).await {
+        Outcome::Accepted => (),
+        Outcome::Rejected(reject) => panic!("the requested transaction was rejected: {reject:?}"),
+    }
+    assert!(node
+        .rpc()
+        .unwrap()
+        .get_raw_mempool()
+        .await
+        .unwrap()
+        .contains(&hash));
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c025_t4_INV_duplicate_tx_is_requested_once() {
+    // zcashd: pass
+    // zebra:  unsupported (no wallet)
+    //
+    // zcashd ignores announcements of a transaction it's about to request from the same peer, and
+    // once requested, delays asking again until 2 minutes have passed without it being delivered.
+
+    let (mut node, input) = start_funded_node().await;
+    let tx = create_transaction(&node, &input, input.amount - FEE).await;
+    let hash = tx.double_sha256().unwrap();
+
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+
+    for _ in 0..3 {
+        announce(&node, &synthetic_node, hash);
+    }
+
+    let mut requests = 0;
+    let window_end = Instant::now() + DEDUP_WINDOW;
+    loop {
+        let remaining = window_end.saturating_duration_since(Instant::now());
+        let Some(inv) = next_get_data(&mut synthetic_node, remaining).await else {
+            break;
+        };
+        requests += inv
+            .inventory
+            .iter()
+            .filter(|item| **item == InvHash::Tx(hash))
+            .count();
+    }
+    assert_eq!(
+        requests, 1,
+        "the transaction should be requested exactly once"
+    );
+
+    // The single request is answered, which completes the relay.
+    match submit(&node, &mut synthetic_node, tx).await {
+        Outcome::Accepted => (),
+        Outcome::Rejected(reject) => panic!("the requested transaction was rejected: {reject:?}"),
+    }
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c025_t5_TX_invalid_is_rejected_with_matching_code() {
+    // zcashd: pass
+    // zebra:  unsupported (no wallet, doesn't send `Reject`)
+
+    let (mut node, inputs) = start_node_with_inputs(4).await;
+
+    for invalid in invalid_transactions(&node, &inputs).await {
+        let hash = invalid.tx.double_sha256().unwrap();
+
+        // A fresh peer each time, zcashd disconnects peers sending some of these.
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_network_magic(node.magic())
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(node.addr()).await.unwrap();
+
+        for tx in invalid.setup {
+            match submit(&node, &mut synthetic_node, tx).await {
+                Outcome::Accepted => (),
+                Outcome::Rejected(reject) => {
+                    panic!("{}: the setup was rejected: {reject:?}", invalid.name)
+                }
+            }
+        }
+
+        let outcome = submit(&node, &mut synthetic_node, invalid.tx).await;
+        match (invalid.expected(node.kind()), outcome) {
+            (Some((ccode, reason)), Outcome::Rejected(reject)) => {
+                assert_eq!(reject.ccode, ccode, "{}: {reject:?}", invalid.name);
+                assert!(
+                    reject.reason.0.starts_with(reason),
+                    "{}: {reject:?}",
+                    invalid.name
+                );
+                assert_eq!(Hash::decode(&mut &reject.data[..]).unwrap(), hash);
+            }
+            (None, Outcome::Accepted) => (),
+            (expected, outcome) => {
+                panic!("{}: expected {expected:?}, got {outcome:?}", invalid.name)
+            }
+        }
+
+        assert!(
+            !node
+                .rpc()
+                .unwrap()
+                .get_raw_mempool()
+                .await
+                .unwrap()
+                .contains(&hash),
+            "{}: the transaction was added to the mempool",
+            invalid.name
+        );
+
+        synthetic_node.shut_down().await;
+    }
+
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+#[allow(non_snake_case)]
+async fn c025_t6_TX_announced_by_wtxid_after_WTXIDRELAY() {
+    // zcashd: pass
+    // zebra:  unsupported (no wallet)
+    //
+    // Neither node negotiates `wtxidrelay`, see `NodeKind::supports_wtxid_relay`, V5 transactions
+    // are announced by wtxid regardless (ZIP-239).
+
+    let (mut node, input) = start_funded_node().await;
+    let tx = create_transaction(&node, &input, input.amount - FEE).await;
+    let wtxid = tx.wtxid().unwrap();
+    let is_v5 = matches!(tx, Tx::V5(_));
+
+    let mut sender = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    sender.connect(node.addr()).await.unwrap();
+
+    let mut observer = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_wtxid_relay()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    observer.connect(node.addr()).await.unwrap();
+    observer
+        .unicast(node.addr(), Message::FilterLoad(BloomFilter::match_all()))
+        .unwrap();
+    assert_eq!(
+        observer.negotiated_wtxid_relay(node.addr()),
+        node.kind().supports_wtxid_relay()
+    );
+
+    match submit(&node, &mut sender, tx).await {
+        Outcome::Accepted => (),
+        Outcome::Rejected(reject) => panic!("the valid transaction was rejected: {reject:?}"),
+    }
+
+    // Either kind of announcement is picked up, so the wrong one fails the assertions below
+    // rather than timing out.
+    let announced = loop {
+        match observer.recv_message_timeout(LONG_TIMEOUT).await {
+            Ok((_, Message::Inv(inv))) => {
+                if let Some(item) = inv.inventory.into_iter().find(|item| match item {
+                    InvHash::Tx(txid) => *txid == wtxid.id,
+                    InvHash::MsgWtx(announced) => announced.id == wtxid.id,
+                    _ => false,
+                }) {
+                    break item;
+                }
+            }
+            Ok(_) => continue,
+            Err(e) => panic!("the transaction wasn't announced: {e}"),
+        }
+    };
+
+    if is_v5 {
+        assert_eq!(
+            announced,
+            InvHash::MsgWtx(wtxid),
+            "a V5 transaction wasn't announced by its wtxid"
+        );
+    } else {
+        assert_eq!(announced, InvHash::Tx(wtxid.id));
+    }
+
+    sender.shut_down().await;
+    observer.shut_down().await;
+    node.stop().unwrap();
+}
@@ -1,4 +1,6 @@
 mod conformance;
+mod differential;
 mod idle_node_in_the_background;
 mod performance;
 mod resistance;
+mod round_trip;
@@ -1,3 +1,5 @@
 mod connections;
 mod getdata_blocks;
 mod ping_pong;
+mod throughput;
+mod tx_propagation;
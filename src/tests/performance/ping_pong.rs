@@ -1,5 +1,9 @@
-use std::{net::SocketAddr, time::Duration};
+use std::{collections::VecDeque, net::SocketAddr, time::Duration};
 
+use tokio::{
+    sync::mpsc::{self, Sender},
+    time::{Instant, MissedTickBehavior},
+};
 use ziggurat_core_metrics::{
     latency_tables::{LatencyRequestStats, LatencyRequestsTable},
     recorder::TestMetrics,
@@ -9,11 +13,13 @@ use ziggurat_core_metrics::{
 use crate::{
     protocol::{message::Message, payload::Nonce},
     setup::node::{Action, Node},
-    tools::synthetic_node::SyntheticNode,
+    tools::{metrics::LatencyHistogram, synthetic_node::SyntheticNode, LONG_TIMEOUT},
 };
 
 const PINGS: u16 = 1000;
 const METRIC_LATENCY: &str = "ping_perf_latency";
+/// The maximum number of unanswered Pings while flooding.
+const MAX_IN_FLIGHT: usize = 100;
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
 #[allow(non_snake_case)]
@@ -236,3 +242,112 @@ async fn simulate_peer(node_addr: SocketAddr) {
         }
     }
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+#[allow(non_snake_case)]
+async fn p001_t3_PING_PONG_latency_under_flooding() {
+    // ZG-PERFORMANCE-001, Ping-Pong latency under flooding
+    //
+    // The node's Pong latency doesn't degrade while it is flooded with Pings.
+    //
+    //  1. Handshake a synthetic node with the node
+    //  2. Send Pings at `R` per second for `T` seconds, with at most `MAX_IN_FLIGHT` unanswered
+    //  3. Record each Pong's round-trip time
+    //  4. Expect the p99 latency to stay under the configured bound
+    //
+    // `R`, `T` and the bound are set in the `[performance]` table of `config.toml`, see the README.
+    //
+    // *NOTE* run with `cargo test --release tests::performance::ping_pong::p001_t3 -- --nocapture`
+
+    let mut node = Node::new().unwrap();
+    let config = node.performance_config().clone();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    let mut synth_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synth_node.connect(node_addr).await.unwrap();
+
+    // Pings are produced at the configured rate into a bounded channel, which is only drained
+    // while fewer than `MAX_IN_FLIGHT` Pings are unanswered. This blocks the producer whenever
+    // the node falls behind, instead of queueing up an unbounded number of Pings.
+    let (ping_tx, mut ping_rx) = mpsc::channel(MAX_IN_FLIGHT);
+    let producer = tokio::spawn(produce_pings(
+        ping_tx,
+        config.flood_ping_rate,
+        Duration::from_secs(config.flood_duration_secs),
+    ));
+
+    let mut in_flight = VecDeque::with_capacity(MAX_IN_FLIGHT);
+    let mut latencies = LatencyHistogram::new();
+    let mut producing = true;
+
+    while producing || !in_flight.is_empty() {
+        tokio::select! {
+            ping = ping_rx.recv(), if producing && in_flight.len() < MAX_IN_FLIGHT => match ping {
+                Some(nonce) => {
+                    synth_node.unicast(node_addr, Message::Ping(nonce)).unwrap();
+                    in_flight.push_back((nonce, Instant::now()));
+                }
+                None => producing = false,
+            },
+            reply = synth_node.recv_message_timeout(LONG_TIMEOUT) => match reply {
+                Ok((_, Message::Pong(nonce))) => {
+                    let (expected, sent_at) = in_flight.pop_front().expect("unsolicited Pong");
+                    assert_eq!(nonce, expected);
+                    latencies.record(sent_at.elapsed());
+                }
+                // Ignore any other (unfiltered) traffic.
+                Ok(_) => (),
+                Err(_timeout) => {
+                    panic!("the node stopped replying, {} Pings unanswered", in_flight.len())
+                }
+            },
+        }
+    }
+
+    let sent = producer.await.unwrap();
+
+    synth_node.shut_down().await;
+    node.stop().unwrap();
+
+    // Display results table
+    println!(
+        "\r\nSent {sent} of {} Pings\r\n{latencies}",
+        config.flood_ping_rate as u64 * config.flood_duration_secs
+    );
+
+    let max_p99 = Duration::from_millis(config.max_p99_pong_ms);
+    assert!(
+        latencies.p99() <= max_p99,
+        "p99 latency {:?} exceeds {max_p99:?}",
+        latencies.p99()
+    );
+}
+
+/// Produces Pings at `rate` per second for `duration`, waiting whenever the channel is full.
+/// Returns the number of Pings produced.
+async fn produce_pings(ping_tx: Sender<Nonce>, rate: u32, duration: Duration) -> u64 {
+    let mut ticker = tokio::time::interval(Duration::from_secs(1) / rate.max(1));
+    // Don't burst to catch up on the ticks missed while waiting on the node.
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let deadline = Instant::now() + duration;
+    let mut produced = 0;
+    while Instant::now() < deadline {
+        ticker.tick().await;
+        if ping_tx.send(Nonce::default()).await.is_err() {
+            break;
+        }
+        produced += 1;
+    }
+
+    produced
+}
@@ -0,0 +1,130 @@
+use std::{net::SocketAddr, time::Duration};
+
+use tabled::{Table, Tabled};
+use tokio::time::Instant;
+use ziggurat_core_metrics::tables::fmt_table;
+
+use crate::{
+    setup::{
+        node::{Action, Node},
+        PerformanceConfig,
+    },
+    tools::{metrics::LatencyHistogram, synthetic_node::SyntheticNode},
+};
+
+/// The outcome of a connection throughput run, checked against the configured SLA.
+#[derive(Debug, Tabled)]
+struct ThroughputStats {
+    #[tabled(rename = " connections ")]
+    connections: u16,
+    #[tabled(rename = " accepted ")]
+    accepted: u16,
+    #[tabled(rename = " accept \n time (ms) ")]
+    accept_time_ms: u128,
+    #[tabled(rename = " handshake \n p50 (ms) ")]
+    p50_ms: u128,
+    #[tabled(rename = " handshake \n p95 (ms) ")]
+    p95_ms: u128,
+    #[tabled(rename = " SLA \n met ")]
+    sla_met: bool,
+}
+
+impl ThroughputStats {
+    fn new(
+        config: &PerformanceConfig,
+        latencies: &LatencyHistogram,
+        accept_time: Duration,
+    ) -> Self {
+        let accepted = latencies.len() as u16;
+        let accept_time_ms = accept_time.as_millis();
+        let p50_ms = latencies.p50().as_millis();
+        let p95_ms = latencies.percentile(95.0).as_millis();
+
+        let sla_met = accepted == config.connections
+            && accept_time_ms <= config.max_accept_time_ms as u128
+            && p50_ms <= config.max_p50_handshake_ms as u128
+            && p95_ms <= config.max_p95_handshake_ms as u128;
+
+        Self {
+            connections: config.connections,
+            accepted,
+            accept_time_ms,
+            p50_ms,
+            p95_ms,
+            sla_met,
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn p003_connections_throughput() {
+    // ZG-PERFORMANCE-003
+    //
+    // The node accepts a burst of concurrent connections within the configured SLA.
+    //
+    //  1. Start a node with max_peers comfortably above `N`
+    //  2. Concurrently connect and handshake `N` synthetic nodes
+    //  3. Measure the time taken to accept all of them and the p50/p95 handshake latency
+    //  4. Check the results against the SLA
+    //
+    // `N` and the SLA are set in the `[performance]` table of `config.toml`, see the README.
+    //
+    // *NOTE* run with `cargo test --release tests::performance::throughput -- --nocapture`
+
+    let mut node = Node::new().unwrap();
+    let config = node.performance_config().clone();
+    node.initial_action(Action::WaitForConnection)
+        .max_peers(config.connections as usize * 2 + 10)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    let test_start = Instant::now();
+
+    let synth_handles = (0..config.connections)
+        .map(|_| tokio::spawn(timed_handshake(node_addr)))
+        .collect::<Vec<_>>();
+
+    // Keep the accepted connections open until all peers are done, so the node can't free up
+    // slots during the run.
+    let mut synth_nodes = Vec::with_capacity(synth_handles.len());
+    let mut latencies = LatencyHistogram::new();
+    for handle in synth_handles {
+        if let Some((synth_node, latency)) = handle.await.unwrap() {
+            synth_nodes.push(synth_node);
+            latencies.record(latency);
+        }
+    }
+
+    let accept_time = test_start.elapsed();
+
+    for synth_node in synth_nodes {
+        synth_node.shut_down().await;
+    }
+    node.stop().unwrap();
+
+    let stats = ThroughputStats::new(&config, &latencies, accept_time);
+
+    // Display results tables
+    println!("\r\n{}", fmt_table(Table::new([&stats])));
+    println!("Handshake latency\r\n{latencies}");
+
+    assert!(stats.sla_met, "SLA: {config:?}, Stats: {stats:?}");
+}
+
+/// Connects a new synthetic node to the node and returns it along with the handshake latency, or
+/// `None` if the connection failed.
+async fn timed_handshake(node_addr: SocketAddr) -> Option<(SyntheticNode, Duration)> {
+    let synth_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .ok()?;
+
+    let start = Instant::now();
+    synth_node.connect(node_addr).await.ok()?;
+
+    Some((synth_node, start.elapsed()))
+}
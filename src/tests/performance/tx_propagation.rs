@@ -0,0 +1,190 @@
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::{
+    protocol::{
+        message::Message,
+        payload::{codec::Codec, BloomFilter, Hash, InvHash, Tx},
+    },
+    setup::{
+        node::{Action, Node},
+        rpc::COINBASE_MATURITY,
+        Network,
+    },
+    tools::{metrics::LatencyHistogram, synthetic_node::SyntheticNode},
+};
+
+/// The fee left by each transaction, enough to be relayed under ZIP-317.
+const FEE: f64 = 0.0001;
+/// The delay between two transactions. zcashd announces transactions to inbound peers on a
+/// Poisson timer (5s on average), spacing them out samples the whole trickle interval instead of
+/// a single announcement.
+const TX_INTERVAL: Duration = Duration::from_millis(250);
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn p004_tx_propagation_latency() {
+    // ZG-PERFORMANCE-004
+    //
+    // The node announces the transactions it receives from a peer to its other peers within the
+    // configured SLA.
+    //
+    //  1. Start a regtest node whose wallet has `N` spendable outputs
+    //  2. Connect a sender, and an observer which asks for transactions to be relayed
+    //  3. Send a transaction spending each output through the sender, every `TX_INTERVAL`
+    //  4. Measure the time until the observer receives the `Inv` for each of them
+    //  5. Check the p95 latency against the SLA
+    //
+    // The latencies include the node's randomized trickle delay, so the SLA is expected to be
+    // in the order of seconds. `N` and the SLA are set in the `[performance]` table of
+    // `config.toml`, see the README.
+    //
+    // Note: requires the `[rpc]` table in `config.toml`, the transactions are created and
+    // signed by the node's wallet without being broadcast.
+    //
+    // zcashd: pass
+    // zebra:  unsupported (no wallet)
+    //
+    // *NOTE* run with `cargo test --release tests::performance::tx_propagation -- --nocapture`
+
+    let mut node = Node::new().unwrap();
+    let config = node.performance_config().clone();
+    node.network(Network::Regtest)
+        .initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    let txs = create_transactions(&node, config.relay_txs as usize).await;
+
+    let mut sender = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    sender.connect(node_addr).await.unwrap();
+
+    // Our version message doesn't ask for transactions to be relayed, loading a filter does.
+    let mut observer = SyntheticNode::builder()
+        .with_network_magic(node.magic())
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    observer.connect(node_addr).await.unwrap();
+    observer
+        .unicast(node_addr, Message::FilterLoad(BloomFilter::match_all()))
+        .unwrap();
+
+    let sending = tokio::spawn(async move {
+        let sent_at = send_transactions(&mut sender, node_addr, txs).await;
+        sender.shut_down().await;
+        sent_at
+    });
+
+    // Wait for the last transaction to get its chance at being announced as late as the SLA
+    // allows.
+    let max_p95 = Duration::from_millis(config.max_p95_tx_relay_ms);
+    let deadline = Instant::now() + TX_INTERVAL * config.relay_txs as u32 + max_p95 * 2;
+
+    let mut announced_at = HashMap::new();
+    while announced_at.len() < config.relay_txs as usize {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match observer.recv_message_timeout(remaining).await {
+            Ok((_, Message::Inv(inv))) => {
+                for item in inv.inventory {
+                    if let InvHash::Tx(hash) = item {
+                        announced_at.entry(hash).or_insert_with(Instant::now);
+                    }
+                }
+            }
+            // Ignore any other (unfiltered) traffic.
+            Ok(_) => (),
+            Err(_timeout) => break,
+        }
+    }
+
+    let sent_at = sending.await.unwrap();
+
+    observer.shut_down().await;
+    node.stop().unwrap();
+
+    let mut latencies = LatencyHistogram::new();
+    for (hash, sent_at) in &sent_at {
+        if let Some(announced_at) = announced_at.get(hash) {
+            latencies.record(announced_at.saturating_duration_since(*sent_at));
+        }
+    }
+
+    // Display results table
+    println!("\r\nTransaction propagation latency\r\n{latencies}");
+
+    assert_eq!(
+        latencies.len(),
+        sent_at.len() as u64,
+        "not every transaction was announced"
+    );
+    let p95 = latencies.percentile(95.0);
+    assert!(p95 <= max_p95, "p95 latency {p95:?} exceeds {max_p95:?}");
+}
+
+/// Mines enough blocks for the node's wallet to have `n` spendable coinbase outputs, and has it
+/// sign a transaction spending each of them, without them being broadcast.
+async fn create_transactions(node: &Node, n: usize) -> Vec<Tx> {
+    let rpc = node.rpc().unwrap();
+    rpc.generate(COINBASE_MATURITY + n as u32).await.unwrap();
+
+    let inputs = rpc
+        .list_unspent()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|output| output.spendable)
+        .take(n)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        inputs.len(),
+        n,
+        "the wallet doesn't have enough spendable outputs"
+    );
+
+    let mut txs = Vec::with_capacity(n);
+    for input in inputs {
+        let address = rpc.get_new_address().await.unwrap();
+        let unsigned = rpc
+            .create_raw_transaction(&input, &address, input.amount - FEE)
+            .await
+            .unwrap();
+        let signed = rpc.sign_raw_transaction(&unsigned).await.unwrap();
+        txs.push(Tx::decode(&mut &signed[..]).unwrap());
+    }
+
+    txs
+}
+
+/// Sends `txs` to the node every [`TX_INTERVAL`], returning when each one was sent.
+async fn send_transactions(
+    sender: &mut SyntheticNode,
+    node_addr: SocketAddr,
+    txs: Vec<Tx>,
+) -> Vec<(Hash, Instant)> {
+    let mut ticker = tokio::time::interval(TX_INTERVAL);
+    let mut sent_at = Vec::with_capacity(txs.len());
+
+    for tx in txs {
+        ticker.tick().await;
+
+        let hash = tx.double_sha256().unwrap();
+        sent_at.push((hash, Instant::now()));
+        sender.unicast(node_addr, Message::Tx(tx)).unwrap();
+
+        // The sender's inbound queue isn't read otherwise.
+        sender.discard_messages();
+    }
+
+    sent_at
+}
@@ -0,0 +1,134 @@
+//! Contains tests closing many connections in the middle of a message, either gracefully (FIN)
+//! or abruptly (RST), and checking the node reclaims their slots.
+
+use bytes::BytesMut;
+
+use crate::{
+    protocol::{
+        message::{constants::MAGIC, Message},
+        payload::Nonce,
+    },
+    setup::node::{Action, Node},
+    tools::{
+        synthetic_node::{RetryPolicy, SyntheticNode},
+        DISCONNECT_TIMEOUT, RECV_TIMEOUT,
+    },
+};
+
+/// The number of connections the node accepts, all of them are used in each round.
+const MAX_PEERS: usize = 20;
+
+/// The number of times the node's slots are filled and the connections killed.
+const ROUNDS: usize = 3;
+
+/// How the synthetic nodes close their connections.
+#[derive(Debug, Clone, Copy)]
+enum Close {
+    Clean,
+    Abrupt,
+}
+
+/// Fills the node's slots `ROUNDS` times over, killing each connection halfway through a
+/// message, then expects a full set of new peers to be served.
+///
+/// The connections are retried, the node may take a moment to notice the previous ones are gone.
+async fn fill_and_kill_connections(close: Close) {
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .max_peers(MAX_PEERS)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    let mut ping = BytesMut::new();
+    Message::Ping(Nonce::default())
+        .encode(MAGIC, &mut ping)
+        .unwrap();
+    let partial_ping = ping[..ping.len() / 2].to_vec();
+
+    let synth_builder = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply();
+
+    for round in 0..ROUNDS {
+        let mut synth_nodes = Vec::with_capacity(MAX_PEERS);
+        for _ in 0..MAX_PEERS {
+            let synth_node = synth_builder.build().await.unwrap();
+            if let Err(e) = synth_node
+                .connect_with_retry(node_addr, RetryPolicy::default())
+                .await
+            {
+                panic!("round {round}: the node didn't reclaim a slot: {e}");
+            }
+            synth_node
+                .send_direct_bytes(node_addr, partial_ping.clone())
+                .unwrap();
+            synth_nodes.push(synth_node);
+        }
+
+        for synth_node in synth_nodes {
+            match close {
+                // The node may keep waiting for the rest of the message, i.e. never close its
+                // side, which the next round would reveal.
+                Close::Clean => {
+                    let _ = synth_node
+                        .disconnect_clean(node_addr, DISCONNECT_TIMEOUT)
+                        .await;
+                }
+                Close::Abrupt => {
+                    synth_node.disconnect_abrupt(node_addr).await;
+                }
+            }
+            synth_node.shut_down().await;
+        }
+    }
+
+    // A full set of new peers is served.
+    let mut synth_nodes = Vec::with_capacity(MAX_PEERS);
+    for _ in 0..MAX_PEERS {
+        let mut synth_node = synth_builder.build().await.unwrap();
+        synth_node
+            .connect_with_retry(node_addr, RetryPolicy::default())
+            .await
+            .unwrap();
+        synth_nodes.push(synth_node);
+    }
+    for synth_node in &mut synth_nodes {
+        synth_node
+            .ping_pong_timeout(node_addr, RECV_TIMEOUT)
+            .await
+            .unwrap();
+    }
+
+    for synth_node in synth_nodes {
+        synth_node.shut_down().await;
+    }
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+async fn r014_t1_clean_disconnects_mid_message() {
+    // ZG-RESISTANCE-014
+    //
+    // The node reclaims the slots of peers which close their connection gracefully (FIN) in the
+    // middle of a message.
+    //
+    // zcashd: pass
+    // zebra:  pass
+
+    fill_and_kill_connections(Close::Clean).await;
+}
+
+#[tokio::test]
+async fn r014_t2_abrupt_disconnects_mid_message() {
+    // ZG-RESISTANCE-014
+    //
+    // The node reclaims the slots of peers which reset their connection (RST) in the middle of a
+    // message.
+    //
+    // zcashd: pass
+    // zebra:  pass
+
+    fill_and_kill_connections(Close::Abrupt).await;
+}
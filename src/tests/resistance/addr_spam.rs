@@ -0,0 +1,136 @@
+//! Contains tests flooding the node with peer addresses, many of them invalid or unroutable.
+//!
+//! zcashd scores an `Addr` with more than `MAX_ADDR_TO_SEND` (1000) entries as misbehaviour, and
+//! only relays the entries of messages with at most 10 of them, so large batches are never
+//! relayed. zebra doesn't relay gossiped addresses, it only shares its address book in response to
+//! `GetAddr`.
+//!
+//! Only `Addr` is sent, the message codec doesn't support `AddrV2` (BIP 155).
+
+use std::{collections::HashSet, net::SocketAddr, time::Duration};
+
+use tokio::time::{timeout, Instant};
+
+use crate::{
+    protocol::message::Message,
+    setup::node::{Action, Node},
+    tools::{
+        fuzzing::{addr_spam, is_bogus_addr, seeded_rng},
+        synthetic_node::{PingPongError, SyntheticNode},
+        RECV_TIMEOUT,
+    },
+};
+
+/// zcashd's `MAX_ADDR_TO_SEND`, the maximum number of entries in an `Addr`.
+const MAX_ADDR_TO_SEND: usize = 1000;
+
+/// The number of `Addr` messages sent.
+const BATCHES: usize = 10;
+
+/// How long the observer waits for addresses to be relayed, longer than zcashd's trickle delay.
+const RELAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[tokio::test]
+async fn r011_addr_spam() {
+    // ZG-RESISTANCE-011
+    //
+    // The node survives thousands of gossiped addresses, half of them bogus. It doesn't relay the
+    // bogus ones, relays at most a fraction of the rest, and doesn't connect to any of them. The
+    // spammer's connection stays alive, or is dropped cleanly.
+    //
+    // zcashd: pass
+    // zebra:  pass
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let mut spammer = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    spammer.connect(node.addr()).await.unwrap();
+
+    let mut observer = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    observer.connect(node.addr()).await.unwrap();
+
+    let mut rng = seeded_rng();
+    let batches = addr_spam(&mut rng, BATCHES, MAX_ADDR_TO_SEND);
+    let spammed = batches
+        .iter()
+        .flat_map(|batch| batch.iter().map(|addr| addr.addr))
+        .collect::<HashSet<SocketAddr>>();
+
+    for batch in batches {
+        // The node may drop us for spamming.
+        if spammer.unicast(node.addr(), Message::Addr(batch)).is_err() {
+            break;
+        }
+    }
+
+    // The spammer is either still connected, or was dropped.
+    match spammer.ping_pong_timeout(node.addr(), RECV_TIMEOUT).await {
+        Ok(()) | Err(PingPongError::ConnectionAborted) => {}
+        Err(e) => panic!("the spammer's connection is in an unexpected state: {e}"),
+    }
+
+    // Bogus entries aren't relayed, and the rest are rate limited.
+    let mut relayed = 0;
+    let deadline = Instant::now() + RELAY_TIMEOUT;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match timeout(remaining, observer.recv_message()).await {
+            Ok((_, Message::Addr(addr))) => {
+                for entry in addr.iter().filter(|entry| spammed.contains(&entry.addr)) {
+                    assert!(
+                        !is_bogus_addr(&entry.addr),
+                        "a bogus address was relayed: {}",
+                        entry.addr
+                    );
+                    relayed += 1;
+                }
+            }
+            Ok(_) => continue,
+            Err(_) => break,
+        }
+    }
+    assert!(
+        relayed <= MAX_ADDR_TO_SEND,
+        "{relayed} of the {} spammed addresses were relayed",
+        spammed.len()
+    );
+
+    // The node is still up.
+    observer
+        .ping_pong_timeout(node.addr(), RECV_TIMEOUT)
+        .await
+        .unwrap();
+
+    // The node didn't connect to any of the spammed addresses. zebra doesn't support
+    // `getpeerinfo`, and the check is skipped if RPC isn't configured.
+    if let Ok(rpc) = node.rpc() {
+        if rpc.supports("getpeerinfo") {
+            for peer in rpc.get_peer_info().await.unwrap() {
+                if let Ok(addr) = peer.addr.parse::<SocketAddr>() {
+                    assert!(
+                        peer.inbound || !spammed.contains(&addr),
+                        "the node connected to a spammed address: {addr}"
+                    );
+                }
+            }
+        }
+    }
+
+    spammer.shut_down().await;
+    observer.shut_down().await;
+    node.stop().unwrap();
+}
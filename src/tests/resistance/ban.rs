@@ -0,0 +1,82 @@
+//! Contains tests of the node banning peers which repeatedly violate the protocol.
+//!
+//! zcashd scores misbehaviour per peer, e.g. `20` for an `Inv` with more than `MAX_INV_SZ`
+//! entries, and bans the peer's address for `-bantime` (24 hours by default) once its score
+//! reaches `-banscore` (100 by default). Peers on a local address are only disconnected, never
+//! banned, and whitelisted peers are neither.
+//!
+//! zebra doesn't keep a misbehaviour score, it closes connections on protocol errors without
+//! banning the address, so the peer can reconnect straight away.
+
+use crate::{
+    protocol::{
+        message::Message,
+        payload::{Hash, Inv, InvHash},
+    },
+    setup::node::{Action, Node},
+    tools::{synthetic_node::SyntheticNode, DISCONNECT_TIMEOUT},
+};
+
+/// zcashd's `MAX_INV_SZ`, the maximum number of entries in an `Inv`.
+const MAX_INV_SZ: usize = 50_000;
+
+/// The number of violations sent, enough to reach zcashd's default `-banscore` of 100.
+const VIOLATIONS: usize = 5;
+
+#[tokio::test]
+async fn r010_misbehaving_peer_is_banned() {
+    // ZG-RESISTANCE-010
+    //
+    // The node refuses new connections from an address which sent it a sequence of protocol
+    // violations.
+    //
+    // zcashd: fail (the synthetic nodes connect over loopback, and local peers are never banned)
+    // zebra:  fail (no ban policy, the peer can reconnect)
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let mut offender = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    offender.connect(node.addr()).await.unwrap();
+
+    let oversized_inv = Message::Inv(Inv::new(vec![
+        InvHash::Block(Hash::new([0; 32]));
+        MAX_INV_SZ + 1
+    ]));
+    for _ in 0..VIOLATIONS {
+        // The node may drop the connection before all the violations are sent.
+        if offender
+            .unicast(node.addr(), oversized_inv.clone())
+            .is_err()
+        {
+            break;
+        }
+    }
+
+    // The last violation brings the score to the threshold, the node drops the offender.
+    offender
+        .wait_for_disconnect(node.addr(), DISCONNECT_TIMEOUT)
+        .await
+        .unwrap();
+    offender.shut_down().await;
+
+    // A fresh node from the same IP is refused.
+    let mut probe = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    assert!(probe.is_banned(node.addr()).await);
+
+    probe.shut_down().await;
+    node.stop().unwrap();
+}
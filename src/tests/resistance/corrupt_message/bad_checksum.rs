@@ -4,13 +4,15 @@ use rand::prelude::SliceRandom;
 use crate::{
     protocol::message::Message,
     setup::node::{Action, Node},
-    tests::resistance::{DISCONNECT_TIMEOUT, ITERATIONS},
+    tests::resistance::ITERATIONS,
     tools::{
         fuzzing::{
             default_fuzz_messages, encode_message_with_corrupt_checksum,
             encode_messages_with_corrupt_checksum, seeded_rng,
         },
+        reaction::{classify, ExpectedBehavior, Reaction},
         synthetic_node::SyntheticNode,
+        DISCONNECT_TIMEOUT,
     },
 };
 
@@ -221,6 +223,8 @@ async fn r005_t5_post_handshake() {
     //
     // zebra: disconnects.
     // zcashd: logs indicate message was ignored, doesn't disconnect.
+    const EXPECTED: ExpectedBehavior =
+        ExpectedBehavior::new(Reaction::Ignored, Reaction::Disconnected);
 
     let mut rng = seeded_rng();
     let mut node = Node::new().unwrap();
@@ -244,13 +248,8 @@ async fn r005_t5_post_handshake() {
         synth_node.connect(node.addr()).await.unwrap();
 
         // Write messages with wrong checksum.
-        synth_node.send_direct_bytes(node.addr(), payload).unwrap();
-
-        // Make sure node doesn't terminate the connection.
-        assert!(synth_node
-            .wait_for_disconnect(node.addr(), DISCONNECT_TIMEOUT)
-            .await
-            .is_err());
+        let reaction = classify(&mut synth_node, node.addr(), payload, DISCONNECT_TIMEOUT).await;
+        EXPECTED.assert_matches(node.kind(), reaction);
         synth_node.shut_down().await;
     }
 
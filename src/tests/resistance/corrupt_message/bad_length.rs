@@ -2,15 +2,19 @@ use assert_matches::assert_matches;
 use rand::prelude::SliceRandom;
 
 use crate::{
-    protocol::message::Message,
+    protocol::message::{
+        constants::{BLOCK_COMMAND, MAGIC, MAX_MESSAGE_LEN},
+        Message, MessageHeader,
+    },
     setup::node::{Action, Node},
-    tests::resistance::{DISCONNECT_TIMEOUT, ITERATIONS},
+    tests::resistance::ITERATIONS,
     tools::{
         fuzzing::{
             default_fuzz_messages, encode_message_with_corrupt_body_length,
             encode_messages_with_corrupt_body_length, seeded_rng,
         },
         synthetic_node::SyntheticNode,
+        DISCONNECT_TIMEOUT,
     },
 };
 
@@ -256,3 +260,42 @@ async fn r005_t6_post_handshake() {
 
     node.stop().unwrap();
 }
+
+#[tokio::test]
+async fn r005_t7_oversized_body_length_post_handshake() {
+    // ZG-RESISTANCE-005 (part 7)
+    //
+    // The node rejects a header announcing a body far beyond the max message length, without
+    // waiting for the body to arrive.
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let mut synth_node = SyntheticNode::builder()
+        .with_all_auto_reply()
+        .with_full_handshake()
+        .build()
+        .await
+        .unwrap();
+    synth_node.connect(node.addr()).await.unwrap();
+
+    // 500MB are announced, but only the header is sent.
+    let declared_len = 500 * 1024 * 1024;
+    assert!(declared_len as usize > MAX_MESSAGE_LEN);
+    let mut header = Vec::new();
+    MessageHeader::raw(MAGIC, BLOCK_COMMAND, declared_len, 0)
+        .encode_with_body(&[], &mut header)
+        .unwrap();
+    synth_node.send_direct_bytes(node.addr(), header).unwrap();
+
+    assert!(synth_node
+        .wait_for_disconnect(node.addr(), DISCONNECT_TIMEOUT)
+        .await
+        .is_ok());
+
+    synth_node.shut_down().await;
+    node.stop().unwrap();
+}
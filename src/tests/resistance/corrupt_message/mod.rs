@@ -7,10 +7,11 @@ use assert_matches::assert_matches;
 use crate::{
     protocol::message::Message,
     setup::node::{Action, Node},
-    tests::resistance::{DISCONNECT_TIMEOUT, ITERATIONS},
+    tests::resistance::ITERATIONS,
     tools::{
         fuzzing::{default_fuzz_messages, encode_slightly_corrupted_messages, seeded_rng},
         synthetic_node::SyntheticNode,
+        DISCONNECT_TIMEOUT,
     },
 };
 
@@ -3,10 +3,11 @@ use assert_matches::assert_matches;
 use crate::{
     protocol::message::Message,
     setup::node::{Action, Node},
-    tests::resistance::{DISCONNECT_TIMEOUT, ITERATIONS},
+    tests::resistance::ITERATIONS,
     tools::{
         fuzzing::{metadata_compliant_random_bytes, seeded_rng, COMMANDS_WITH_PAYLOADS},
         synthetic_node::SyntheticNode,
+        DISCONNECT_TIMEOUT,
     },
 };
 
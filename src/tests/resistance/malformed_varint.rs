@@ -0,0 +1,67 @@
+//! Contains tests sending messages whose vector length prefix (a `CompactSize`) claims far more
+//! entries than fit in a message, see [`malformed_varint_messages`].
+//!
+//! zcashd fails to read a `CompactSize` above `MAX_SIZE` (32 MiB) with "size too large" and
+//! doesn't pre-allocate more than 5 MB for a vector, zebra limits vector lengths by the number of
+//! entries the message body could hold.
+
+use crate::{
+    setup::node::{Action, Node},
+    tests::resistance::ITERATIONS,
+    tools::{
+        fuzzing::{malformed_varint_messages, seeded_rng},
+        reaction::{classify, Reaction},
+        synthetic_node::SyntheticNode,
+        DISCONNECT_TIMEOUT, RECV_TIMEOUT,
+    },
+};
+
+#[tokio::test]
+async fn r012_malformed_varint_allocation_bomb() {
+    // ZG-RESISTANCE-012
+    //
+    // The node survives messages claiming vectors of up to `u64::MAX` entries: it ignores,
+    // rejects or disconnects each of them, and keeps serving other peers.
+    //
+    // zcashd: pass
+    // zebra:  pass
+
+    let mut rng = seeded_rng();
+    let payloads = malformed_varint_messages(&mut rng, ITERATIONS);
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let synth_builder = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply();
+
+    for payload in payloads {
+        let mut synth_node = synth_builder.build().await.unwrap();
+        synth_node.connect(node.addr()).await.unwrap();
+
+        // A reply is fine, e.g. a `Reject`, as long as the node keeps answering.
+        let reaction = classify(&mut synth_node, node.addr(), payload, DISCONNECT_TIMEOUT).await;
+        assert_ne!(
+            reaction,
+            Reaction::Unresponsive,
+            "the node stopped responding after a malformed length"
+        );
+
+        synth_node.shut_down().await;
+    }
+
+    // The node is still up for new peers.
+    let mut synth_node = synth_builder.build().await.unwrap();
+    synth_node.connect(node.addr()).await.unwrap();
+    synth_node
+        .ping_pong_timeout(node.addr(), RECV_TIMEOUT)
+        .await
+        .unwrap();
+
+    synth_node.shut_down().await;
+    node.stop().unwrap();
+}
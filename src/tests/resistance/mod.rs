@@ -1,9 +1,16 @@
+mod abrupt_disconnect;
+mod addr_spam;
+mod ban;
 mod corrupt_message;
+mod malformed_varint;
+mod orphan_tx;
+mod oversized_message;
 mod random_bytes;
+mod self_connection;
+mod slow_loris;
+mod stalled_handshake;
 mod stress_test;
+mod truncated_message;
 mod zeroes;
 
-use std::time::Duration;
-
 const ITERATIONS: usize = 50;
-const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
@@ -0,0 +1,106 @@
+//! Contains tests with transactions spending outputs unknown to the node.
+//!
+//! zcashd keeps such orphans in a bounded orphan pool, and may request the missing parent from the
+//! peer which sent the orphan. Orphans aren't in the mempool, so they are never relayed.
+//!
+//! zebra has no orphan pool, it drops transactions whose inputs it can't find. Its mempool is also
+//! disabled until it's close to the network's tip, in which case it ignores the transaction
+//! entirely.
+
+use std::time::Duration;
+
+use tokio::time::{timeout, Instant};
+
+use crate::{
+    protocol::{
+        message::Message,
+        payload::{BloomFilter, Hash, InvHash, Tx},
+    },
+    setup::node::{Action, Node},
+    tools::{synthetic_node::SyntheticNode, RECV_TIMEOUT},
+};
+
+/// How long the observer waits for the orphan to be relayed, longer than zcashd's trickle delay.
+const RELAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[tokio::test]
+async fn r009_orphan_transaction_is_not_relayed() {
+    // ZG-RESISTANCE-009
+    //
+    // The node doesn't accept or relay a transaction spending an unknown output, it may request
+    // the missing parent with `GetData` instead.
+    //
+    // zcashd: pass
+    // zebra:  pass
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+
+    let mut sender = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    sender.connect(node.addr()).await.unwrap();
+
+    // Our version message doesn't ask for transactions to be relayed, loading a filter does.
+    let mut observer = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    observer.connect(node.addr()).await.unwrap();
+    observer
+        .unicast(node.addr(), Message::FilterLoad(BloomFilter::match_all()))
+        .unwrap();
+
+    let parent = Hash::new(rand::random());
+    let orphan = Tx::spending(parent, 0, 1000);
+    let orphan_hash = orphan.inv_hash();
+    sender.unicast(node.addr(), Message::Tx(orphan)).unwrap();
+
+    // The orphan must not be announced to the other peer.
+    let deadline = Instant::now() + RELAY_TIMEOUT;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match timeout(remaining, observer.recv_message()).await {
+            Ok((_, Message::Inv(inv))) => assert!(
+                !inv.inventory.contains(&orphan_hash),
+                "the orphan transaction was relayed"
+            ),
+            Ok(_) => continue,
+            Err(_) => break,
+        }
+    }
+
+    // Requesting the parent is optional, but it's the only other message the orphan may cause.
+    loop {
+        match sender.recv_message_timeout(RECV_TIMEOUT).await {
+            Ok((_, Message::GetData(inv))) => assert!(
+                inv.inventory.contains(&InvHash::Tx(parent)),
+                "the node requested unrelated data: {inv:?}"
+            ),
+            Ok((_, Message::Inv(inv))) => assert!(
+                !inv.inventory.contains(&orphan_hash),
+                "the orphan transaction was announced back to its sender"
+            ),
+            Ok(_) => continue,
+            Err(_) => break,
+        }
+    }
+
+    // The connection is still usable.
+    sender
+        .ping_pong_timeout(node.addr(), RECV_TIMEOUT)
+        .await
+        .unwrap();
+
+    sender.shut_down().await;
+    observer.shut_down().await;
+    node.stop().unwrap();
+}
@@ -0,0 +1,137 @@
+//! Contains tests sending messages with a valid header whose body is too large for their command,
+//! see [`oversized_valid_command_messages`].
+//!
+//! Besides the global [`MAX_MESSAGE_LEN`] cap, which can be checked from the header, some commands
+//! have limits of their own (see the `constants`), checked while the body is parsed. Only a block
+//! may legitimately come close to the global cap, up to [`MAX_BLOCK_SIZE`].
+//!
+//! [`MAX_MESSAGE_LEN`]: crate::protocol::message::constants::MAX_MESSAGE_LEN
+//! [`MAX_BLOCK_SIZE`]: crate::protocol::message::constants::MAX_BLOCK_SIZE
+
+use std::net::SocketAddr;
+
+use tokio::time::Instant;
+
+use crate::{
+    protocol::message::{
+        constants::{HEADER_LEN, VERSION_COMMAND},
+        Message, MessageHeader,
+    },
+    setup::node::{Action, Node},
+    tests::resistance::ITERATIONS,
+    tools::{
+        fuzzing::{max_size_block, oversized_valid_command_messages, seeded_rng},
+        reaction::{observe_replies, Reaction},
+        synthetic_node::SyntheticNode,
+        DISCONNECT_TIMEOUT, RECV_TIMEOUT,
+    },
+};
+
+/// Waits for the node to reject the last message sent, either with a `Reject` or by
+/// disconnecting.
+///
+/// The connection is polled rather than probed with a `Ping`, which the node ignores before the
+/// handshake.
+async fn is_rejected(synthetic_node: &mut SyntheticNode, node_addr: SocketAddr) -> bool {
+    let deadline = Instant::now() + DISCONNECT_TIMEOUT;
+
+    while synthetic_node.is_connected(node_addr) {
+        match synthetic_node.recv_message_timeout(RECV_TIMEOUT).await {
+            Ok((_, Message::Reject(_))) => return true,
+            Ok(_) => (),
+            Err(_) if Instant::now() >= deadline => return false,
+            Err(_) => (),
+        }
+    }
+
+    true
+}
+
+#[tokio::test]
+async fn r016_t1_oversized_messages_are_rejected() {
+    // ZG-RESISTANCE-016
+    //
+    // The node rejects messages over their command's size limit, or over the global one.
+    // `Version`s are sent in place of the peer's, the others after the handshake.
+    //
+    // zcashd: fail (oversized `Inv`, `GetData`, `NotFound` and `Addr` only raise the misbehaviour
+    //         score, see ZG-RESISTANCE-010)
+    // zebra:  pass
+
+    let mut rng = seeded_rng();
+    let payloads = oversized_valid_command_messages(&mut rng, ITERATIONS);
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    for payload in payloads {
+        let header = MessageHeader::decode(&mut &payload[..HEADER_LEN]).unwrap();
+
+        let builder = SyntheticNode::builder().with_all_auto_reply();
+        let builder = match header.command {
+            VERSION_COMMAND => builder,
+            _ => builder.with_full_handshake(),
+        };
+        let mut synth_node = builder.build().await.unwrap();
+        synth_node.connect(node_addr).await.unwrap();
+
+        synth_node.send_direct_bytes(node_addr, payload).unwrap();
+        assert!(
+            is_rejected(&mut synth_node, node_addr).await,
+            "a {} of {} bytes was accepted",
+            String::from_utf8_lossy(&header.command).trim_end_matches('\0'),
+            header.body_length
+        );
+
+        synth_node.shut_down().await;
+    }
+
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+async fn r016_t2_max_size_block_is_read() {
+    // ZG-RESISTANCE-016
+    //
+    // The node reads a block of up to `MAX_BLOCK_SIZE`, well over the other commands' limits: the
+    // block is invalid, but may only be rejected for its content, not its size.
+    //
+    // zcashd: pass (rejects the block with a `bad-txnmrklroot` reason)
+    // zebra:  pass
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    let mut synth_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synth_node.connect(node_addr).await.unwrap();
+
+    let block = Message::Block(Box::new(max_size_block()));
+    synth_node.unicast(node_addr, block).unwrap();
+    let observation = observe_replies(&mut synth_node, node_addr, DISCONNECT_TIMEOUT).await;
+
+    // A `Reject` means the block was parsed, anything but a size rejection is fine.
+    let rejected_for_content = observation.replies.iter().any(|(_, reply)| {
+        matches!(reply, Message::Reject(reject) if !reject.reason.0.contains("length"))
+    });
+    assert!(
+        matches!(observation.reaction, Reaction::Ignored | Reaction::Replied)
+            || rejected_for_content,
+        "the block was refused: {observation:?}"
+    );
+
+    synth_node.shut_down().await;
+    node.stop().unwrap();
+}
@@ -3,10 +3,11 @@ use assert_matches::assert_matches;
 use crate::{
     protocol::message::Message,
     setup::node::{Action, Node},
-    tests::resistance::{DISCONNECT_TIMEOUT, ITERATIONS},
+    tests::resistance::ITERATIONS,
     tools::{
         fuzzing::{random_bytes, seeded_rng},
         synthetic_node::SyntheticNode,
+        DISCONNECT_TIMEOUT,
     },
 };
 
@@ -221,7 +222,7 @@ async fn r005_t2_post_handshake() {
             .unwrap();
         synth_node.connect(node.addr()).await.unwrap();
 
-        // Write random bytes in place of Verack.
+        // Write random bytes on the handshaken connection.
         synth_node.send_direct_bytes(node.addr(), payload).unwrap();
         assert!(synth_node
             .wait_for_disconnect(node.addr(), DISCONNECT_TIMEOUT)
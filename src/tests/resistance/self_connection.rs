@@ -0,0 +1,94 @@
+//! Contains tests of the node's self-connection guard: a `Version` carrying the nonce the node
+//! sent on the same connection means the node connected to itself.
+//!
+//! zcashd checks the nonce of every `Version` it receives against the ones it sent on its
+//! outbound connections and drops the connection on a match, zebra does the same.
+
+use std::{net::SocketAddr, time::Duration};
+
+use assert_matches::assert_matches;
+use tokio::time::{sleep, Instant};
+
+use crate::{
+    protocol::{
+        message::Message,
+        payload::{Nonce, Version},
+    },
+    setup::node::Node,
+    tools::{
+        reaction::{ExpectedBehavior, Reaction},
+        synthetic_node::SyntheticNode,
+        DISCONNECT_TIMEOUT, LONG_TIMEOUT,
+    },
+};
+
+#[tokio::test]
+async fn r013_reflected_nonce_during_handshake() {
+    // ZG-RESISTANCE-013
+    //
+    // The node drops a connection whose peer answers its `Version` with one carrying the same
+    // nonce, i.e. a connection to itself.
+    //
+    // zcashd: pass (shuts down the write half of the stream)
+    // zebra:  pass
+    const EXPECTED: ExpectedBehavior = ExpectedBehavior::both(Reaction::Disconnected);
+
+    // No handshake, the node's `Version` is read by the test.
+    let mut synthetic_node = SyntheticNode::builder().build().await.unwrap();
+
+    let mut node = Node::new().unwrap();
+    node.initial_peers(vec![synthetic_node.listening_addr()])
+        .start()
+        .await
+        .unwrap();
+
+    let (source, nonce) = recv_version_nonce(&mut synthetic_node).await;
+
+    let reflected = Version::new(node.addr(), synthetic_node.listening_addr()).with_nonce(nonce);
+    synthetic_node
+        .unicast(source, Message::Version(reflected))
+        .unwrap();
+
+    let reaction = observe_handshake(&mut synthetic_node, DISCONNECT_TIMEOUT).await;
+    EXPECTED.assert_matches(node.kind(), reaction);
+
+    synthetic_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+/// Waits for the node to connect and send its `Version`, returning the connection's address and
+/// the nonce the node picked for it.
+async fn recv_version_nonce(synthetic_node: &mut SyntheticNode) -> (SocketAddr, Nonce) {
+    let (source, version) = synthetic_node
+        .recv_message_timeout(LONG_TIMEOUT)
+        .await
+        .unwrap();
+    let nonce = assert_matches!(version, Message::Version(version) => version.nonce);
+
+    (source, nonce)
+}
+
+/// Classifies the node's reaction to the last message of a pending handshake.
+///
+/// The node doesn't answer a `Ping` before the handshake completes, so unlike
+/// [`observe`](crate::tools::reaction::observe) the connection isn't probed: any message the node
+/// sends within `timeout` counts as a reply, e.g. a `Verack`, and a still open connection with
+/// none as the message being ignored.
+async fn observe_handshake(synthetic_node: &mut SyntheticNode, timeout: Duration) -> Reaction {
+    let deadline = Instant::now() + timeout;
+    let mut replied = false;
+
+    while Instant::now() < deadline {
+        if synthetic_node.num_connected() == 0 {
+            return Reaction::Disconnected;
+        }
+        replied |= synthetic_node.discard_messages() != 0;
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    if replied {
+        Reaction::Replied
+    } else {
+        Reaction::Ignored
+    }
+}
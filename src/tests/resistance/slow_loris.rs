@@ -0,0 +1,85 @@
+//! Contains tests where messages are written to the node one byte at a time.
+
+use std::time::Duration;
+
+use assert_matches::assert_matches;
+use bytes::BytesMut;
+use tokio::time::{sleep, Instant};
+
+use crate::{
+    protocol::{
+        message::{constants::MAGIC, Message},
+        payload::Version,
+    },
+    setup::node::{Action, Node},
+    tools::synthetic_node::SyntheticNode,
+};
+
+#[tokio::test]
+async fn r007_t1_slow_loris_version_when_node_receives_connection() {
+    // ZG-RESISTANCE-007
+    //
+    // The node tolerates a peer writing its Version one byte at a time: it either completes the
+    // handshake or disconnects the peer within the timeout, but doesn't hang on the partial read.
+    //
+    // The per-byte delay and the timeout are set in the `[resistance]` table of `config.toml`, see
+    // the README.
+
+    let mut node = Node::new().unwrap();
+    let config = node.resistance_config().clone();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    // No handshake, the Version is written manually.
+    let mut synth_node = SyntheticNode::builder()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synth_node.connect(node_addr).await.unwrap();
+
+    let mut version = BytesMut::new();
+    Message::Version(Version::new(node_addr, synth_node.listening_addr()))
+        .encode(MAGIC, &mut version)
+        .unwrap();
+
+    let timeout = Duration::from_secs(config.slow_loris_timeout_secs);
+    let byte_delay = Duration::from_millis(config.slow_loris_byte_delay_ms);
+    let start = Instant::now();
+
+    for byte in version {
+        if !synth_node.is_connected(node_addr)
+            || synth_node.send_direct_bytes(node_addr, vec![byte]).is_err()
+        {
+            break;
+        }
+
+        sleep(byte_delay).await;
+    }
+
+    if synth_node.is_connected(node_addr) {
+        // The whole Version was delivered, the node should complete the handshake.
+        let (_, reply) = synth_node
+            .recv_message_timeout(timeout.saturating_sub(start.elapsed()))
+            .await
+            .expect("the node neither replied nor disconnected the peer");
+        assert_matches!(reply, Message::Version(..));
+
+        synth_node.unicast(node_addr, Message::Verack).unwrap();
+        let (_, reply) = synth_node.next_message().await.unwrap();
+        assert_matches!(reply, Message::Verack);
+    } else {
+        // The node gave up on the peer, which is fine as long as it did so within the timeout.
+        assert!(
+            start.elapsed() <= timeout,
+            "the peer was disconnected after {:?}",
+            start.elapsed()
+        );
+    }
+
+    synth_node.shut_down().await;
+    node.stop().unwrap();
+}
@@ -0,0 +1,131 @@
+//! Contains tests where peers never complete the handshake.
+
+use std::{net::SocketAddr, time::Duration};
+
+use tokio::time::{sleep, Instant};
+
+use crate::{
+    setup::node::{Action, Node},
+    tools::synthetic_node::SyntheticNode,
+};
+
+/// The number of half-open connections opened at once.
+const STALLED_CONNECTIONS: usize = 50;
+
+/// The interval at which the connection state is polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Waits for the node at `addr` to drop the connection, returning how long it took or `None` if it
+/// stays connected for longer than `timeout`.
+///
+/// The state is polled rather than probed with a `Ping`, which the node may reject before the
+/// handshake is done.
+async fn wait_for_shedding(
+    synthetic_node: &SyntheticNode,
+    addr: SocketAddr,
+    timeout: Duration,
+) -> Option<Duration> {
+    let start = Instant::now();
+
+    while synthetic_node.is_connected(addr) {
+        if start.elapsed() > timeout {
+            return None;
+        }
+
+        sleep(POLL_INTERVAL).await;
+    }
+
+    Some(start.elapsed())
+}
+
+#[tokio::test]
+async fn r008_t1_stalled_handshake_is_shed() {
+    // ZG-RESISTANCE-008
+    //
+    // The node disconnects a peer which sends its Version but never a Verack.
+    //
+    // The timeout is set in the `[resistance]` table of `config.toml`, see the README.
+
+    let mut node = Node::new().unwrap();
+    let config = node.resistance_config().clone();
+    node.initial_action(Action::WaitForConnection)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    let synth_node = SyntheticNode::builder()
+        .with_partial_handshake()
+        .build()
+        .await
+        .unwrap();
+    synth_node.connect(node_addr).await.unwrap();
+
+    let timeout = Duration::from_secs(config.handshake_timeout_secs);
+    let elapsed = wait_for_shedding(&synth_node, node_addr, timeout).await;
+    assert!(
+        elapsed.is_some(),
+        "the half-open connection was kept for longer than {timeout:?}"
+    );
+
+    synth_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+async fn r008_t2_many_stalled_handshakes_are_shed() {
+    // ZG-RESISTANCE-008
+    //
+    // The node sheds many half-open connections at once, and still accepts new peers afterwards.
+
+    let mut node = Node::new().unwrap();
+    let config = node.resistance_config().clone();
+    node.initial_action(Action::WaitForConnection)
+        .max_peers(STALLED_CONNECTIONS * 2)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    let (synth_nodes, _) = SyntheticNode::builder()
+        .with_partial_handshake()
+        .build_n(STALLED_CONNECTIONS)
+        .await
+        .unwrap();
+
+    // Connections the node refuses outright are already shed.
+    for synth_node in &synth_nodes {
+        let _ = synth_node.connect(node_addr).await;
+    }
+
+    // The connections were all opened at about the same time, so share a single deadline.
+    let deadline = Instant::now() + Duration::from_secs(config.handshake_timeout_secs);
+    let mut kept = 0;
+    for synth_node in &synth_nodes {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if wait_for_shedding(synth_node, node_addr, timeout)
+            .await
+            .is_none()
+        {
+            kept += 1;
+        }
+    }
+    assert_eq!(
+        kept, 0,
+        "{kept} of {STALLED_CONNECTIONS} half-open connections were kept"
+    );
+
+    // The stalled peers mustn't exhaust the node's connection slots.
+    let synth_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .build()
+        .await
+        .unwrap();
+    synth_node.connect(node_addr).await.unwrap();
+
+    for synth_node in synth_nodes {
+        synth_node.shut_down().await;
+    }
+    synth_node.shut_down().await;
+    node.stop().unwrap();
+}
@@ -419,17 +419,16 @@ fn generate_corrupt_messages(rng: &mut ChaCha8Rng, n: usize) -> Vec<Vec<u8>> {
 }
 
 fn is_valid_message_bytes(bytes: &mut std::io::Cursor<&[u8]>) -> bool {
-    let header = match MessageHeader::decode(bytes) {
+    let header = match MessageHeader::decode_with_magic(bytes, MAGIC) {
         Ok(header) => header,
         Err(_) => return false,
     };
 
-    // check magic
-    if header.magic != MAGIC {
-        return false;
-    }
-
-    Message::decode(header.command, bytes).is_ok()
+    // Unknown commands decode fine, but aren't valid messages.
+    matches!(
+        Message::decode(header.command, bytes),
+        Ok(message) if !matches!(message, Message::Unknown { .. })
+    )
 }
 
 async fn simulate_peer(
@@ -0,0 +1,89 @@
+//! Contains tests where valid messages are cut short at a random offset within their body, see
+//! [`truncated_messages`].
+//!
+//! The header announces the full body, so the node can only wait for the rest of it: the
+//! connection is stalled mid-read until the node times the peer out.
+
+use std::time::Duration;
+
+use tokio::time::{sleep, Instant};
+
+use crate::{
+    setup::node::{Action, Node},
+    tools::{
+        fuzzing::{default_fuzz_messages, seeded_rng, truncated_messages},
+        synthetic_node::SyntheticNode,
+        RECV_TIMEOUT,
+    },
+};
+
+/// The number of connections stalled at once.
+const STALLED_CONNECTIONS: usize = 20;
+
+/// The interval at which the connection state is polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[tokio::test]
+async fn r015_truncated_messages_post_handshake() {
+    // ZG-RESISTANCE-015
+    //
+    // The node keeps serving other peers while connections are stalled in the middle of a
+    // message, and eventually disconnects the stalled peers.
+    //
+    // The timeout is set in the `[resistance]` table of `config.toml`, see the README.
+    //
+    // zcashd: fail (peers are only dropped after 20 minutes of inactivity)
+    // zebra:  pass (the heartbeat `Ping` goes unanswered)
+
+    let mut rng = seeded_rng();
+    let payloads = truncated_messages(&mut rng, STALLED_CONNECTIONS, &default_fuzz_messages());
+
+    let mut node = Node::new().unwrap();
+    let config = node.resistance_config().clone();
+    node.initial_action(Action::WaitForConnection)
+        .max_peers(STALLED_CONNECTIONS * 2)
+        .start()
+        .await
+        .unwrap();
+    let node_addr = node.addr();
+
+    let synth_builder = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply();
+
+    let mut stalled = Vec::with_capacity(STALLED_CONNECTIONS);
+    for payload in payloads {
+        let synth_node = synth_builder.build().await.unwrap();
+        synth_node.connect(node_addr).await.unwrap();
+        synth_node.send_direct_bytes(node_addr, payload).unwrap();
+        stalled.push(synth_node);
+    }
+    let start = Instant::now();
+
+    // The partial reads don't hold up the node.
+    let mut synth_node = synth_builder.build().await.unwrap();
+    synth_node.connect(node_addr).await.unwrap();
+    synth_node
+        .ping_pong_timeout(node_addr, RECV_TIMEOUT)
+        .await
+        .unwrap();
+    synth_node.shut_down().await;
+
+    // Polled rather than probed with a `Ping`, which would be read as part of the body.
+    let timeout = Duration::from_secs(config.partial_message_timeout_secs);
+    while stalled
+        .iter()
+        .any(|synth_node| synth_node.is_connected(node_addr))
+    {
+        assert!(
+            start.elapsed() <= timeout,
+            "stalled peers were kept for longer than {timeout:?}"
+        );
+        sleep(POLL_INTERVAL).await;
+    }
+
+    for synth_node in stalled {
+        synth_node.shut_down().await;
+    }
+    node.stop().unwrap();
+}
@@ -5,10 +5,11 @@ use assert_matches::assert_matches;
 use crate::{
     protocol::message::Message,
     setup::node::{Action, Node},
-    tests::resistance::{DISCONNECT_TIMEOUT, ITERATIONS},
+    tests::resistance::ITERATIONS,
     tools::{
         fuzzing::{seeded_rng, zeroes},
         synthetic_node::SyntheticNode,
+        DISCONNECT_TIMEOUT,
     },
 };
 
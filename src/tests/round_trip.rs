@@ -0,0 +1,288 @@
+//! Property-based tests checking every payload decodes back into the value it was encoded from.
+//!
+//! The strategies only generate well-formed values, e.g. addresses surviving the IPv4-mapped
+//! encoding and header solutions of the expected size, as the encoding is otherwise lossy.
+
+use std::{
+    io::Cursor,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use proptest::{collection::vec, prelude::*};
+use time::OffsetDateTime;
+
+use crate::protocol::{
+    message::constants::{MAGIC_TESTNET, MAX_MESSAGE_LEN},
+    payload::{
+        addr::NetworkAddr,
+        block::{equihash_solution_size, Block, Header, Headers, LocatorHashes},
+        codec::Codec,
+        filter::{MAX_BLOOM_FILTER_SIZE, MAX_FILTER_ADD_SIZE, MAX_HASH_FUNCS},
+        inv::{InvHash, WtxId},
+        reject::CCode,
+        tx::strategies::{bytes, hash, tx},
+        version::RELAY_VERSION,
+        Addr, BloomFilter, FeeFilter, FilterAdd, Inv, Nonce, ProtocolVersion, Reject, VarInt,
+        VarStr, Version,
+    },
+};
+
+/// Encodes `value` and checks decoding the bytes yields the same value, consuming all of them.
+fn assert_round_trip<T: Codec + PartialEq + std::fmt::Debug>(
+    value: T,
+) -> Result<(), TestCaseError> {
+    let mut bytes = Vec::new();
+    value.encode(&mut bytes).unwrap();
+
+    let mut cursor = Cursor::new(&bytes[..]);
+    let decoded = T::decode(&mut cursor).unwrap();
+
+    prop_assert_eq!(cursor.position() as usize, bytes.len());
+    prop_assert_eq!(value, decoded);
+
+    Ok(())
+}
+
+fn nonce() -> impl Strategy<Value = Nonce> {
+    any::<u64>().prop_map(Nonce::new)
+}
+
+fn var_str() -> impl Strategy<Value = VarStr> {
+    any::<String>().prop_map(VarStr)
+}
+
+/// Only whole seconds are encoded, so the timestamps have no sub-second part.
+fn timestamp() -> impl Strategy<Value = OffsetDateTime> {
+    (0..=i64::from(u32::MAX)).prop_map(|secs| OffsetDateTime::from_unix_timestamp(secs).unwrap())
+}
+
+/// IPv6 addresses the decoder would read as IPv4 are excluded, IPv4 ones are encoded as mapped.
+fn socket_addr() -> impl Strategy<Value = SocketAddr> {
+    let ip = prop_oneof![
+        any::<u32>().prop_map(|ip| IpAddr::V4(Ipv4Addr::from(ip))),
+        any::<u128>()
+            .prop_map(Ipv6Addr::from)
+            .prop_filter("decodes as IPv4", |ip| ip.to_ipv4_mapped().is_none())
+            .prop_map(IpAddr::V6),
+    ];
+
+    (ip, any::<u16>()).prop_map(|(ip, port)| SocketAddr::new(ip, port))
+}
+
+/// Generates a network address as found in `Addr`, `last_seen` is only omitted in `Version`.
+fn network_addr(last_seen: bool) -> impl Strategy<Value = NetworkAddr> {
+    (timestamp(), any::<u64>(), socket_addr()).prop_map(move |(timestamp, services, addr)| {
+        NetworkAddr {
+            last_seen: last_seen.then_some(timestamp),
+            services,
+            addr,
+        }
+    })
+}
+
+fn version() -> impl Strategy<Value = Version> {
+    (
+        any::<u32>(),
+        any::<u64>(),
+        timestamp(),
+        network_addr(false),
+        network_addr(false),
+        nonce(),
+        var_str(),
+        any::<i32>(),
+        any::<bool>(),
+    )
+        .prop_map(
+            |(
+                version,
+                services,
+                timestamp,
+                addr_recv,
+                addr_from,
+                nonce,
+                user_agent,
+                start_height,
+                relay,
+            )| Version {
+                version: ProtocolVersion(version),
+                services,
+                timestamp,
+                addr_recv,
+                addr_from,
+                nonce,
+                user_agent,
+                start_height,
+                // The field isn't encoded for older versions, which always relay.
+                relay: relay || version < RELAY_VERSION,
+            },
+        )
+}
+
+fn inv_hash() -> impl Strategy<Value = InvHash> {
+    prop_oneof![
+        Just(InvHash::Error),
+        hash().prop_map(InvHash::Tx),
+        hash().prop_map(InvHash::Block),
+        hash().prop_map(InvHash::FilteredBlock),
+        (hash(), hash()).prop_map(|(id, auth_digest)| InvHash::MsgWtx(WtxId { id, auth_digest })),
+    ]
+}
+
+/// The solution must have the size of a known network's parameters, it's otherwise rejected.
+fn header() -> impl Strategy<Value = Header> {
+    let solution_size = equihash_solution_size(MAGIC_TESTNET);
+
+    (
+        any::<u32>(),
+        hash(),
+        hash(),
+        hash(),
+        any::<u32>(),
+        any::<u32>(),
+        bytes::<32>(),
+        vec(any::<u8>(), solution_size),
+    )
+        .prop_map(
+            |(
+                version,
+                prev_block,
+                merkle_root,
+                light_client_root,
+                timestamp,
+                bits,
+                nonce,
+                solution,
+            )| {
+                Header {
+                    version: ProtocolVersion(version),
+                    prev_block,
+                    merkle_root,
+                    light_client_root,
+                    timestamp,
+                    bits,
+                    nonce,
+                    solution_size: VarInt::new(solution.len()),
+                    solution: solution.into(),
+                }
+            },
+        )
+}
+
+fn ccode() -> impl Strategy<Value = CCode> {
+    prop_oneof![
+        Just(CCode::Malformed),
+        Just(CCode::Invalid),
+        Just(CCode::Obsolete),
+        Just(CCode::Duplicate),
+        Just(CCode::NonStandard),
+        Just(CCode::Dust),
+        Just(CCode::InsufficientFee),
+        Just(CCode::Checkpoint),
+        Just(CCode::Other),
+    ]
+}
+
+proptest! {
+    #[test]
+    #[ignore]
+    fn nonce_round_trip(nonce in nonce()) {
+        assert_round_trip(nonce)?;
+    }
+
+    #[test]
+    #[ignore]
+    fn hash_round_trip(hash in hash()) {
+        assert_round_trip(hash)?;
+    }
+
+    #[test]
+    #[ignore]
+    fn var_int_round_trip(value in 0..=MAX_MESSAGE_LEN) {
+        assert_round_trip(VarInt::new(value))?;
+    }
+
+    #[test]
+    #[ignore]
+    fn version_round_trip(version in version()) {
+        assert_round_trip(version)?;
+    }
+
+    #[test]
+    #[ignore]
+    fn addr_round_trip(addrs in vec(network_addr(true), 0..16)) {
+        assert_round_trip(Addr::new(addrs))?;
+    }
+
+    #[test]
+    #[ignore]
+    fn inv_round_trip(inventory in vec(inv_hash(), 0..16)) {
+        assert_round_trip(Inv::new(inventory))?;
+    }
+
+    #[test]
+    #[ignore]
+    fn locator_hashes_round_trip(
+        version in any::<u32>(),
+        block_locator_hashes in vec(hash(), 0..16),
+        hash_stop in hash(),
+    ) {
+        assert_round_trip(LocatorHashes {
+            version: ProtocolVersion(version),
+            block_locator_hashes,
+            hash_stop,
+        })?;
+    }
+
+    #[test]
+    #[ignore]
+    fn headers_round_trip(headers in vec(header(), 0..4)) {
+        assert_round_trip(Headers::new(headers))?;
+    }
+
+    #[test]
+    #[ignore]
+    fn reject_round_trip(
+        message in var_str(),
+        ccode in ccode(),
+        reason in var_str(),
+        data in vec(any::<u8>(), 0..64),
+    ) {
+        assert_round_trip(Reject { message, ccode, reason, data })?;
+    }
+
+    #[test]
+    #[ignore]
+    fn filter_round_trip(
+        filter in vec(any::<u8>(), 0..=MAX_BLOOM_FILTER_SIZE),
+        hash_fn_count in 0..=MAX_HASH_FUNCS,
+        tweak in any::<u32>(),
+        flags in any::<u8>(),
+        data in vec(any::<u8>(), 0..=MAX_FILTER_ADD_SIZE),
+    ) {
+        assert_round_trip(BloomFilter { filter, hash_fn_count, tweak, flags })?;
+        assert_round_trip(FilterAdd { data })?;
+    }
+
+    #[test]
+    #[ignore]
+    fn fee_filter_round_trip(feerate in any::<u64>()) {
+        assert_round_trip(FeeFilter::new(feerate))?;
+    }
+}
+
+proptest! {
+    // Transactions are large, so fewer cases are generated.
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    #[ignore]
+    fn tx_round_trip(tx in tx()) {
+        assert_round_trip(tx)?;
+    }
+
+    #[test]
+    #[ignore]
+    fn block_round_trip(header in header(), txs in vec(tx(), 0..4)) {
+        assert_round_trip(Block { header, txs })?;
+    }
+}
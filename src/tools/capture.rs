@@ -0,0 +1,219 @@
+//! Recording of the frames exchanged by a [`SyntheticNode`], so a connection can be replayed later.
+//!
+//! A capture is a sequence of length-prefixed records, each made of:
+//!
+//! - the direction, `0` for inbound and `1` for outbound (1 byte),
+//! - the frame's sequence number, see [`SyntheticNode::sequence_number`] (8 bytes, LE),
+//! - the time the frame was read or written, in microseconds since the Unix epoch (8 bytes, LE),
+//! - the frame's length (4 bytes, LE),
+//! - the frame itself, header included.
+//!
+//! [`SyntheticNode`]: crate::tools::synthetic_node::SyntheticNode
+//! [`SyntheticNode::sequence_number`]: crate::tools::synthetic_node::SyntheticNode::sequence_number
+
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::BytesMut;
+use parking_lot::Mutex;
+use tokio_util::codec::Decoder;
+
+use crate::{
+    protocol::message::{constants::MAGIC_LEN, Message},
+    tools::synthetic_node::MessageCodec,
+};
+
+/// The size of a record's direction, sequence number, timestamp and length.
+const RECORD_HEADER_LEN: usize = 1 + 8 + 8 + 4;
+
+/// The direction of a captured frame, from the capturing node's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// A captured frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub direction: Direction,
+    /// The frame's position among all the frames read and written by the node, from 1.
+    pub sequence: u64,
+    /// The time since the Unix epoch at which the frame was read or written.
+    pub timestamp: Duration,
+    /// The frame's bytes, header included.
+    pub frame: Vec<u8>,
+}
+
+/// Writes records to a capture file, shared by all the connections of a node.
+#[derive(Debug)]
+pub(crate) struct CaptureWriter {
+    file: Mutex<File>,
+}
+
+impl CaptureWriter {
+    /// Creates the capture file at `path`, truncating any existing one.
+    pub(crate) fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            file: Mutex::new(File::create(path)?),
+        })
+    }
+
+    /// Appends a record for `frame`. A record is written with a single unbuffered write, so the
+    /// capture is complete up to the last frame even if the test panics.
+    pub(crate) fn record(
+        &self,
+        direction: Direction,
+        sequence: u64,
+        frame: &[u8],
+    ) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + frame.len());
+        record.push(match direction {
+            Direction::Inbound => 0,
+            Direction::Outbound => 1,
+        });
+        record.extend_from_slice(&sequence.to_le_bytes());
+        record.extend_from_slice(&(timestamp.as_micros() as u64).to_le_bytes());
+        record.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        record.extend_from_slice(frame);
+
+        self.file.lock().write_all(&record)
+    }
+}
+
+/// Reads all the records of the capture at `path`.
+pub fn read_capture(path: impl AsRef<Path>) -> io::Result<Vec<Record>> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    let mut records = Vec::new();
+    let mut remaining = &bytes[..];
+    while !remaining.is_empty() {
+        if remaining.len() < RECORD_HEADER_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated capture record header",
+            ));
+        }
+
+        let direction = match remaining[0] {
+            0 => Direction::Inbound,
+            1 => Direction::Outbound,
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown capture record direction: {other}"),
+                ))
+            }
+        };
+        let sequence = u64::from_le_bytes(remaining[1..9].try_into().unwrap());
+        let timestamp =
+            Duration::from_micros(u64::from_le_bytes(remaining[9..17].try_into().unwrap()));
+        let len = u32::from_le_bytes(remaining[17..21].try_into().unwrap()) as usize;
+
+        remaining = &remaining[RECORD_HEADER_LEN..];
+        if remaining.len() < len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated capture record frame",
+            ));
+        }
+
+        records.push(Record {
+            direction,
+            sequence,
+            timestamp,
+            frame: remaining[..len].to_vec(),
+        });
+        remaining = &remaining[len..];
+    }
+
+    Ok(records)
+}
+
+/// Feeds the inbound frames of the capture at `path` into a [`MessageCodec`], returning the
+/// decoded messages or the first decoding error, as the capturing node would have encountered it.
+///
+/// The codec expects the magic of the first inbound frame.
+pub fn replay(path: impl AsRef<Path>) -> io::Result<Vec<Message>> {
+    let inbound = read_capture(path)?
+        .into_iter()
+        .filter(|record| record.direction == Direction::Inbound)
+        .map(|record| record.frame)
+        .collect::<Vec<_>>();
+
+    let Some(magic) = inbound
+        .first()
+        .and_then(|frame| frame.get(..MAGIC_LEN))
+        .map(|magic| <[u8; MAGIC_LEN]>::try_from(magic).unwrap())
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut codec = MessageCodec::new(magic);
+    let mut buffer = BytesMut::new();
+    let mut messages = Vec::new();
+    for frame in inbound {
+        buffer.extend_from_slice(&frame);
+        while let Some(message) = codec.decode(&mut buffer)? {
+            messages.push(message);
+        }
+    }
+
+    // Bytes left over are a frame which was cut off, e.g. by the connection dropping.
+    if let Some(message) = codec.decode_eof(&mut buffer)? {
+        messages.push(message);
+    }
+
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{message::constants::MAGIC_TESTNET, payload::Nonce};
+
+    #[test]
+    #[ignore]
+    fn capture_replays_inbound_frames() {
+        let path = std::env::temp_dir().join(format!("capture-{}.bin", rand::random::<u64>()));
+        let writer = CaptureWriter::create(&path).unwrap();
+
+        let nonce = Nonce::default();
+        let mut ping = BytesMut::new();
+        Message::Ping(nonce)
+            .encode(MAGIC_TESTNET, &mut ping)
+            .unwrap();
+        let mut pong = BytesMut::new();
+        Message::Pong(nonce)
+            .encode(MAGIC_TESTNET, &mut pong)
+            .unwrap();
+
+        writer.record(Direction::Inbound, 1, &ping).unwrap();
+        writer.record(Direction::Outbound, 2, &pong).unwrap();
+        writer.record(Direction::Inbound, 3, &ping).unwrap();
+        drop(writer);
+
+        let records = read_capture(&path).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].direction, Direction::Outbound);
+        assert_eq!(records[1].sequence, 2);
+        assert_eq!(records[1].frame, pong.to_vec());
+        assert!(records[0].timestamp <= records[2].timestamp);
+
+        assert_eq!(
+            replay(&path).unwrap(),
+            vec![Message::Ping(nonce), Message::Ping(nonce)]
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,403 @@
+//! A small DSL for scripting message exchanges with a node, e.g.:
+//!
+//! ```ignore
+//! Conversation::new(&mut synthetic_node, node_addr)
+//!     .send(Message::Version(version))
+//!     .expect(message_matches!(Message::Version(..)))
+//!     .send(Message::Verack)
+//!     .expect(message_matches!(Message::Verack))
+//!     .run()
+//!     .await?;
+//! ```
+//!
+//! A failed expectation returns a [`ConversationError`] which shows the transcript so far, with
+//! the expected and received message diffed at the step which failed.
+
+use std::{fmt, io, net::SocketAddr, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::{
+    protocol::message::Message,
+    tools::{synthetic_node::SyntheticNode, DISCONNECT_TIMEOUT, RECV_TIMEOUT},
+};
+
+/// How often the connection is checked while waiting for a message.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Creates an [`Expectation`] matching messages against a pattern, described by the pattern
+/// itself.
+///
+/// ```ignore
+/// message_matches!(Message::Pong(nonce) if *nonce == ping_nonce)
+/// ```
+#[macro_export]
+macro_rules! message_matches {
+    ($pattern: pat $(if $guard: expr)?) => {
+        $crate::tools::conversation::Expectation::new(
+            stringify!($pattern $(if $guard)?),
+            move |message| matches!(message, $pattern $(if $guard)?),
+        )
+    };
+}
+
+/// A predicate the next message received must satisfy.
+pub struct Expectation {
+    description: String,
+    predicate: Box<dyn Fn(&Message) -> bool + Send>,
+}
+
+impl Expectation {
+    /// Creates an expectation, `description` is shown in place of the message when it fails.
+    pub fn new(
+        description: impl Into<String>,
+        predicate: impl Fn(&Message) -> bool + Send + 'static,
+    ) -> Self {
+        Self {
+            description: description.into(),
+            predicate: Box::new(predicate),
+        }
+    }
+
+    /// Indicates if `message` satisfies the expectation.
+    pub fn matches(&self, message: &Message) -> bool {
+        (self.predicate)(message)
+    }
+}
+
+enum Step {
+    Send(Message),
+    Expect(Expectation),
+    ExpectDisconnect,
+}
+
+/// A scripted exchange of messages with a single peer, see the module docs.
+pub struct Conversation<'a> {
+    synthetic_node: &'a mut SyntheticNode,
+    target: SocketAddr,
+    steps: Vec<Step>,
+    recv_timeout: Duration,
+    disconnect_timeout: Duration,
+}
+
+impl<'a> Conversation<'a> {
+    /// Starts a conversation between `synthetic_node` and `target`, which it should be connected
+    /// to. Messages from other peers are skipped.
+    pub fn new(synthetic_node: &'a mut SyntheticNode, target: SocketAddr) -> Self {
+        Self {
+            synthetic_node,
+            target,
+            steps: Vec::new(),
+            recv_timeout: RECV_TIMEOUT,
+            disconnect_timeout: DISCONNECT_TIMEOUT,
+        }
+    }
+
+    /// Sets how long each expectation waits for a message, [`RECV_TIMEOUT`] by default.
+    pub fn with_recv_timeout(mut self, timeout: Duration) -> Self {
+        self.recv_timeout = timeout;
+        self
+    }
+
+    /// Sets how long [`expect_disconnect`](Self::expect_disconnect) waits, [`DISCONNECT_TIMEOUT`]
+    /// by default.
+    pub fn with_disconnect_timeout(mut self, timeout: Duration) -> Self {
+        self.disconnect_timeout = timeout;
+        self
+    }
+
+    /// Sends `message` to the peer.
+    pub fn send(mut self, message: Message) -> Self {
+        self.steps.push(Step::Send(message));
+        self
+    }
+
+    /// Expects the next message from the peer to satisfy `expectation`, see [`message_matches`].
+    pub fn expect(mut self, expectation: Expectation) -> Self {
+        self.steps.push(Step::Expect(expectation));
+        self
+    }
+
+    /// Expects the peer to close the connection, messages received in the meantime are recorded
+    /// but don't fail the conversation.
+    pub fn expect_disconnect(mut self) -> Self {
+        self.steps.push(Step::ExpectDisconnect);
+        self
+    }
+
+    /// Runs the steps in order, stopping at the first one which fails.
+    pub async fn run(self) -> Result<Transcript, ConversationError> {
+        let mut transcript = Transcript::default();
+
+        for step in self.steps {
+            let failure = match step {
+                Step::Send(message) => {
+                    match self.synthetic_node.unicast(self.target, message.clone()) {
+                        Ok(()) => {
+                            transcript.0.push(Entry::Sent(message));
+                            continue;
+                        }
+                        Err(error) => Failure::Send { message, error },
+                    }
+                }
+                Step::Expect(expectation) => {
+                    match recv(self.synthetic_node, self.target, self.recv_timeout).await {
+                        Received::Message(message) if expectation.matches(&message) => {
+                            transcript.0.push(Entry::Received(message));
+                            continue;
+                        }
+                        Received::Message(message) => Failure::Unexpected {
+                            expected: expectation.description,
+                            received: message,
+                        },
+                        Received::Disconnected => Failure::Disconnected {
+                            expected: expectation.description,
+                        },
+                        Received::Nothing => Failure::Timeout {
+                            expected: expectation.description,
+                            timeout: self.recv_timeout,
+                        },
+                    }
+                }
+                Step::ExpectDisconnect => {
+                    let deadline = Instant::now() + self.disconnect_timeout;
+                    loop {
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        match recv(self.synthetic_node, self.target, remaining).await {
+                            Received::Message(message) => {
+                                transcript.0.push(Entry::Received(message))
+                            }
+                            Received::Disconnected | Received::Nothing => break,
+                        }
+                    }
+
+                    if !self.synthetic_node.is_connected(self.target) {
+                        transcript.0.push(Entry::Disconnected);
+                        continue;
+                    }
+                    Failure::StillConnected {
+                        timeout: self.disconnect_timeout,
+                    }
+                }
+            };
+
+            return Err(ConversationError {
+                target: self.target,
+                transcript,
+                failure,
+            });
+        }
+
+        Ok(transcript)
+    }
+}
+
+enum Received {
+    Message(Message),
+    Disconnected,
+    Nothing,
+}
+
+/// Waits up to `timeout` for the next message from `target`, messages from other peers are
+/// skipped.
+async fn recv(
+    synthetic_node: &mut SyntheticNode,
+    target: SocketAddr,
+    timeout: Duration,
+) -> Received {
+    let deadline = Instant::now() + timeout;
+    loop {
+        // Drain the queue before checking the connection, the peer may reply and then disconnect.
+        match synthetic_node.recv_message_timeout(POLL_INTERVAL).await {
+            Ok((source, message)) if source == target => return Received::Message(message),
+            Ok(_) => continue,
+            Err(_) if !synthetic_node.is_connected(target) => return Received::Disconnected,
+            Err(_) if Instant::now() >= deadline => return Received::Nothing,
+            Err(_) => {}
+        }
+    }
+}
+
+/// An event of a [`Conversation`].
+#[derive(Debug, Clone)]
+pub enum Entry {
+    Sent(Message),
+    Received(Message),
+    Disconnected,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Sent(message) => write!(f, "-> {message}"),
+            Entry::Received(message) => write!(f, "<- {message}"),
+            Entry::Disconnected => f.write_str("<- (disconnected)"),
+        }
+    }
+}
+
+/// The messages exchanged during a [`Conversation`], in order.
+#[derive(Debug, Clone, Default)]
+pub struct Transcript(Vec<Entry>);
+
+impl Transcript {
+    /// Returns the events of the conversation.
+    pub fn entries(&self) -> &[Entry] {
+        &self.0
+    }
+
+    /// Returns the messages received from the peer.
+    pub fn received(&self) -> impl Iterator<Item = &Message> {
+        self.0.iter().filter_map(|entry| match entry {
+            Entry::Received(message) => Some(message),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, entry) in self.0.iter().enumerate() {
+            writeln!(f, "  {:>3} {entry}", i + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Why a [`Conversation`] failed.
+#[derive(Debug)]
+pub enum Failure {
+    /// A message couldn't be sent.
+    Send { message: Message, error: io::Error },
+    /// A message didn't satisfy the expectation.
+    Unexpected { expected: String, received: Message },
+    /// No message arrived in time.
+    Timeout { expected: String, timeout: Duration },
+    /// The peer disconnected while a message was expected.
+    Disconnected { expected: String },
+    /// The peer didn't disconnect in time.
+    StillConnected { timeout: Duration },
+}
+
+/// A failed [`Conversation`], displayed as the transcript with the failed step diffed.
+pub struct ConversationError {
+    pub target: SocketAddr,
+    /// The events up to the failure.
+    pub transcript: Transcript,
+    pub failure: Failure,
+}
+
+impl fmt::Display for ConversationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "the conversation with {} failed:", self.target)?;
+        write!(f, "{}", self.transcript)?;
+
+        let step = self.transcript.0.len() + 1;
+        match &self.failure {
+            Failure::Send { message, error } => {
+                writeln!(f, "- {step:>3} -> {message}")?;
+                write!(f, "+ {step:>3} (couldn't send: {error})")
+            }
+            Failure::Unexpected { expected, received } => {
+                writeln!(f, "- {step:>3} <- {expected}")?;
+                write!(f, "+ {step:>3} <- {received}")
+            }
+            Failure::Timeout { expected, timeout } => {
+                writeln!(f, "- {step:>3} <- {expected}")?;
+                write!(
+                    f,
+                    "+ {step:>3} (nothing after {:.3}s)",
+                    timeout.as_secs_f64()
+                )
+            }
+            Failure::Disconnected { expected } => {
+                writeln!(f, "- {step:>3} <- {expected}")?;
+                write!(f, "+ {step:>3} <- (disconnected)")
+            }
+            Failure::StillConnected { timeout } => {
+                writeln!(f, "- {step:>3} <- (disconnected)")?;
+                write!(
+                    f,
+                    "+ {step:>3} (still connected after {:.3}s)",
+                    timeout.as_secs_f64()
+                )
+            }
+        }
+    }
+}
+
+// Shows the diff when a test unwraps the result.
+impl fmt::Debug for ConversationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl std::error::Error for ConversationError {}
+
+impl From<ConversationError> for io::Error {
+    fn from(error: ConversationError) -> Self {
+        let kind = match error.failure {
+            Failure::Send { ref error, .. } => error.kind(),
+            Failure::Timeout { .. } | Failure::StillConnected { .. } => io::ErrorKind::TimedOut,
+            Failure::Disconnected { .. } => io::ErrorKind::ConnectionAborted,
+            Failure::Unexpected { .. } => io::ErrorKind::Other,
+        };
+
+        io::Error::new(kind, error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::payload::Nonce;
+
+    #[tokio::test]
+    #[ignore]
+    async fn conversation_reports_unexpected_message() {
+        let peer = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(peer.listening_addr()).await.unwrap();
+
+        let nonce = Nonce::default();
+        let transcript = Conversation::new(&mut synthetic_node, peer.listening_addr())
+            .send(Message::Ping(nonce))
+            .expect(crate::message_matches!(Message::Pong(pong) if *pong == nonce))
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(transcript.received().count(), 1);
+
+        let error = Conversation::new(&mut synthetic_node, peer.listening_addr())
+            .send(Message::Ping(Nonce::default()))
+            .expect(crate::message_matches!(Message::Verack))
+            .run()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.failure,
+            Failure::Unexpected {
+                received: Message::Pong(..),
+                ..
+            }
+        ));
+
+        let diff = error.to_string();
+        assert!(diff.contains("-   2 <- Message::Verack"), "{diff}");
+        assert!(diff.contains("+   2 <- Pong("), "{diff}");
+
+        synthetic_node.shut_down().await;
+        peer.shut_down().await;
+    }
+}
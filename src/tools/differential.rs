@@ -0,0 +1,284 @@
+//! Runs the same probe against a zcashd and a zebra node side by side and reports where their
+//! responses diverge, see [`run_against_both`].
+//!
+//! Both kinds need to be configured in `config.toml`, in their `[zcashd]` and `[zebra]` tables.
+//! The nodes are started without their RPC servers, and zebra listens on [`ZEBRA_PORT`].
+
+use std::{fmt, io, mem, time::Duration};
+
+use crate::{
+    protocol::message::Message,
+    setup::{
+        node::{Action, Node},
+        NodeKind,
+    },
+    tools::{
+        reaction::{observe_replies, Observation, Probe, Reaction},
+        synthetic_node::SyntheticNode,
+        DISCONNECT_TIMEOUT,
+    },
+};
+
+/// The port the zebra node listens on, zcashd keeps the default one.
+pub const ZEBRA_PORT: u16 = 8081;
+/// How much the nodes' reply latencies can differ before it's reported, replies within the same
+/// round trip are considered alike.
+pub const TIMING_TOLERANCE: Duration = Duration::from_millis(500);
+
+/// A way in which the nodes' responses to a step differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The nodes reacted differently, e.g. one disconnected and the other ignored the message.
+    Reaction { zcashd: Reaction, zebra: Reaction },
+    /// Both replied, but with different messages (compared by type, in order).
+    Messages,
+    /// Both replied with the same messages, but the first reply latencies differ by more than
+    /// [`TIMING_TOLERANCE`].
+    Timing { zcashd: Duration, zebra: Duration },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Reaction { zcashd, zebra } => {
+                write!(f, "zcashd reacted with {zcashd}, zebra with {zebra}")
+            }
+            Divergence::Messages => f.write_str("the replies differ"),
+            Divergence::Timing { zcashd, zebra } => write!(
+                f,
+                "zcashd replied after {:.3}s, zebra after {:.3}s",
+                zcashd.as_secs_f64(),
+                zebra.as_secs_f64()
+            ),
+        }
+    }
+}
+
+/// The responses of both nodes to a single step of the probe.
+#[derive(Debug, Clone)]
+pub struct StepDiff {
+    /// The index of the step in the probe.
+    pub step: usize,
+    pub zcashd: Observation,
+    pub zebra: Observation,
+    /// Empty if the nodes responded alike.
+    pub divergences: Vec<Divergence>,
+}
+
+impl StepDiff {
+    /// Compares the responses of both nodes to `step`.
+    pub fn new(step: usize, zcashd: Observation, zebra: Observation) -> Self {
+        let mut divergences = Vec::new();
+
+        if zcashd.reaction != zebra.reaction {
+            divergences.push(Divergence::Reaction {
+                zcashd: zcashd.reaction,
+                zebra: zebra.reaction,
+            });
+        } else if !same_messages(&zcashd.replies, &zebra.replies) {
+            divergences.push(Divergence::Messages);
+        } else if let (Some(&(zcashd_latency, _)), Some(&(zebra_latency, _))) =
+            (zcashd.replies.first(), zebra.replies.first())
+        {
+            let difference = zcashd_latency.max(zebra_latency) - zcashd_latency.min(zebra_latency);
+            if difference > TIMING_TOLERANCE {
+                divergences.push(Divergence::Timing {
+                    zcashd: zcashd_latency,
+                    zebra: zebra_latency,
+                });
+            }
+        }
+
+        Self {
+            step,
+            zcashd,
+            zebra,
+            divergences,
+        }
+    }
+
+    /// Returns `true` if the nodes responded alike.
+    pub fn is_equivalent(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+fn same_messages(a: &[(Duration, Message)], b: &[(Duration, Message)]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|((_, a), (_, b))| mem::discriminant(a) == mem::discriminant(b))
+}
+
+/// The step by step comparison of both nodes' responses to a probe, displayed as the diverging
+/// steps.
+#[derive(Debug, Clone)]
+pub struct DifferentialReport {
+    pub steps: Vec<StepDiff>,
+}
+
+impl DifferentialReport {
+    /// Returns the steps the nodes responded differently to.
+    pub fn divergences(&self) -> impl Iterator<Item = &StepDiff> {
+        self.steps.iter().filter(|step| !step.is_equivalent())
+    }
+
+    /// Returns `true` if the nodes responded alike to every step.
+    pub fn is_equivalent(&self) -> bool {
+        self.divergences().next().is_none()
+    }
+}
+
+impl fmt::Display for DifferentialReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_equivalent() {
+            return writeln!(
+                f,
+                "zcashd and zebra responded alike to all {} steps",
+                self.steps.len()
+            );
+        }
+
+        let replies = |observation: &Observation| {
+            observation
+                .replies
+                .iter()
+                .map(|(_, message)| message.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        for step in self.divergences() {
+            writeln!(f, "step {}:", step.step + 1)?;
+            for divergence in &step.divergences {
+                writeln!(f, "  {divergence}")?;
+            }
+            writeln!(f, "  zcashd <- [{}]", replies(&step.zcashd))?;
+            writeln!(f, "  zebra  <- [{}]", replies(&step.zebra))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Starts a zcashd and a zebra node, sends each step of `probe` to both at the same time from a
+/// synthetic node, and compares their responses step by step.
+///
+/// Each step waits up to [`DISCONNECT_TIMEOUT`] for the node's reaction, see
+/// [`observe_replies`]. Once a node has disconnected, its remaining steps are observed as
+/// [`Reaction::Disconnected`].
+pub async fn run_against_both(probe: &[Probe]) -> io::Result<DifferentialReport> {
+    let (zcashd, zebra) = tokio::join!(
+        run_against(NodeKind::Zcashd, probe),
+        run_against(NodeKind::Zebra, probe)
+    );
+
+    let steps = zcashd?
+        .into_iter()
+        .zip(zebra?)
+        .enumerate()
+        .map(|(step, (zcashd, zebra))| StepDiff::new(step, zcashd, zebra))
+        .collect();
+
+    Ok(DifferentialReport { steps })
+}
+
+async fn run_against(kind: NodeKind, probe: &[Probe]) -> io::Result<Vec<Observation>> {
+    let mut node = Node::with_kind(kind)?;
+    node.initial_action(Action::WaitForConnection).disable_rpc();
+    if kind == NodeKind::Zebra {
+        node.listening_port(ZEBRA_PORT);
+    }
+    node.start().await?;
+
+    let mut synthetic_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await?;
+    synthetic_node.connect(node.addr()).await?;
+
+    let mut observations = Vec::with_capacity(probe.len());
+    for step in probe {
+        let observation = match step.clone().send(&synthetic_node, node.addr()) {
+            Ok(()) => observe_replies(&mut synthetic_node, node.addr(), DISCONNECT_TIMEOUT).await,
+            Err(_) => Observation {
+                reaction: Reaction::Disconnected,
+                replies: Vec::new(),
+            },
+        };
+        observations.push(observation);
+    }
+
+    synthetic_node.shut_down().await;
+    node.stop()?;
+
+    Ok(observations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::payload::Nonce;
+
+    fn observation(reaction: Reaction, replies: &[(u64, Message)]) -> Observation {
+        Observation {
+            reaction,
+            replies: replies
+                .iter()
+                .map(|(ms, message)| (Duration::from_millis(*ms), message.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn divergent_responses_are_reported() {
+        let pong = Message::Pong(Nonce::default());
+
+        let alike = StepDiff::new(
+            0,
+            observation(Reaction::Replied, &[(10, pong.clone())]),
+            observation(Reaction::Replied, &[(20, Message::Pong(Nonce::default()))]),
+        );
+        assert!(alike.is_equivalent());
+
+        let reaction = StepDiff::new(
+            1,
+            observation(Reaction::Ignored, &[]),
+            observation(Reaction::Disconnected, &[]),
+        );
+        assert_eq!(
+            reaction.divergences,
+            vec![Divergence::Reaction {
+                zcashd: Reaction::Ignored,
+                zebra: Reaction::Disconnected
+            }]
+        );
+
+        let messages = StepDiff::new(
+            2,
+            observation(Reaction::Replied, &[(10, pong.clone())]),
+            observation(Reaction::Replied, &[(10, Message::Verack)]),
+        );
+        assert_eq!(messages.divergences, vec![Divergence::Messages]);
+
+        let timing = StepDiff::new(
+            3,
+            observation(Reaction::Replied, &[(10, pong.clone())]),
+            observation(Reaction::Replied, &[(1_000, pong)]),
+        );
+        assert!(matches!(
+            timing.divergences[..],
+            [Divergence::Timing { .. }]
+        ));
+
+        let report = DifferentialReport {
+            steps: vec![alike, reaction, messages, timing],
+        };
+        assert_eq!(report.divergences().count(), 3);
+        let text = report.to_string();
+        assert!(!text.contains("step 1:"), "{text}");
+        assert!(text.contains("step 3:\n  the replies differ"), "{text}");
+    }
+}
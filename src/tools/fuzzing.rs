@@ -1,11 +1,9 @@
 //! Useful helper functions for fuzzing.
+//!
+//! Messages are encoded for testnet ([`MAGIC`]), the network the resistance tests run on.
 
-use std::{
-    convert::TryInto,
-    net::{IpAddr, Ipv4Addr, SocketAddr},
-};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
-use bytes::BufMut;
 use rand::{
     distributions::Standard,
     prelude::{Rng, SeedableRng, SliceRandom},
@@ -16,9 +14,10 @@ use rand_chacha::ChaCha8Rng;
 use crate::protocol::{
     message::{constants::*, Message, MessageHeader},
     payload::{
-        block::{Headers, LocatorHashes},
+        addr::NetworkAddr,
+        block::{Block, Headers, LocatorHashes},
         codec::Codec,
-        Addr, Inv, Nonce, Version,
+        Addr, Hash, Inv, InvHash, Nonce, VarStr, Version,
     },
 };
 
@@ -41,6 +40,9 @@ pub const COMMANDS_WITH_PAYLOADS: [[u8; 12]; 13] = [
 
 const CORRUPTION_PROBABILITY: f64 = 0.5;
 
+/// The probability of an [`addr_spam`] entry being invalid or unroutable.
+const BOGUS_ADDR_PROBABILITY: f64 = 0.5;
+
 /// Returns a randomly seeded `ChaCha8Rng` instance, useful for making tests reproducible.
 pub fn seeded_rng() -> ChaCha8Rng {
     let mut seed: <ChaCha8Rng as SeedableRng>::Seed = Default::default();
@@ -111,7 +113,7 @@ pub fn metadata_compliant_random_bytes(
             let mut random_payload: Vec<u8> = rng.sample_iter(Standard).take(random_len).collect();
 
             let command = commands.choose(rng).unwrap();
-            let header = MessageHeader::new(*command, &random_payload);
+            let header = MessageHeader::new(MAGIC, *command, &random_payload);
 
             let mut buffer = Vec::with_capacity(HEADER_LEN + random_payload.len());
             header.encode(&mut buffer).unwrap();
@@ -138,7 +140,7 @@ pub fn encode_slightly_corrupted_messages(
 
 fn corrupt_message(rng: &mut ChaCha8Rng, message: &Message) -> Vec<u8> {
     let mut bytes = Default::default();
-    message.encode(&mut bytes).unwrap();
+    message.encode(MAGIC, &mut bytes).unwrap();
     let vec: Vec<_> = bytes.to_vec();
     let (valid_header, valid_message) = vec.split_at(HEADER_LEN);
 
@@ -166,30 +168,52 @@ fn corrupt_bytes(rng: &mut ChaCha8Rng, serialized: &[u8]) -> Vec<u8> {
 
 /// Encodes a message and corrupts the body length bytes.
 pub fn encode_message_with_corrupt_body_length(rng: &mut ChaCha8Rng, message: &Message) -> Vec<u8> {
-    let mut bytes = Default::default();
-    message.encode(&mut bytes).unwrap();
-    let mut vec: Vec<_> = bytes.to_vec();
+    let (header, body) = encode_header_and_body(message);
 
-    let invalid_body_length = random_non_valid_u32(rng, (vec.len() - HEADER_LEN) as u32);
-    (&mut vec[MAGIC_LEN + COMMAND_LEN..][..4]).put_u32_le(invalid_body_length);
+    let invalid_body_length = random_non_valid_u32(rng, header.body_length);
+    let header = MessageHeader::raw(
+        header.magic,
+        header.command,
+        invalid_body_length,
+        header.checksum,
+    );
+
+    let mut vec = Vec::with_capacity(HEADER_LEN + body.len());
+    header.encode_with_body(&body, &mut vec).unwrap();
 
     vec
 }
 
 /// Encodes a message and corrupts the checksum bytes.
 pub fn encode_message_with_corrupt_checksum(rng: &mut ChaCha8Rng, message: &Message) -> Vec<u8> {
-    let mut bytes = Default::default();
-    message.encode(&mut bytes).unwrap();
-    let mut vec: Vec<_> = bytes.to_vec();
+    let (header, body) = encode_header_and_body(message);
+
+    let invalid_checksum = random_non_valid_u32(rng, header.checksum);
+    let header = MessageHeader::raw(
+        header.magic,
+        header.command,
+        header.body_length,
+        invalid_checksum,
+    );
 
-    let offset = MAGIC_LEN + COMMAND_LEN + 4; // 4 = sizeof MessageHeader.body_length
-    let valid_checksum = u32::from_le_bytes(vec[offset..][..4].try_into().unwrap());
-    let invalid_checksum = random_non_valid_u32(rng, valid_checksum);
-    (&mut vec[offset..][..4]).put_u32_le(invalid_checksum);
+    let mut vec = Vec::with_capacity(HEADER_LEN + body.len());
+    header.encode_with_body(&body, &mut vec).unwrap();
 
     vec
 }
 
+/// Encodes a message and splits it into its (valid) header and body.
+fn encode_header_and_body(message: &Message) -> (MessageHeader, Vec<u8>) {
+    let mut bytes = Default::default();
+    message.encode(MAGIC, &mut bytes).unwrap();
+    let (header, body) = bytes.split_at(HEADER_LEN);
+
+    (
+        MessageHeader::decode(&mut &header[..]).unwrap(),
+        body.to_vec(),
+    )
+}
+
 /// Returns a random u32 which isn't the supplied value.
 fn random_non_valid_u32(rng: &mut ChaCha8Rng, value: u32) -> u32 {
     // Make sure the generated value isn't the same.
@@ -230,3 +254,364 @@ pub fn encode_messages_with_corrupt_checksum(
         })
         .collect()
 }
+
+/// Picks `n` random messages with a body from `message_pool`, encodes them and truncates them at a
+/// random offset within the body.
+///
+/// The header is left intact, so a reader waits for the rest of the body, which never comes.
+pub fn truncated_messages(
+    rng: &mut ChaCha8Rng,
+    n: usize,
+    message_pool: &[Message],
+) -> Vec<Vec<u8>> {
+    let encoded: Vec<Vec<u8>> = message_pool
+        .iter()
+        .map(|message| {
+            let mut bytes = Default::default();
+            message.encode(MAGIC, &mut bytes).unwrap();
+            bytes.to_vec()
+        })
+        .filter(|bytes| bytes.len() > HEADER_LEN)
+        .collect();
+
+    (0..n)
+        .map(|_| {
+            let bytes = encoded.choose(rng).unwrap();
+            let len = rng.gen_range(HEADER_LEN..bytes.len());
+
+            bytes[..len].to_vec()
+        })
+        .collect()
+}
+
+/// The maximum bytes past [`MAX_MESSAGE_LEN`] in [`oversized_valid_command_messages`].
+const MAX_OVERSIZE: usize = 1024;
+
+/// Returns `n` encoded messages with a valid header (the body length and checksum match), whose
+/// body is too large for their command. Either over [`MAX_MESSAGE_LEN`], or within it but over
+/// the command's own limit:
+///
+/// - a `Version` whose user agent is longer than [`MAX_USER_AGENT_LEN`],
+/// - an `Inv`, `GetData` or `NotFound` with more than [`MAX_INV_LEN`] entries,
+/// - an `Addr` with more than [`MAX_ADDR_LEN`] entries.
+///
+/// The bodies within [`MAX_MESSAGE_LEN`] are otherwise well-formed, only a `Block` may be that
+/// large, see [`max_size_block`].
+pub fn oversized_valid_command_messages(rng: &mut ChaCha8Rng, n: usize) -> Vec<Vec<u8>> {
+    // The most entries of `entry_len` bytes which fit in a message, after the length prefix.
+    let max_entries = |entry_len: usize| (MAX_MESSAGE_LEN - 9) / entry_len;
+
+    (0..n)
+        .map(|_| {
+            let message = match rng.gen_range(0..4) {
+                0 => return over_max_message_len(rng),
+                1 => {
+                    // Leaves room for the version's other fields.
+                    let len = rng.gen_range(MAX_USER_AGENT_LEN + 1..=MAX_MESSAGE_LEN - 128);
+                    let localhost = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+                    let mut version = Version::new(localhost, localhost);
+                    version.user_agent = VarStr("z".repeat(len));
+
+                    Message::Version(version)
+                }
+                2 => {
+                    let len = rng.gen_range(MAX_INV_LEN + 1..=max_entries(36));
+                    let inv = Inv::new(
+                        (0..len)
+                            .map(|_| InvHash::Tx(Hash::new(rng.gen())))
+                            .collect(),
+                    );
+
+                    match rng.gen_range(0..3) {
+                        0 => Message::Inv(inv),
+                        1 => Message::GetData(inv),
+                        _ => Message::NotFound(inv),
+                    }
+                }
+                _ => {
+                    let len = rng.gen_range(MAX_ADDR_LEN + 1..=max_entries(30));
+                    let addrs = (0..len)
+                        .map(|_| {
+                            let ip = IpAddr::V4(Ipv4Addr::from(rng.gen::<u32>()));
+                            NetworkAddr::new(SocketAddr::new(ip, 8233))
+                        })
+                        .collect();
+
+                    Message::Addr(Addr::new(addrs))
+                }
+            };
+
+            let mut bytes = Default::default();
+            message.encode(MAGIC, &mut bytes).unwrap();
+
+            bytes.to_vec()
+        })
+        .collect()
+}
+
+/// Returns a message with a random command among [`COMMANDS_WITH_PAYLOADS`] and a random body
+/// just over [`MAX_MESSAGE_LEN`].
+fn over_max_message_len(rng: &mut ChaCha8Rng) -> Vec<u8> {
+    let command = *COMMANDS_WITH_PAYLOADS.choose(rng).unwrap();
+    let len = rng.gen_range(MAX_MESSAGE_LEN + 1..=MAX_MESSAGE_LEN + MAX_OVERSIZE);
+    let body: Vec<u8> = rng.sample_iter(Standard).take(len).collect();
+
+    let mut buffer = Vec::with_capacity(HEADER_LEN + body.len());
+    MessageHeader::new(MAGIC, command, &body)
+        .encode_with_body(&body, &mut buffer)
+        .unwrap();
+
+    buffer
+}
+
+/// Returns the testnet block at height 1, padded with copies of its coinbase transaction to just
+/// under [`MAX_BLOCK_SIZE`].
+///
+/// The merkle root no longer matches, so the node rejects the block, but not for its size.
+pub fn max_size_block() -> Block {
+    let mut block = Block::testnet_1();
+    let coinbase = block.txs[0].clone();
+
+    let mut tx_bytes = Vec::new();
+    coinbase.encode(&mut tx_bytes).unwrap();
+    let mut block_bytes = Vec::new();
+    block.encode(&mut block_bytes).unwrap();
+
+    // Leaves room for the transaction count growing to a 3 byte `CompactSize`.
+    let copies = (MAX_BLOCK_SIZE - block_bytes.len() - 2) / tx_bytes.len();
+    block.txs.extend(std::iter::repeat(coinbase).take(copies));
+
+    block
+}
+
+/// Returns `n` [`Addr`] messages of `batch_size` entries each, mixing routable addresses with
+/// invalid or unroutable ones (see [`is_bogus_addr`]).
+pub fn addr_spam(rng: &mut ChaCha8Rng, n: usize, batch_size: usize) -> Vec<Addr> {
+    (0..n)
+        .map(|_| {
+            let addrs = (0..batch_size)
+                .map(|_| {
+                    let addr = if rng.gen_bool(BOGUS_ADDR_PROBABILITY) {
+                        bogus_addr(rng)
+                    } else {
+                        routable_addr(rng)
+                    };
+
+                    NetworkAddr::new(addr)
+                })
+                .collect();
+
+            Addr::new(addrs)
+        })
+        .collect()
+}
+
+/// Indicates if a node shouldn't connect to or relay `addr`: it's unspecified, loopback, private,
+/// link-local, multicast, broadcast, reserved, in a documentation range or has a zero port.
+pub fn is_bogus_addr(addr: &SocketAddr) -> bool {
+    if addr.port() == 0 {
+        return true;
+    }
+
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_multicast()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // 240.0.0.0/4 is reserved for future use.
+                || ip.octets()[0] >= 240
+        }
+        IpAddr::V6(ip) => ip.is_unspecified() || ip.is_loopback() || ip.is_multicast(),
+    }
+}
+
+/// Returns a random IPv4 address which isn't [bogus](is_bogus_addr).
+fn routable_addr(rng: &mut ChaCha8Rng) -> SocketAddr {
+    loop {
+        let ip = Ipv4Addr::from(rng.gen::<u32>());
+        let addr = SocketAddr::new(IpAddr::V4(ip), rng.gen_range(1..=u16::MAX));
+
+        if !is_bogus_addr(&addr) {
+            return addr;
+        }
+    }
+}
+
+/// Returns a random [bogus](is_bogus_addr) IPv4 address.
+fn bogus_addr(rng: &mut ChaCha8Rng) -> SocketAddr {
+    let port = rng.gen_range(1..=u16::MAX);
+    let ip = match rng.gen_range(0..8) {
+        0 => Ipv4Addr::UNSPECIFIED,
+        1 => Ipv4Addr::new(127, rng.gen(), rng.gen(), rng.gen()),
+        2 => Ipv4Addr::new(10, rng.gen(), rng.gen(), rng.gen()),
+        3 => Ipv4Addr::new(192, 168, rng.gen(), rng.gen()),
+        4 => Ipv4Addr::new(169, 254, rng.gen(), rng.gen()),
+        5 => Ipv4Addr::new(rng.gen_range(224..240), rng.gen(), rng.gen(), rng.gen()),
+        6 => Ipv4Addr::new(192, 0, 2, rng.gen()),
+        // A routable address with a zero port.
+        _ => return SocketAddr::new(routable_addr(rng).ip(), 0),
+    };
+
+    SocketAddr::new(IpAddr::V4(ip), port)
+}
+
+/// Commands whose payload starts with a vector, with the number of bytes before its length prefix.
+const VECTOR_COMMANDS: [([u8; 12], usize); 7] = [
+    (ADDR_COMMAND, 0),
+    (INV_COMMAND, 0),
+    (GETDATA_COMMAND, 0),
+    (NOTFOUND_COMMAND, 0),
+    (HEADERS_COMMAND, 0),
+    // The protocol version precedes the block locator hashes.
+    (GETHEADERS_COMMAND, 4),
+    (GETBLOCKS_COMMAND, 4),
+];
+
+/// The maximum number of bytes following the length prefix in [`malformed_varint_messages`].
+const MAX_VARINT_TAIL_LEN: usize = 64;
+
+/// Returns `n` encoded messages with a valid header, whose payload starts with a vector claiming
+/// far more entries than fit in a message (up to `u64::MAX`) followed by only a few bytes.
+///
+/// A decoder pre-allocating the vector from its length prefix would try to allocate terabytes (an
+/// "allocation bomb"), a robust one rejects the length or runs out of bytes.
+pub fn malformed_varint_messages(rng: &mut ChaCha8Rng, n: usize) -> Vec<Vec<u8>> {
+    (0..n)
+        .map(|_| {
+            let (command, prefix_len) = *VECTOR_COMMANDS.choose(rng).unwrap();
+            let tail_len = rng.gen_range(0..=MAX_VARINT_TAIL_LEN);
+
+            let mut body: Vec<u8> = (0..prefix_len).map(|_| rng.gen()).collect();
+            body.extend_from_slice(&huge_var_int(rng));
+            body.extend((0..tail_len).map(|_| rng.gen::<u8>()));
+
+            let mut buffer = Vec::with_capacity(HEADER_LEN + body.len());
+            MessageHeader::new(MAGIC, command, &body)
+                .encode_with_body(&body, &mut buffer)
+                .unwrap();
+
+            buffer
+        })
+        .collect()
+}
+
+/// Returns an encoded `CompactSize` of at least [`MAX_MESSAGE_LEN`], which is more entries than a
+/// message can hold.
+fn huge_var_int(rng: &mut ChaCha8Rng) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(9);
+    match rng.gen_range(0..4) {
+        0 => {
+            bytes.push(0xff);
+            bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        }
+        1 => {
+            bytes.push(0xff);
+            let len = rng.gen_range(MAX_MESSAGE_LEN as u64 + 1..=u64::MAX);
+            bytes.extend_from_slice(&len.to_le_bytes());
+        }
+        2 => {
+            bytes.push(0xfe);
+            let len = rng.gen_range(MAX_MESSAGE_LEN as u32 + 1..=u32::MAX);
+            bytes.extend_from_slice(&len.to_le_bytes());
+        }
+        // Exactly at the limit, which is still far more than the bytes that follow.
+        _ => {
+            bytes.push(0xfe);
+            bytes.extend_from_slice(&(MAX_MESSAGE_LEN as u32).to_le_bytes());
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn malformed_varint_messages_are_rejected_without_allocating() {
+        let mut rng = seeded_rng();
+
+        for bytes in malformed_varint_messages(&mut rng, 100) {
+            let (header, mut body) = bytes.split_at(HEADER_LEN);
+            let header = MessageHeader::decode(&mut &header[..]).unwrap();
+
+            // Pre-allocating the claimed length would abort the process or take far longer.
+            let start = Instant::now();
+            let decoded = Message::decode(header.command, &mut body);
+            assert!(start.elapsed() < Duration::from_secs(1));
+
+            assert!(
+                decoded.is_err(),
+                "decoded a message with a malformed length: {decoded:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn oversized_messages_exceed_their_command_limit() {
+        let mut rng = seeded_rng();
+
+        for bytes in oversized_valid_command_messages(&mut rng, 20) {
+            let (header, mut body) = bytes.split_at(HEADER_LEN);
+            let header = MessageHeader::decode(&mut &header[..]).unwrap();
+
+            // The header is consistent with the body.
+            assert_eq!(header.body_length as usize, body.len());
+            assert_eq!(
+                header.checksum,
+                MessageHeader::new(header.magic, header.command, body).checksum
+            );
+
+            if body.len() > MAX_MESSAGE_LEN {
+                continue;
+            }
+            match Message::decode(header.command, &mut body).unwrap() {
+                Message::Version(version) => {
+                    assert!(version.user_agent.0.len() > MAX_USER_AGENT_LEN)
+                }
+                Message::Inv(inv) | Message::GetData(inv) | Message::NotFound(inv) => {
+                    assert!(inv.inventory.len() > MAX_INV_LEN)
+                }
+                Message::Addr(addr) => assert!(addr.addrs.len() > MAX_ADDR_LEN),
+                message => panic!("unexpected message: {message:?}"),
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn max_size_block_is_within_the_limit() {
+        let block = max_size_block();
+
+        let mut bytes = Vec::new();
+        block.encode(&mut bytes).unwrap();
+        assert!(bytes.len() <= MAX_BLOCK_SIZE);
+        // Well over the other messages' limits.
+        assert!(bytes.len() > MAX_BLOCK_SIZE * 9 / 10);
+    }
+
+    #[test]
+    #[ignore]
+    fn truncated_messages_are_cut_within_the_body() {
+        let mut rng = seeded_rng();
+
+        for bytes in truncated_messages(&mut rng, 100, &default_fuzz_messages()) {
+            let (header, mut body) = bytes.split_at(HEADER_LEN);
+            let header = MessageHeader::decode(&mut &header[..]).unwrap();
+            assert!(body.len() < header.body_length as usize);
+
+            // The partial body isn't mistaken for a complete message.
+            let decoded = Message::decode(header.command, &mut body);
+            assert!(decoded.is_err(), "decoded a truncated message: {decoded:?}");
+        }
+    }
+}
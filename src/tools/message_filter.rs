@@ -26,17 +26,23 @@ pub enum Filter {
 /// - [`GetHeaders`]
 /// - [`GetAddr`]
 /// - [`GetData`]
+/// - [`FeeFilter`] (there is no reply, so [`Filter::AutoReply`] drops it like [`Filter::Enabled`])
+/// - [`Unknown`] (likewise, there is no reply)
 ///
 /// [`Ping`]: Message::Ping
 /// [`GetHeaders`]: Message::GetHeaders
 /// [`GetAddr`]: Message::GetAddr
 /// [`GetData`]: Message::GetData
+/// [`FeeFilter`]: Message::FeeFilter
+/// [`Unknown`]: Message::Unknown
 #[derive(Debug, Clone)]
 pub struct MessageFilter {
     ping: Filter,
     getheaders: Filter,
     getaddr: Filter,
     getdata: Filter,
+    feefilter: Filter,
+    unknown: Filter,
     // todo: inv
     // todo: getblocks
     // todo: mempool
@@ -52,6 +58,8 @@ impl MessageFilter {
             getheaders: Disabled,
             getaddr: Disabled,
             getdata: Disabled,
+            feefilter: Disabled,
+            unknown: Disabled,
         }
     }
 
@@ -64,6 +72,8 @@ impl MessageFilter {
             getheaders: Enabled,
             getaddr: Enabled,
             getdata: Enabled,
+            feefilter: Enabled,
+            unknown: Enabled,
         }
     }
 
@@ -76,6 +86,8 @@ impl MessageFilter {
             getheaders: AutoReply,
             getaddr: AutoReply,
             getdata: AutoReply,
+            feefilter: AutoReply,
+            unknown: AutoReply,
         }
     }
 
@@ -103,6 +115,22 @@ impl MessageFilter {
         self
     }
 
+    /// Sets the [`Filter`] response for [`FeeFilter`] messages.
+    ///
+    /// [`FeeFilter`]: Message::FeeFilter
+    pub fn with_feefilter_filter(mut self, filter: Filter) -> Self {
+        self.feefilter = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`Unknown`] messages.
+    ///
+    /// [`Unknown`]: Message::Unknown
+    pub fn with_unknown_filter(mut self, filter: Filter) -> Self {
+        self.unknown = filter;
+        self
+    }
+
     /// Sets the [`Filter`] response for [`Ping`] messages.
     ///
     /// [`Ping`]: Message::Ping
@@ -118,6 +146,11 @@ impl MessageFilter {
             Message::GetAddr => self.getaddr,
             Message::GetHeaders(_) => self.getheaders,
             Message::GetData(_) => self.getdata,
+            // There is nothing to reply with, the message is only dropped.
+            Message::FeeFilter(_) if self.feefilter == Filter::AutoReply => Filter::Enabled,
+            Message::FeeFilter(_) => self.feefilter,
+            Message::Unknown { .. } if self.unknown == Filter::AutoReply => Filter::Enabled,
+            Message::Unknown { .. } => self.unknown,
             _ => Filter::Disabled,
         }
     }
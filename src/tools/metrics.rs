@@ -0,0 +1,246 @@
+//! Latency measurement utilities for performance tests.
+
+use std::{fmt, time::Duration};
+
+use tabled::{Table, Tabled};
+use ziggurat_core_metrics::tables::fmt_table;
+
+/// The number of bits used for the linear sub-buckets of each power of two, this bounds the
+/// relative error of the reported percentiles to `1 / 2^SUB_BUCKET_BITS` (< 1%).
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKET_COUNT: u64 = 1 << SUB_BUCKET_BITS;
+
+/// A bucketed (HDR-style) histogram of latency samples.
+///
+/// Samples are recorded with microsecond precision into logarithmically sized buckets, each split
+/// into linear sub-buckets, so recording is `O(1)` and memory is bounded regardless of the number
+/// of samples. Min, max and mean are exact; percentiles are accurate to within 1%.
+#[derive(Debug, Default, Clone)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    len: u64,
+    sum_us: u128,
+    min_us: u64,
+    max_us: u64,
+}
+
+impl LatencyHistogram {
+    /// Creates an empty histogram.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a single sample.
+    pub fn record(&mut self, latency: Duration) {
+        let us = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+
+        let index = bucket_index(us);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+
+        self.min_us = if self.len == 0 {
+            us
+        } else {
+            self.min_us.min(us)
+        };
+        self.max_us = self.max_us.max(us);
+        self.sum_us += us as u128;
+        self.len += 1;
+    }
+
+    /// Adds all the samples recorded by `other` to this histogram.
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        if other.is_empty() {
+            return;
+        }
+
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+
+        self.min_us = if self.is_empty() {
+            other.min_us
+        } else {
+            self.min_us.min(other.min_us)
+        };
+        self.max_us = self.max_us.max(other.max_us);
+        self.sum_us += other.sum_us;
+        self.len += other.len;
+    }
+
+    /// Returns the number of recorded samples.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if no samples have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the smallest recorded sample, or zero if the histogram is empty.
+    pub fn min(&self) -> Duration {
+        Duration::from_micros(self.min_us)
+    }
+
+    /// Returns the largest recorded sample, or zero if the histogram is empty.
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max_us)
+    }
+
+    /// Returns the mean of the recorded samples, or zero if the histogram is empty.
+    pub fn mean(&self) -> Duration {
+        if self.is_empty() {
+            return Duration::ZERO;
+        }
+
+        Duration::from_micros((self.sum_us / self.len as u128) as u64)
+    }
+
+    /// Returns the `p`th percentile (`0.0..=100.0`) of the recorded samples, or zero if the
+    /// histogram is empty.
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.is_empty() {
+            return Duration::ZERO;
+        }
+
+        // Nearest-rank, at least one sample is always required.
+        let rank = ((p.clamp(0.0, 100.0) / 100.0 * self.len as f64).ceil() as u64).max(1);
+
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                // Clamping to the exact extremes keeps the edges of the distribution precise.
+                let us = bucket_value(index).clamp(self.min_us, self.max_us);
+                return Duration::from_micros(us);
+            }
+        }
+
+        self.max()
+    }
+
+    /// Shorthand for the 50th percentile.
+    pub fn p50(&self) -> Duration {
+        self.percentile(50.0)
+    }
+
+    /// Shorthand for the 90th percentile.
+    pub fn p90(&self) -> Duration {
+        self.percentile(90.0)
+    }
+
+    /// Shorthand for the 99th percentile.
+    pub fn p99(&self) -> Duration {
+        self.percentile(99.0)
+    }
+}
+
+/// Returns the index of the bucket `value` falls in.
+fn bucket_index(value: u64) -> usize {
+    // Values below two sub-bucket ranges are stored exactly.
+    if value < 2 * SUB_BUCKET_COUNT {
+        return value as usize;
+    }
+
+    let magnitude = u64::BITS - 1 - value.leading_zeros();
+    let shift = magnitude - SUB_BUCKET_BITS;
+    let sub_bucket = (value >> shift) - SUB_BUCKET_COUNT;
+
+    (2 * SUB_BUCKET_COUNT + (shift - 1) as u64 * SUB_BUCKET_COUNT + sub_bucket) as usize
+}
+
+/// Returns the midpoint of the range of values stored in the bucket at `index`.
+fn bucket_value(index: usize) -> u64 {
+    let index = index as u64;
+    if index < 2 * SUB_BUCKET_COUNT {
+        return index;
+    }
+
+    let shift = (index - 2 * SUB_BUCKET_COUNT) / SUB_BUCKET_COUNT + 1;
+    let sub_bucket = (index - 2 * SUB_BUCKET_COUNT) % SUB_BUCKET_COUNT + SUB_BUCKET_COUNT;
+
+    (sub_bucket << shift) + (1 << (shift - 1))
+}
+
+#[derive(Tabled)]
+struct LatencyRow {
+    #[tabled(rename = " samples ")]
+    samples: u64,
+    #[tabled(rename = " min (ms) ")]
+    min: String,
+    #[tabled(rename = " mean (ms) ")]
+    mean: String,
+    #[tabled(rename = " p50 (ms) ")]
+    p50: String,
+    #[tabled(rename = " p90 (ms) ")]
+    p90: String,
+    #[tabled(rename = " p99 (ms) ")]
+    p99: String,
+    #[tabled(rename = " max (ms) ")]
+    max: String,
+}
+
+impl fmt::Display for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |duration: Duration| format!("{:.3}", duration.as_secs_f64() * 1000.0);
+
+        let row = LatencyRow {
+            samples: self.len,
+            min: ms(self.min()),
+            mean: ms(self.mean()),
+            p50: ms(self.p50()),
+            p90: ms(self.p90()),
+            p99: ms(self.p99()),
+            max: ms(self.max()),
+        };
+
+        write!(f, "{}", fmt_table(Table::new([row])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn bucket_index_and_value_round_trip() {
+        for value in (0..1_000_000).step_by(7).chain([u64::MAX / 2, u64::MAX]) {
+            let approximation = bucket_value(bucket_index(value));
+            let error = approximation.abs_diff(value) as f64;
+
+            assert!(
+                error <= value as f64 / SUB_BUCKET_COUNT as f64,
+                "value: {value}, approximation: {approximation}"
+            );
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn percentiles_are_within_one_percent() {
+        let mut histogram = LatencyHistogram::new();
+        for us in 1..=100_000 {
+            histogram.record(Duration::from_micros(us));
+        }
+
+        assert_eq!(histogram.len(), 100_000);
+        assert_eq!(histogram.min(), Duration::from_micros(1));
+        assert_eq!(histogram.max(), Duration::from_micros(100_000));
+        assert_eq!(histogram.mean(), Duration::from_micros(50_000));
+
+        for (p, expected) in [(50.0, 50_000.0), (90.0, 90_000.0), (99.0, 99_000.0)] {
+            let actual = histogram.percentile(p).as_micros() as f64;
+            assert!(
+                (actual - expected).abs() / expected < 0.01,
+                "p{p}: {actual}"
+            );
+        }
+    }
+}
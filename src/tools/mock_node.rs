@@ -0,0 +1,413 @@
+//! A stand-in for the node under test, speaking just enough of the protocol to unit-test the
+//! protocol layer (and the tools built on it) without zcashd or zebra binaries.
+//!
+//! A [`MockNode`] accepts connections on localhost, completes the handshake (its `Version` and
+//! `Verack` in reply to the peer's) and answers pings with the same nonce. Anything else is
+//! handled by its rules, e.g. to misbehave:
+//!
+//! ```ignore
+//! let mock_node = MockNode::builder()
+//!     .on(message_matches!(Message::GetAddr), Reply::Send(vec![Message::Addr(addrs)]))
+//!     .on(message_matches!(Message::Ping(..)), Reply::Disconnect)
+//!     .build()
+//!     .await?;
+//! ```
+//!
+//! Exact sequences, the handshake included, are scripted with [`ScriptedResponse`]s instead:
+//!
+//! ```ignore
+//! // Sends `Verack` twice.
+//! let mock_node = MockNode::builder()
+//!     .without_handshake()
+//!     .with_script(vec![ScriptedResponse::on(
+//!         message_matches!(Message::Version(..)),
+//!         Reply::Send(vec![Message::Version(version), Message::Verack, Message::Verack]),
+//!     )])
+//!     .build()
+//!     .await?;
+//! ```
+
+use std::{collections::VecDeque, io, net::SocketAddr, sync::Arc};
+
+use parking_lot::Mutex;
+use tokio::{sync::oneshot, task::JoinHandle};
+use tracing::*;
+
+use crate::{
+    protocol::message::Message,
+    tools::{conversation::Expectation, synthetic_node::SyntheticNode},
+};
+
+/// How a [`MockNode`] reacts to a message matching a rule of its script.
+#[derive(Debug, Clone)]
+pub enum Reply {
+    /// Sends the messages, in order.
+    Send(Vec<Message>),
+    /// Sends the bytes as is, e.g. a corrupt message.
+    SendBytes(Vec<u8>),
+    /// Drops the connection.
+    Disconnect,
+    /// Doesn't react, e.g. to leave a ping unanswered.
+    Ignore,
+}
+
+/// A step of a [`MockNode`]'s script, see [`MockNodeBuilder::with_script`].
+pub struct ScriptedResponse {
+    expectation: Option<Expectation>,
+    reply: Reply,
+}
+
+impl ScriptedResponse {
+    /// Replies to the next message with `reply`, whatever it is.
+    pub fn any(reply: Reply) -> Self {
+        Self {
+            expectation: None,
+            reply,
+        }
+    }
+
+    /// Replies to the next message satisfying `expectation` with `reply`.
+    pub fn on(expectation: Expectation, reply: Reply) -> Self {
+        Self {
+            expectation: Some(expectation),
+            reply,
+        }
+    }
+
+    fn matches(&self, message: &Message) -> bool {
+        self.expectation
+            .as_ref()
+            .map_or(true, |expectation| expectation.matches(message))
+    }
+}
+
+/// A builder for [`MockNode`].
+pub struct MockNodeBuilder {
+    handshake: bool,
+    script: VecDeque<ScriptedResponse>,
+    rules: Vec<(Expectation, Reply)>,
+}
+
+impl Default for MockNodeBuilder {
+    fn default() -> Self {
+        Self {
+            handshake: true,
+            script: VecDeque::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl MockNodeBuilder {
+    /// Leaves the handshake to the script (or the rules): the peer's `Version` is handled like
+    /// any other message.
+    pub fn without_handshake(mut self) -> Self {
+        self.handshake = false;
+        self
+    }
+
+    /// Replies to messages with the script's responses, in order.
+    ///
+    /// Each response is used once, for the first message satisfying its expectation once the
+    /// previous one was used. Messages which aren't, and any message after the script has run
+    /// out, are handled by the rules.
+    pub fn with_script(mut self, script: Vec<ScriptedResponse>) -> Self {
+        self.script = script.into();
+        self
+    }
+
+    /// Replies to the messages satisfying `expectation` with `reply`.
+    ///
+    /// Rules are tried in the order they were added, the first match wins. Pings which don't
+    /// match any rule are answered with a pong, other messages are ignored.
+    pub fn on(mut self, expectation: Expectation, reply: Reply) -> Self {
+        self.rules.push((expectation, reply));
+        self
+    }
+
+    /// Starts the node, listening on localhost.
+    pub async fn build(self) -> io::Result<MockNode> {
+        // Every message after the handshake goes through the script, pings included.
+        let mut builder = SyntheticNode::builder();
+        if self.handshake {
+            builder = builder.with_full_handshake();
+        }
+        let synthetic_node = builder.build().await?;
+
+        let addr = synthetic_node.listening_addr();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let task = tokio::spawn(run_script(
+            synthetic_node,
+            self.script,
+            self.rules,
+            received.clone(),
+            shutdown_rx,
+        ));
+
+        Ok(MockNode {
+            addr,
+            received,
+            shutdown_tx,
+            task,
+        })
+    }
+}
+
+/// A scripted node, see the module docs.
+pub struct MockNode {
+    addr: SocketAddr,
+    received: Arc<Mutex<Vec<(SocketAddr, Message)>>>,
+    shutdown_tx: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl MockNode {
+    pub fn builder() -> MockNodeBuilder {
+        MockNodeBuilder::default()
+    }
+
+    /// Returns the address the node accepts connections on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the messages received after the handshake (or all of them without it), in order,
+    /// with their source.
+    pub fn received(&self) -> Vec<(SocketAddr, Message)> {
+        self.received.lock().clone()
+    }
+
+    /// Stops the node, dropping its connections.
+    pub async fn shut_down(self) {
+        // The task has only stopped already if it panicked.
+        let _ = self.shutdown_tx.send(());
+        let _ = self.task.await;
+    }
+}
+
+/// Reacts to the messages `synthetic_node` receives according to `script`, then `rules`, until
+/// shut down.
+async fn run_script(
+    mut synthetic_node: SyntheticNode,
+    mut script: VecDeque<ScriptedResponse>,
+    rules: Vec<(Expectation, Reply)>,
+    received: Arc<Mutex<Vec<(SocketAddr, Message)>>>,
+    mut shutdown_rx: oneshot::Receiver<()>,
+) {
+    loop {
+        let (source, message) = tokio::select! {
+            _ = &mut shutdown_rx => break,
+            inbound = synthetic_node.recv_message() => inbound,
+        };
+        received.lock().push((source, message.clone()));
+
+        let reply = if script.front().is_some_and(|step| step.matches(&message)) {
+            script.pop_front().unwrap().reply
+        } else {
+            rule_reply(&rules, &message)
+        };
+
+        let result = match reply {
+            Reply::Send(messages) => messages
+                .into_iter()
+                .try_for_each(|message| synthetic_node.unicast(source, message)),
+            Reply::SendBytes(bytes) => synthetic_node.send_direct_bytes(source, bytes),
+            Reply::Disconnect => {
+                synthetic_node.disconnect(source).await;
+                Ok(())
+            }
+            Reply::Ignore => Ok(()),
+        };
+        if let Err(e) = result {
+            warn!("the mock node couldn't reply to {source}: {e}");
+        }
+    }
+
+    synthetic_node.shut_down().await;
+}
+
+/// Returns the reply of the first rule `message` satisfies, by default pings are answered and
+/// other messages ignored.
+fn rule_reply(rules: &[(Expectation, Reply)], message: &Message) -> Reply {
+    match rules
+        .iter()
+        .find(|(expectation, _)| expectation.matches(message))
+    {
+        Some((_, reply)) => reply.clone(),
+        None => match message {
+            Message::Ping(nonce) => Reply::Send(vec![Message::Pong(*nonce)]),
+            _ => Reply::Ignore,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        message_matches,
+        protocol::payload::{
+            reject::{CCode, Reject},
+            Addr, Nonce, VarStr, Version,
+        },
+        tools::{conversation::Conversation, RECV_TIMEOUT},
+    };
+
+    fn version() -> Message {
+        let addr = "127.0.0.1:8233".parse().unwrap();
+        Message::Version(Version::new(addr, addr))
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn mock_node_handshakes_and_answers_pings() {
+        let mock_node = MockNode::builder().build().await.unwrap();
+
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(mock_node.addr()).await.unwrap();
+        synthetic_node
+            .ping_pong_timeout(mock_node.addr(), RECV_TIMEOUT)
+            .await
+            .unwrap();
+
+        assert!(matches!(mock_node.received()[..], [(_, Message::Ping(..))]));
+
+        synthetic_node.shut_down().await;
+        mock_node.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn mock_node_follows_its_script() {
+        let mock_node = MockNode::builder()
+            .on(
+                message_matches!(Message::GetAddr),
+                Reply::Send(vec![Message::Addr(Addr::empty())]),
+            )
+            .on(message_matches!(Message::Ping(..)), Reply::Disconnect)
+            .build()
+            .await
+            .unwrap();
+
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(mock_node.addr()).await.unwrap();
+
+        Conversation::new(&mut synthetic_node, mock_node.addr())
+            .send(Message::GetAddr)
+            .expect(message_matches!(Message::Addr(addr) if addr.addrs.is_empty()))
+            .send(Message::Ping(Nonce::default()))
+            .expect_disconnect()
+            .run()
+            .await
+            .unwrap();
+
+        synthetic_node.shut_down().await;
+        mock_node.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn scripted_handshake_can_send_verack_twice() {
+        let mock_node = MockNode::builder()
+            .without_handshake()
+            .with_script(vec![ScriptedResponse::on(
+                message_matches!(Message::Version(..)),
+                Reply::Send(vec![version(), Message::Verack, Message::Verack]),
+            )])
+            .build()
+            .await
+            .unwrap();
+
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(mock_node.addr()).await.unwrap();
+
+        // The second `Verack` arrives after the handshake.
+        let (_, message) = synthetic_node
+            .recv_message_timeout(RECV_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(message, Message::Verack);
+
+        synthetic_node.shut_down().await;
+        mock_node.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn scripted_reject_after_version_refuses_the_handshake() {
+        let reject = Reject {
+            message: VarStr("version".to_owned()),
+            ccode: CCode::Obsolete,
+            reason: VarStr("Version must be 170100 or greater".to_owned()),
+            data: Vec::new(),
+        };
+        let mock_node = MockNode::builder()
+            .without_handshake()
+            .with_script(vec![ScriptedResponse::any(Reply::Send(vec![
+                Message::Reject(reject),
+            ]))])
+            .build()
+            .await
+            .unwrap();
+
+        let synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        let error = synthetic_node.connect(mock_node.addr()).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+
+        synthetic_node.shut_down().await;
+        mock_node.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn script_runs_before_the_rules() {
+        // Answers the first ping only.
+        let mock_node = MockNode::builder()
+            .with_script(vec![ScriptedResponse::on(
+                message_matches!(Message::Ping(..)),
+                Reply::Send(vec![Message::Pong(Nonce::default())]),
+            )])
+            .on(message_matches!(Message::Ping(..)), Reply::Ignore)
+            .build()
+            .await
+            .unwrap();
+
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(mock_node.addr()).await.unwrap();
+
+        Conversation::new(&mut synthetic_node, mock_node.addr())
+            .send(Message::Ping(Nonce::default()))
+            .expect(message_matches!(Message::Pong(..)))
+            .run()
+            .await
+            .unwrap();
+        assert!(synthetic_node
+            .ping_pong_timeout(mock_node.addr(), RECV_TIMEOUT)
+            .await
+            .is_err());
+
+        synthetic_node.shut_down().await;
+        mock_node.shut_down().await;
+    }
+}
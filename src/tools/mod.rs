@@ -1,16 +1,55 @@
 //! Utilities for network testing.
 
+pub mod capture;
+pub mod conversation;
+pub mod differential;
 pub mod fuzzing;
 pub mod message_filter;
+pub mod metrics;
+pub mod mock_node;
+pub mod pool;
+pub mod rate_limiter;
+pub mod reaction;
 pub mod synthetic_node;
 
-use std::time::Duration;
+use std::{io, time::Duration};
 
 /// Default timeout for connection operations in seconds.
 /// TODO: move to config file.
 pub const LONG_TIMEOUT: Duration = Duration::from_secs(30);
 /// Default timeout for response-specific reads in seconds.
 pub const RECV_TIMEOUT: Duration = Duration::from_millis(300);
+/// Default time the node has to drop a connection after receiving malformed data.
+pub const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long without a new message ends a drain of the inbound queue, see
+/// [`SyntheticNode::drain_available`](synthetic_node::SyntheticNode::drain_available).
+pub const DRAIN_QUIET_PERIOD: Duration = Duration::from_millis(50);
+
+/// Indicates if the error means the connection was terminated by the peer (or the OS on its
+/// behalf), as opposed to a local failure.
+///
+/// Writes to a peer which silently went away can also time out, so [`io::ErrorKind::TimedOut`]
+/// is included; don't use this to classify read timeouts.
+pub fn is_connection_terminated(error: &io::Error) -> bool {
+    use io::ErrorKind::*;
+
+    // ECONNRESET and ECONNABORTED, which aren't always mapped to an `ErrorKind`.
+    #[cfg(target_os = "linux")]
+    const RESET_CODES: [i32; 2] = [104, 103];
+    #[cfg(target_os = "macos")]
+    const RESET_CODES: [i32; 2] = [54, 53];
+    #[cfg(windows)]
+    const RESET_CODES: [i32; 2] = [10054, 10053];
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    const RESET_CODES: [i32; 0] = [];
+
+    matches!(
+        error.kind(),
+        ConnectionReset | ConnectionAborted | BrokenPipe | NotConnected | UnexpectedEof | TimedOut
+    ) || error
+        .raw_os_error()
+        .is_some_and(|code| RESET_CODES.contains(&code))
+}
 
 /// Waits until an expression is true or times out.
 ///
@@ -35,3 +74,26 @@ macro_rules! wait_until {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn connection_termination_errors() {
+        for kind in [
+            io::ErrorKind::ConnectionReset,
+            io::ErrorKind::ConnectionAborted,
+            io::ErrorKind::BrokenPipe,
+            io::ErrorKind::TimedOut,
+        ] {
+            assert!(is_connection_terminated(&kind.into()));
+        }
+
+        assert!(!is_connection_terminated(
+            &io::ErrorKind::InvalidData.into()
+        ));
+        assert!(!is_connection_terminated(&io::ErrorKind::NotFound.into()));
+    }
+}
@@ -0,0 +1,186 @@
+//! A pool of [`SyntheticNode`]s kept connected to the node under test between uses.
+//!
+//! Load tests spend most of their time opening connections and handshaking when each task builds
+//! its own synthetic node. The pool handshakes its nodes once and hands them out to tasks, which
+//! also better models real peers staying connected.
+
+use std::{
+    io,
+    net::SocketAddr,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::Mutex;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::tools::synthetic_node::{SyntheticNode, SyntheticNodeBuilder};
+
+/// A fixed size pool of synthetic nodes connected to `target`.
+pub struct ConnectionPool {
+    target: SocketAddr,
+    idle: Mutex<Vec<SyntheticNode>>,
+    // One permit per idle node, so tasks wait for a node to be returned.
+    permits: Arc<Semaphore>,
+    reconnections: AtomicUsize,
+}
+
+impl ConnectionPool {
+    /// Builds `size` nodes from `builder` and connects them to `target`, the builder should enable
+    /// a handshake.
+    pub async fn new(
+        builder: SyntheticNodeBuilder,
+        target: SocketAddr,
+        size: usize,
+    ) -> io::Result<Arc<Self>> {
+        let mut idle = Vec::with_capacity(size);
+        for _ in 0..size {
+            let node = builder.build().await?;
+            node.connect(target).await?;
+            idle.push(node);
+        }
+
+        Ok(Arc::new(Self {
+            target,
+            idle: Mutex::new(idle),
+            permits: Arc::new(Semaphore::new(size)),
+            reconnections: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Waits for a node to be available and hands it out, it returns to the pool once dropped.
+    ///
+    /// A node whose connection was dropped by the target is reconnected (and handshaked) first,
+    /// messages left over by its previous user are discarded.
+    pub async fn get(self: &Arc<Self>) -> io::Result<PooledNode> {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the pool's semaphore is never closed");
+        let mut node = self
+            .idle
+            .lock()
+            .pop()
+            .expect("each permit accounts for an idle node");
+
+        if !node.is_connected(self.target) {
+            if let Err(e) = node.connect(self.target).await {
+                self.idle.lock().push(node);
+                return Err(e);
+            }
+            self.reconnections.fetch_add(1, Ordering::Relaxed);
+        }
+        node.discard_messages();
+
+        Ok(PooledNode {
+            node: Some(node),
+            pool: self.clone(),
+            _permit: permit,
+        })
+    }
+
+    /// Returns the address the pool's nodes connect to.
+    pub fn target(&self) -> SocketAddr {
+        self.target
+    }
+
+    /// Returns how many times a node had to be reconnected since the pool was created.
+    pub fn reconnections(&self) -> usize {
+        self.reconnections.load(Ordering::Relaxed)
+    }
+
+    /// Shuts down the idle nodes, nodes which are still handed out aren't affected.
+    pub async fn shut_down(&self) {
+        let nodes = std::mem::take(&mut *self.idle.lock());
+        for node in nodes {
+            node.shut_down().await;
+        }
+    }
+}
+
+/// A node handed out by a [`ConnectionPool`], returned to it when dropped.
+pub struct PooledNode {
+    // Only `None` while being returned.
+    node: Option<SyntheticNode>,
+    pool: Arc<ConnectionPool>,
+    // Released after the node is back in the pool, see the `Drop` implementation.
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Deref for PooledNode {
+    type Target = SyntheticNode;
+
+    fn deref(&self) -> &Self::Target {
+        self.node.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledNode {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.node.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledNode {
+    fn drop(&mut self) {
+        if let Some(node) = self.node.take() {
+            self.pool.idle.lock().push(node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{tools::RECV_TIMEOUT, wait_until};
+
+    #[tokio::test]
+    #[ignore]
+    async fn pool_reconnects_dropped_connection() {
+        let target = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+        let target_addr = target.listening_addr();
+
+        let pool = ConnectionPool::new(
+            SyntheticNode::builder().with_full_handshake(),
+            target_addr,
+            1,
+        )
+        .await
+        .unwrap();
+
+        {
+            let node = pool.get().await.unwrap();
+            assert!(node.is_connected(target_addr));
+
+            // The target drops the connection while the node is handed out.
+            for peer in target.connected_peers() {
+                target.disconnect(peer).await;
+            }
+            wait_until!(Duration::from_secs(1), !node.is_connected(target_addr));
+        }
+        assert_eq!(pool.reconnections(), 0);
+
+        let mut node = pool.get().await.unwrap();
+        assert!(node.is_connected(target_addr));
+        assert_eq!(pool.reconnections(), 1);
+        node.ping_pong_timeout(target_addr, RECV_TIMEOUT)
+            .await
+            .unwrap();
+
+        drop(node);
+        pool.shut_down().await;
+        target.shut_down().await;
+    }
+}
@@ -0,0 +1,84 @@
+//! A token bucket pacing the messages sent by a
+//! [`SyntheticNode`](crate::tools::synthetic_node::SyntheticNode), see
+//! [`SyntheticNodeBuilder::with_rate_limit`](crate::tools::synthetic_node::SyntheticNodeBuilder::with_rate_limit).
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Allows `rate` messages per second on average, and bursts of up to `burst` messages.
+///
+/// The bucket holds up to `burst` tokens and refills continuously at `rate` tokens per second,
+/// each message takes a token. A message finding the bucket empty takes a token in advance and
+/// waits for it to be refilled, so messages queued behind it wait their turn as well.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    // Negative when messages are waiting on tokens which haven't been refilled yet.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket, `rate` and `burst` are raised to 1 if they're 0.
+    pub fn new(rate: u32, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+
+        Self {
+            rate: f64::from(rate.max(1)),
+            burst,
+            tokens: burst,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes a token for a message sent at `now`, returning how long it has to wait before it's
+    /// sent, [`Duration::ZERO`] if a token was available.
+    pub fn reserve(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.burst);
+        self.last_refill = self.last_refill.max(now);
+
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    /// Waits until a message can be sent.
+    pub async fn acquire(&mut self) {
+        let delay = self.reserve(Instant::now());
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn bursts_are_bounded_then_paced() {
+        let mut bucket = TokenBucket::new(10, 3);
+        let start = bucket.last_refill;
+
+        // The burst goes through at once, the next messages wait 100ms each.
+        for _ in 0..3 {
+            assert_eq!(bucket.reserve(start), Duration::ZERO);
+        }
+        assert_eq!(bucket.reserve(start), Duration::from_millis(100));
+        assert_eq!(bucket.reserve(start), Duration::from_millis(200));
+
+        // The two tokens taken in advance are paid back, and the refill is capped at the burst.
+        let later = start + Duration::from_secs(1);
+        for _ in 0..3 {
+            assert_eq!(bucket.reserve(later), Duration::ZERO);
+        }
+        assert_eq!(bucket.reserve(later), Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,272 @@
+//! Classifies a node's reaction to a probe message, and encodes how each implementation is
+//! expected to react so tests can assert the known zcashd and zebra deviations instead of only
+//! documenting them.
+
+use std::{fmt, io, net::SocketAddr, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::{
+    protocol::{message::Message, payload::Nonce},
+    setup::NodeKind,
+    tools::{synthetic_node::SyntheticNode, RECV_TIMEOUT},
+};
+
+/// A message sent to the node to observe its reaction, see [`classify`].
+#[derive(Debug, Clone)]
+pub enum Probe {
+    /// A well-formed message.
+    Message(Message),
+    /// Raw bytes, e.g. a corrupted message.
+    Bytes(Vec<u8>),
+}
+
+impl Probe {
+    /// Sends the probe to `target`.
+    pub fn send(self, synthetic_node: &SyntheticNode, target: SocketAddr) -> io::Result<()> {
+        match self {
+            Probe::Message(message) => synthetic_node.unicast(target, message),
+            Probe::Bytes(bytes) => synthetic_node.send_direct_bytes(target, bytes),
+        }
+    }
+}
+
+impl From<Message> for Probe {
+    fn from(message: Message) -> Self {
+        Self::Message(message)
+    }
+}
+
+impl From<Vec<u8>> for Probe {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+/// A node's observed reaction to a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reaction {
+    /// The node sent nothing and still answers a `Ping`.
+    Ignored,
+    /// The node closed the connection, possibly after sending some messages.
+    Disconnected,
+    /// The node sent at least one message and still answers a `Ping`.
+    Replied,
+    /// The node kept the connection open, but stopped answering a `Ping`.
+    Unresponsive,
+}
+
+/// The [`Reaction`] each implementation is expected to have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpectedBehavior {
+    pub zcashd: Reaction,
+    pub zebra: Reaction,
+}
+
+impl ExpectedBehavior {
+    /// Returns a new `ExpectedBehavior` instance.
+    pub const fn new(zcashd: Reaction, zebra: Reaction) -> Self {
+        Self { zcashd, zebra }
+    }
+
+    /// Returns an `ExpectedBehavior` instance where both implementations react the same way.
+    pub const fn both(reaction: Reaction) -> Self {
+        Self::new(reaction, reaction)
+    }
+
+    /// Returns the reaction expected from a node of the given `kind`.
+    pub fn for_kind(&self, kind: NodeKind) -> Reaction {
+        match kind {
+            NodeKind::Zcashd => self.zcashd,
+            NodeKind::Zebra => self.zebra,
+        }
+    }
+
+    /// Panics if `observed` isn't the reaction expected from a node of the given `kind`.
+    pub fn assert_matches(&self, kind: NodeKind, observed: Reaction) {
+        let expected = self.for_kind(kind);
+        assert_eq!(
+            observed, expected,
+            "{kind:?} was expected to react with {expected}, but reacted with {observed}"
+        );
+    }
+}
+
+impl fmt::Display for Reaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reaction = match self {
+            Reaction::Ignored => "ignoring the message",
+            Reaction::Disconnected => "disconnecting",
+            Reaction::Replied => "a reply",
+            Reaction::Unresponsive => "becoming unresponsive",
+        };
+
+        f.write_str(reaction)
+    }
+}
+
+/// Sends `probe` to `target` and classifies the node's reaction, see [`observe`].
+pub async fn classify(
+    synthetic_node: &mut SyntheticNode,
+    target: SocketAddr,
+    probe: impl Into<Probe>,
+    timeout: Duration,
+) -> Reaction {
+    match probe.into().send(synthetic_node, target) {
+        Ok(()) => observe(synthetic_node, target, timeout).await,
+        Err(_) => Reaction::Disconnected,
+    }
+}
+
+/// Observes the node's reaction to the last message sent to `target`.
+///
+/// The connection is probed with a `Ping`, which the node handles after the message: messages
+/// received before the `Pong` count as a reply, and no `Pong` within `timeout` means the node is
+/// unresponsive. Messages handled by the synthetic node's filter aren't seen, so they don't count
+/// as a reply.
+pub async fn observe(
+    synthetic_node: &mut SyntheticNode,
+    target: SocketAddr,
+    timeout: Duration,
+) -> Reaction {
+    observe_replies(synthetic_node, target, timeout)
+        .await
+        .reaction
+}
+
+/// A node's [`Reaction`] along with the messages it replied with.
+#[derive(Debug, Clone)]
+pub struct Observation {
+    pub reaction: Reaction,
+    /// The messages received before the `Pong` (or the disconnect), with the time they took to
+    /// arrive since the observation started.
+    pub replies: Vec<(Duration, Message)>,
+}
+
+/// Like [`observe`], but also records the messages the node replied with.
+pub async fn observe_replies(
+    synthetic_node: &mut SyntheticNode,
+    target: SocketAddr,
+    timeout: Duration,
+) -> Observation {
+    let start = Instant::now();
+    let mut replies = Vec::new();
+
+    let nonce = Nonce::default();
+    if synthetic_node
+        .unicast(target, Message::Ping(nonce))
+        .is_err()
+    {
+        return Observation {
+            reaction: Reaction::Disconnected,
+            replies,
+        };
+    }
+
+    let deadline = start + timeout;
+    loop {
+        // Reads are sliced to notice a disconnect without waiting for the whole timeout.
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match synthetic_node
+            .recv_message_timeout(remaining.min(RECV_TIMEOUT))
+            .await
+        {
+            Ok((_, Message::Pong(rx_nonce))) if rx_nonce == nonce => {
+                let reaction = match replies.is_empty() {
+                    true => Reaction::Ignored,
+                    false => Reaction::Replied,
+                };
+                return Observation { reaction, replies };
+            }
+            Ok((_, message)) => replies.push((start.elapsed(), message)),
+            Err(_) if !synthetic_node.is_connected(target) => {
+                return Observation {
+                    reaction: Reaction::Disconnected,
+                    replies,
+                }
+            }
+            Err(_) if Instant::now() >= deadline => {
+                return Observation {
+                    reaction: Reaction::Unresponsive,
+                    replies,
+                }
+            }
+            Err(_) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        message_matches,
+        protocol::payload::Addr,
+        tools::mock_node::{MockNode, Reply},
+    };
+
+    #[test]
+    #[ignore]
+    fn expected_reaction_follows_node_kind() {
+        let expected = ExpectedBehavior::new(Reaction::Ignored, Reaction::Disconnected);
+
+        assert_eq!(expected.for_kind(NodeKind::Zcashd), Reaction::Ignored);
+        assert_eq!(expected.for_kind(NodeKind::Zebra), Reaction::Disconnected);
+        expected.assert_matches(NodeKind::Zebra, Reaction::Disconnected);
+
+        let both = ExpectedBehavior::both(Reaction::Replied);
+        assert_eq!(both.zcashd, both.zebra);
+    }
+
+    #[test]
+    #[ignore]
+    #[should_panic(expected = "Zcashd was expected to react with ignoring the message")]
+    fn unexpected_reaction_panics() {
+        ExpectedBehavior::new(Reaction::Ignored, Reaction::Disconnected)
+            .assert_matches(NodeKind::Zcashd, Reaction::Disconnected);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn classify_mock_node_reactions() {
+        // The reply to the probe, and to the ping following it (a pong by default).
+        let cases = [
+            (Reply::Ignore, None, Reaction::Ignored),
+            (
+                Reply::Send(vec![Message::Addr(Addr::empty())]),
+                None,
+                Reaction::Replied,
+            ),
+            (Reply::Disconnect, None, Reaction::Disconnected),
+            (Reply::Ignore, Some(Reply::Ignore), Reaction::Unresponsive),
+        ];
+
+        for (probe_reply, ping_reply, expected) in cases {
+            let mut builder =
+                MockNode::builder().on(message_matches!(Message::GetAddr), probe_reply);
+            if let Some(ping_reply) = ping_reply {
+                builder = builder.on(message_matches!(Message::Ping(..)), ping_reply);
+            }
+            let mock_node = builder.build().await.unwrap();
+
+            let mut synthetic_node = SyntheticNode::builder()
+                .with_full_handshake()
+                .build()
+                .await
+                .unwrap();
+            synthetic_node.connect(mock_node.addr()).await.unwrap();
+
+            let reaction = classify(
+                &mut synthetic_node,
+                mock_node.addr(),
+                Message::GetAddr,
+                RECV_TIMEOUT,
+            )
+            .await;
+            assert_eq!(reaction, expected);
+
+            synthetic_node.shut_down().await;
+            mock_node.shut_down().await;
+        }
+    }
+}
@@ -1,23 +1,28 @@
 //! A lightweight node implementation to be used as peers in tests.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::{self, Error, ErrorKind},
-    net::{IpAddr, Ipv4Addr, SocketAddr},
-    sync::Arc,
-    time::Duration,
+    net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 use assert_matches::assert_matches;
-use bytes::{BufMut, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
 use futures_util::{sink::SinkExt, TryStreamExt};
 use parking_lot::Mutex;
 use pea2pea::{
     protocols::{Disconnect, Handshake, Reading, Writing},
     Config as NodeConfig, Connection, ConnectionInfo, ConnectionSide, Node, Pea2Pea,
 };
+use socket2::{SockRef, Socket};
 use tokio::{
-    sync::mpsc::{self, Receiver, Sender},
+    sync::mpsc::{self, error::TrySendError, Receiver, Sender},
     time::timeout,
 };
 use tokio_util::codec::{Decoder, Encoder, Framed, LengthDelimitedCodec};
@@ -25,10 +30,27 @@ use tracing::*;
 
 use crate::{
     protocol::{
-        message::{Message, MessageHeader},
-        payload::{codec::Codec, Nonce, Version},
+        message::{
+            constants::{
+                COMMAND_LEN, HEADER_LEN, MAGIC, MAGIC_LEN, MAX_MESSAGE_LEN, PROTOCOL_VERSION,
+            },
+            Message, MessageHeader,
+        },
+        payload::{
+            block::Block,
+            codec::Codec,
+            version::{Version, NODE_NETWORK},
+            Inv, InvHash, Nonce,
+        },
+    },
+    setup::{rpc::RpcClient, AddressFamily},
+    tools::{
+        capture::{CaptureWriter, Direction},
+        is_connection_terminated,
+        message_filter::{Filter, MessageFilter},
+        rate_limiter::TokenBucket,
+        DISCONNECT_TIMEOUT, DRAIN_QUIET_PERIOD, RECV_TIMEOUT,
     },
-    tools::message_filter::{Filter, MessageFilter},
 };
 
 /// An [`Error`](std::error::Error) type for [`SyntheticNode::inbound_rx`]
@@ -55,6 +77,51 @@ impl std::fmt::Display for ConnectionError {
 
 impl std::error::Error for ConnectionError {}
 
+/// Describes how a connection was terminated, see [`SyntheticNode::wait_for_disconnect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectKind {
+    /// The peer closed the connection cleanly (FIN).
+    Clean,
+    /// The connection was reset by the peer (RST) or aborted by an I/O error.
+    Aborted,
+    /// The connection was closed by the synthetic node itself.
+    Local,
+    /// The connection was closed by the synthetic node because the peer's unread messages
+    /// exceeded [`SyntheticNodeBuilder::with_max_inflight_bytes`].
+    InflightLimitExceeded,
+}
+
+/// Tracks how each connection was terminated, shared between a node and its codecs.
+type Terminations = Arc<Mutex<HashMap<SocketAddr, DisconnectKind>>>;
+
+/// A message in the inbound queue, along with its encoded length.
+type InboundMessage = (SocketAddr, Message, usize);
+
+/// The cap on a connection's unread inbound bytes, enforced by its reading codec.
+///
+/// Frames count from the moment they're read until the message leaves the inbound queue, or is
+/// dropped or answered by the message filter.
+#[derive(Clone)]
+struct InflightLimit {
+    addr: SocketAddr,
+    bytes: Arc<Mutex<HashMap<SocketAddr, usize>>>,
+    max: usize,
+}
+
+impl InflightLimit {
+    /// Accounts for a frame of `len` bytes, returning `false` if it doesn't fit under the cap.
+    fn reserve(&self, len: usize) -> bool {
+        let mut bytes = self.bytes.lock();
+        let inflight = bytes.entry(self.addr).or_default();
+        if *inflight + len > self.max {
+            return false;
+        }
+
+        *inflight += len;
+        true
+    }
+}
+
 /// An [`Error`](std::error::Error) type for [`SyntheticNode::ping_pong_timeout`]
 pub enum PingPongError {
     /// The connection was aborted during the [`Ping`](Message::Ping)-[`Pong`](Message::Pong) exchange.
@@ -112,6 +179,64 @@ impl From<PingPongError> for io::Error {
     }
 }
 
+/// The reply to a [`GetData`](Message::GetData) request, see [`SyntheticNode::get_data`].
+///
+/// The data messages are in the order they were received.
+#[derive(Debug)]
+pub enum GetDataResult {
+    /// Every requested item was received.
+    Found(Vec<Message>),
+    /// Every requested item was answered, but some with [`NotFound`](Message::NotFound).
+    NotFound {
+        found: Vec<Message>,
+        not_found: Vec<InvHash>,
+    },
+    /// The peer stopped replying before answering for every item, `missing` lists the items
+    /// which were neither received nor reported as not found.
+    TimedOut {
+        found: Vec<Message>,
+        missing: Vec<InvHash>,
+    },
+    /// The peer disconnected before answering for every item.
+    Disconnected {
+        found: Vec<Message>,
+        missing: Vec<InvHash>,
+    },
+}
+
+impl GetDataResult {
+    /// Returns the data messages received, whether or not every item was.
+    pub fn found(&self) -> &[Message] {
+        match self {
+            Self::Found(found)
+            | Self::NotFound { found, .. }
+            | Self::TimedOut { found, .. }
+            | Self::Disconnected { found, .. } => found,
+        }
+    }
+}
+
+/// Returns the position in `pending` of the item `message` is the data of, if any.
+fn requested_item(pending: &[InvHash], message: &Message) -> Option<usize> {
+    let inv_hash = match message {
+        Message::Block(block) => block.inv_hash(),
+        Message::MerkleBlock(merkle_block) => {
+            InvHash::FilteredBlock(merkle_block.header.double_sha256().ok()?)
+        }
+        Message::Tx(tx) => tx.inv_hash(),
+        _ => return None,
+    };
+
+    let mut position = pending.iter().position(|item| *item == inv_hash);
+    if let (None, Message::Tx(tx)) = (position, message) {
+        // Transactions requested by wtxid.
+        let wtxid = InvHash::MsgWtx(tx.wtxid().ok()?);
+        position = pending.iter().position(|item| *item == wtxid);
+    }
+
+    position
+}
+
 /// Enables tracing for all [`SyntheticNode`] instances (usually scoped by test).
 pub fn enable_tracing() {
     use tracing_subscriber::{fmt, EnvFilter};
@@ -134,6 +259,58 @@ pub enum HandshakeKind {
     ///
     /// [`Version`]: enum@crate::protocol::message::Message::Version
     VersionOnly,
+    /// Only our [`Version`] is sent, and [`Verack`] never is: the handshake is left half-open.
+    /// Anything the peer replies with is delivered as a regular message.
+    ///
+    /// [`Version`]: enum@crate::protocol::message::Message::Version
+    /// [`Verack`]: enum@crate::protocol::message::Message::Verack
+    Partial,
+}
+
+/// How [`SyntheticNode::connect_with_retry`] retries failed connection attempts, waiting
+/// exponentially longer between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The delay before the first retry.
+    pub initial_delay: Duration,
+    /// The factor the delay grows by after each retry.
+    pub multiplier: u32,
+    /// The cap on the delay between two attempts.
+    pub max_delay: Duration,
+    /// The number of retries after the first attempt, before giving up.
+    pub max_retries: usize,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            multiplier: 2,
+            max_delay: Duration::from_secs(5),
+            max_retries: 10,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before retry number `retry`, counting from `0`.
+    pub fn delay(&self, retry: usize) -> Duration {
+        let factor = self
+            .multiplier
+            .checked_pow(retry.try_into().unwrap_or(u32::MAX))
+            .unwrap_or(u32::MAX);
+
+        self.initial_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+
+    /// Returns the total time spent waiting if all the retries are used, excluding the time taken
+    /// by the attempts themselves.
+    pub fn max_total_delay(&self) -> Duration {
+        (0..self.max_retries).map(|retry| self.delay(retry)).sum()
+    }
 }
 
 /// A builder for [`SyntheticNode`].
@@ -142,6 +319,19 @@ pub struct SyntheticNodeBuilder {
     network_config: NodeConfig,
     handshake: Option<HandshakeKind>,
     message_filter: MessageFilter,
+    magic: [u8; MAGIC_LEN],
+    send_headers: bool,
+    wtxid_relay: bool,
+    recv_timeout: Duration,
+    max_inflight_bytes: Option<usize>,
+    capture_path: Option<PathBuf>,
+    services: u64,
+    protocol_version: u32,
+    // The messages per second and burst size of the outbound rate limit.
+    rate_limit: Option<(u32, u32)>,
+    version_nonce: Option<Nonce>,
+    nodelay: bool,
+    linger: Option<Duration>,
 }
 
 impl Default for SyntheticNodeBuilder {
@@ -154,6 +344,18 @@ impl Default for SyntheticNodeBuilder {
             },
             handshake: None,
             message_filter: MessageFilter::with_all_disabled(),
+            magic: MAGIC,
+            send_headers: false,
+            wtxid_relay: false,
+            recv_timeout: RECV_TIMEOUT,
+            max_inflight_bytes: None,
+            capture_path: None,
+            services: NODE_NETWORK,
+            protocol_version: PROTOCOL_VERSION,
+            rate_limit: None,
+            version_nonce: None,
+            nodelay: true,
+            linger: None,
         }
     }
 }
@@ -166,8 +368,13 @@ impl SyntheticNodeBuilder {
 
         // Inbound channel size of 100 messages.
         let (tx, rx) = mpsc::channel(100);
-        let inner_node =
-            InnerNode::new(node, tx, self.message_filter.clone(), self.handshake).await;
+        let capture = self
+            .capture_path
+            .as_deref()
+            .map(CaptureWriter::create)
+            .transpose()?
+            .map(Arc::new);
+        let inner_node = InnerNode::new(node, tx, self, capture).await;
 
         // Enable the read and write protocols
         inner_node.enable_reading().await;
@@ -177,9 +384,16 @@ impl SyntheticNodeBuilder {
         // Always start listening as inner node expects `listening_addr` to be always available.
         inner_node.node().start_listening().await?;
 
+        let paced_tx = self
+            .rate_limit
+            .map(|(rate, burst)| spawn_pacer(inner_node.clone(), TokenBucket::new(rate, burst)));
+
         Ok(SyntheticNode {
             inner_node,
             inbound_rx: rx,
+            recv_timeout: self.recv_timeout,
+            paced_tx,
+            scratch: Default::default(),
         })
     }
 
@@ -214,12 +428,156 @@ impl SyntheticNodeBuilder {
         self
     }
 
+    /// Enables handshaking with [`HandshakeKind::Partial`].
+    pub fn with_partial_handshake(mut self) -> Self {
+        self.handshake = Some(HandshakeKind::Partial);
+        self
+    }
+
+    /// Sends [`SendHeaders`] right after a [`HandshakeKind::Full`] handshake, so the peer announces
+    /// new blocks with [`Headers`] instead of [`Inv`] from the start of the connection.
+    ///
+    /// [`SendHeaders`]: enum@crate::protocol::message::Message::SendHeaders
+    /// [`Headers`]: enum@crate::protocol::message::Message::Headers
+    /// [`Inv`]: enum@crate::protocol::message::Message::Inv
+    pub fn with_send_headers(mut self) -> Self {
+        self.send_headers = true;
+        self
+    }
+
+    /// Sends [`WtxidRelay`] between [`Version`] and [`Verack`] during a [`HandshakeKind::Full`]
+    /// handshake, asking the peer to announce transactions by wtxid, see
+    /// [`SyntheticNode::negotiated_wtxid_relay`].
+    ///
+    /// [`WtxidRelay`]: enum@crate::protocol::message::Message::WtxidRelay
+    /// [`Version`]: enum@crate::protocol::message::Message::Version
+    /// [`Verack`]: enum@crate::protocol::message::Message::Verack
+    pub fn with_wtxid_relay(mut self) -> Self {
+        self.wtxid_relay = true;
+        self
+    }
+
+    /// Sets the services advertised in the node's [`Version`], defaults to [`NODE_NETWORK`].
+    ///
+    /// See [`version`](crate::protocol::payload::version) for the known service bits.
+    pub fn with_services(mut self, services: u64) -> Self {
+        self.services = services;
+        self
+    }
+
+    /// Sets the protocol version claimed in the node's [`Version`], defaults to
+    /// [`PROTOCOL_VERSION`].
+    ///
+    /// The version used on a connection is the lower of both sides', see
+    /// [`SyntheticNode::negotiated_version`].
+    pub fn with_protocol_version(mut self, version: u32) -> Self {
+        self.protocol_version = version;
+        self
+    }
+
+    /// Sets the nonce of the node's [`Version`], instead of a random one per connection, e.g. to
+    /// replay a nonce the peer sent and pose as the peer itself.
+    pub fn with_version_nonce(mut self, nonce: Nonce) -> Self {
+        self.version_nonce = Some(nonce);
+        self
+    }
+
+    /// Sets `TCP_NODELAY` on the node's connections, enabled by default so messages are sent
+    /// as soon as they're written instead of being held back by Nagle's algorithm, which would
+    /// skew latency measurements.
+    pub fn with_nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Sets `SO_LINGER` on the node's connections, unset by default.
+    ///
+    /// With a zero duration, closing a socket resets the connection (RST) and discards any unsent
+    /// data instead of going through the regular close sequence (FIN), e.g. to test the node's
+    /// handling of abrupt resets.
+    pub fn with_linger(mut self, linger: Duration) -> Self {
+        self.linger = Some(linger);
+        self
+    }
+
+    /// Paces the messages sent with [`SyntheticNode::unicast`] and
+    /// [`SyntheticNode::send_direct_bytes`] (and the helpers built on them) to `messages_per_sec`
+    /// on average, in bursts of at most `burst` messages, see [`TokenBucket`].
+    ///
+    /// Paced messages are queued and sent in order by a background task, so sending only fails
+    /// upfront if the peer isn't connected, or with [`ErrorKind::WouldBlock`] if
+    /// [`PACED_QUEUE_LEN`] messages are already waiting. The handshake and automatic replies aren't
+    /// paced.
+    pub fn with_rate_limit(mut self, messages_per_sec: u32, burst: u32) -> Self {
+        self.rate_limit = Some((messages_per_sec, burst));
+        self
+    }
+
+    /// Sets the timeout used by [`SyntheticNode::next_message`], defaults to [`RECV_TIMEOUT`].
+    pub fn with_recv_timeout(mut self, timeout: Duration) -> Self {
+        self.recv_timeout = timeout;
+        self
+    }
+
+    /// Caps the encoded size of the messages each peer can have read but not yet consumed (mostly
+    /// waiting in the inbound queue), so a peer can't pin unbounded memory by sending many large
+    /// messages which aren't read.
+    ///
+    /// Sizes are taken from the frames as they're read. Peers exceeding the cap fail to decode and
+    /// are disconnected, see [`DisconnectKind::InflightLimitExceeded`]. There is no cap, and
+    /// nothing is tracked, by default.
+    pub fn with_max_inflight_bytes(mut self, max_inflight_bytes: usize) -> Self {
+        self.max_inflight_bytes = Some(max_inflight_bytes);
+        self
+    }
+
+    /// Records every frame the node reads or writes, handshake included, to the file at `path`.
+    ///
+    /// The capture can be inspected with [`read_capture`] and its inbound frames fed back into the
+    /// decoder with [`replay`], see [`capture`] for the format. The file is truncated when the node
+    /// is built, so nodes built from the same builder shouldn't share it.
+    ///
+    /// [`read_capture`]: crate::tools::capture::read_capture
+    /// [`replay`]: crate::tools::capture::replay
+    /// [`capture`]: crate::tools::capture
+    pub fn with_pcap_capture(mut self, path: impl Into<PathBuf>) -> Self {
+        self.capture_path = Some(path.into());
+        self
+    }
+
     /// Sets the node's [`MessageFilter`].
     pub fn with_message_filter(mut self, filter: MessageFilter) -> Self {
         self.message_filter = filter;
         self
     }
 
+    /// Delivers [`Ping`] messages to the inbound queue instead of answering them, e.g. to play a
+    /// peer which stopped responding. Must be set after the message filter, which resets it.
+    ///
+    /// [`Ping`]: enum@crate::protocol::message::Message::Ping
+    pub fn without_auto_pong(mut self) -> Self {
+        self.message_filter = self.message_filter.with_ping_filter(Filter::Disabled);
+        self
+    }
+
+    /// Sets the magic the node encodes messages with and expects from its peers, defaults to
+    /// [`MAGIC`] (testnet).
+    ///
+    /// Nodes talking to a node under test on another network need its magic, see
+    /// [`Node::magic`](crate::setup::node::Node::magic).
+    pub fn with_network_magic(mut self, magic: [u8; MAGIC_LEN]) -> Self {
+        self.magic = magic;
+        self
+    }
+
+    /// Sets the address family the node listens on, defaults to [`AddressFamily::Ipv4`].
+    ///
+    /// Outbound connections can be made to either family regardless.
+    pub fn with_address_family(mut self, family: AddressFamily) -> Self {
+        self.network_config.listener_ip = Some(family.loopback());
+        self
+    }
+
     /// Sets the node's [`NodeConfig`].
     pub fn with_network_config(mut self, config: NodeConfig) -> Self {
         self.network_config = config;
@@ -230,7 +588,12 @@ impl SyntheticNodeBuilder {
 /// Convenient abstraction over a `pea2pea` node.
 pub struct SyntheticNode {
     inner_node: InnerNode,
-    inbound_rx: Receiver<(SocketAddr, Message)>,
+    inbound_rx: Receiver<InboundMessage>,
+    recv_timeout: Duration,
+    // Set if the outbound messages are rate limited, they're sent by the pacing task.
+    paced_tx: Option<mpsc::Sender<(SocketAddr, MessageOrBytes)>>,
+    // Batches are encoded into it, its allocation is reclaimed once the previous batch is written.
+    scratch: Mutex<BytesMut>,
 }
 
 impl SyntheticNode {
@@ -244,6 +607,36 @@ impl SyntheticNode {
         self.inner_node.handshake_info(addr)
     }
 
+    /// Returns the protocol version used on the connection with `addr`, i.e. the lower of the
+    /// versions exchanged during the handshake.
+    ///
+    /// See [`SyntheticNodeBuilder::with_protocol_version`].
+    pub fn negotiated_version(&self, addr: SocketAddr) -> Option<u32> {
+        let peer_version = self.inner_node.handshake_info(&addr)?.version.0;
+
+        Some(peer_version.min(self.inner_node.protocol_version))
+    }
+
+    /// Returns the sequence number of the last message sent or received, 0 if there was none.
+    ///
+    /// Messages are numbered from 1 in the order they're read or written, across all connections
+    /// and handshakes included. The number is the `seq` field of the node's `sent` and `received`
+    /// tracing events, and [`Record::sequence`] in captures.
+    ///
+    /// [`Record::sequence`]: crate::tools::capture::Record::sequence
+    pub fn sequence_number(&self) -> u64 {
+        self.inner_node.sequence.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if transactions are relayed by wtxid on the connection with `addr`, i.e. both
+    /// sides sent [`WtxidRelay`] during the handshake, see
+    /// [`SyntheticNodeBuilder::with_wtxid_relay`].
+    ///
+    /// [`WtxidRelay`]: enum@crate::protocol::message::Message::WtxidRelay
+    pub fn negotiated_wtxid_relay(&self, addr: SocketAddr) -> bool {
+        self.inner_node.wtxid_relay && self.inner_node.wtxid_relay_peers.lock().contains(&addr)
+    }
+
     /// Returns the listening address of the node.
     pub fn listening_addr(&self) -> SocketAddr {
         self.inner_node.node().listening_addr().unwrap()
@@ -253,18 +646,111 @@ impl SyntheticNode {
     ///
     /// If the handshake protocol is enabled it will be executed as well.
     pub async fn connect(&self, target: SocketAddr) -> io::Result<()> {
+        // Forget how a previous connection to the target was terminated.
+        self.inner_node.terminations.lock().remove(&target);
         self.inner_node.node().connect(target).await?;
 
         Ok(())
     }
 
+    /// Connects to the target address like [`SyntheticNode::connect`], retrying failed attempts as
+    /// set by `policy`. Returns the number of retries it took, or the last attempt's error once
+    /// the retries are exhausted.
+    ///
+    /// This allows reconnecting to a node which dropped the connection, or is being restarted.
+    pub async fn connect_with_retry(
+        &self,
+        target: SocketAddr,
+        policy: RetryPolicy,
+    ) -> io::Result<usize> {
+        let mut retry = 0;
+        loop {
+            match self.connect(target).await {
+                Ok(()) => return Ok(retry),
+                Err(e) if retry >= policy.max_retries => return Err(e),
+                Err(e) => {
+                    let delay = policy.delay(retry);
+                    debug!(
+                        parent: self.inner_node.node().span(),
+                        "connecting to {target} failed ({e}), retrying in {delay:?}"
+                    );
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+            }
+        }
+    }
+
     /// Disconnects from the target address.
     ///
     /// Returns `true` if an actual disconnect took place.
     pub async fn disconnect(&self, target: SocketAddr) -> bool {
+        if self.is_connected(target) {
+            self.inner_node
+                .terminations
+                .lock()
+                .insert(target, DisconnectKind::Local);
+        }
+
         self.inner_node.node().disconnect(target).await
     }
 
+    /// Closes the connection to the target address gracefully: shuts down the write half (FIN) and
+    /// waits for the peer to close its own, i.e. reads to the end of the stream.
+    ///
+    /// Returns an error if there is no such connection, or if the peer doesn't close it within
+    /// `duration`, in which case it's disconnected locally.
+    pub async fn disconnect_clean(&self, target: SocketAddr, duration: Duration) -> io::Result<()> {
+        let shutdown = self
+            .inner_node
+            .sockets
+            .lock()
+            .get(&target)
+            .map(|socket| socket.shutdown(Shutdown::Write));
+        match shutdown {
+            Some(result) => result?,
+            None => return Err(ErrorKind::NotConnected.into()),
+        }
+
+        let start = Instant::now();
+        while self.is_connected(target) {
+            if start.elapsed() > duration {
+                self.disconnect(target).await;
+                return Err(Error::new(
+                    ErrorKind::TimedOut,
+                    format!("{target} didn't close the connection"),
+                ));
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        Ok(())
+    }
+
+    /// Closes the connection to the target address abruptly, resetting it (RST) rather than
+    /// going through the regular close sequence, see [`SyntheticNodeBuilder::with_linger`].
+    ///
+    /// Returns `true` if an actual disconnect took place.
+    pub async fn disconnect_abrupt(&self, target: SocketAddr) -> bool {
+        let socket = self.inner_node.sockets.lock().remove(&target);
+        if let Some(socket) = socket {
+            // The option is set on the socket shared by both handles, it's reset once the last
+            // one is closed.
+            if let Err(e) = socket.set_linger(Some(Duration::ZERO)) {
+                warn!(parent: self.inner_node.node().span(), "couldn't reset {target}: {e}");
+            }
+        }
+
+        self.disconnect(target).await
+    }
+
+    /// Returns how the connection to `addr` was terminated, or `None` if it is still active (or
+    /// never existed).
+    pub fn disconnect_kind(&self, addr: SocketAddr) -> Option<DisconnectKind> {
+        self.inner_node.terminations.lock().get(&addr).copied()
+    }
+
     /// Indicates if the `addr` is registered as a connected peer.
     pub fn is_connected(&self, addr: SocketAddr) -> bool {
         self.inner_node.node().is_connected(addr)
@@ -298,20 +784,63 @@ impl SyntheticNode {
         }
     }
 
+    /// Waits until the node has at least one connection and returns its SocketAddr, failing with
+    /// [`ErrorKind::TimedOut`] if there is none before the timeout duration has elapsed.
+    pub async fn wait_for_connection_timeout(&self, duration: Duration) -> io::Result<SocketAddr> {
+        timeout(duration, self.wait_for_connection())
+            .await
+            .map_err(|_| {
+                Error::new(
+                    ErrorKind::TimedOut,
+                    format!("no connection after {0:.3}s", duration.as_secs_f64()),
+                )
+            })
+    }
+
     /// Sends a direct message to the target address.
     pub fn unicast(&self, target: SocketAddr, message: Message) -> io::Result<()> {
-        self.inner_node
-            .unicast(target, MessageOrBytes::Message(message.into()))?;
-
-        Ok(())
+        self.send(target, MessageOrBytes::Message(message.into()))
     }
 
     /// Sends bytes directly to the target address.
     pub fn send_direct_bytes(&self, target: SocketAddr, data: Vec<u8>) -> io::Result<()> {
-        self.inner_node
-            .unicast(target, MessageOrBytes::Bytes(data))?;
+        self.send(target, MessageOrBytes::Bytes(data.into()))
+    }
 
-        Ok(())
+    /// Encodes `messages` back to back into a buffer reused across batches, and sends them to the
+    /// target address with a single write.
+    ///
+    /// Meant for flooding: there is one allocation and one write per batch rather than per
+    /// message. The batch is sent, logged, numbered (and rate limited) as one message.
+    pub fn unicast_batch(&self, target: SocketAddr, messages: &[Message]) -> io::Result<()> {
+        let batch = encode_batch(&mut self.scratch.lock(), self.inner_node.magic, messages)?;
+        self.send(target, MessageOrBytes::Bytes(batch))
+    }
+
+    /// Sends `message` right away, or queues it for the pacing task if rate limited.
+    fn send(&self, target: SocketAddr, message: MessageOrBytes) -> io::Result<()> {
+        let Some(paced_tx) = &self.paced_tx else {
+            self.inner_node.unicast(target, message)?;
+            return Ok(());
+        };
+
+        // The actual send happens later, only a missing connection can be reported now.
+        if !self.is_connected(target) {
+            return Err(Error::new(
+                ErrorKind::NotConnected,
+                format!("not connected to {target}"),
+            ));
+        }
+
+        paced_tx.try_send((target, message)).map_err(|e| match e {
+            TrySendError::Full(_) => Error::new(
+                ErrorKind::WouldBlock,
+                format!("{PACED_QUEUE_LEN} paced messages are already queued"),
+            ),
+            TrySendError::Closed(_) => {
+                Error::new(ErrorKind::BrokenPipe, "the pacing task has stopped")
+            }
+        })
     }
 
     /// Reads a message from the inbound (internal) queue of the node.
@@ -320,7 +849,10 @@ impl SyntheticNode {
     /// Messages are sent to the queue when unfiltered by the message filter.
     pub async fn recv_message(&mut self) -> (SocketAddr, Message) {
         match self.inbound_rx.recv().await {
-            Some(message) => message,
+            Some((source, message, len)) => {
+                self.inner_node.release_inflight(source, len);
+                (source, message)
+            }
             None => panic!("all senders dropped!"),
         }
     }
@@ -330,10 +862,52 @@ impl SyntheticNode {
     ///
     /// Messages are sent to the queue when unfiltered by the message filter.
     pub async fn try_recv_message(&mut self) -> Result<(SocketAddr, Message), ConnectionError> {
-        self.inbound_rx
+        let (source, message, len) = self
+            .inbound_rx
             .recv()
             .await
-            .ok_or(ConnectionError::ConnectionDropped)
+            .ok_or(ConnectionError::ConnectionDropped)?;
+        self.inner_node.release_inflight(source, len);
+
+        Ok((source, message))
+    }
+
+    /// Drops the messages currently in the inbound (internal) queue of the node, returning how many
+    /// there were.
+    pub fn discard_messages(&mut self) -> usize {
+        let mut discarded = 0;
+        while let Ok((source, _, len)) = self.inbound_rx.try_recv() {
+            self.inner_node.release_inflight(source, len);
+            discarded += 1;
+        }
+
+        discarded
+    }
+
+    /// Reads the messages in the inbound (internal) queue of the node, along with those arriving
+    /// shortly after, and returns them in order.
+    ///
+    /// Queued messages are taken without waiting. The drain ends once no message has arrived for
+    /// [`DRAIN_QUIET_PERIOD`], which restarts with each message: a burst of replies is read in
+    /// full however long it lasts, while an idle queue only costs a single quiet period.
+    pub async fn drain_available(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+
+        loop {
+            while let Ok((source, message, len)) = self.inbound_rx.try_recv() {
+                self.inner_node.release_inflight(source, len);
+                messages.push(message);
+            }
+
+            match timeout(DRAIN_QUIET_PERIOD, self.inbound_rx.recv()).await {
+                Ok(Some((source, message, len))) => {
+                    self.inner_node.release_inflight(source, len);
+                    messages.push(message);
+                }
+                // The queue stayed empty, or every sender was dropped.
+                Err(_) | Ok(None) => return messages,
+            }
+        }
     }
 
     // Attempts to read a message from the inbound (internal) queue of the node before the timeout
@@ -355,6 +929,13 @@ impl SyntheticNode {
         }
     }
 
+    /// Reads the next message from the inbound (internal) queue of the node, failing with
+    /// [`ErrorKind::TimedOut`] if none arrives within the node's receive timeout, see
+    /// [`SyntheticNodeBuilder::with_recv_timeout`].
+    pub async fn next_message(&mut self) -> io::Result<(SocketAddr, Message)> {
+        self.recv_message_timeout(self.recv_timeout).await
+    }
+
     /// Sends [`Ping`], and expects [`Pong`] with a matching [`Nonce`] in reply.
     ///
     /// Uses polling to check that connection is still alive. Returns a [`PingPongError`] if:
@@ -376,17 +957,38 @@ impl SyntheticNode {
         &mut self,
         target: SocketAddr,
         duration: Duration,
+    ) -> Result<(), PingPongError> {
+        self.ping_pong_nonces_timeout(target, &[Nonce::default()], duration)
+            .await
+    }
+
+    /// Sends a [`Ping`] for each of the (distinct) `nonces` before reading any reply, and expects
+    /// a [`Pong`] for each of them, in any order.
+    ///
+    /// Fails like [`ping_pong_timeout`](Self::ping_pong_timeout), a [`Pong`] for a nonce which
+    /// isn't outstanding (e.g. answered twice) is [`Unexpected`](PingPongError::Unexpected).
+    ///
+    /// [`Ping`]: enum@crate::protocol::message::Message::Ping
+    /// [`Pong`]: enum@crate::protocol::message::Message::Pong
+    pub async fn ping_pong_nonces_timeout(
+        &mut self,
+        target: SocketAddr,
+        nonces: &[Nonce],
+        duration: Duration,
     ) -> Result<(), PingPongError> {
         const SLEEP: Duration = Duration::from_millis(10);
 
         let now = std::time::Instant::now();
-        let ping_nonce = Nonce::default();
-        if let Err(err) = self.unicast(target, Message::Ping(ping_nonce)) {
-            if !self.is_connected(target) {
-                return Err(PingPongError::ConnectionAborted);
-            } else {
-                return Err(PingPongError::IoErr(err));
+        let mut outstanding = HashSet::with_capacity(nonces.len());
+        for &nonce in nonces {
+            if let Err(err) = self.unicast(target, Message::Ping(nonce)) {
+                if !self.is_connected(target) || is_connection_terminated(&err) {
+                    return Err(PingPongError::ConnectionAborted);
+                } else {
+                    return Err(PingPongError::IoErr(err));
+                }
             }
+            outstanding.insert(nonce);
         }
 
         while now.elapsed() < duration {
@@ -397,8 +999,10 @@ impl SyntheticNode {
                         return Err(PingPongError::ConnectionAborted);
                     }
                 }
-                Ok((_, Message::Pong(nonce))) if nonce == ping_nonce => {
-                    return Ok(());
+                Ok((_, Message::Pong(nonce))) if outstanding.remove(&nonce) => {
+                    if outstanding.is_empty() {
+                        return Ok(());
+                    }
                 }
                 Ok((_, message)) => {
                     return Err(PingPongError::Unexpected(message.into()));
@@ -409,22 +1013,151 @@ impl SyntheticNode {
         Err(PingPongError::Timeout(duration))
     }
 
-    /// Waits for the target to disconnect by sending a [`Ping`] request. Errors if
-    /// the target responds or doesn't disconnect within the timeout.
+    /// Sends [`GetData`] for `inv` and collects the reply, which may span several messages: the
+    /// data of each item, in any order, and a [`NotFound`] for those the target doesn't have.
+    ///
+    /// Waits up to the node's receive timeout for each message, see
+    /// [`SyntheticNodeBuilder::with_recv_timeout`]. Messages from other peers, and those which
+    /// aren't part of the reply, are skipped.
+    ///
+    /// [`GetData`]: enum@crate::protocol::message::Message::GetData
+    /// [`NotFound`]: enum@crate::protocol::message::Message::NotFound
+    pub async fn get_data(&mut self, target: SocketAddr, inv: Inv) -> GetDataResult {
+        const SLEEP: Duration = Duration::from_millis(10);
+
+        let mut pending = inv.inventory.clone();
+        let mut found = Vec::new();
+        let mut not_found = Vec::new();
+
+        if self.unicast(target, Message::GetData(inv)).is_err() {
+            return GetDataResult::Disconnected {
+                found,
+                missing: pending,
+            };
+        }
+
+        let mut last_reply = std::time::Instant::now();
+        while !pending.is_empty() {
+            match self.recv_message_timeout(SLEEP).await {
+                Ok((source, message)) if source == target => {
+                    if let Message::NotFound(inv) = &message {
+                        for item in &inv.inventory {
+                            if let Some(i) = pending.iter().position(|pending| pending == item) {
+                                not_found.push(pending.remove(i));
+                            }
+                        }
+                    } else if let Some(i) = requested_item(&pending, &message) {
+                        pending.remove(i);
+                        found.push(message);
+                    } else {
+                        continue;
+                    }
+                    last_reply = std::time::Instant::now();
+                }
+                Ok(_) => continue,
+                // Check that connection is still alive, so that we can exit sooner.
+                Err(_) if !self.is_connected(target) => {
+                    return GetDataResult::Disconnected {
+                        found,
+                        missing: pending,
+                    };
+                }
+                Err(_) if last_reply.elapsed() >= self.recv_timeout => {
+                    return GetDataResult::TimedOut {
+                        found,
+                        missing: pending,
+                    };
+                }
+                Err(_) => {}
+            }
+        }
+
+        if not_found.is_empty() {
+            GetDataResult::Found(found)
+        } else {
+            GetDataResult::NotFound { found, not_found }
+        }
+    }
+
+    /// Fetches the block at `height` in the target's best chain: its hash is resolved with the
+    /// `getblockhash` RPC, and the block itself requested with [`get_data`](Self::get_data).
+    ///
+    /// `rpc` should be the target's RPC client, see [`Node::rpc`](crate::setup::node::Node::rpc).
+    pub async fn fetch_block_at_height(
+        &mut self,
+        rpc: &RpcClient,
+        target: SocketAddr,
+        height: u32,
+    ) -> io::Result<Block> {
+        let hash = rpc.get_block_hash(height).await?;
+
+        match self
+            .get_data(target, Inv::new(vec![InvHash::Block(hash)]))
+            .await
+        {
+            GetDataResult::Found(mut found) => match found.pop() {
+                Some(Message::Block(block)) => Ok(*block),
+                message => Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("expected the block at height {height}, got {message:?}"),
+                )),
+            },
+            GetDataResult::NotFound { .. } => Err(Error::new(
+                ErrorKind::NotFound,
+                format!("the block at height {height} wasn't found"),
+            )),
+            GetDataResult::TimedOut { .. } => Err(Error::new(
+                ErrorKind::TimedOut,
+                format!("the block at height {height} wasn't received"),
+            )),
+            GetDataResult::Disconnected { .. } => Err(Error::new(
+                ErrorKind::ConnectionAborted,
+                format!("disconnected while fetching the block at height {height}"),
+            )),
+        }
+    }
+
+    /// Waits for the target to disconnect by sending a [`Ping`] request, and returns how the
+    /// connection was terminated. Errors if the target responds or doesn't disconnect within the
+    /// timeout.
     ///
     /// [`Ping`]: enum@crate::protocol::message::Message::Ping
     pub async fn wait_for_disconnect(
         &mut self,
         target: SocketAddr,
         duration: Duration,
-    ) -> io::Result<()> {
+    ) -> io::Result<DisconnectKind> {
         match self.ping_pong_timeout(target, duration).await {
             Ok(_) => Err(Error::new(ErrorKind::Other, "connection still active")),
-            Err(PingPongError::ConnectionAborted) => Ok(()),
+            // The termination is recorded before the connection is unregistered.
+            Err(PingPongError::ConnectionAborted) => Ok(self
+                .disconnect_kind(target)
+                .unwrap_or(DisconnectKind::Aborted)),
             Err(err) => Err(err.into()),
         }
     }
 
+    /// Attempts a fresh connection to the target and returns `true` if it was refused, i.e. the
+    /// connection or handshake failed, or the target dropped it instead of answering a [`Ping`].
+    ///
+    /// The node should enable a handshake and mustn't already be connected to the target, the
+    /// connection is closed again if it was accepted.
+    ///
+    /// [`Ping`]: enum@crate::protocol::message::Message::Ping
+    pub async fn is_banned(&mut self, target: SocketAddr) -> bool {
+        if self.connect(target).await.is_err() {
+            return true;
+        }
+
+        let banned = matches!(
+            self.ping_pong_timeout(target, DISCONNECT_TIMEOUT).await,
+            Err(PingPongError::ConnectionAborted)
+        );
+        self.disconnect(target).await;
+
+        banned
+    }
+
     /// Gracefully shuts down the node.
     pub async fn shut_down(&self) {
         self.inner_node.node().shut_down().await
@@ -435,29 +1168,66 @@ impl SyntheticNode {
 struct InnerNode {
     node: Node,
     handshake: Option<HandshakeKind>,
-    inbound_tx: Sender<(SocketAddr, Message)>,
+    inbound_tx: Sender<InboundMessage>,
     message_filter: MessageFilter,
     handshake_infos: Arc<Mutex<HashMap<SocketAddr, Version>>>,
+    terminations: Terminations,
+    magic: [u8; MAGIC_LEN],
+    send_headers: bool,
+    wtxid_relay: bool,
+    // The peers which sent `WtxidRelay` during the handshake.
+    wtxid_relay_peers: Arc<Mutex<HashSet<SocketAddr>>>,
+    // The encoded size of each peer's messages in the inbound queue, and its cap.
+    inflight_bytes: Arc<Mutex<HashMap<SocketAddr, usize>>>,
+    max_inflight_bytes: Option<usize>,
+    capture: Option<Arc<CaptureWriter>>,
+    // The sequence number of the last message read or written.
+    sequence: Arc<AtomicU64>,
+    // When each connection was set up, message events are timed relative to it.
+    connection_starts: Arc<Mutex<HashMap<SocketAddr, Instant>>>,
+    // A handle on each connection's socket, which pea2pea doesn't expose once it's set up.
+    sockets: Arc<Mutex<HashMap<SocketAddr, Socket>>>,
+    services: u64,
+    protocol_version: u32,
+    version_nonce: Option<Nonce>,
+    nodelay: bool,
+    linger: Option<Duration>,
 }
 
 impl InnerNode {
+    /// Creates the node with the settings from `builder`, `capture` is opened from its path.
     async fn new(
         node: Node,
-        tx: Sender<(SocketAddr, Message)>,
-        message_filter: MessageFilter,
-        handshake: Option<HandshakeKind>,
+        tx: Sender<InboundMessage>,
+        builder: &SyntheticNodeBuilder,
+        capture: Option<Arc<CaptureWriter>>,
     ) -> Self {
         let node = Self {
             node,
-            handshake,
+            handshake: builder.handshake,
             inbound_tx: tx,
-            message_filter,
+            message_filter: builder.message_filter.clone(),
             handshake_infos: Default::default(),
+            terminations: Default::default(),
+            magic: builder.magic,
+            send_headers: builder.send_headers,
+            wtxid_relay: builder.wtxid_relay,
+            wtxid_relay_peers: Default::default(),
+            inflight_bytes: Default::default(),
+            max_inflight_bytes: builder.max_inflight_bytes,
+            capture,
+            sequence: Default::default(),
+            connection_starts: Default::default(),
+            sockets: Default::default(),
+            services: builder.services,
+            protocol_version: builder.protocol_version,
+            version_nonce: builder.version_nonce,
+            nodelay: builder.nodelay,
+            linger: builder.linger,
         };
 
-        if handshake.is_some() {
-            node.enable_handshake().await;
-        }
+        // Also sets the socket options, so it's enabled without a handshake too.
+        node.enable_handshake().await;
 
         node
     }
@@ -465,6 +1235,61 @@ impl InnerNode {
     fn handshake_info(&self, addr: &SocketAddr) -> Option<Version> {
         Some(self.handshake_infos.lock().get(addr)?.clone())
     }
+
+    /// Returns the [`Version`] sent to `addr_recv` during the handshake.
+    fn own_version(&self, addr_recv: SocketAddr) -> Message {
+        let own_listening_addr = self.node().listening_addr().unwrap();
+        let mut version = Version::new(addr_recv, own_listening_addr)
+            .with_services(self.services)
+            .with_version(self.protocol_version);
+        if let Some(nonce) = self.version_nonce {
+            version = version.with_nonce(nonce);
+        }
+
+        Message::Version(version)
+    }
+
+    /// Returns the cap on unread bytes from `addr`, if there is one.
+    fn inflight_limit(&self, addr: SocketAddr) -> Option<InflightLimit> {
+        Some(InflightLimit {
+            addr,
+            bytes: self.inflight_bytes.clone(),
+            max: self.max_inflight_bytes?,
+        })
+    }
+
+    /// Accounts for a message of `len` bytes from `source` being consumed, see [`InflightLimit`].
+    fn release_inflight(&self, source: SocketAddr, len: usize) {
+        // Nothing is tracked without a cap.
+        if self.max_inflight_bytes.is_none() {
+            return;
+        }
+
+        if let Some(inflight) = self.inflight_bytes.lock().get_mut(&source) {
+            *inflight = inflight.saturating_sub(len);
+        }
+    }
+
+    /// Returns a span identifying the connection with `addr`, nested in the node's span.
+    fn connection_span(&self, addr: SocketAddr) -> Span {
+        info_span!(parent: self.node().span(), "conn", peer = %addr)
+    }
+
+    /// Returns a codec for the connection with `addr`.
+    fn connection_codec(&self, addr: SocketAddr) -> MessageCodec {
+        // The first codec (usually the handshake's) sets the start for the whole connection.
+        let started_at = *self
+            .connection_starts
+            .lock()
+            .entry(addr)
+            .or_insert_with(Instant::now);
+
+        MessageCodec::new(self.magic)
+            .with_span(self.connection_span(addr))
+            .with_capture(self.capture.clone())
+            .with_sequence(self.sequence.clone())
+            .with_start(started_at)
+    }
 }
 
 impl Pea2Pea for InnerNode {
@@ -476,39 +1301,217 @@ impl Pea2Pea for InnerNode {
 // TODO: move to protocol
 pub struct MessageCodec {
     codec: LengthDelimitedCodec,
+    magic: [u8; MAGIC_LEN],
+    // Set for reading codecs, to record a clean termination on EOF.
+    termination: Option<(SocketAddr, Terminations)>,
+    // The span of the connection the codec is used for, message events are emitted within it.
+    span: Span,
+    // Set if the node records the frames it reads and writes.
+    capture: Option<Arc<CaptureWriter>>,
+    // The sequence number of the last message read or written, shared by the node's codecs.
+    sequence: Arc<AtomicU64>,
+    // When the connection was set up, shared by the connection's codecs.
+    started_at: Instant,
+    // Set for reading codecs if the node caps the peer's unread bytes.
+    inflight_limit: Option<InflightLimit>,
 }
 
 impl Default for MessageCodec {
     fn default() -> Self {
+        Self::new(MAGIC)
+    }
+}
+
+impl MessageCodec {
+    /// Creates a codec for the network identified by `magic`, messages with a different magic
+    /// fail to decode with [`WrongNetworkMagic`](crate::protocol::message::WrongNetworkMagic).
+    pub fn new(magic: [u8; MAGIC_LEN]) -> Self {
         Self {
             codec: LengthDelimitedCodec::builder()
                 .length_adjustment(24)
                 .length_field_offset(16)
                 .little_endian()
                 .num_skip(0)
-                // The biggest currently observed frame was 627412 bytes long, so taking some reserve
-                // to catch frames up to 1MB.
-                .max_frame_length(1048576)
+                // Longer bodies are rejected with `BodyTooLong` before this limit is hit.
+                .max_frame_length(HEADER_LEN + MAX_MESSAGE_LEN)
                 .new_codec(),
+            magic,
+            termination: None,
+            span: Span::none(),
+            capture: None,
+            sequence: Default::default(),
+            started_at: Instant::now(),
+            inflight_limit: None,
+        }
+    }
+
+    fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    fn track_termination(mut self, addr: SocketAddr, terminations: Terminations) -> Self {
+        self.termination = Some((addr, terminations));
+        self
+    }
+
+    fn with_capture(mut self, capture: Option<Arc<CaptureWriter>>) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    fn with_sequence(mut self, sequence: Arc<AtomicU64>) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    fn with_start(mut self, started_at: Instant) -> Self {
+        self.started_at = started_at;
+        self
+    }
+
+    fn with_inflight_limit(mut self, inflight_limit: Option<InflightLimit>) -> Self {
+        self.inflight_limit = inflight_limit;
+        self
+    }
+
+    /// Returns the microseconds elapsed since the connection was set up, on a monotonic clock.
+    fn elapsed_us(&self) -> u64 {
+        self.started_at.elapsed().as_micros() as u64
+    }
+
+    /// Assigns the next sequence number to a message read or written.
+    fn next_sequence(&self) -> u64 {
+        self.sequence.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Writes `message` as is.
+    fn encode_raw(&mut self, message: &[u8], dst: &mut BytesMut) -> io::Result<()> {
+        let seq = self.next_sequence();
+        debug!(
+            parent: &self.span,
+            seq,
+            elapsed_us = self.elapsed_us(),
+            bytes = message.len(),
+            "sent raw bytes"
+        );
+        self.record(Direction::Outbound, seq, message);
+        dst.put_slice(message);
+
+        Ok(())
+    }
+
+    /// Records `frame` if capturing, a failed write is logged but doesn't affect the connection.
+    fn record(&self, direction: Direction, sequence: u64, frame: &[u8]) {
+        if let Some(capture) = &self.capture {
+            if let Err(e) = capture.record(direction, sequence, frame) {
+                warn!(parent: &self.span, "failed to record a frame: {e}");
+            }
         }
     }
 }
 
-impl Decoder for MessageCodec {
-    type Item = Message;
-    type Error = io::Error;
+impl MessageCodec {
+    /// Decodes the next message, along with the length of its frame.
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<(Message, usize)>> {
+        // Reject oversized bodies as soon as the header is in, without waiting for (and buffering)
+        // the body.
+        if src.len() >= HEADER_LEN {
+            MessageHeader::decode_with_magic(&mut &src[..HEADER_LEN], self.magic)?
+                .check_body_length()?;
+        }
 
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // Frozen, the frame is shared rather than copied by the parts of the message kept as raw
+        // bytes (e.g. the bodies of unknown messages).
         let mut bytes = if let Some(bytes) = self.codec.decode(src)? {
-            bytes
+            bytes.freeze()
         } else {
             return Ok(None);
         };
+        let len = bytes.len();
+        if let Some(limit) = &self.inflight_limit {
+            if !limit.reserve(len) {
+                if let Some((addr, terminations)) = &self.termination {
+                    terminations
+                        .lock()
+                        .insert(*addr, DisconnectKind::InflightLimitExceeded);
+                }
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "the inbound queue holds too many unread bytes",
+                ));
+            }
+        }
 
-        let header = MessageHeader::decode(&mut bytes)?;
+        let seq = self.next_sequence();
+        self.record(Direction::Inbound, seq, &bytes);
+
+        let header = MessageHeader::decode_with_magic(&mut bytes, self.magic)?;
+        debug!(
+            parent: &self.span,
+            seq,
+            elapsed_us = self.elapsed_us(),
+            command = command_name(&header.command),
+            bytes = HEADER_LEN + header.body_length as usize,
+            "received"
+        );
         let message = Message::decode(header.command, &mut bytes)?;
 
-        Ok(Some(message))
+        Ok(Some((message, len)))
+    }
+
+    /// Decodes the last message once the stream has ended, see [`MessageCodec::decode_frame`].
+    fn decode_frame_eof(&mut self, src: &mut BytesMut) -> io::Result<Option<(Message, usize)>> {
+        if let Some(frame) = self.decode_frame(src)? {
+            return Ok(Some(frame));
+        }
+
+        // The peer closed its side of the connection (FIN), a reset would surface as a read error
+        // instead.
+        if let Some((addr, terminations)) = &self.termination {
+            terminations
+                .lock()
+                .entry(*addr)
+                .or_insert(DisconnectKind::Clean);
+        }
+
+        if src.is_empty() {
+            Ok(None)
+        } else {
+            Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("the stream ended {} bytes into a message", src.len()),
+            ))
+        }
+    }
+}
+
+impl Decoder for MessageCodec {
+    type Item = Message;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.decode_frame(src)?.map(|(message, _)| message))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.decode_frame_eof(src)?.map(|(message, _)| message))
+    }
+}
+
+/// The codec of a node's reading side, which also yields the length of each message's frame.
+struct ReadingCodec(MessageCodec);
+
+impl Decoder for ReadingCodec {
+    type Item = (Message, usize);
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.0.decode_frame(src)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.0.decode_frame_eof(src)
     }
 }
 
@@ -516,9 +1519,7 @@ impl Encoder<Vec<u8>> for MessageCodec {
     type Error = io::Error;
 
     fn encode(&mut self, message: Vec<u8>, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        dst.put_slice(&message);
-
-        Ok(())
+        self.encode_raw(&message, dst)
     }
 }
 
@@ -526,7 +1527,24 @@ impl Encoder<Message> for MessageCodec {
     type Error = io::Error;
 
     fn encode(&mut self, message: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        message.encode(dst)
+        let start = dst.len();
+        message.encode(self.magic, dst)?;
+
+        // Some messages (e.g. alerts) aren't sent at all.
+        if let Some(command) = dst.get(start + MAGIC_LEN..start + MAGIC_LEN + COMMAND_LEN) {
+            let seq = self.next_sequence();
+            debug!(
+                parent: &self.span,
+                seq,
+                elapsed_us = self.elapsed_us(),
+                command = command_name(command),
+                bytes = dst.len() - start,
+                "sent"
+            );
+            self.record(Direction::Outbound, seq, &dst[start..]);
+        }
+
+        Ok(())
     }
 }
 
@@ -535,33 +1553,92 @@ impl Encoder<MessageOrBytes> for MessageCodec {
 
     fn encode(&mut self, message: MessageOrBytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
         match message {
-            MessageOrBytes::Bytes(message) => Encoder::<Vec<u8>>::encode(self, message, dst),
+            MessageOrBytes::Bytes(message) => self.encode_raw(&message, dst),
             MessageOrBytes::Message(message) => Encoder::<Message>::encode(self, *message, dst),
         }
     }
 }
 
+/// Encodes `messages` back to back into `scratch`, and splits them off as a single frame.
+fn encode_batch(
+    scratch: &mut BytesMut,
+    magic: [u8; MAGIC_LEN],
+    messages: &[Message],
+) -> io::Result<Bytes> {
+    for message in messages {
+        if let Err(e) = message.encode(magic, scratch) {
+            scratch.clear();
+            return Err(e);
+        }
+    }
+
+    Ok(scratch.split().freeze())
+}
+
+/// Returns the printable part of a message header's command.
+fn command_name(command: &[u8]) -> &str {
+    std::str::from_utf8(command)
+        .unwrap_or("<invalid>")
+        .trim_end_matches('\0')
+}
+
+/// The most paced messages waiting to be sent, see [`SyntheticNodeBuilder::with_rate_limit`].
+pub const PACED_QUEUE_LEN: usize = 1024;
+
+/// Spawns the task sending the messages of a rate limited node, in order, as `bucket` allows.
+fn spawn_pacer(
+    inner_node: InnerNode,
+    mut bucket: TokenBucket,
+) -> mpsc::Sender<(SocketAddr, MessageOrBytes)> {
+    let (tx, mut rx) = mpsc::channel::<(SocketAddr, MessageOrBytes)>(PACED_QUEUE_LEN);
+
+    // Stops once the node is dropped, along with the sender.
+    tokio::spawn(async move {
+        while let Some((target, message)) = rx.recv().await {
+            bucket.acquire().await;
+            if let Err(e) = inner_node.unicast(target, message) {
+                warn!(
+                    parent: inner_node.node().span(),
+                    "couldn't send a paced message to {target}: {e}"
+                );
+            }
+        }
+    });
+
+    tx
+}
+
 // TODO: move to protocol
 enum MessageOrBytes {
     Message(Box<Message>),
-    Bytes(Vec<u8>),
+    Bytes(Bytes),
 }
 
 #[async_trait::async_trait]
 impl Reading for InnerNode {
-    type Message = Message;
-    type Codec = MessageCodec;
-
-    fn codec(&self, _addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
-        Default::default()
+    type Message = (Message, usize);
+    type Codec = ReadingCodec;
+
+    fn codec(&self, addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
+        ReadingCodec(
+            self.connection_codec(addr)
+                .track_termination(addr, self.terminations.clone())
+                .with_inflight_limit(self.inflight_limit(addr)),
+        )
     }
 
-    async fn process_message(&self, source: SocketAddr, message: Self::Message) -> io::Result<()> {
-        let span = self.node().span().clone();
+    async fn process_message(
+        &self,
+        source: SocketAddr,
+        (message, len): Self::Message,
+    ) -> io::Result<()> {
+        let span = self.connection_span(source);
 
         info!(parent: span.clone(), "processing {:?}", message);
         match self.message_filter.message_filter_type(&message) {
             Filter::AutoReply => {
+                self.release_inflight(source, len);
+
                 // Autoreply with the appropriate response.
                 let response = self.message_filter.reply_message(&message);
 
@@ -576,12 +1653,14 @@ impl Reading for InnerNode {
                     "sending the message to the node's inbound queue"
                 );
                 self.inbound_tx
-                    .send((source, message))
+                    .send((source, message, len))
                     .await
                     .map_err(|e| io::Error::new(io::ErrorKind::ConnectionAborted, e))?;
             }
 
             Filter::Enabled => {
+                self.release_inflight(source, len);
+
                 // Ignore the message.
                 debug!(parent: span, "message was ignored by the filter");
             }
@@ -595,8 +1674,8 @@ impl Writing for InnerNode {
     type Message = MessageOrBytes;
     type Codec = MessageCodec;
 
-    fn codec(&self, _addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
-        Default::default()
+    fn codec(&self, addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
+        self.connection_codec(addr)
     }
 }
 
@@ -606,26 +1685,49 @@ impl Handshake for InnerNode {
         let mut version_data: Option<Version> = None;
         let node_conn_side = !conn.side();
         let conn_addr = conn.addr();
-        let own_listening_addr = self.node().listening_addr().unwrap();
-        let mut framed_stream = Framed::new(self.borrow_stream(&mut conn), MessageCodec::default());
+
+        let stream = self.borrow_stream(&mut conn);
+        stream.set_nodelay(self.nodelay)?;
+        if self.linger.is_some() {
+            stream.set_linger(self.linger)?;
+        }
+        // Registered once the handshake succeeds, until then it's closed along with `conn`.
+        let socket = SockRef::from(&*stream).try_clone()?;
+
+        let mut framed_stream = Framed::new(stream, self.connection_codec(conn_addr));
 
         match (self.handshake, node_conn_side) {
             (Some(HandshakeKind::Full), ConnectionSide::Initiator) => {
                 // Send and receive Version.
-                let own_version = Message::Version(Version::new(conn_addr, own_listening_addr));
+                let own_version = self.own_version(conn_addr);
                 framed_stream.send(own_version).await?;
 
                 let peer_version = framed_stream.try_next().await?;
                 match peer_version {
                     Some(Message::Version(version)) => {
+                        if self.wtxid_relay {
+                            framed_stream.send(Message::WtxidRelay).await?;
+                        }
+
                         // Send and receive Verack.
                         framed_stream.send(Message::Verack).await?;
 
-                        let peer_verack = framed_stream.try_next().await?;
+                        let mut peer_verack = framed_stream.try_next().await?;
+                        if peer_verack == Some(Message::WtxidRelay) {
+                            self.wtxid_relay_peers.lock().insert(conn_addr);
+                            peer_verack = framed_stream.try_next().await?;
+                        }
                         assert_matches!(peer_verack, Some(Message::Verack));
 
                         version_data = Some(version);
                     }
+                    // E.g. an obsolete version, the peer disconnects right after.
+                    Some(Message::Reject(reject)) => {
+                        return Err(io::Error::new(
+                            ErrorKind::ConnectionRefused,
+                            format!("the version was rejected: {reject:?}"),
+                        ));
+                    }
                     Some(other) => {
                         let span = self.node().span().clone();
                         error!(
@@ -660,22 +1762,36 @@ impl Handshake for InnerNode {
                     None => return Err(io::ErrorKind::InvalidData.into()),
                 };
 
-                let own_version = Message::Version(Version::new(node_addr, own_listening_addr));
+                let own_version = self.own_version(node_addr);
                 framed_stream.send(own_version).await?;
+                if self.wtxid_relay {
+                    framed_stream.send(Message::WtxidRelay).await?;
+                }
 
                 // Receive and send Verack.
-                let peer_verack = framed_stream.try_next().await?;
+                let mut peer_verack = framed_stream.try_next().await?;
+                if peer_verack == Some(Message::WtxidRelay) {
+                    self.wtxid_relay_peers.lock().insert(conn_addr);
+                    peer_verack = framed_stream.try_next().await?;
+                }
                 assert_matches!(peer_verack, Some(Message::Verack));
 
                 framed_stream.send(Message::Verack).await?;
             }
             (Some(HandshakeKind::VersionOnly), ConnectionSide::Initiator) => {
-                let own_version = Message::Version(Version::new(conn_addr, own_listening_addr));
+                let own_version = self.own_version(conn_addr);
                 framed_stream.send(own_version).await?;
 
                 let peer_version = framed_stream.try_next().await?;
                 match peer_version {
                     Some(Message::Version(version)) => version_data = Some(version),
+                    // E.g. an obsolete version, the peer disconnects right after.
+                    Some(Message::Reject(reject)) => {
+                        return Err(io::Error::new(
+                            ErrorKind::ConnectionRefused,
+                            format!("the version was rejected: {reject:?}"),
+                        ));
+                    }
                     Some(other) => {
                         let span = self.node().span().clone();
                         error!(
@@ -707,17 +1823,48 @@ impl Handshake for InnerNode {
                     None => return Err(io::ErrorKind::InvalidData.into()),
                 };
 
-                let own_version = Message::Version(Version::new(node_addr, own_listening_addr));
+                let own_version = self.own_version(node_addr);
+                framed_stream.send(own_version).await?;
+            }
+            (Some(HandshakeKind::Partial), ConnectionSide::Initiator) => {
+                // Stop after sending Version, the peer's reply is left to the reader.
+                let own_version = self.own_version(conn_addr);
+                framed_stream.send(own_version).await?;
+            }
+            (Some(HandshakeKind::Partial), ConnectionSide::Responder) => {
+                // Receive and send Version, but never Verack.
+                let peer_version = framed_stream.try_next().await?;
+                let node_addr = match peer_version {
+                    Some(Message::Version(version)) => version.addr_from.addr,
+                    Some(other) => {
+                        let span = self.node().span().clone();
+                        error!(
+                            parent: span,
+                            "received non-version message during handshake: {:?}", other
+                        );
+                        panic!("Expected Version, got {other:?}");
+                    }
+                    None => return Err(io::ErrorKind::InvalidData.into()),
+                };
+
+                let own_version = self.own_version(node_addr);
                 framed_stream.send(own_version).await?;
             }
             (None, _) => {}
         }
 
+        // Sent as part of the handshake, so it precedes anything which could trigger an
+        // announcement.
+        if self.send_headers && self.handshake == Some(HandshakeKind::Full) {
+            framed_stream.send(Message::SendHeaders).await?;
+        }
+
         // Let's print some info about our new connection.
         if let Some(version) = version_data {
             info!("Handshake done with {conn_addr} => {version:?}");
             self.handshake_infos.lock().insert(conn_addr, version);
         }
+        self.sockets.lock().insert(conn_addr, socket);
 
         Ok(conn)
     }
@@ -727,5 +1874,653 @@ impl Handshake for InnerNode {
 impl Disconnect for InnerNode {
     async fn handle_disconnect(&self, addr: SocketAddr) {
         self.handshake_infos.lock().remove(&addr);
+        self.wtxid_relay_peers.lock().remove(&addr);
+        self.inflight_bytes.lock().remove(&addr);
+        self.connection_starts.lock().remove(&addr);
+        self.sockets.lock().remove(&addr);
+
+        // Neither a clean close nor a local disconnect was recorded, so the read failed.
+        self.terminations
+            .lock()
+            .entry(addr)
+            .or_insert(DisconnectKind::Aborted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::message::{
+        constants::{MAGIC_MAINNET, MAGIC_TESTNET},
+        BodyTooLong, WrongNetworkMagic,
+    };
+    use crate::protocol::payload::Hash;
+
+    #[test]
+    #[ignore]
+    fn codec_reports_truncated_messages_at_eof() {
+        use crate::tools::fuzzing::{default_fuzz_messages, seeded_rng, truncated_messages};
+
+        let mut rng = seeded_rng();
+        for bytes in truncated_messages(&mut rng, 100, &default_fuzz_messages()) {
+            let mut codec = MessageCodec::default();
+            let mut src = BytesMut::from(&bytes[..]);
+
+            // The rest of the body may still arrive.
+            assert!(codec.decode(&mut src).unwrap().is_none());
+
+            let error = codec.decode_eof(&mut src).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn codec_rejects_wrong_network_magic() {
+        let mut bytes = BytesMut::new();
+        Message::Verack.encode(MAGIC_TESTNET, &mut bytes).unwrap();
+
+        let error = MessageCodec::new(MAGIC_MAINNET)
+            .decode(&mut bytes)
+            .unwrap_err();
+
+        assert_eq!(
+            WrongNetworkMagic::from_io_error(&error),
+            Some(&WrongNetworkMagic {
+                expected: MAGIC_MAINNET,
+                actual: MAGIC_TESTNET,
+            })
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn codec_records_clean_termination_on_eof() {
+        let addr = "127.0.0.1:8233".parse().unwrap();
+        let terminations = Terminations::default();
+        let mut codec =
+            MessageCodec::new(MAGIC_TESTNET).track_termination(addr, terminations.clone());
+
+        assert!(codec.decode_eof(&mut BytesMut::new()).unwrap().is_none());
+        assert_eq!(terminations.lock().get(&addr), Some(&DisconnectKind::Clean));
+    }
+
+    #[test]
+    #[ignore]
+    fn codec_decodes_unknown_command() {
+        let body = vec![1, 2, 3, 4, 5];
+        let mut bytes = BytesMut::new();
+        MessageHeader::new(MAGIC_TESTNET, *b"checkorder\0\0", &body)
+            .encode(&mut bytes)
+            .unwrap();
+        bytes.put_slice(&body);
+        // A known message following the unknown one is still read.
+        Message::Verack.encode(MAGIC_TESTNET, &mut bytes).unwrap();
+
+        let mut codec = MessageCodec::new(MAGIC_TESTNET);
+        assert_eq!(
+            codec.decode(&mut bytes).unwrap(),
+            Some(Message::unknown("checkorder", body).unwrap())
+        );
+        assert_eq!(codec.decode(&mut bytes).unwrap(), Some(Message::Verack));
+    }
+
+    #[test]
+    #[ignore]
+    fn unknown_command_round_trips_exactly() {
+        // Neither valid UTF-8 nor padded with trailing `NUL`s.
+        let command = *b"\xffcheck\0ordr\x01";
+        let body = vec![1, 2, 3];
+        let mut bytes = BytesMut::new();
+        MessageHeader::new(MAGIC_TESTNET, command, &body)
+            .encode_with_body(&body, &mut bytes)
+            .unwrap();
+        let original = bytes.clone();
+
+        let message = MessageCodec::new(MAGIC_TESTNET)
+            .decode(&mut bytes)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            message,
+            Message::Unknown {
+                command,
+                body: body.into()
+            }
+        );
+
+        let mut encoded = BytesMut::new();
+        message.encode(MAGIC_TESTNET, &mut encoded).unwrap();
+        assert_eq!(encoded, original);
+
+        let error = Message::unknown("thirteenbytes", Bytes::new()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    #[ignore]
+    fn codec_encodes_messages_back_to_back() {
+        // The writer encodes into a buffer which may still hold earlier messages.
+        let messages = [
+            Message::Ping(Nonce::default()),
+            Message::Verack,
+            Message::Inv(Inv::new(vec![InvHash::Block(Hash::zeroed()); 3])),
+        ];
+
+        let mut codec = MessageCodec::new(MAGIC_TESTNET);
+        let mut bytes = BytesMut::new();
+        for message in messages.clone() {
+            Encoder::<Message>::encode(&mut codec, message, &mut bytes).unwrap();
+        }
+
+        for message in messages {
+            assert_eq!(codec.decode(&mut bytes).unwrap(), Some(message));
+        }
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    #[ignore]
+    fn codec_rejects_oversized_body_before_reading_it() {
+        // 500MB are announced, but only the header is available.
+        let mut bytes = BytesMut::new();
+        MessageHeader::raw(MAGIC, *b"block\0\0\0\0\0\0\0", 500 * 1024 * 1024, 0)
+            .encode(&mut bytes)
+            .unwrap();
+
+        let error = MessageCodec::default().decode(&mut bytes).unwrap_err();
+
+        assert_eq!(
+            BodyTooLong::from_io_error(&error),
+            Some(&BodyTooLong {
+                declared: 500 * 1024 * 1024
+            })
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn retry_delays_grow_exponentially_up_to_the_cap() {
+        let policy = RetryPolicy {
+            initial_delay: Duration::from_millis(100),
+            multiplier: 2,
+            max_delay: Duration::from_millis(500),
+            max_retries: 5,
+        };
+
+        let delays = (0..5).map(|retry| policy.delay(retry)).collect::<Vec<_>>();
+        assert_eq!(delays, [100, 200, 400, 500, 500].map(Duration::from_millis));
+        assert_eq!(policy.max_total_delay(), Duration::from_millis(1700));
+        // Overflowing the factor saturates at the cap.
+        assert_eq!(policy.delay(usize::MAX), policy.max_delay);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn unread_messages_over_inflight_cap_disconnect_peer() {
+        // A 24 byte header and an 8 byte nonce.
+        const PING_LEN: usize = HEADER_LEN + 8;
+
+        let receiver = SyntheticNode::builder()
+            .with_max_inflight_bytes(3 * PING_LEN)
+            .build()
+            .await
+            .unwrap();
+        let sender = SyntheticNode::builder().build().await.unwrap();
+        sender.connect(receiver.listening_addr()).await.unwrap();
+        let sender_addr = receiver
+            .wait_for_connection_timeout(Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        // None of the pings are read, the fourth one doesn't fit.
+        for _ in 0..4 {
+            sender
+                .unicast(receiver.listening_addr(), Message::Ping(Nonce::default()))
+                .unwrap();
+        }
+
+        let start = std::time::Instant::now();
+        while receiver.is_connected(sender_addr) {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            receiver.disconnect_kind(sender_addr),
+            Some(DisconnectKind::InflightLimitExceeded)
+        );
+
+        sender.shut_down().await;
+        receiver.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn wait_for_disconnect_tells_resets_from_clean_closes() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let peer_addr = listener.local_addr().unwrap();
+        let mut synthetic_node = SyntheticNode::builder().build().await.unwrap();
+
+        for (linger, expected) in [
+            (None, DisconnectKind::Clean),
+            (Some(Duration::ZERO), DisconnectKind::Aborted),
+        ] {
+            synthetic_node.connect(peer_addr).await.unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+
+            // Closing the socket with a zero linger sends a RST instead of a FIN.
+            stream.set_linger(linger).unwrap();
+            drop(stream);
+
+            let kind = synthetic_node
+                .wait_for_disconnect(peer_addr, Duration::from_secs(1))
+                .await
+                .unwrap();
+            assert_eq!(kind, expected, "linger: {linger:?}");
+        }
+
+        synthetic_node.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn disconnect_clean_waits_for_the_peer_to_close() {
+        let peer = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        let synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(peer.listening_addr()).await.unwrap();
+        let synthetic_node_addr = peer
+            .wait_for_connection_timeout(Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        synthetic_node
+            .disconnect_clean(peer.listening_addr(), Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        // Both sides saw the other's FIN.
+        assert_eq!(
+            peer.disconnect_kind(synthetic_node_addr),
+            Some(DisconnectKind::Clean)
+        );
+        assert_eq!(
+            synthetic_node.disconnect_kind(peer.listening_addr()),
+            Some(DisconnectKind::Clean)
+        );
+
+        peer.shut_down().await;
+        synthetic_node.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn disconnect_abrupt_resets_the_connection() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let peer_addr = listener.local_addr().unwrap();
+        let synthetic_node = SyntheticNode::builder().build().await.unwrap();
+
+        synthetic_node.connect(peer_addr).await.unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+        assert!(synthetic_node.disconnect_abrupt(peer_addr).await);
+
+        let error = stream.read(&mut [0; 1]).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ConnectionReset);
+
+        synthetic_node.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn get_data_collects_data_and_not_found() {
+        let mut peer = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        let peer_addr = peer.listening_addr();
+        synthetic_node.connect(peer_addr).await.unwrap();
+
+        let block = Block::testnet_genesis();
+        let missing = InvHash::Block(Hash::new([1; 32]));
+        let inv = Inv::new(vec![block.inv_hash(), missing]);
+
+        // The peer replies with the block and a `NotFound` for the other item, with a ping in
+        // between which isn't part of the reply.
+        let peer_task = tokio::spawn(async move {
+            let (source, message) = peer.recv_message().await;
+            assert!(matches!(message, Message::GetData(_)));
+            for reply in [
+                Message::Block(Box::new(Block::testnet_genesis())),
+                Message::Ping(Nonce::default()),
+                Message::NotFound(Inv::new(vec![missing])),
+            ] {
+                peer.unicast(source, reply).unwrap();
+            }
+            peer
+        });
+
+        let result = synthetic_node.get_data(peer_addr, inv).await;
+        match result {
+            GetDataResult::NotFound { found, not_found } => {
+                assert_eq!(found, vec![Message::Block(Box::new(block))]);
+                assert_eq!(not_found, vec![missing]);
+            }
+            result => panic!("expected a NotFound result, got {result:?}"),
+        }
+
+        synthetic_node.shut_down().await;
+        peer_task.await.unwrap().shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn messages_are_numbered_in_order() {
+        let peer = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(synthetic_node.sequence_number(), 0);
+
+        // Both sides' `Version` and `Verack`.
+        synthetic_node.connect(peer.listening_addr()).await.unwrap();
+        assert_eq!(synthetic_node.sequence_number(), 4);
+
+        synthetic_node
+            .ping_pong_timeout(peer.listening_addr(), RECV_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(synthetic_node.sequence_number(), 6);
+
+        synthetic_node.shut_down().await;
+        peer.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn drain_available_reads_a_burst_of_messages() {
+        let peer = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(peer.listening_addr()).await.unwrap();
+        crate::wait_until!(RECV_TIMEOUT, peer.num_connected() == 1);
+        let source = peer.connected_peers()[0];
+
+        assert!(synthetic_node.drain_available().await.is_empty());
+
+        // A burst with gaps shorter than the quiet period is read in full.
+        for _ in 0..3 {
+            peer.unicast(source, Message::GetAddr).unwrap();
+            tokio::time::sleep(DRAIN_QUIET_PERIOD / 5).await;
+        }
+        let messages = synthetic_node.drain_available().await;
+        assert_eq!(messages, vec![Message::GetAddr; 3]);
+
+        synthetic_node.shut_down().await;
+        peer.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn rate_limit_paces_sent_messages() {
+        const RATE: u32 = 100;
+        const MESSAGES: u32 = 51;
+
+        let mut peer = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        let synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_rate_limit(RATE, 1)
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(peer.listening_addr()).await.unwrap();
+
+        for _ in 0..MESSAGES {
+            synthetic_node
+                .unicast(peer.listening_addr(), Message::GetAddr)
+                .unwrap();
+        }
+
+        // The first message goes out at once, the others one per 1/RATE seconds.
+        peer.recv_message_timeout(RECV_TIMEOUT).await.unwrap();
+        let start = std::time::Instant::now();
+        for _ in 1..MESSAGES {
+            peer.recv_message_timeout(RECV_TIMEOUT).await.unwrap();
+        }
+        let observed = f64::from(MESSAGES - 1) / start.elapsed().as_secs_f64();
+
+        let tolerance = f64::from(RATE) * 0.2;
+        assert!(
+            (observed - f64::from(RATE)).abs() <= tolerance,
+            "observed {observed:.1} messages per second instead of {RATE}"
+        );
+
+        synthetic_node.shut_down().await;
+        peer.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn rate_limit_queue_is_bounded() {
+        let peer = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        let synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_rate_limit(1, 1)
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(peer.listening_addr()).await.unwrap();
+
+        let mut queued = 0;
+        let error = loop {
+            match synthetic_node.unicast(peer.listening_addr(), Message::GetAddr) {
+                Ok(()) => queued += 1,
+                Err(e) => break e,
+            }
+            assert!(queued <= PACED_QUEUE_LEN + 2, "the queue isn't bounded");
+        };
+
+        // The pacing task may have taken up to two messages off the queue already.
+        assert_eq!(error.kind(), ErrorKind::WouldBlock);
+        assert!(queued >= PACED_QUEUE_LEN);
+
+        synthetic_node.shut_down().await;
+        peer.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn batch_is_received_as_separate_messages() {
+        let mut peer = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        let synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        synthetic_node.connect(peer.listening_addr()).await.unwrap();
+
+        let messages = [
+            Message::Ping(Nonce::default()),
+            Message::GetAddr,
+            Message::Inv(Inv::new(vec![InvHash::Block(Hash::zeroed()); 3])),
+        ];
+        synthetic_node
+            .unicast_batch(peer.listening_addr(), &messages)
+            .unwrap();
+        for message in &messages {
+            let (_, received) = peer.recv_message_timeout(RECV_TIMEOUT).await.unwrap();
+            assert_eq!(&received, message);
+        }
+
+        synthetic_node.shut_down().await;
+        peer.shut_down().await;
+    }
+
+    #[test]
+    #[ignore]
+    fn batch_encoding_has_valid_checksums() {
+        let messages = [
+            Message::Ping(Nonce::default()),
+            Message::Inv(Inv::new(vec![InvHash::Block(Hash::zeroed()); 3])),
+        ];
+        let mut scratch = BytesMut::new();
+
+        // Once written, the first batch is released and its space reused by the second.
+        drop(encode_batch(&mut scratch, MAGIC_TESTNET, &messages).unwrap());
+        let mut batch = encode_batch(&mut scratch, MAGIC_TESTNET, &messages).unwrap();
+
+        // The codec doesn't verify checksums, check them against the bodies.
+        for _ in &messages {
+            let header = MessageHeader::decode(&mut batch).unwrap();
+            let body = batch.split_to(header.body_length as usize);
+            let expected = MessageHeader::new(MAGIC_TESTNET, header.command, &body);
+            assert_eq!(header.checksum, expected.checksum);
+        }
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    #[ignore]
+    fn unknown_bodies_share_the_frozen_frame() {
+        let mut bytes = BytesMut::new();
+        Message::unknown("checkorder", vec![7u8; 1024])
+            .unwrap()
+            .encode(MAGIC_TESTNET, &mut bytes)
+            .unwrap();
+        let mut frame = bytes.split_to(bytes.len()).freeze();
+        let body_start = frame.as_ptr().wrapping_add(HEADER_LEN);
+
+        let header = MessageHeader::decode_with_magic(&mut frame, MAGIC_TESTNET).unwrap();
+        let message = Message::decode(header.command, &mut frame).unwrap();
+
+        // The body points into the frame rather than into a copy of it.
+        assert_matches!(message, Message::Unknown { body, .. } if body.as_ptr() == body_start);
+    }
+
+    #[test]
+    #[ignore]
+    fn dev_frozen_frame_decode_throughput() {
+        // *NOTE* run with `cargo test --release dev_frozen_frame -- --ignored --nocapture`
+        const MESSAGES: usize = 1_000;
+        // Close to the largest message, see `MAX_MESSAGE_LEN`.
+        const BODY_LEN: usize = 2_000_000;
+
+        let mut bytes = BytesMut::new();
+        Message::unknown("checkorder", vec![7u8; BODY_LEN])
+            .unwrap()
+            .encode(MAGIC_TESTNET, &mut bytes)
+            .unwrap();
+        let frame = bytes.freeze();
+        let total_mb = (frame.len() * MESSAGES) as f64 / 1_000_000.0;
+
+        println!("\r\n  frame  | throughput");
+        for frozen in [false, true] {
+            let start = std::time::Instant::now();
+            for _ in 0..MESSAGES {
+                // A slice is copied from as the message is decoded, a frozen frame is shared.
+                let message = if frozen {
+                    let mut frame = frame.clone();
+                    let header = MessageHeader::decode_with_magic(&mut frame, MAGIC_TESTNET);
+                    Message::decode(header.unwrap().command, &mut frame)
+                } else {
+                    let mut frame = &frame[..];
+                    let header = MessageHeader::decode_with_magic(&mut frame, MAGIC_TESTNET);
+                    Message::decode(header.unwrap().command, &mut frame)
+                };
+                assert_matches!(message, Ok(Message::Unknown { .. }));
+            }
+            let throughput = total_mb / start.elapsed().as_secs_f64();
+
+            let frame = if frozen { "frozen" } else { "slice" };
+            println!("  {frame:>6} | {throughput:>9.0} MB/s");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn dev_batched_sends_throughput() {
+        // *NOTE* run with `cargo test --release dev_batched_sends -- --ignored --nocapture`
+        const MESSAGES: usize = 10_000;
+        const BATCH_SIZE: usize = 100;
+
+        let messages = vec![Message::Ping(Nonce::default()); MESSAGES];
+
+        println!("\r\n  batch size | throughput");
+        for batch_size in [1, BATCH_SIZE] {
+            let mut receiver = SyntheticNode::builder()
+                .with_full_handshake()
+                .build()
+                .await
+                .unwrap();
+            let sender = SyntheticNode::builder()
+                .with_full_handshake()
+                .build()
+                .await
+                .unwrap();
+            sender.connect(receiver.listening_addr()).await.unwrap();
+
+            let start = std::time::Instant::now();
+            for batch in messages.chunks(batch_size) {
+                if batch_size == 1 {
+                    sender
+                        .unicast(receiver.listening_addr(), batch[0].clone())
+                        .unwrap();
+                } else {
+                    sender
+                        .unicast_batch(receiver.listening_addr(), batch)
+                        .unwrap();
+                }
+            }
+            for _ in 0..MESSAGES {
+                let (_, message) = receiver.recv_message_timeout(RECV_TIMEOUT).await.unwrap();
+                assert_matches!(message, Message::Ping(..));
+            }
+            let throughput = MESSAGES as f64 / start.elapsed().as_secs_f64();
+
+            println!("  {batch_size:>10} | {throughput:>9.0} msg/s");
+
+            sender.shut_down().await;
+            receiver.shut_down().await;
+        }
     }
 }
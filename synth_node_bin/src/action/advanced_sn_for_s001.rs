@@ -6,6 +6,7 @@ use std::{
 use anyhow::Result;
 use pea2pea::Config as NodeConfig;
 use tokio::time::{interval, sleep, Duration};
+use tokio_util::sync::CancellationToken;
 use ziggurat_zcash::{
     protocol::{
         message::Message,
@@ -17,7 +18,7 @@ use ziggurat_zcash::{
     },
 };
 
-use super::{ActionCfg, SynthNodeAction};
+use super::{ActionCfg, ActionOutcome, SynthNodeAction};
 
 // Configurable status printout interval.
 const DBG_INFO_LOG_INTERVAL_SEC: Duration = Duration::from_secs(10);
@@ -50,7 +51,13 @@ impl SynthNodeAction for Action {
     }
 
     #[allow(unused_variables)]
-    async fn run(&self, synth_node: &mut SyntheticNode, addr: Option<SocketAddr>) -> Result<()> {
+    async fn run(
+        &self,
+        synth_node: &mut SyntheticNode,
+        addr: Option<SocketAddr>,
+        _cfg: &ActionCfg,
+        _cancel: &CancellationToken,
+    ) -> Result<ActionOutcome> {
         // Sleep for three seconds before taking any actions - so the GetHeaders is handled before we
         // send GetAddr to the zcashd node for our only outbound connection.
         sleep(Duration::from_secs(3)).await;
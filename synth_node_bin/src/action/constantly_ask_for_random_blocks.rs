@@ -6,6 +6,7 @@ use rand::{
     rngs::StdRng,
     SeedableRng,
 };
+use tokio_util::sync::CancellationToken;
 use ziggurat_zcash::{
     protocol::{
         message::Message,
@@ -14,7 +15,7 @@ use ziggurat_zcash::{
     tools::synthetic_node::SyntheticNode,
 };
 
-use super::{ActionCfg, SynthNodeAction};
+use super::{ActionCfg, ActionOutcome, SynthNodeAction};
 
 pub(super) struct Action;
 
@@ -35,8 +36,14 @@ impl SynthNodeAction for Action {
     }
 
     #[allow(unused_variables)]
-    async fn run(&self, synth_node: &mut SyntheticNode, addr: Option<SocketAddr>) -> Result<()> {
-        println!("Synthetic node performs an action.");
+    async fn run(
+        &self,
+        synth_node: &mut SyntheticNode,
+        addr: Option<SocketAddr>,
+        _cfg: &ActionCfg,
+        _cancel: &CancellationToken,
+    ) -> Result<ActionOutcome> {
+        tracing::info!("synthetic node performs an action");
 
         let addr = if let Some(addr) = addr {
             addr
@@ -86,7 +93,7 @@ impl SynthNodeAction for Action {
                         count += 1;
 
                         if count == BLOCKS_FOR_AVG {
-                            println!(
+                            tracing::info!(
                                 "min: {} ms, max: {} ms, avg: {} ms",
                                 min,
                                 max,
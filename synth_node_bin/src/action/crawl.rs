@@ -0,0 +1,199 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    net::SocketAddr,
+};
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::json;
+use tokio::time::{timeout, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use ziggurat_zcash::{protocol::message::Message, tools::synthetic_node::SyntheticNode};
+
+use super::{ActionCfg, ActionOutcome, SynthNodeAction};
+
+/// How long to wait for a peer to accept the connection and complete the handshake.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for a peer to answer `GetAddr`.
+const ADDR_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(super) struct Action;
+
+pub(super) fn action() -> Box<dyn SynthNodeAction> {
+    Box::new(Action {})
+}
+
+/// A reachable peer and what it advertised in its `Version`.
+#[derive(Serialize)]
+struct PeerInfo {
+    addr: SocketAddr,
+    depth: usize,
+    user_agent: String,
+    start_height: i32,
+    addrs: usize,
+}
+
+#[async_trait::async_trait]
+impl SynthNodeAction for Action {
+    fn info(&self) -> &str {
+        "walks the peer graph from a seed node with GetAddr, bounded by depth and node count, and reports the reachable peers"
+    }
+
+    fn config(&self) -> ActionCfg {
+        ActionCfg::default()
+    }
+
+    async fn run(
+        &self,
+        synth_node: &mut SyntheticNode,
+        addr: Option<SocketAddr>,
+        cfg: &ActionCfg,
+        cancel: &CancellationToken,
+    ) -> Result<ActionOutcome> {
+        let seed = if let Some(seed) = cfg.crawl_seed.or(addr) {
+            seed
+        } else {
+            anyhow::bail!("neither a seed nor a node address provided");
+        };
+
+        let mut queue = VecDeque::from([(seed, 0)]);
+        let mut seen = HashSet::from([seed]);
+        // In the order the peers were visited, i.e. by depth.
+        let mut reachable = Vec::new();
+        let mut unreachable = Vec::new();
+
+        while let Some((peer, depth)) = queue.pop_front() {
+            if cancel.is_cancelled() {
+                break;
+            }
+            if reachable.len() + unreachable.len() >= cfg.crawl_max_nodes {
+                tracing::info!("reached the limit of {} nodes", cfg.crawl_max_nodes);
+                break;
+            }
+
+            // The runner may already be connected to the seed.
+            let was_connected = synth_node.is_connected(peer);
+            if !was_connected {
+                match timeout(CONNECT_TIMEOUT, synth_node.connect(peer)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => {
+                        tracing::debug!("{peer} is unreachable: {e}");
+                        unreachable.push(peer);
+                        continue;
+                    }
+                    Err(_) => {
+                        tracing::debug!("{peer} is unreachable: timed out");
+                        unreachable.push(peer);
+                        continue;
+                    }
+                }
+            }
+
+            let version = synth_node.handshake_info(&peer);
+            let addrs = request_addrs(synth_node, peer, cancel).await;
+
+            if !was_connected {
+                synth_node.disconnect(peer).await;
+            }
+
+            tracing::info!("{peer} (depth {depth}) returned {} addresses", addrs.len());
+            reachable.push(PeerInfo {
+                addr: peer,
+                depth,
+                user_agent: version
+                    .as_ref()
+                    .map(|version| version.user_agent.0.clone())
+                    .unwrap_or_default(),
+                start_height: version.map_or(0, |version| version.start_height),
+                addrs: addrs.len(),
+            });
+
+            if depth < cfg.crawl_max_depth {
+                for addr in addrs {
+                    if seen.insert(addr) {
+                        queue.push_back((addr, depth + 1));
+                    }
+                }
+            }
+        }
+
+        log_summary(&reachable, &unreachable, queue.len());
+
+        Ok(ActionOutcome {
+            summary: Some(format!(
+                "{} reachable, {} unreachable, {} left unvisited",
+                reachable.len(),
+                unreachable.len(),
+                queue.len()
+            )),
+            details: Some(json!({
+                "reachable": reachable,
+                "unreachable": unreachable,
+                "not_visited": queue.len(),
+            })),
+            ..Default::default()
+        })
+    }
+}
+
+/// Sends `GetAddr` to `peer` and returns the addresses it answers with.
+///
+/// Peers may first advertise only themselves, so addresses are collected until an `Addr` with
+/// more than one entry arrives, the timeout expires or the connection drops.
+async fn request_addrs(
+    synth_node: &mut SyntheticNode,
+    peer: SocketAddr,
+    cancel: &CancellationToken,
+) -> Vec<SocketAddr> {
+    let mut addrs = Vec::new();
+    if synth_node.unicast(peer, Message::GetAddr).is_err() {
+        return addrs;
+    }
+
+    let deadline = Instant::now() + ADDR_TIMEOUT;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let message = tokio::select! {
+            _ = cancel.cancelled() => break,
+            message = timeout(remaining, synth_node.try_recv_message()) => message,
+        };
+
+        match message {
+            Ok(Ok((source, Message::Addr(addr)))) if source == peer => {
+                addrs.extend(addr.iter().map(|entry| entry.addr));
+                if addr.addrs.len() > 1 {
+                    break;
+                }
+            }
+            Ok(Ok((source, message))) => {
+                tracing::trace!("message received from {source}: {message:?}")
+            }
+            // Timed out or the node shut down.
+            Ok(Err(_)) | Err(_) => break,
+        }
+
+        if !synth_node.is_connected(peer) {
+            break;
+        }
+    }
+
+    addrs
+}
+
+fn log_summary(reachable: &[PeerInfo], unreachable: &[SocketAddr], not_visited: usize) {
+    tracing::info!(
+        "crawl finished: {} reachable, {} unreachable, {not_visited} left unvisited",
+        reachable.len(),
+        unreachable.len()
+    );
+    for info in reachable {
+        tracing::info!(
+            "  {} depth={} user_agent={:?} height={} addrs={}",
+            info.addr,
+            info.depth,
+            info.user_agent,
+            info.start_height,
+            info.addrs
+        );
+    }
+}
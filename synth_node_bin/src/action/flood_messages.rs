@@ -0,0 +1,164 @@
+use std::{
+    fmt::{self, Display},
+    net::SocketAddr,
+    str::FromStr,
+};
+
+use anyhow::Result;
+use serde_json::json;
+use tokio::time::{interval, sleep, Duration, Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use ziggurat_zcash::{
+    protocol::{message::Message, payload::Nonce},
+    tools::synthetic_node::SyntheticNode,
+};
+
+use super::{ActionCfg, ActionOutcome, SynthNodeAction};
+
+/// Messages which can be sent by the flooding action.
+#[derive(Clone, Copy, Debug)]
+pub enum FloodMessage {
+    Ping,
+    GetAddr,
+    MemPool,
+}
+
+impl FloodMessage {
+    fn message(&self) -> Message {
+        match self {
+            Self::Ping => Message::Ping(Nonce::default()),
+            Self::GetAddr => Message::GetAddr,
+            Self::MemPool => Message::MemPool,
+        }
+    }
+}
+
+impl Display for FloodMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Ping => "Ping",
+                Self::GetAddr => "GetAddr",
+                Self::MemPool => "MemPool",
+            }
+        )
+    }
+}
+
+impl FromStr for FloodMessage {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Ping" => Ok(Self::Ping),
+            "GetAddr" => Ok(Self::GetAddr),
+            "MemPool" => Ok(Self::MemPool),
+            _ => Err("Invalid flood message"),
+        }
+    }
+}
+
+pub(super) struct Action;
+
+pub(super) fn action() -> Box<dyn SynthNodeAction> {
+    Box::new(Action {})
+}
+
+#[async_trait::async_trait]
+impl SynthNodeAction for Action {
+    fn info(&self) -> &str {
+        "a synth node which sends a message at a fixed rate for a while and then disconnects properly"
+    }
+
+    fn config(&self) -> ActionCfg {
+        ActionCfg::default()
+    }
+
+    async fn run(
+        &self,
+        synth_node: &mut SyntheticNode,
+        addr: Option<SocketAddr>,
+        cfg: &ActionCfg,
+        cancel: &CancellationToken,
+    ) -> Result<ActionOutcome> {
+        let addr = if let Some(addr) = addr {
+            addr
+        } else {
+            anyhow::bail!("address not provided");
+        };
+
+        tracing::info!(
+            "sending {} {} messages per second for {:?}",
+            cfg.flood_message,
+            cfg.flood_rate,
+            cfg.flood_duration
+        );
+
+        let mut ticker = interval(Duration::from_secs(1) / cfg.flood_rate.max(1));
+        // Don't burst to catch up on missed ticks, the rate is an upper bound.
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        let deadline = sleep(cfg.flood_duration);
+        tokio::pin!(deadline);
+
+        let start = Instant::now();
+        let mut sent = 0u64;
+        let mut errors = 0u64;
+        let mut received = 0u64;
+
+        loop {
+            tokio::select! {
+                _ = &mut deadline => break,
+                _ = cancel.cancelled() => {
+                    tracing::info!("cancelled, stopping the flood");
+                    break;
+                }
+                _ = ticker.tick() => {
+                    if let Err(e) = synth_node.unicast(addr, cfg.flood_message.message()) {
+                        errors += 1;
+                        tracing::warn!("failed to send {}: {e}", cfg.flood_message);
+
+                        if !synth_node.is_connected(addr) {
+                            tracing::warn!("connection closed");
+                            break;
+                        }
+                    } else {
+                        sent += 1;
+                    }
+                }
+                // Drain the replies, so the inbound queue never fills up and blocks reading.
+                message = synth_node.try_recv_message() => {
+                    let (_, msg) = message?;
+                    received += 1;
+                    tracing::debug!("message received: {msg:?}");
+                }
+            }
+        }
+
+        let elapsed = start.elapsed();
+        let summary = format!(
+            "sent {sent} {} messages in {:.1}s ({:.1}/s), {errors} errors",
+            cfg.flood_message,
+            elapsed.as_secs_f64(),
+            sent as f64 / elapsed.as_secs_f64()
+        );
+        tracing::info!("{summary}");
+
+        synth_node.disconnect(addr).await;
+
+        Ok(ActionOutcome {
+            messages_sent: sent,
+            messages_received: received,
+            summary: Some(summary),
+            details: Some(json!({
+                "message": cfg.flood_message.to_string(),
+                "sent": sent,
+                "errors": errors,
+                "elapsed_secs": elapsed.as_secs_f64(),
+            })),
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,70 @@
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use tokio::time::{timeout, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use ziggurat_zcash::tools::synthetic_node::SyntheticNode;
+
+use super::{ActionCfg, ActionOutcome, SynthNodeAction};
+
+/// How often the connection is checked while idling.
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(super) struct Action;
+
+pub(super) fn action() -> Box<dyn SynthNodeAction> {
+    Box::new(Action {})
+}
+
+#[async_trait::async_trait]
+impl SynthNodeAction for Action {
+    fn info(&self) -> &str {
+        "a synth node which completes the handshake, idles (answering pings) and then disconnects properly"
+    }
+
+    fn config(&self) -> ActionCfg {
+        ActionCfg::default()
+    }
+
+    async fn run(
+        &self,
+        synth_node: &mut SyntheticNode,
+        addr: Option<SocketAddr>,
+        cfg: &ActionCfg,
+        cancel: &CancellationToken,
+    ) -> Result<ActionOutcome> {
+        let addr = if let Some(addr) = addr {
+            addr
+        } else {
+            anyhow::bail!("address not provided");
+        };
+
+        tracing::info!("idling for {:?}", cfg.idle_duration);
+        let deadline = Instant::now() + cfg.idle_duration;
+        let mut outcome = ActionOutcome::default();
+
+        // Pings are answered by the auto-reply filter, anything else is only logged.
+        while Instant::now() < deadline {
+            let wait = CONNECTION_CHECK_INTERVAL.min(deadline - Instant::now());
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                message = timeout(wait, synth_node.try_recv_message()) => {
+                    if let Ok(message) = message {
+                        let (_, msg) = message?;
+                        outcome.messages_received += 1;
+                        tracing::info!("message received: {msg:?}");
+                    }
+                }
+            }
+
+            if !synth_node.is_connected(addr) {
+                anyhow::bail!("connection closed");
+            }
+        }
+
+        tracing::info!("synthetic node disconnecting");
+        synth_node.disconnect(addr).await;
+
+        Ok(outcome)
+    }
+}
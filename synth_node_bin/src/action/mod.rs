@@ -2,14 +2,25 @@ use std::{
     fmt::{self, Display},
     net::{IpAddr, Ipv4Addr, SocketAddr},
     str::FromStr,
+    time::Duration,
 };
 
 use anyhow::Result;
 use pea2pea::Config as NodeConfig;
-use ziggurat_zcash::tools::{message_filter::MessageFilter, synthetic_node::SyntheticNode};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use ziggurat_zcash::{
+    protocol::payload::Version,
+    tools::{message_filter::MessageFilter, synthetic_node::SyntheticNode},
+};
+
+pub use self::flood_messages::FloodMessage;
 
 mod advanced_sn_for_s001;
 mod constantly_ask_for_random_blocks;
+mod crawl;
+mod flood_messages;
+mod handshake_and_idle;
 mod quick_connect_and_then_clean_disconnect;
 mod quick_connect_with_improper_disconnect;
 mod rt_s1_collector;
@@ -20,7 +31,7 @@ mod send_get_addr_and_forever_sleep;
 ///
 /// It simplifies adding new actions and allows to separate different actions with modules.
 #[async_trait::async_trait]
-trait SynthNodeAction {
+trait SynthNodeAction: Send + Sync {
     /// Action description.
     ///
     /// It can be displayed during the runtime.
@@ -33,8 +44,67 @@ trait SynthNodeAction {
 
     /// Defines the core action functionality.
     ///
-    /// All the program logic happens here.
-    async fn run(&self, synth_node: &mut SyntheticNode, addr: Option<SocketAddr>) -> Result<()>;
+    /// All the program logic happens here, `cfg` is the action's configuration with any overrides
+    /// from the command line applied. The returned outcome is included in the connection's result.
+    ///
+    /// `cancel` is triggered when the binary is shutting down. Observing it is optional: long
+    /// running actions should stop and disconnect cleanly when it fires, the ones which don't are
+    /// dropped by the runner after a short grace period.
+    async fn run(
+        &self,
+        synth_node: &mut SyntheticNode,
+        addr: Option<SocketAddr>,
+        cfg: &ActionCfg,
+        cancel: &CancellationToken,
+    ) -> Result<ActionOutcome>;
+}
+
+/// The structured result of an action's run, aggregated by the runner into its report.
+///
+/// Actions which only connect can return the default outcome, the runner fills in the `version`.
+#[derive(Debug, Default, Serialize)]
+pub struct ActionOutcome {
+    /// The `Version` the node sent during the handshake.
+    pub version: Option<PeerVersion>,
+    /// The number of messages the action sent.
+    pub messages_sent: u64,
+    /// The number of messages the action received.
+    pub messages_received: u64,
+    /// The latencies (in ms) the action measured, e.g. between a request and its response.
+    pub latencies_ms: Vec<f64>,
+    /// A short description of the run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Action specific results, e.g. the peers reached by a crawl.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ActionOutcome {
+    /// Records a latency measured by the action.
+    pub fn record_latency(&mut self, latency: Duration) {
+        self.latencies_ms.push(latency.as_secs_f64() * 1000.0);
+    }
+}
+
+/// The fields of a node's `Version` included in an [`ActionOutcome`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerVersion {
+    pub version: u32,
+    pub services: u64,
+    pub user_agent: String,
+    pub start_height: i32,
+}
+
+impl From<Version> for PeerVersion {
+    fn from(version: Version) -> Self {
+        Self {
+            version: version.version.0,
+            services: version.services,
+            user_agent: version.user_agent.0,
+            start_height: version.start_height,
+        }
+    }
 }
 
 /// List of available actions.
@@ -47,6 +117,9 @@ pub enum ActionType {
     ConstantlyAskForRandomBlocks,
     RtS1Collector,
     RtS1Tainter,
+    HandshakeAndIdle,
+    FloodMessages,
+    Crawl,
 }
 
 impl Display for ActionType {
@@ -62,29 +135,51 @@ impl Display for ActionType {
                 Self::ConstantlyAskForRandomBlocks => "ConstantlyAskForRandomBlocks",
                 Self::RtS1Collector => "RtS1Collector",
                 Self::RtS1Tainter => "RtS1Tainter",
+                Self::HandshakeAndIdle => "HandshakeAndIdle",
+                Self::FloodMessages => "FloodMessages",
+                Self::Crawl => "Crawl",
             }
         )
     }
 }
 
+impl ActionType {
+    /// All the available actions.
+    pub const ALL: [ActionType; 10] = [
+        Self::SendGetAddrAndForeverSleep,
+        Self::AdvancedSnForS001,
+        Self::QuickConnectAndThenCleanDisconnect,
+        Self::QuickConnectWithImproperDisconnect,
+        Self::ConstantlyAskForRandomBlocks,
+        Self::RtS1Collector,
+        Self::RtS1Tainter,
+        Self::HandshakeAndIdle,
+        Self::FloodMessages,
+        Self::Crawl,
+    ];
+}
+
 impl FromStr for ActionType {
-    type Err = &'static str;
+    type Err = String;
 
+    /// Parses an action from its name, either as displayed (`HandshakeAndIdle`) or in snake case
+    /// (`handshake_and_idle`).
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "SendGetAddrAndForeverSleep" => Ok(Self::SendGetAddrAndForeverSleep),
-            "AdvancedSnForS001" => Ok(Self::AdvancedSnForS001),
-            "QuickConnectAndThenCleanDisconnect" => Ok(Self::QuickConnectAndThenCleanDisconnect),
-            "QuickConnectWithImproperDisconnect" => Ok(Self::QuickConnectWithImproperDisconnect),
-            "ConstantlyAskForRandomBlocks" => Ok(Self::ConstantlyAskForRandomBlocks),
-            "RtS1Collector" => Ok(Self::RtS1Collector),
-            "RtS1Tainter" => Ok(Self::RtS1Tainter),
-            _ => Err("Invalid action type"),
-        }
+        let normalize = |name: &str| name.replace('_', "").to_lowercase();
+        let name = normalize(s);
+
+        Self::ALL
+            .into_iter()
+            .find(|action| normalize(&action.to_string()) == name)
+            .ok_or_else(|| {
+                let available = Self::ALL.map(|action| action.to_string()).join(" / ");
+                format!("invalid action type, available actions: {available}")
+            })
     }
 }
 
 /// Action configuration options.
+#[derive(Clone)]
 pub struct ActionCfg {
     /// A message filter for a synthetic node.
     pub msg_filter: MessageFilter,
@@ -94,6 +189,42 @@ pub struct ActionCfg {
 
     /// When enabled, the shutdown API in synthetic node is skipped.
     pub allow_proper_shutdown: bool,
+
+    /// How long to keep an idle connection open, for actions which idle.
+    pub idle_duration: Duration,
+
+    /// The message sent by flooding actions.
+    pub flood_message: FloodMessage,
+
+    /// The number of messages per second sent by flooding actions.
+    pub flood_rate: u32,
+
+    /// How long flooding actions send messages for.
+    pub flood_duration: Duration,
+
+    /// The number of connections to run the action on, each one with its own synthetic node.
+    pub connections: usize,
+
+    /// The maximum number of synthetic nodes running at once, the others wait for one to stop
+    /// before connecting. Keeps large fan-outs from exhausting file descriptors.
+    pub max_concurrent_connections: usize,
+
+    /// The addresses of the nodes to connect to, connections are distributed across them
+    /// round-robin. If empty, the synthetic nodes don't initiate any connections.
+    pub targets: Vec<SocketAddr>,
+
+    /// The node the crawling action starts from, defaults to the connection's target.
+    pub crawl_seed: Option<SocketAddr>,
+
+    /// How many `GetAddr` hops away from the seed the crawling action goes.
+    pub crawl_max_depth: usize,
+
+    /// The maximum number of nodes the crawling action connects to, reachable or not.
+    pub crawl_max_nodes: usize,
+
+    /// The maximum time the action may run for on a connection, it's dropped once exceeded so a
+    /// single stuck connection doesn't stall the others.
+    pub timeout: Duration,
 }
 
 impl Default for ActionCfg {
@@ -105,6 +236,17 @@ impl Default for ActionCfg {
                 ..Default::default()
             },
             allow_proper_shutdown: true,
+            idle_duration: Duration::from_secs(60),
+            flood_message: FloodMessage::Ping,
+            flood_rate: 100,
+            flood_duration: Duration::from_secs(10),
+            connections: 1,
+            max_concurrent_connections: 256,
+            targets: Vec::new(),
+            crawl_seed: None,
+            crawl_max_depth: 2,
+            crawl_max_nodes: 100,
+            timeout: Duration::from_secs(60 * 60),
         }
     }
 }
@@ -133,23 +275,37 @@ impl ActionHandler {
             ActionType::ConstantlyAskForRandomBlocks => constantly_ask_for_random_blocks::action(),
             ActionType::RtS1Collector => rt_s1_collector::action(),
             ActionType::RtS1Tainter => rt_s1_tainter::action(),
+            ActionType::HandshakeAndIdle => handshake_and_idle::action(),
+            ActionType::FloodMessages => flood_messages::action(),
+            ActionType::Crawl => crawl::action(),
         };
         let cfg = action.config();
 
-        println!(
-            "Running a synth node which performs the following:\n\t{}",
+        tracing::info!(
+            "running a synth node which performs the following: {}",
             action.info()
         );
 
         Self { action, cfg }
     }
 
+    /// Returns the target of the connection with the given index, targets are assigned
+    /// round-robin.
+    pub fn target(&self, connection: usize) -> Option<SocketAddr> {
+        if self.cfg.targets.is_empty() {
+            return None;
+        }
+
+        Some(self.cfg.targets[connection % self.cfg.targets.len()])
+    }
+
     /// Runs the underlying action.
     pub async fn execute(
         &self,
         synth_node: &mut SyntheticNode,
         addr: Option<SocketAddr>,
-    ) -> Result<()> {
-        self.action.run(synth_node, addr).await
+        cancel: &CancellationToken,
+    ) -> Result<ActionOutcome> {
+        self.action.run(synth_node, addr, &self.cfg, cancel).await
     }
 }
@@ -1,9 +1,10 @@
 use std::net::SocketAddr;
 
 use anyhow::Result;
+use tokio_util::sync::CancellationToken;
 use ziggurat_zcash::tools::synthetic_node::SyntheticNode;
 
-use super::{ActionCfg, SynthNodeAction};
+use super::{ActionCfg, ActionOutcome, SynthNodeAction};
 
 pub(super) struct Action;
 
@@ -25,19 +26,25 @@ impl SynthNodeAction for Action {
     }
 
     #[allow(unused_variables)]
-    async fn run(&self, synth_node: &mut SyntheticNode, addr: Option<SocketAddr>) -> Result<()> {
+    async fn run(
+        &self,
+        synth_node: &mut SyntheticNode,
+        addr: Option<SocketAddr>,
+        _cfg: &ActionCfg,
+        _cancel: &CancellationToken,
+    ) -> Result<ActionOutcome> {
         let addr = if let Some(addr) = addr {
             addr
         } else {
             anyhow::bail!("address not provided");
         };
 
-        println!("Synthetic node connected to {addr}!");
+        tracing::info!("synthetic node connected to {addr}");
 
         // An optional short sleep.
         //tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
 
-        println!("Synthetic node disconnecting!");
-        Ok(())
+        tracing::info!("synthetic node disconnecting");
+        Ok(ActionOutcome::default())
     }
 }
@@ -4,6 +4,7 @@ use anyhow::Result;
 use pea2pea::Config as NodeConfig;
 use rand::{seq::SliceRandom, thread_rng};
 use tokio::time::{interval, Duration};
+use tokio_util::sync::CancellationToken;
 use ziggurat_zcash::{
     protocol::{
         message::Message,
@@ -12,7 +13,7 @@ use ziggurat_zcash::{
     tools::{message_filter::MessageFilter, synthetic_node::SyntheticNode},
 };
 
-use super::{ActionCfg, SynthNodeAction};
+use super::{ActionCfg, ActionOutcome, SynthNodeAction};
 
 // Configurable status printout interval.
 const BROADCAST_INTERVAL_SEC: Duration = Duration::from_secs(60);
@@ -46,7 +47,13 @@ impl SynthNodeAction for Action {
     }
 
     #[allow(unused_variables)]
-    async fn run(&self, synth_node: &mut SyntheticNode, addr: Option<SocketAddr>) -> Result<()> {
+    async fn run(
+        &self,
+        synth_node: &mut SyntheticNode,
+        addr: Option<SocketAddr>,
+        _cfg: &ActionCfg,
+        _cancel: &CancellationToken,
+    ) -> Result<ActionOutcome> {
         let mut broadcast_msgs_interval = interval(BROADCAST_INTERVAL_SEC);
         let mut dbg_info_interval = interval(DBG_INFO_LOG_INTERVAL_SEC);
         let mut num_connected = synth_node.num_connected();
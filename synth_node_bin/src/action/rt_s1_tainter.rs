@@ -3,6 +3,7 @@ use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use anyhow::Result;
 use pea2pea::Config as NodeConfig;
 use tokio::time::{interval, Duration};
+use tokio_util::sync::CancellationToken;
 use ziggurat_zcash::{
     protocol::{
         message::Message,
@@ -14,7 +15,7 @@ use ziggurat_zcash::{
     },
 };
 
-use super::{ActionCfg, SynthNodeAction};
+use super::{ActionCfg, ActionOutcome, SynthNodeAction};
 
 // Configurable status printout interval.
 const DBG_INFO_LOG_INTERVAL_SEC: Duration = Duration::from_secs(10);
@@ -46,7 +47,13 @@ impl SynthNodeAction for Action {
     }
 
     #[allow(unused_variables)]
-    async fn run(&self, synth_node: &mut SyntheticNode, addr: Option<SocketAddr>) -> Result<()> {
+    async fn run(
+        &self,
+        synth_node: &mut SyntheticNode,
+        addr: Option<SocketAddr>,
+        _cfg: &ActionCfg,
+        _cancel: &CancellationToken,
+    ) -> Result<ActionOutcome> {
         let addr = if let Some(addr) = addr {
             addr
         } else {
@@ -2,9 +2,10 @@ use std::net::SocketAddr;
 
 use anyhow::Result;
 use tokio::time::{sleep, Duration};
+use tokio_util::sync::CancellationToken;
 use ziggurat_zcash::{protocol::message::Message, tools::synthetic_node::SyntheticNode};
 
-use super::{ActionCfg, SynthNodeAction};
+use super::{ActionCfg, ActionOutcome, SynthNodeAction};
 
 pub(super) struct Action;
 
@@ -23,8 +24,14 @@ impl SynthNodeAction for Action {
     }
 
     #[allow(unused_variables)]
-    async fn run(&self, synth_node: &mut SyntheticNode, addr: Option<SocketAddr>) -> Result<()> {
-        println!("Synthetic node performs an action.");
+    async fn run(
+        &self,
+        synth_node: &mut SyntheticNode,
+        addr: Option<SocketAddr>,
+        _cfg: &ActionCfg,
+        _cancel: &CancellationToken,
+    ) -> Result<ActionOutcome> {
+        tracing::info!("synthetic node performs an action");
 
         let addr = if let Some(addr) = addr {
             addr
@@ -1,93 +1,402 @@
 //! A synthetic node binary can be used to interact with the node in the
 //! background from a different runtime environment.
-use std::{net::SocketAddr, process::ExitCode};
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use action::{ActionHandler, ActionType};
+use action::{ActionCfg, ActionHandler, ActionType, FloodMessage};
 use anyhow::Result;
 use clap::Parser;
+use report::{ConnectionResult, OutputFormat, OutputLine, Report};
+use tokio::{
+    sync::Semaphore,
+    task::JoinHandle,
+    time::{sleep, timeout, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{Instrument, Level};
 use ziggurat_zcash::tools::synthetic_node::SyntheticNode;
 
 use crate::ActionType::SendGetAddrAndForeverSleep;
 
 mod action;
+mod report;
+
+/// How long actions are given to stop on their own after a shutdown signal before they're dropped.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(2);
 
 /// A synthetic node which can connect to the node and preform some actions independently.
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct CmdArgs {
-    /// An address of the node in the <ip>:<port> format.
-    #[arg(short = 'n', long)]
-    node_addr: Option<SocketAddr>,
+    /// The action to run (see `--action-type` for the available actions).
+    #[arg(conflicts_with = "action_type")]
+    action: Option<ActionType>,
+
+    /// An address of the node in the <ip>:<port> format, can be repeated to target several nodes.
+    #[arg(short = 'n', long, visible_alias = "addr")]
+    node_addr: Vec<SocketAddr>,
+
+    /// A file with a newline-delimited list of node addresses (<ip>:<port>) to target, in addition
+    /// to any `--node-addr`. Empty lines and lines starting with `#` are ignored.
+    #[arg(long)]
+    targets_file: Option<PathBuf>,
+
+    /// The number of connections to run the action on, distributed across the nodes round-robin.
+    #[arg(short = 'c', long)]
+    connections: Option<usize>,
+
+    /// The maximum number of connections open at once, further connections wait for one to close.
+    #[arg(long)]
+    max_concurrent_connections: Option<usize>,
 
     /// Always reconnect in the case the connection fails - synthetic node never dies.
     #[arg(short = 's', long, default_value_t = false)]
     stubborn: bool,
 
-    /// Enable tracing.
+    /// How the results are reported: text / json. With json, a JSON object per connection and one
+    /// for the final report are printed to stdout, and the logs go to stderr.
+    #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Enable tracing of the synthetic node internals (connections, messages sent and received),
+    /// otherwise only the action's own events are logged.
     #[arg(short = 't', long, default_value_t = false)]
     tracing: bool,
 
+    /// The log level: error / warn / info / debug / trace. `RUST_LOG` takes precedence if set.
+    #[arg(short = 'l', long, default_value_t = Level::INFO)]
+    log_level: Level,
+
     /// A desired listening port.
     #[arg(short = 'p', long)]
     desired_listening_port: Option<u16>,
 
-    /// Possible actions:
+    /// Possible actions (also accepted in snake case, e.g. handshake_and_idle):
     /// SendGetAddrAndForeverSleep / AdvancedSnForS001 / QuickConnectAndThenCleanDisconnect /
-    /// QuickConnectWithImproperDisconnect / ConstantlyAskForRandomBlocks / RtS1Collector / RtS1Tainter
+    /// QuickConnectWithImproperDisconnect / ConstantlyAskForRandomBlocks / RtS1Collector / RtS1Tainter /
+    /// HandshakeAndIdle / FloodMessages / Crawl
     #[arg(short = 'a', long, default_value_t = SendGetAddrAndForeverSleep)]
     action_type: ActionType,
+
+    /// The maximum time a connection's action may run for, e.g. `30s` or `10m`. Actions still
+    /// running are dropped and the connection is reported as timed out.
+    #[arg(long, value_parser = parse_duration)]
+    timeout: Option<Duration>,
+
+    /// How long time-bound actions (idling, flooding) run for, e.g. `30s`, `5m` or `500ms`.
+    /// Overrides the more specific durations below.
+    #[arg(short = 'd', long, value_parser = parse_duration)]
+    duration: Option<Duration>,
+
+    /// How long (in seconds) idling actions keep the connection open.
+    #[arg(short = 'i', long)]
+    idle_duration: Option<u64>,
+
+    /// The message flooding actions send: Ping / GetAddr / MemPool.
+    #[arg(long)]
+    flood_message: Option<FloodMessage>,
+
+    /// The number of messages per second flooding actions send.
+    #[arg(long)]
+    flood_rate: Option<u32>,
+
+    /// How long (in seconds) flooding actions send messages for.
+    #[arg(long)]
+    flood_duration: Option<u64>,
+
+    /// The node the crawling action starts from, defaults to the node address.
+    #[arg(long)]
+    crawl_seed: Option<SocketAddr>,
+
+    /// How many `GetAddr` hops away from the seed the crawling action goes.
+    #[arg(long)]
+    crawl_max_depth: Option<usize>,
+
+    /// The maximum number of nodes the crawling action connects to.
+    #[arg(long)]
+    crawl_max_nodes: Option<usize>,
+}
+
+impl CmdArgs {
+    /// Returns the selected action, the positional argument takes precedence.
+    fn action_type(&self) -> ActionType {
+        self.action.unwrap_or(self.action_type)
+    }
+
+    /// Overrides the action's default configuration with the values set by the user.
+    fn apply_overrides(&self, cfg: &mut ActionCfg) -> Result<()> {
+        let mut targets = self.node_addr.clone();
+        if let Some(path) = &self.targets_file {
+            targets.extend(read_targets(path)?);
+        }
+        if !targets.is_empty() {
+            cfg.targets = targets;
+        }
+        if let Some(connections) = self.connections {
+            cfg.connections = connections;
+        }
+        if let Some(max) = self.max_concurrent_connections {
+            cfg.max_concurrent_connections = max;
+        }
+        if self.desired_listening_port.is_some() {
+            cfg.network_cfg.desired_listening_port = self.desired_listening_port;
+        }
+        if let Some(secs) = self.idle_duration {
+            cfg.idle_duration = Duration::from_secs(secs);
+        }
+        if let Some(message) = self.flood_message {
+            cfg.flood_message = message;
+        }
+        if let Some(rate) = self.flood_rate {
+            cfg.flood_rate = rate;
+        }
+        if let Some(secs) = self.flood_duration {
+            cfg.flood_duration = Duration::from_secs(secs);
+        }
+        if self.crawl_seed.is_some() {
+            cfg.crawl_seed = self.crawl_seed;
+        }
+        if let Some(depth) = self.crawl_max_depth {
+            cfg.crawl_max_depth = depth;
+        }
+        if let Some(nodes) = self.crawl_max_nodes {
+            cfg.crawl_max_nodes = nodes;
+        }
+        if let Some(timeout) = self.timeout {
+            cfg.timeout = timeout;
+        }
+        if let Some(duration) = self.duration {
+            cfg.idle_duration = duration;
+            cfg.flood_duration = duration;
+        }
+
+        Ok(())
+    }
 }
 
 #[tokio::main]
 async fn main() -> ExitCode {
     let args = CmdArgs::parse();
-    let node_addr = args.node_addr;
 
-    if args.tracing {
-        println!("Enabling tracing.");
-        use tracing_subscriber::{fmt, EnvFilter};
+    init_logging(args.log_level, args.tracing, args.output);
 
-        fmt()
-            .with_test_writer()
-            .with_env_filter(EnvFilter::from_default_env())
-            .init();
+    // Select an action.
+    let mut action = ActionHandler::new(args.action_type());
+    // A user can always override a default value from an action.
+    if let Err(e) = args.apply_overrides(&mut action.cfg) {
+        tracing::error!("invalid arguments: {e:?}");
+        return ExitCode::FAILURE;
     }
 
-    loop {
-        println!("Starting a synthetic node.");
+    if action.cfg.connections > 1 && action.cfg.network_cfg.desired_listening_port.is_some() {
+        tracing::error!("a desired listening port can only be used with a single connection");
+        return ExitCode::FAILURE;
+    }
 
-        if let Err(e) =
-            run_synth_node(node_addr, args.action_type, args.desired_listening_port).await
-        {
-            eprintln!("The synthetic node stopped: {e:?}.");
-        }
+    if action.cfg.max_concurrent_connections == 0 {
+        tracing::error!("the maximum number of concurrent connections must be at least 1");
+        return ExitCode::FAILURE;
+    }
 
-        // Use the stubborn option to run the synth node infinitely.
-        if !args.stubborn {
-            break;
+    // Signals all the running actions to disconnect on Ctrl-C.
+    let cancel = CancellationToken::new();
+    tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                tracing::info!("shutdown signal received, stopping the synthetic nodes");
+                cancel.cancel();
+            }
         }
+    });
+
+    let report = Arc::new(Mutex::new(Report::default()));
+    let handles = spawn_connections(
+        Arc::new(action),
+        args.action_type(),
+        args.stubborn,
+        args.output,
+        report.clone(),
+        cancel,
+    );
+
+    for handle in handles {
+        let _ = handle.await;
+    }
+
+    let report = report.lock().unwrap();
+    match args.output {
+        OutputFormat::Text => report.log(),
+        OutputFormat::Json => OutputLine::Report(&report).print(),
     }
 
     ExitCode::SUCCESS
 }
 
-async fn run_synth_node(
-    node_addr: Option<SocketAddr>,
+/// Spawns a task per connection running the action, at most `max_concurrent_connections` of the
+/// synthetic nodes run at once. Each run's result is reported in the `output` format and
+/// aggregated into `report`.
+fn spawn_connections(
+    action: Arc<ActionHandler>,
     action_type: ActionType,
-    desired_listening_port: Option<u16>,
-) -> Result<()> {
-    // Select an action.
-    let action = ActionHandler::new(action_type);
+    stubborn: bool,
+    output: OutputFormat,
+    report: Arc<Mutex<Report>>,
+    cancel: CancellationToken,
+) -> Vec<JoinHandle<()>> {
+    let permits = Arc::new(Semaphore::new(action.cfg.max_concurrent_connections));
 
-    let mut net_cfg = action.cfg.network_cfg.clone();
-    // A user can always override a default value from an action.
-    if desired_listening_port.is_some() {
-        net_cfg.desired_listening_port = desired_listening_port;
+    (0..action.cfg.connections)
+        .map(|id| {
+            let action = action.clone();
+            let permits = permits.clone();
+            let report = report.clone();
+            let cancel = cancel.clone();
+
+            tokio::spawn(async move {
+                let node_addr = action.target(id);
+                // Events can be filtered by the connection's action span.
+                let span =
+                    tracing::info_span!("action", kind = %action_type, id, node = ?node_addr);
+
+                loop {
+                    // Held until the synthetic node is shut down, reconnections are gated too.
+                    let _permit = tokio::select! {
+                        permit = permits.acquire() => {
+                            permit.expect("the semaphore is never closed")
+                        }
+                        _ = cancel.cancelled() => break,
+                    };
+
+                    tracing::info!(parent: &span, "starting a synthetic node");
+
+                    let mut result = ConnectionResult::new(id, action_type.to_string(), node_addr);
+                    let start = Instant::now();
+
+                    if let Err(e) = run_synth_node(&action, node_addr, &cancel, &mut result)
+                        .instrument(span.clone())
+                        .await
+                    {
+                        tracing::error!(parent: &span, "the synthetic node stopped: {e:?}");
+                        result.success = false;
+                        result.error = Some(format!("{e:#}"));
+                    }
+
+                    result.duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+                    if output == OutputFormat::Json {
+                        OutputLine::Connection(&result).print();
+                    }
+                    report.lock().unwrap().add(&result);
+
+                    // Use the stubborn option to run the synth node infinitely.
+                    if !stubborn || cancel.is_cancelled() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+/// Reads the node addresses listed in the file at `path`, one per line.
+///
+/// Malformed lines are reported with their line number and skipped, it's an error if no valid
+/// address is found.
+fn read_targets(path: &Path) -> Result<Vec<SocketAddr>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("couldn't read {}: {e}", path.display()))?;
+
+    let mut targets = Vec::new();
+    let mut malformed = 0;
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.parse() {
+            Ok(addr) => targets.push(addr),
+            Err(e) => {
+                malformed += 1;
+                tracing::warn!(
+                    "{}:{}: invalid address {line:?}: {e}",
+                    path.display(),
+                    number + 1
+                );
+            }
+        }
+    }
+
+    if targets.is_empty() {
+        anyhow::bail!("no valid addresses in {}", path.display());
+    }
+
+    tracing::info!(
+        "read {} targets from {} ({malformed} malformed lines skipped)",
+        targets.len(),
+        path.display()
+    );
+
+    Ok(targets)
+}
+
+/// Parses a duration with an optional unit suffix (`ms`, `s`, `m` or `h`), defaults to seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration: {s}"))?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "" | "s" => Ok(Duration::from_secs(value)),
+        "m" => Ok(Duration::from_secs(value * 60)),
+        "h" => Ok(Duration::from_secs(value * 60 * 60)),
+        _ => Err(format!(
+            "invalid duration unit: {unit}, use one of ms / s / m / h"
+        )),
+    }
+}
+
+fn init_logging(level: Level, trace_internals: bool, output: OutputFormat) {
+    use tracing_subscriber::{fmt, EnvFilter};
+
+    let level = level.as_str().to_lowercase();
+    let directive = if trace_internals {
+        level
+    } else {
+        format!("synth_node_bin={level}")
+    };
+
+    let subscriber = fmt().with_env_filter(
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(directive)),
+    );
+
+    // Keep stdout for the results.
+    match output {
+        OutputFormat::Text => subscriber.init(),
+        OutputFormat::Json => subscriber.with_writer(std::io::stderr).init(),
     }
+}
 
+/// Runs the action on a new synthetic node, recording the handshake and the action's outcome in
+/// `result` as it goes.
+async fn run_synth_node(
+    action: &ActionHandler,
+    node_addr: Option<SocketAddr>,
+    cancel: &CancellationToken,
+    result: &mut ConnectionResult,
+) -> Result<()> {
     // Create a synthetic node and enable handshaking.
     let mut synth_node = SyntheticNode::builder()
-        .with_network_config(net_cfg)
+        .with_network_config(action.cfg.network_cfg.clone())
         .with_full_handshake()
         .with_message_filter(action.cfg.msg_filter.clone())
         .build()
@@ -95,16 +404,155 @@ async fn run_synth_node(
 
     // Perform the handshake.
     if let Some(addr) = node_addr {
-        synth_node.connect(addr).await?;
+        let start = Instant::now();
+        tokio::select! {
+            connected = synth_node.connect(addr) => connected?,
+            _ = cancel.cancelled() => {
+                synth_node.shut_down().await;
+                return Ok(());
+            }
+        }
+
+        result.handshake_latency_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
     }
 
-    // Run the wanted action with the node.
-    action.execute(&mut synth_node, node_addr).await?;
+    // Run the wanted action with the node, actions which don't observe the cancellation token are
+    // dropped once the grace period after the shutdown signal runs out. Stuck actions are dropped
+    // once they exceed their timeout.
+    let grace_period_expired = async {
+        cancel.cancelled().await;
+        sleep(SHUTDOWN_GRACE_PERIOD).await;
+    };
+    let run = timeout(
+        action.cfg.timeout,
+        action.execute(&mut synth_node, node_addr, cancel),
+    );
+    tokio::select! {
+        outcome = run => match outcome {
+            Ok(outcome) => result.outcome = outcome?,
+            Err(_) => {
+                tracing::warn!("the action timed out, dropping it");
+                result.timed_out = true;
+            }
+        },
+        _ = grace_period_expired => tracing::warn!("the action didn't stop in time, dropping it"),
+    }
 
-    if action.cfg.allow_proper_shutdown {
+    // Actions don't need to report the negotiated version themselves.
+    if result.outcome.version.is_none() {
+        result.outcome.version = node_addr
+            .and_then(|addr| synth_node.handshake_info(&addr))
+            .map(Into::into);
+    }
+
+    // Always disconnect cleanly on shutdown or timeout, so no connections are left half-open.
+    if action.cfg.allow_proper_shutdown || cancel.is_cancelled() || result.timed_out {
         // Stop the synthetic node.
         synth_node.shut_down().await;
     }
 
+    if result.timed_out {
+        anyhow::bail!("the action timed out after {:?}", action.cfg.timeout);
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore]
+    async fn concurrent_connections_are_limited() {
+        const CONNECTIONS: usize = 8;
+        const MAX_CONCURRENT_CONNECTIONS: usize = 3;
+
+        let target = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+
+        let mut action = ActionHandler::new(ActionType::HandshakeAndIdle);
+        action.cfg.targets = vec![target.listening_addr()];
+        action.cfg.connections = CONNECTIONS;
+        action.cfg.max_concurrent_connections = MAX_CONCURRENT_CONNECTIONS;
+        action.cfg.idle_duration = Duration::from_millis(500);
+
+        let handles = spawn_connections(
+            Arc::new(action),
+            ActionType::HandshakeAndIdle,
+            false,
+            OutputFormat::Text,
+            Default::default(),
+            CancellationToken::new(),
+        );
+        let finished = async {
+            for handle in handles {
+                handle.await.unwrap();
+            }
+        };
+        tokio::pin!(finished);
+
+        // Sample the number of connections the target sees until all the actions are done.
+        let mut peak = 0;
+        loop {
+            tokio::select! {
+                _ = &mut finished => break,
+                _ = sleep(Duration::from_millis(10)) => peak = peak.max(target.num_connected()),
+            }
+        }
+
+        assert!(peak > 0);
+        assert!(
+            peak <= MAX_CONCURRENT_CONNECTIONS,
+            "{peak} connections were open at once"
+        );
+
+        target.shut_down().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn stuck_action_times_out() {
+        const TIMEOUT: Duration = Duration::from_millis(200);
+
+        let target = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+
+        // The action idles well past its deadline.
+        let mut action = ActionHandler::new(ActionType::HandshakeAndIdle);
+        action.cfg.targets = vec![target.listening_addr()];
+        action.cfg.idle_duration = Duration::from_secs(60);
+        action.cfg.timeout = TIMEOUT;
+
+        let report = Arc::new(Mutex::new(Report::default()));
+        let handles = spawn_connections(
+            Arc::new(action),
+            ActionType::HandshakeAndIdle,
+            false,
+            OutputFormat::Text,
+            report.clone(),
+            CancellationToken::new(),
+        );
+        for handle in handles {
+            tokio::time::timeout(Duration::from_secs(10), handle)
+                .await
+                .expect("the action hung past its timeout")
+                .unwrap();
+        }
+
+        let report = report.lock().unwrap();
+        assert_eq!(report.connections, 1);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.timed_out, 1);
+
+        target.shut_down().await;
+    }
+}
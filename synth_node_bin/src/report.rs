@@ -0,0 +1,211 @@
+//! The results of the synthetic nodes' runs, and their aggregation into a report.
+
+use std::net::SocketAddr;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::action::ActionOutcome;
+
+/// How the results are reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Log lines meant for people.
+    Text,
+    /// A JSON object per connection and one for the final report on stdout, logs are written to
+    /// stderr instead.
+    Json,
+}
+
+/// The result of a synthetic node's run.
+#[derive(Debug, Serialize)]
+pub struct ConnectionResult {
+    /// The index of the connection.
+    pub id: usize,
+    pub action: String,
+    pub node: Option<SocketAddr>,
+    pub success: bool,
+    /// Whether the action was dropped for exceeding its timeout.
+    pub timed_out: bool,
+    /// How long connecting and handshaking took.
+    pub handshake_latency_ms: Option<f64>,
+    /// How long the synthetic node ran for, handshake included.
+    pub duration_ms: f64,
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub outcome: ActionOutcome,
+}
+
+impl ConnectionResult {
+    /// Returns a successful result without an outcome, to be filled in as the run progresses.
+    pub fn new(id: usize, action: String, node: Option<SocketAddr>) -> Self {
+        Self {
+            id,
+            action,
+            node,
+            success: true,
+            timed_out: false,
+            handshake_latency_ms: None,
+            duration_ms: 0.0,
+            error: None,
+            outcome: ActionOutcome::default(),
+        }
+    }
+}
+
+/// A line of the JSON output.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputLine<'a> {
+    Connection(&'a ConnectionResult),
+    Report(&'a Report),
+}
+
+impl OutputLine<'_> {
+    /// Prints the line to stdout.
+    pub fn print(&self) {
+        match serde_json::to_string(self) {
+            Ok(line) => println!("{line}"),
+            Err(e) => tracing::error!("couldn't serialize the output: {e}"),
+        }
+    }
+}
+
+/// The aggregated results of all the runs.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub connections: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// The failed connections which timed out.
+    pub timed_out: usize,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub handshake_latency_ms: Option<LatencyStats>,
+    /// The latencies measured by the actions.
+    pub action_latency_ms: Option<LatencyStats>,
+    #[serde(skip)]
+    handshake_latencies: Vec<f64>,
+    #[serde(skip)]
+    action_latencies: Vec<f64>,
+}
+
+impl Report {
+    /// Accounts for the result of a run.
+    pub fn add(&mut self, result: &ConnectionResult) {
+        self.connections += 1;
+        if result.success {
+            self.succeeded += 1;
+        } else {
+            self.failed += 1;
+        }
+        if result.timed_out {
+            self.timed_out += 1;
+        }
+
+        self.messages_sent += result.outcome.messages_sent;
+        self.messages_received += result.outcome.messages_received;
+        self.handshake_latencies
+            .extend(result.handshake_latency_ms.iter());
+        self.action_latencies
+            .extend_from_slice(&result.outcome.latencies_ms);
+
+        self.handshake_latency_ms = LatencyStats::from_samples(&mut self.handshake_latencies);
+        self.action_latency_ms = LatencyStats::from_samples(&mut self.action_latencies);
+    }
+
+    /// Logs the report.
+    pub fn log(&self) {
+        tracing::info!(
+            "{} connections: {} succeeded, {} failed ({} timed out), {} messages sent, {} received",
+            self.connections,
+            self.succeeded,
+            self.failed,
+            self.timed_out,
+            self.messages_sent,
+            self.messages_received
+        );
+        if let Some(stats) = &self.handshake_latency_ms {
+            tracing::info!("handshake latency (ms): {stats}");
+        }
+        if let Some(stats) = &self.action_latency_ms {
+            tracing::info!("action latency (ms): {stats}");
+        }
+    }
+}
+
+/// Statistics over a set of latencies, in ms.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LatencyStats {
+    pub min: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl LatencyStats {
+    /// Computes the statistics of `samples`, sorting them. Returns `None` if there are none.
+    fn from_samples(samples: &mut [f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        samples.sort_by(f64::total_cmp);
+        let percentile = |p: usize| samples[(samples.len() - 1) * p / 100];
+
+        Some(Self {
+            min: samples[0],
+            mean: samples.iter().sum::<f64>() / samples.len() as f64,
+            p50: percentile(50),
+            p95: percentile(95),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
+
+impl std::fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "min {:.1}, mean {:.1}, p50 {:.1}, p95 {:.1}, max {:.1}",
+            self.min, self.mean, self.p50, self.p95, self.max
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn report_aggregates_results() {
+        let mut report = Report::default();
+        for (id, latency) in [30.0, 10.0, 20.0].into_iter().enumerate() {
+            let mut result = ConnectionResult::new(id, "Test".to_string(), None);
+            result.handshake_latency_ms = Some(latency);
+            result.outcome.messages_sent = 2;
+            report.add(&result);
+        }
+
+        let mut failed = ConnectionResult::new(3, "Test".to_string(), None);
+        failed.success = false;
+        report.add(&failed);
+
+        assert_eq!(report.connections, 4);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.messages_sent, 6);
+        assert_eq!(
+            report.handshake_latency_ms,
+            Some(LatencyStats {
+                min: 10.0,
+                mean: 20.0,
+                p50: 20.0,
+                p95: 20.0,
+                max: 30.0,
+            })
+        );
+        assert_eq!(report.action_latency_ms, None);
+    }
+}